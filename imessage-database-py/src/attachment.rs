@@ -0,0 +1,89 @@
+/*!
+Rows in the `attachment` table.
+*/
+
+use pyo3::prelude::*;
+
+use imessage_database::tables::attachment::Attachment;
+
+use crate::{connection::Connection, convert::to_python, error::table_error, message::PyMessage};
+
+/// A single row in the `attachment` table
+#[pyclass(module = "imessage_database", name = "Attachment", frozen)]
+pub struct PyAttachment {
+    inner: Attachment,
+}
+
+#[pymethods]
+impl PyAttachment {
+    /// The attachments of a message, in the same order as the attachments in its `body()`
+    #[staticmethod]
+    fn from_message(db: &Connection, msg: &PyMessage) -> PyResult<Vec<PyAttachment>> {
+        Attachment::from_message(&db.db(), &db.capabilities, &msg.inner)
+            .map(|attachments| {
+                attachments
+                    .into_iter()
+                    .map(|inner| PyAttachment { inner })
+                    .collect()
+            })
+            .map_err(table_error)
+    }
+
+    /// Every field of the attachment
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.inner)
+    }
+
+    #[getter]
+    fn rowid(&self) -> i32 {
+        self.inner.rowid
+    }
+
+    /// The path to the file on disk, which may start with `~`
+    #[getter]
+    fn filename(&self) -> Option<&str> {
+        self.inner.filename.as_deref()
+    }
+
+    #[getter]
+    fn uti(&self) -> Option<&str> {
+        self.inner.uti.as_deref()
+    }
+
+    #[getter]
+    fn mime_type(&self) -> Option<&str> {
+        self.inner.mime_type.as_deref()
+    }
+
+    #[getter]
+    fn transfer_name(&self) -> Option<&str> {
+        self.inner.transfer_name.as_deref()
+    }
+
+    #[getter]
+    fn total_bytes(&self) -> i64 {
+        self.inner.total_bytes
+    }
+
+    #[getter]
+    fn is_sticker(&self) -> bool {
+        self.inner.is_sticker
+    }
+
+    #[getter]
+    fn hide_attachment(&self) -> i32 {
+        self.inner.hide_attachment
+    }
+
+    #[getter]
+    fn emoji_description(&self) -> Option<&str> {
+        self.inner.emoji_description.as_deref()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Attachment(rowid={}, filename={:?})",
+            self.inner.rowid, self.inner.filename
+        )
+    }
+}
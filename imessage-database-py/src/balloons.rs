@@ -0,0 +1,82 @@
+/*!
+Parsers for app balloons and edited messages.
+
+Each parser is available for a message read from the database and for raw payloads, such as `payload_data` or
+`message_summary_info` blobs read some other way.
+*/
+
+use std::io::Cursor;
+
+use plist::Value;
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rusqlite::Connection;
+
+use imessage_database::{
+    message_types::{
+        app::AppMessage,
+        edited::EditedMessage,
+        url::URLMessage,
+        variants::{BalloonProvider, ParsedBalloon},
+    },
+    tables::messages::Message,
+    util::plist::parse_ns_keyed_archiver,
+};
+
+use crate::{convert::to_python, error::plist_error};
+
+/// Parse the payload of a message's app balloon, see `Message.balloon()`
+pub(crate) fn parse_balloon(
+    py: Python<'_>,
+    msg: &Message,
+    db: &Connection,
+) -> PyResult<Option<PyObject>> {
+    let Some(payload) = msg.balloon_payload(db) else {
+        return Ok(None);
+    };
+    let payload = payload.map_err(plist_error)?;
+
+    match payload.parse().map_err(plist_error)? {
+        ParsedBalloon::URL(balloon) => to_python(py, &balloon).map(Some),
+        ParsedBalloon::App(balloon) => to_python(py, &balloon).map(Some),
+    }
+}
+
+/// Parse a property list from raw bytes
+fn read_plist(payload: &[u8]) -> PyResult<Value> {
+    Value::from_reader(Cursor::new(payload)).map_err(|why| PyValueError::new_err(why.to_string()))
+}
+
+/// Parse an `NSKeyedArchiver` property list from raw bytes, like the `payload_data` of an app balloon
+fn read_archive(payload: &[u8]) -> PyResult<Value> {
+    parse_ns_keyed_archiver(&read_plist(payload)?).map_err(plist_error)
+}
+
+fn url_message(py: Python<'_>, payload: &Value) -> PyResult<PyObject> {
+    let balloon = URLMessage::get_url_message_override(payload).map_err(plist_error)?;
+    to_python(py, &balloon)
+}
+
+fn app_message(py: Python<'_>, payload: &Value) -> PyResult<PyObject> {
+    let balloon = AppMessage::from_map(payload).map_err(plist_error)?;
+    to_python(py, &balloon)
+}
+
+/// Parse a `message_summary_info` payload into the edit history of each part of a message
+#[pyfunction]
+pub fn parse_edited_message(py: Python<'_>, payload: &[u8]) -> PyResult<PyObject> {
+    let edited = EditedMessage::from_map(&read_plist(payload)?).map_err(plist_error)?;
+    to_python(py, &edited)
+}
+
+/// Parse a `payload_data` payload from a generic app balloon, such as Apple Pay or Check In
+#[pyfunction]
+pub fn parse_app_message(py: Python<'_>, payload: &[u8]) -> PyResult<PyObject> {
+    app_message(py, &read_archive(payload)?)
+}
+
+/// Parse a `payload_data` payload from a URL balloon, which may also be an Apple Music, App Store, Collaboration, or
+/// shared Placemark message
+#[pyfunction]
+pub fn parse_url_message(py: Python<'_>, payload: &[u8]) -> PyResult<PyObject> {
+    url_message(py, &read_archive(payload)?)
+}
@@ -0,0 +1,57 @@
+/*!
+Read-only connections to an iMessage database.
+*/
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use pyo3::prelude::*;
+
+use imessage_database::tables::{schema::SchemaCapabilities, table};
+
+use crate::error::table_error;
+
+/// A read-only connection to an iMessage database, created by `get_connection()`
+#[pyclass(module = "imessage_database", frozen)]
+pub struct Connection {
+    /// The path the connection was opened from, used to open more connections for iterators
+    pub(crate) path: PathBuf,
+    /// The columns and tables present in the database, probed when it is opened
+    pub(crate) capabilities: SchemaCapabilities,
+    db: Mutex<rusqlite::Connection>,
+}
+
+impl Connection {
+    /// Get the underlying `SQLite` connection
+    pub(crate) fn db(&self) -> MutexGuard<'_, rusqlite::Connection> {
+        // A panic while the lock is held cannot leave a read-only connection in a bad state
+        self.db.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[pymethods]
+impl Connection {
+    /// The path to the database
+    #[getter]
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Connection(path={:?})", self.path)
+    }
+}
+
+/// Open a read-only connection to the iMessage database at `path`
+#[pyfunction]
+pub fn get_connection(path: PathBuf) -> PyResult<Connection> {
+    let db = table::get_connection(&path).map_err(table_error)?;
+    let capabilities = SchemaCapabilities::probe(&db).map_err(table_error)?;
+    Ok(Connection {
+        path,
+        capabilities,
+        db: Mutex::new(db),
+    })
+}
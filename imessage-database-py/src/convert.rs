@@ -0,0 +1,50 @@
+/*!
+Converts the library's `serde` output to native Python objects.
+*/
+
+use pyo3::{
+    IntoPyObjectExt,
+    exceptions::PyValueError,
+    prelude::*,
+    types::{PyDict, PyList},
+};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Convert a value to the Python objects that match its JSON representation
+pub(crate) fn to_python<T: Serialize + ?Sized>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+    let value =
+        serde_json::to_value(value).map_err(|why| PyValueError::new_err(why.to_string()))?;
+    from_json(py, &value)
+}
+
+fn from_json(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    match value {
+        Value::Null => Ok(py.None()),
+        Value::Bool(boolean) => boolean.into_py_any(py),
+        Value::Number(number) => {
+            if let Some(int) = number.as_i64() {
+                int.into_py_any(py)
+            } else if let Some(int) = number.as_u64() {
+                int.into_py_any(py)
+            } else {
+                number.as_f64().unwrap_or(f64::NAN).into_py_any(py)
+            }
+        }
+        Value::String(string) => string.into_py_any(py),
+        Value::Array(items) => {
+            let list = PyList::empty(py);
+            for item in items {
+                list.append(from_json(py, item)?)?;
+            }
+            list.into_py_any(py)
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, item) in map {
+                dict.set_item(key, from_json(py, item)?)?;
+            }
+            dict.into_py_any(py)
+        }
+    }
+}
@@ -0,0 +1,44 @@
+/*!
+Python exceptions raised for each of the library's error types.
+*/
+
+use pyo3::{PyErr, create_exception, exceptions::PyException};
+
+use imessage_database::error::{message, plist, table};
+
+create_exception!(
+    imessage_database,
+    DatabaseError,
+    PyException,
+    "Base class for errors raised while reading an iMessage database"
+);
+create_exception!(
+    imessage_database,
+    TableError,
+    DatabaseError,
+    "Raised when a table cannot be read"
+);
+create_exception!(
+    imessage_database,
+    MessageError,
+    DatabaseError,
+    "Raised when the contents of a message cannot be parsed"
+);
+create_exception!(
+    imessage_database,
+    PlistParseError,
+    DatabaseError,
+    "Raised when a balloon or edit payload cannot be parsed"
+);
+
+pub(crate) fn table_error(why: table::TableError) -> PyErr {
+    TableError::new_err(why.to_string())
+}
+
+pub(crate) fn message_error(why: message::MessageError) -> PyErr {
+    MessageError::new_err(why.to_string())
+}
+
+pub(crate) fn plist_error(why: plist::PlistParseError) -> PyErr {
+    PlistParseError::new_err(why.to_string())
+}
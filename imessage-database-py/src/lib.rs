@@ -0,0 +1,63 @@
+/*!
+Python bindings for [`imessage_database`], built with [maturin](https://www.maturin.rs).
+
+The bindings wrap the Rust parsers instead of reimplementing them, so `typedstream` and `plist` decoding stays in one
+place. Rust types with their own data are exposed as Python classes, and parsed data such as message bodies, variants,
+edits, and balloons is converted to Python `dict`s and `list`s with the same shape as the library's `serde` output.
+
+# Example
+
+```python
+import imessage_database as imdb
+
+db = imdb.get_connection("chat.db")
+context = imdb.QueryContext(limit=100)
+
+for message in imdb.Message.stream_rows(db, context):
+    message.generate_text(db)
+    print(message.guid, message.variant(), message.body())
+    for attachment in imdb.Attachment.from_message(db, message):
+        print(attachment.filename)
+```
+*/
+
+mod attachment;
+mod balloons;
+mod connection;
+mod convert;
+mod error;
+mod message;
+mod query_context;
+
+use pyo3::prelude::*;
+
+use crate::{
+    attachment::PyAttachment,
+    connection::Connection,
+    error::{DatabaseError, MessageError, PlistParseError, TableError},
+    message::{MessageIterator, PyMessage},
+    query_context::PyQueryContext,
+};
+
+#[pymodule]
+fn imessage_database(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+
+    m.add_function(wrap_pyfunction!(connection::get_connection, m)?)?;
+    m.add_function(wrap_pyfunction!(balloons::parse_edited_message, m)?)?;
+    m.add_function(wrap_pyfunction!(balloons::parse_app_message, m)?)?;
+    m.add_function(wrap_pyfunction!(balloons::parse_url_message, m)?)?;
+
+    m.add_class::<Connection>()?;
+    m.add_class::<PyQueryContext>()?;
+    m.add_class::<PyMessage>()?;
+    m.add_class::<MessageIterator>()?;
+    m.add_class::<PyAttachment>()?;
+
+    m.add("DatabaseError", py.get_type::<DatabaseError>())?;
+    m.add("TableError", py.get_type::<TableError>())?;
+    m.add("MessageError", py.get_type::<MessageError>())?;
+    m.add("PlistParseError", py.get_type::<PlistParseError>())?;
+
+    Ok(())
+}
@@ -0,0 +1,386 @@
+/*!
+Messages and iterators over the `message` table.
+*/
+
+use std::{
+    path::PathBuf,
+    sync::{
+        Mutex, PoisonError,
+        mpsc::{Receiver, sync_channel},
+    },
+    thread,
+};
+
+use pyo3::prelude::*;
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{
+        messages::Message,
+        schema::SchemaCapabilities,
+        table::{AttributedBody, Table, get_connection},
+    },
+    util::query_context::QueryContext,
+};
+
+use crate::{
+    balloons::parse_balloon, connection::Connection, convert::to_python, error::message_error,
+    error::table_error, query_context::PyQueryContext,
+};
+
+/// The number of messages buffered between the reading thread and the iterator
+const CAPACITY: usize = 64;
+
+/// A single row in the `message` table
+#[pyclass(module = "imessage_database", name = "Message")]
+pub struct PyMessage {
+    pub(crate) inner: Message,
+}
+
+#[pymethods]
+impl PyMessage {
+    /// Iterate over the messages that match `context`, or every message if it is `None`
+    #[staticmethod]
+    #[pyo3(signature = (db, context = None))]
+    fn stream_rows(db: &Connection, context: Option<&PyQueryContext>) -> MessageIterator {
+        let context = context.map(|context| context.inner.clone());
+        MessageIterator::spawn(db.path.clone(), context.unwrap_or_default())
+    }
+
+    /// Generate the text of the message, populating `text`, `body()`, and `edited_parts`
+    fn generate_text(&mut self, db: &Connection) -> PyResult<String> {
+        self.inner
+            .generate_text(&db.db())
+            .map(String::from)
+            .map_err(message_error)
+    }
+
+    /// The components of the message body; empty until `generate_text()` is called
+    fn body(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.inner.body())
+    }
+
+    /// The variant of the message, such as a normal message, a tapback, or an app balloon
+    fn variant(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.inner.variant())
+    }
+
+    /// The parsed payload of an app balloon, or `None` if the message is not an app message
+    ///
+    /// Handwriting and Digital Touch messages are not property lists, so they are also `None`.
+    fn balloon(&self, py: Python<'_>, db: &Connection) -> PyResult<Option<PyObject>> {
+        parse_balloon(py, &self.inner, &db.db())
+    }
+
+    /// Every field of the message
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.inner)
+    }
+
+    #[getter]
+    fn rowid(&self) -> i32 {
+        self.inner.rowid
+    }
+
+    #[getter]
+    fn guid(&self) -> &str {
+        &self.inner.guid
+    }
+
+    #[getter]
+    fn text(&self) -> Option<&str> {
+        self.inner.text.as_deref()
+    }
+
+    #[getter]
+    fn service(&self) -> Option<&str> {
+        self.inner.service.as_deref()
+    }
+
+    #[getter]
+    fn handle_id(&self) -> Option<i32> {
+        self.inner.handle_id
+    }
+
+    #[getter]
+    fn destination_caller_id(&self) -> Option<&str> {
+        self.inner.destination_caller_id.as_deref()
+    }
+
+    #[getter]
+    fn subject(&self) -> Option<&str> {
+        self.inner.subject.as_deref()
+    }
+
+    #[getter]
+    fn date(&self) -> i64 {
+        self.inner.date
+    }
+
+    #[getter]
+    fn date_read(&self) -> i64 {
+        self.inner.date_read
+    }
+
+    #[getter]
+    fn date_delivered(&self) -> i64 {
+        self.inner.date_delivered
+    }
+
+    #[getter]
+    fn date_edited(&self) -> i64 {
+        self.inner.date_edited
+    }
+
+    #[getter]
+    fn date_retracted(&self) -> i64 {
+        self.inner.date_retracted
+    }
+
+    #[getter]
+    fn schedule_type(&self) -> i32 {
+        self.inner.schedule_type
+    }
+
+    #[getter]
+    fn is_read(&self) -> bool {
+        self.inner.is_read
+    }
+
+    #[getter]
+    fn associated_message_guid(&self) -> Option<&str> {
+        self.inner.associated_message_guid.as_deref()
+    }
+
+    #[getter]
+    fn balloon_bundle_id(&self) -> Option<&str> {
+        self.inner.balloon_bundle_id.as_deref()
+    }
+
+    #[getter]
+    fn expressive_send_style_id(&self) -> Option<&str> {
+        self.inner.expressive_send_style_id.as_deref()
+    }
+
+    #[getter]
+    fn thread_originator_guid(&self) -> Option<&str> {
+        self.inner.thread_originator_guid.as_deref()
+    }
+
+    #[getter]
+    fn thread_originator_part(&self) -> Option<&str> {
+        self.inner.thread_originator_part.as_deref()
+    }
+
+    #[getter]
+    fn chat_id(&self) -> Option<i32> {
+        self.inner.chat_id
+    }
+
+    #[getter]
+    fn num_attachments(&self) -> i32 {
+        self.inner.num_attachments
+    }
+
+    #[getter]
+    fn deleted_from(&self) -> Option<i32> {
+        self.inner.deleted_from
+    }
+
+    #[getter]
+    fn num_replies(&self) -> i32 {
+        self.inner.num_replies
+    }
+
+    /// The edit history of each part of the message; `None` until `generate_text()` is called
+    #[getter]
+    fn edited_parts(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        self.inner
+            .edited_parts
+            .as_ref()
+            .map(|edited| to_python(py, edited))
+            .transpose()
+    }
+
+    /// `True` if the database owner sent the message, including shared content sent to others
+    fn is_from_me(&self) -> bool {
+        self.inner.is_from_me()
+    }
+
+    fn is_reply(&self) -> bool {
+        self.inner.is_reply()
+    }
+
+    fn is_tapback(&self) -> bool {
+        self.inner.is_tapback()
+    }
+
+    fn is_edited(&self) -> bool {
+        self.inner.is_edited()
+    }
+
+    fn is_fully_unsent(&self) -> bool {
+        self.inner.is_fully_unsent()
+    }
+
+    fn is_deleted(&self) -> bool {
+        self.inner.is_deleted()
+    }
+
+    fn has_attachments(&self) -> bool {
+        self.inner.has_attachments()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Message(rowid={}, guid={:?}, text={:?})",
+            self.inner.rowid, self.inner.guid, self.inner.text
+        )
+    }
+}
+
+/// An iterator over messages, created by `Message.stream_rows()`
+///
+/// Like the library's async streams, rows are read on a separate thread with its own connection and sent over a bounded
+/// channel, so memory use does not grow with the size of the table.
+#[pyclass(module = "imessage_database")]
+pub struct MessageIterator {
+    receiver: Mutex<Receiver<Result<Message, TableError>>>,
+}
+
+impl MessageIterator {
+    fn spawn(db_path: PathBuf, context: QueryContext) -> Self {
+        let (sender, receiver) = sync_channel(CAPACITY);
+
+        thread::spawn(move || {
+            let result = get_connection(&db_path).and_then(|db| {
+                let capabilities = SchemaCapabilities::probe(&db)?;
+                let mut statement = Message::stream_rows(&db, &capabilities, &context)?;
+                let messages = statement
+                    .query_map([], |row| Ok(Message::from_row(row)))
+                    .map_err(TableError::Messages)?;
+                for message in messages {
+                    // Stop reading once the iterator has been dropped
+                    if sender.send(Message::extract(message)).is_err() {
+                        break;
+                    }
+                }
+                Ok(())
+            });
+            if let Err(why) = result {
+                // The iterator may have already been dropped, so there is nobody to notify
+                let _ = sender.send(Err(why));
+            }
+        });
+
+        MessageIterator {
+            receiver: Mutex::new(receiver),
+        }
+    }
+}
+
+#[pymethods]
+impl MessageIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<PyMessage>> {
+        // Release the GIL while waiting for the reading thread
+        let next = py.allow_threads(|| {
+            self.receiver
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .recv()
+                .ok()
+        });
+        match next {
+            Some(Ok(inner)) => Ok(Some(PyMessage { inner })),
+            Some(Err(why)) => Err(table_error(why)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all},
+        path::PathBuf,
+        time::Duration,
+    };
+
+    use pyo3::prelude::*;
+
+    use imessage_database::util::fixture::{FixtureBuilder, NewMessage, Schema};
+
+    use crate::{
+        connection::get_connection,
+        message::{CAPACITY, PyMessage},
+    };
+
+    const DATE: i64 = 694_000_000_000_000_000;
+    const MINUTE: i64 = 60_000_000_000;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-py-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn can_stop_reading_when_dropped() {
+        let dir = temp("iterator");
+        let db_path = dir.join("chat.db");
+        let mut fixture = FixtureBuilder::create(&db_path, Schema::Ios16Newer).unwrap();
+        let handle = fixture.handle("+15558675309", "iMessage").unwrap();
+        let chat = fixture.chat("+15558675309", None, &[handle]).unwrap();
+        // Enough messages that the reading thread fills the channel and waits for the iterator
+        let count = CAPACITY * 3;
+        for idx in 0..count {
+            fixture
+                .message(
+                    chat,
+                    NewMessage::text(&format!("Message {idx}"), DATE + idx as i64 * MINUTE),
+                )
+                .unwrap();
+        }
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let db = get_connection(db_path.clone()).unwrap();
+            let iterator = PyMessage::stream_rows(&db, None);
+
+            // Messages are streamed newest first
+            for idx in (count - 3..count).rev() {
+                let message = iterator.__next__(py).unwrap().unwrap();
+                assert_eq!(message.inner.text, Some(format!("Message {idx}")));
+                assert_eq!(message.inner.date, DATE + idx as i64 * MINUTE);
+            }
+
+            // The reading thread holds its read transaction open while it waits to send more rows
+            fixture.connection().busy_timeout(Duration::ZERO).unwrap();
+            assert!(
+                fixture
+                    .connection()
+                    .execute("DELETE FROM message", [])
+                    .is_err()
+            );
+
+            // Once the iterator is dropped, the thread stops reading and releases the database
+            drop(iterator);
+            fixture
+                .connection()
+                .busy_timeout(Duration::from_secs(10))
+                .unwrap();
+            fixture
+                .connection()
+                .execute("DELETE FROM message", [])
+                .unwrap();
+        });
+
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+/*!
+Filters for the messages returned by `Message.stream_rows()`.
+*/
+
+use std::collections::BTreeSet;
+
+use pyo3::prelude::*;
+
+use imessage_database::util::query_context::QueryContext;
+
+/// Filter configurations for a query, see the Rust `QueryContext` for details
+///
+/// Dates use the database's format: nanoseconds since `2001-01-01 00:00:00`.
+#[pyclass(module = "imessage_database", name = "QueryContext")]
+#[derive(Default)]
+pub struct PyQueryContext {
+    pub(crate) inner: QueryContext,
+}
+
+#[pymethods]
+impl PyQueryContext {
+    #[new]
+    #[pyo3(signature = (
+        *,
+        limit = None,
+        start_date = None,
+        end_date = None,
+        selected_handle_ids = None,
+        selected_chat_ids = None,
+        selected_services = None,
+    ))]
+    fn new(
+        limit: Option<i32>,
+        start_date: Option<i64>,
+        end_date: Option<i64>,
+        selected_handle_ids: Option<BTreeSet<i32>>,
+        selected_chat_ids: Option<BTreeSet<i32>>,
+        selected_services: Option<BTreeSet<String>>,
+    ) -> Self {
+        PyQueryContext {
+            inner: QueryContext {
+                limit,
+                start_date,
+                end_date,
+                selected_handle_ids,
+                selected_chat_ids,
+                selected_services,
+            },
+        }
+    }
+
+    #[getter]
+    fn limit(&self) -> Option<i32> {
+        self.inner.limit
+    }
+
+    #[setter]
+    fn set_limit(&mut self, limit: Option<i32>) {
+        self.inner.limit = limit;
+    }
+
+    #[getter]
+    fn start_date(&self) -> Option<i64> {
+        self.inner.start_date
+    }
+
+    #[setter]
+    fn set_start_date(&mut self, start_date: Option<i64>) {
+        self.inner.start_date = start_date;
+    }
+
+    #[getter]
+    fn end_date(&self) -> Option<i64> {
+        self.inner.end_date
+    }
+
+    #[setter]
+    fn set_end_date(&mut self, end_date: Option<i64>) {
+        self.inner.end_date = end_date;
+    }
+
+    #[getter]
+    fn selected_handle_ids(&self) -> Option<BTreeSet<i32>> {
+        self.inner.selected_handle_ids.clone()
+    }
+
+    #[setter]
+    fn set_selected_handle_ids(&mut self, selected_handle_ids: Option<BTreeSet<i32>>) {
+        self.inner.selected_handle_ids = selected_handle_ids;
+    }
+
+    #[getter]
+    fn selected_chat_ids(&self) -> Option<BTreeSet<i32>> {
+        self.inner.selected_chat_ids.clone()
+    }
+
+    #[setter]
+    fn set_selected_chat_ids(&mut self, selected_chat_ids: Option<BTreeSet<i32>>) {
+        self.inner.selected_chat_ids = selected_chat_ids;
+    }
+
+    #[getter]
+    fn selected_services(&self) -> Option<BTreeSet<String>> {
+        self.inner.selected_services.clone()
+    }
+
+    #[setter]
+    fn set_selected_services(&mut self, selected_services: Option<BTreeSet<String>>) {
+        self.inner.selected_services = selected_services;
+    }
+
+    /// `True` if any filter is set
+    fn has_filters(&self) -> bool {
+        self.inner.has_filters()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.inner)
+    }
+}
@@ -0,0 +1,30 @@
+/*!
+ Errors that can happen when restoring a message cursor from a token.
+*/
+
+use std::fmt::{Display, Formatter, Result};
+
+/// Errors that can happen when parsing a [`MessageCursor`](crate::tables::messages::cursor::MessageCursor) token
+#[derive(Debug)]
+pub enum CursorError {
+    /// The token does not have the expected number of sections
+    MalformedToken(String),
+    /// The token was created by an unsupported version of the cursor format
+    UnsupportedVersion(String),
+    /// A section of the token could not be parsed as a number
+    InvalidNumber(String),
+}
+
+impl Display for CursorError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            CursorError::MalformedToken(token) => write!(fmt, "Malformed cursor token: {token}"),
+            CursorError::UnsupportedVersion(version) => {
+                write!(fmt, "Unsupported cursor token version: {version}")
+            }
+            CursorError::InvalidNumber(value) => {
+                write!(fmt, "Invalid number in cursor token: {value}")
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+/*!
+ Errors that can happen when parsing `digital_touch` data.
+*/
+
+use std::fmt::{Display, Formatter, Result};
+
+/// Errors that can happen when parsing `digital_touch` data
+#[derive(Debug)]
+pub enum DigitalTouchError {
+    ProtobufError(protobuf::Error),
+    UnknownKind(i32),
+    InvalidLength(&'static str, usize, usize),
+    MismatchedCounts(&'static str, usize, usize),
+}
+
+impl Display for DigitalTouchError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            DigitalTouchError::ProtobufError(why) => {
+                write!(fmt, "failed to parse digital touch protobuf: {why}")
+            }
+            DigitalTouchError::UnknownKind(kind) => {
+                write!(fmt, "unknown digital touch kind {kind}")
+            }
+            DigitalTouchError::InvalidLength(field, stride, length) => {
+                write!(
+                    fmt,
+                    "expected {field} length to be a multiple of {stride}, got {length}"
+                )
+            }
+            DigitalTouchError::MismatchedCounts(field, expected, got) => {
+                write!(fmt, "expected {expected} {field}, got {got}")
+            }
+        }
+    }
+}
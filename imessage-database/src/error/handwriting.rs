@@ -4,6 +4,8 @@
 
 use std::fmt::{Display, Formatter, Result};
 
+use crate::message_types::handwriting::models::MAX_PNG_PIXELS;
+
 /// Errors that can happen when parsing `handwriting` data
 #[derive(Debug)]
 pub enum HandwritingError {
@@ -16,6 +18,8 @@ pub enum HandwritingError {
     DecompressedNotSet,
     InvalidDecompressedLength(usize, usize),
     ResizeError(std::num::TryFromIntError),
+    InvalidScale(f32),
+    PngError(png::EncodingError),
 }
 
 impl Display for HandwritingError {
@@ -40,6 +44,13 @@ impl Display for HandwritingError {
             HandwritingError::ResizeError(why) => {
                 write!(fmt, "failed to resize handwriting coordinates: {why}")
             }
+            HandwritingError::InvalidScale(scale) => {
+                write!(
+                    fmt,
+                    "expected a positive scale that keeps the image under {MAX_PNG_PIXELS} pixels, got {scale}"
+                )
+            }
+            HandwritingError::PngError(why) => write!(fmt, "failed to encode png: {why}"),
         }
     }
 }
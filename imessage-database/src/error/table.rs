@@ -12,6 +12,7 @@ pub enum TableError {
     Chat(rusqlite::Error),
     Handle(rusqlite::Error),
     Messages(rusqlite::Error),
+    Schema(rusqlite::Error),
     CannotConnect(String),
     CannotRead(std::io::Error),
 }
@@ -24,6 +25,7 @@ impl Display for TableError {
             TableError::Chat(why) => write!(fmt, "Failed to parse chat row: {why}"),
             TableError::Handle(why) => write!(fmt, "Failed to parse handle row: {why}"),
             TableError::Messages(why) => write!(fmt, "Failed to parse messages row: {why}"),
+            TableError::Schema(why) => write!(fmt, "Failed to read database schema: {why}"),
             TableError::CannotConnect(why) => write!(fmt, "{why}"),
             TableError::CannotRead(why) => write!(fmt, "{why}"),
         }
@@ -17,6 +17,7 @@ pub enum TypedStreamError {
     StringParseError(Utf8Error),
     InvalidArray,
     InvalidPointer(u8),
+    LengthOverflow(u64),
 }
 
 impl Display for TypedStreamError {
@@ -32,6 +33,9 @@ impl Display for TypedStreamError {
             TypedStreamError::StringParseError(why) => write!(fmt, "Failed to parse string: {why}"),
             TypedStreamError::InvalidArray => write!(fmt, "Failed to parse array data"),
             TypedStreamError::InvalidPointer(why) => write!(fmt, "Failed to parse pointer: {why}"),
+            TypedStreamError::LengthOverflow(len) => {
+                write!(fmt, "Length {len} is too large to write!")
+            }
         }
     }
 }
@@ -15,6 +15,7 @@ use crate::{
 
 /// This struct represents Apple's [`MSMessageTemplateLayout`](https://developer.apple.com/documentation/messages/msmessagetemplatelayout).
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct AppMessage<'a> {
     /// An image used to represent the message in the transcript
     pub image: Option<&'a str>,
@@ -63,10 +64,10 @@ impl<'a> BalloonProvider<'a> for AppMessage<'a> {
     }
 }
 
-impl AppMessage<'_> {
+impl<'a> AppMessage<'a> {
     /// Parse key/value pairs from the query string in the balloon's a URL
     #[must_use]
-    pub fn parse_query_string(&self) -> HashMap<&str, &str> {
+    pub fn parse_query_string(&self) -> HashMap<&'a str, &'a str> {
         let mut map = HashMap::new();
 
         if let Some(url) = self.url {
@@ -85,6 +86,69 @@ impl AppMessage<'_> {
     }
 }
 
+/// Owned version of [`AppMessage`], used when the parsed data must outlive its payload
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedAppMessage {
+    /// An image used to represent the message in the transcript
+    pub image: Option<String>,
+    /// A URL pointing to a media file used to represent the message in the transcript
+    pub url: Option<String>,
+    /// The title for the image or media file
+    pub title: Option<String>,
+    /// The subtitle for the image or media file
+    pub subtitle: Option<String>,
+    /// A left-aligned caption for the message bubble
+    pub caption: Option<String>,
+    /// A left-aligned subcaption for the message bubble
+    pub subcaption: Option<String>,
+    /// A right-aligned caption for the message bubble
+    pub trailing_caption: Option<String>,
+    /// A right-aligned subcaption for the message bubble
+    pub trailing_subcaption: Option<String>,
+    /// The name of the app that created this message
+    pub app_name: Option<String>,
+    /// This property is set only for Apple system messages,
+    /// it represents the text that displays in the center of the bubble
+    pub ldtext: Option<String>,
+}
+
+impl From<&AppMessage<'_>> for OwnedAppMessage {
+    fn from(message: &AppMessage<'_>) -> Self {
+        Self {
+            image: message.image.map(str::to_string),
+            url: message.url.map(str::to_string),
+            title: message.title.map(str::to_string),
+            subtitle: message.subtitle.map(str::to_string),
+            caption: message.caption.map(str::to_string),
+            subcaption: message.subcaption.map(str::to_string),
+            trailing_caption: message.trailing_caption.map(str::to_string),
+            trailing_subcaption: message.trailing_subcaption.map(str::to_string),
+            app_name: message.app_name.map(str::to_string),
+            ldtext: message.ldtext.map(str::to_string),
+        }
+    }
+}
+
+impl OwnedAppMessage {
+    /// Borrow the owned data as an [`AppMessage`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> AppMessage<'_> {
+        AppMessage {
+            image: self.image.as_deref(),
+            url: self.url.as_deref(),
+            title: self.title.as_deref(),
+            subtitle: self.subtitle.as_deref(),
+            caption: self.caption.as_deref(),
+            subcaption: self.subcaption.as_deref(),
+            trailing_caption: self.trailing_caption.as_deref(),
+            trailing_subcaption: self.trailing_subcaption.as_deref(),
+            app_name: self.app_name.as_deref(),
+            ldtext: self.ldtext.as_deref(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -13,6 +13,7 @@ use crate::{
 /// This struct is not documented by Apple, but represents messages displayed as
 /// `com.apple.messages.URLBalloonProvider` but for App Store apps
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct AppStoreMessage<'a> {
     /// The URL that ended up serving content, after all redirects
     pub url: Option<&'a str>,
@@ -74,6 +75,52 @@ impl<'a> AppStoreMessage<'a> {
     }
 }
 
+/// Owned version of [`AppStoreMessage`], used when the parsed data must outlive its payload
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedAppStoreMessage {
+    /// The URL that ended up serving content, after all redirects
+    pub url: Option<String>,
+    /// The original url, before any redirects
+    pub original_url: Option<String>,
+    /// The full name of the app in the App Store
+    pub app_name: Option<String>,
+    /// The short description of the app in the App Store
+    pub description: Option<String>,
+    /// The platform the app is compiled for
+    pub platform: Option<String>,
+    /// The app's genre
+    pub genre: Option<String>,
+}
+
+impl From<&AppStoreMessage<'_>> for OwnedAppStoreMessage {
+    fn from(message: &AppStoreMessage<'_>) -> Self {
+        Self {
+            url: message.url.map(str::to_string),
+            original_url: message.original_url.map(str::to_string),
+            app_name: message.app_name.map(str::to_string),
+            description: message.description.map(str::to_string),
+            platform: message.platform.map(str::to_string),
+            genre: message.genre.map(str::to_string),
+        }
+    }
+}
+
+impl OwnedAppStoreMessage {
+    /// Borrow the owned data as an [`AppStoreMessage`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> AppStoreMessage<'_> {
+        AppStoreMessage {
+            url: self.url.as_deref(),
+            original_url: self.original_url.as_deref(),
+            app_name: self.app_name.as_deref(),
+            description: self.description.as_deref(),
+            platform: self.platform.as_deref(),
+            genre: self.genre.as_deref(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -0,0 +1,183 @@
+/*!
+ [Apple Pay](https://support.apple.com/en-us/105017) messages send, request, or schedule payments with Apple Cash.
+*/
+
+use plist::Value;
+
+use crate::{
+    error::plist::PlistParseError,
+    message_types::{app::AppMessage, variants::BalloonProvider},
+};
+
+/// How often a recurring payment is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Frequency {
+    Weekly,
+    /// Every two weeks
+    Biweekly,
+    Monthly,
+}
+
+/// The schedule of a recurring payment
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Recurrence<'a> {
+    /// How often the payment is sent
+    pub frequency: Frequency,
+    /// The date of the first payment, as displayed in the message, i.e. `Nov 18, 2023`
+    pub start: Option<&'a str>,
+}
+
+/// This struct is not documented by Apple, but represents messages displayed as
+/// `com.apple.PassbookUIService.PeerPaymentMessagesExtension`
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ApplePayMessage<'a> {
+    /// The amount of the payment
+    pub amount: Option<f64>,
+    /// The currency symbol or code displayed next to the amount, i.e. `$`
+    pub currency: Option<&'a str>,
+    /// The schedule of the payment, if it recurs
+    pub recurrence: Option<Recurrence<'a>>,
+    /// The text displayed in the message bubble, i.e. `Sent $265 with Apple Pay.`
+    pub description: Option<&'a str>,
+}
+
+impl<'a> BalloonProvider<'a> for ApplePayMessage<'a> {
+    fn from_map(payload: &'a Value) -> Result<Self, PlistParseError> {
+        let balloon = AppMessage::from_map(payload)?;
+        let (amount, currency) = [balloon.ldtext, balloon.subcaption, balloon.caption]
+            .into_iter()
+            .flatten()
+            .find_map(parse_amount)
+            .map_or((None, None), |(amount, currency)| {
+                (Some(amount), Some(currency))
+            });
+
+        Ok(Self {
+            amount,
+            currency,
+            recurrence: balloon.ldtext.and_then(parse_recurrence),
+            description: balloon.ldtext,
+        })
+    }
+}
+
+/// Find the first amount in a string like `Sent $265 with Apple Pay.`, with its currency
+fn parse_amount(text: &str) -> Option<(f64, &str)> {
+    text.split(|c: char| c.is_whitespace())
+        .map(|word| word.trim_end_matches(['.', ',']))
+        .find_map(|word| {
+            let start = word.find(|c: char| c.is_ascii_digit())?;
+            let end = word
+                .rfind(|c: char| c.is_ascii_digit())
+                .map(|idx| idx + 1)?;
+            let amount = word[start..end].replace(',', "").parse().ok()?;
+            let currency = if start > 0 {
+                &word[..start]
+            } else {
+                &word[end..]
+            };
+            (!currency.is_empty()).then_some((amount, currency))
+        })
+}
+
+/// Parse the schedule from a string like `Sending you $1 weekly starting Nov 18, 2023`
+fn parse_recurrence(text: &str) -> Option<Recurrence<'_>> {
+    let lower = text.to_lowercase();
+    let frequency = if lower.contains("every 2 weeks")
+        || lower.contains("every two weeks")
+        || lower.contains("biweekly")
+    {
+        Frequency::Biweekly
+    } else if lower.contains("weekly") {
+        Frequency::Weekly
+    } else if lower.contains("monthly") {
+        Frequency::Monthly
+    } else {
+        return None;
+    };
+
+    Some(Recurrence {
+        frequency,
+        start: text
+            .split_once("starting ")
+            .map(|(_, start)| start.trim_end_matches('.')),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        message_types::{
+            apple_pay::{ApplePayMessage, Frequency, Recurrence, parse_amount},
+            variants::BalloonProvider,
+        },
+        util::plist::parse_ns_keyed_archiver,
+    };
+    use plist::Value;
+    use std::env::current_dir;
+    use std::fs::File;
+
+    #[test]
+    fn test_parse_apple_pay_sent_265() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/Sent265.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        let balloon = ApplePayMessage::from_map(&parsed).unwrap();
+        let expected = ApplePayMessage {
+            amount: Some(265.),
+            currency: Some("$"),
+            recurrence: None,
+            description: Some("Sent $265 with Apple\u{a0}Pay."),
+        };
+
+        assert_eq!(balloon, expected);
+    }
+
+    #[test]
+    fn test_parse_apple_pay_recurring() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/ApplePayRecurring.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        let balloon = ApplePayMessage::from_map(&parsed).unwrap();
+        let expected = ApplePayMessage {
+            amount: Some(1.),
+            currency: Some("$"),
+            recurrence: Some(Recurrence {
+                frequency: Frequency::Weekly,
+                start: Some("Nov 18, 2023"),
+            }),
+            description: Some("Sending you $1 weekly starting Nov 18, 2023"),
+        };
+
+        assert_eq!(balloon, expected);
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("Requested $1,250.50."), Some((1250.5, "$")));
+        assert_eq!(
+            parse_amount("Sent €20 with Apple\u{a0}Pay."),
+            Some((20., "€"))
+        );
+        assert_eq!(parse_amount("$265\u{a0}Payment"), Some((265., "$")));
+        assert_eq!(parse_amount("Sent 3 items"), None);
+        assert_eq!(parse_amount("Apple Cash"), None);
+    }
+}
@@ -0,0 +1,211 @@
+/*!
+ [Check In](https://support.apple.com/guide/iphone/use-check-in-iphc143bb7e9/ios) messages tell someone when the sender starts a trip, arrives, or does not arrive when expected.
+*/
+
+use plist::Value;
+
+use crate::{
+    error::plist::PlistParseError,
+    message_types::{app::AppMessage, variants::BalloonProvider},
+};
+
+/// The progress of a check in, parsed from the message's caption
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CheckInStatus {
+    /// The sender started a timer for when they expect to check in
+    TimerStarted,
+    /// The sender did not check in when expected
+    Late {
+        /// Whether the sender's location was shared when they did not check in
+        location_shared: bool,
+    },
+    /// The sender ended the check in
+    Ended,
+    /// The caption names a place, like the sender's destination
+    Location,
+    /// The caption was missing or not recognized
+    Unknown,
+}
+
+/// This struct is not documented by Apple, but represents messages displayed as
+/// `com.apple.SafetyMonitorApp.SafetyMonitorMessages`
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CheckInMessage<'a> {
+    /// The progress of the check in
+    pub status: CheckInStatus,
+    /// The place named in the caption, if the status is [`CheckInStatus::Location`]
+    pub location: Option<&'a str>,
+    /// When the message was sent, as a unix timestamp
+    pub sent: Option<f64>,
+    /// When the sender expects to arrive, as a unix timestamp
+    pub estimated_arrival: Option<f64>,
+    /// When the sender was expected to check in but did not, as a unix timestamp
+    pub expected: Option<f64>,
+    /// The text displayed in the message bubble
+    pub caption: Option<&'a str>,
+}
+
+impl<'a> BalloonProvider<'a> for CheckInMessage<'a> {
+    fn from_map(payload: &'a Value) -> Result<Self, PlistParseError> {
+        AppMessage::from_map(payload).map(|balloon| Self::from_app(&balloon))
+    }
+}
+
+impl<'a> CheckInMessage<'a> {
+    /// Read a Check In message from the app message it was sent as
+    #[must_use]
+    pub fn from_app(balloon: &AppMessage<'a>) -> Self {
+        let caption = balloon.caption.or(balloon.ldtext);
+        let (status, location) = parse_caption(caption);
+
+        let metadata = balloon.parse_query_string();
+        let timestamp = |key: &str| metadata.get(key).and_then(|stamp| stamp.parse().ok());
+
+        Self {
+            status,
+            location,
+            sent: timestamp("sendDate"),
+            estimated_arrival: timestamp("estimatedEndTime"),
+            expected: timestamp("triggerTime"),
+            caption,
+        }
+    }
+
+    /// The number of seconds between sending the message and the estimated arrival, if the sender started a timer
+    #[must_use]
+    pub fn timer(&self) -> Option<f64> {
+        Some(self.estimated_arrival? - self.sent?)
+    }
+}
+
+/// Parse the status from a caption like `Check In: Timer Started`
+fn parse_caption(caption: Option<&str>) -> (CheckInStatus, Option<&str>) {
+    let Some(caption) = caption else {
+        return (CheckInStatus::Unknown, None);
+    };
+    let detail = caption
+        .split_once(':')
+        .map_or(caption, |(_, detail)| detail)
+        .trim();
+
+    let status = match detail {
+        "" => CheckInStatus::Unknown,
+        "Timer Started" => CheckInStatus::TimerStarted,
+        "Ended" | "Timer Ended" => CheckInStatus::Ended,
+        late if late.starts_with("Has not checked in") => CheckInStatus::Late {
+            location_shared: late.contains("location shared"),
+        },
+        location => return (CheckInStatus::Location, Some(location)),
+    };
+    (status, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        message_types::{
+            check_in::{CheckInMessage, CheckInStatus},
+            variants::BalloonProvider,
+        },
+        util::plist::parse_ns_keyed_archiver,
+    };
+    use plist::Value;
+    use std::env::current_dir;
+    use std::fs::File;
+
+    #[test]
+    fn test_parse_check_in_timer() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/CheckinTimer.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        let balloon = CheckInMessage::from_map(&parsed).unwrap();
+        let expected = CheckInMessage {
+            status: CheckInStatus::TimerStarted,
+            location: None,
+            sent: Some(1697316869.688709),
+            estimated_arrival: None,
+            expected: None,
+            caption: Some("Check\u{a0}In: Timer Started"),
+        };
+
+        assert_eq!(balloon, expected);
+    }
+
+    #[test]
+    fn test_parse_check_in_late() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/CheckinLate.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        let balloon = CheckInMessage::from_map(&parsed).unwrap();
+
+        assert_eq!(
+            balloon.status,
+            CheckInStatus::Late {
+                location_shared: true
+            }
+        );
+        assert_eq!(balloon.location, None);
+    }
+
+    #[test]
+    fn test_parse_check_in_ended() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/CheckinEnded.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        let balloon = CheckInMessage::from_map(&parsed).unwrap();
+
+        assert_eq!(balloon.status, CheckInStatus::Ended);
+        assert_eq!(balloon.sent, Some(1697316869.688709));
+    }
+
+    #[test]
+    fn test_parse_check_in_location() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/CheckinLocation.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        let balloon = CheckInMessage::from_map(&parsed).unwrap();
+
+        assert_eq!(balloon.status, CheckInStatus::Location);
+        assert_eq!(balloon.location, Some("Fake Location"));
+    }
+
+    #[test]
+    fn test_parse_check_in_timer_length() {
+        let balloon = CheckInMessage {
+            status: CheckInStatus::TimerStarted,
+            location: None,
+            sent: Some(1697316869.5),
+            estimated_arrival: Some(1697318669.5),
+            expected: None,
+            caption: None,
+        };
+
+        assert_eq!(balloon.timer(), Some(1800.));
+    }
+}
@@ -13,6 +13,7 @@ use crate::{
 /// This struct is not documented by Apple, but represents messages displayed as
 /// `com.apple.messages.URLBalloonProvider` but from [Rich Collaboration](https://developer.apple.com/videos/play/wwdc2022/10095/) messages
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct CollaborationMessage<'a> {
     /// The URL the user interacts with to start the share session
     pub original_url: Option<&'a str>,
@@ -101,6 +102,52 @@ impl<'a> CollaborationMessage<'a> {
     }
 }
 
+/// Owned version of [`CollaborationMessage`], used when the parsed data must outlive its payload
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedCollaborationMessage {
+    /// The URL the user interacts with to start the share session
+    pub original_url: Option<String>,
+    /// The unique URL for the collaboration item
+    pub url: Option<String>,
+    /// The title of the shared file
+    pub title: Option<String>,
+    /// The date the session was initiated
+    pub creation_date: Option<f64>,
+    /// The Bundle ID of the application that generated the message
+    pub bundle_id: Option<String>,
+    /// The name of the application that generated the message
+    pub app_name: Option<String>,
+}
+
+impl From<&CollaborationMessage<'_>> for OwnedCollaborationMessage {
+    fn from(message: &CollaborationMessage<'_>) -> Self {
+        Self {
+            original_url: message.original_url.map(str::to_string),
+            url: message.url.map(str::to_string),
+            title: message.title.map(str::to_string),
+            creation_date: message.creation_date,
+            bundle_id: message.bundle_id.map(str::to_string),
+            app_name: message.app_name.map(str::to_string),
+        }
+    }
+}
+
+impl OwnedCollaborationMessage {
+    /// Borrow the owned data as a [`CollaborationMessage`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> CollaborationMessage<'_> {
+        CollaborationMessage {
+            original_url: self.original_url.as_deref(),
+            url: self.url.as_deref(),
+            title: self.title.as_deref(),
+            creation_date: self.creation_date,
+            bundle_id: self.bundle_id.as_deref(),
+            app_name: self.app_name.as_deref(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -1,14 +1,32 @@
 /*!
 Parser for [Digital Touch](https://support.apple.com/guide/ipod-touch/send-a-digital-touch-effect-iph3fadba219/ios) iMessages.
 This message type is not documented by Apple, but represents messages displayed as `com.apple.DigitalTouchBalloonProvider`.
+
+Each message wraps a payload for its kind. Points, colors, and delays in those payloads are packed into byte arrays:
+ - Points are pairs of little endian `u16`s, scaled so `0` and `u16::MAX` are the edges of the canvas
+ - Colors are `RGBA` bytes
+ - Delays are little endian `u16`s
 */
 
-use crate::message_types::digital_touch::digital_touch_proto::{
-    BaseMessage, TouchKind as DigitalTouch,
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::{
+    error::digital_touch::DigitalTouchError,
+    message_types::digital_touch::digital_touch_proto::{
+        BaseMessage, FireballMessage, HeartbeatMessage, KissMessage, SketchMessage, TapMessage,
+        TouchKind as DigitalTouch,
+    },
 };
 
 use protobuf::Message;
 
+/// Width and height of the square canvas that SVG output is drawn on
+const CANVAS_SIZE: f32 = 300.;
+/// Radius of a tap drawn on the canvas
+const TAP_RADIUS: f32 = 24.;
+/// Width of a sketch's lines drawn on the canvas
+const STROKE_WIDTH: f32 = 6.;
+
 /// Converts a raw byte payload from the database into a [`DigitalTouch`].
 #[must_use]
 pub fn from_payload(payload: &[u8]) -> Option<DigitalTouch> {
@@ -17,12 +35,469 @@ pub fn from_payload(payload: &[u8]) -> Option<DigitalTouch> {
     Some(msg.TouchKind.enum_value_or_default())
 }
 
+/// A decoded [Digital Touch](https://support.apple.com/guide/ipod-touch/send-a-digital-touch-effect-iph3fadba219/ios) message
+#[derive(Debug, PartialEq)]
+pub struct DigitalTouchMessage {
+    pub id: String,
+    /// The drawing, taps, or effect the message contains
+    pub content: DigitalTouchContent,
+}
+
+/// The content of each kind of Digital Touch message
+#[derive(Debug, PartialEq)]
+pub enum DigitalTouchContent {
+    /// Taps on the canvas, each shown as a ripple
+    Tap(Vec<Tap>),
+    /// Lines drawn on the canvas
+    Sketch(Vec<Stroke>),
+    /// Kisses on the canvas, each shown as a pair of lips
+    Kiss(Vec<Kiss>),
+    /// A heartbeat, or a heart that breaks
+    Heartbeat(Heartbeat),
+    /// A fireball dragged across the canvas
+    Fireball(Fireball),
+}
+
+/// A point on the canvas, where `0.0` and `1.0` are its edges
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TouchPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A color stored as `RGBA` bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+/// A single tap
+#[derive(Debug, PartialEq)]
+pub struct Tap {
+    /// Time before the tap appears, likely in milliseconds
+    pub delay: u16,
+    pub location: TouchPoint,
+    pub color: Color,
+}
+
+/// A single line in a sketch
+#[derive(Debug, PartialEq)]
+pub struct Stroke {
+    /// Time before the line starts, likely in milliseconds
+    pub delay: u16,
+    pub points: Vec<TouchPoint>,
+    pub color: Color,
+}
+
+/// A single kiss
+#[derive(Debug, PartialEq)]
+pub struct Kiss {
+    /// Time before the kiss appears, likely in milliseconds
+    pub delay: u16,
+    pub location: TouchPoint,
+    /// Rotation of the lips, as a fraction of a full turn
+    pub rotation: f32,
+}
+
+/// A heartbeat, which is a heartbreak if the heart breaks partway through
+#[derive(Debug, PartialEq)]
+pub struct Heartbeat {
+    /// Beats per minute
+    pub bpm: f32,
+    /// How long the heart beats, in seconds
+    pub duration: u64,
+    /// How many seconds into the animation the heart breaks, if it breaks
+    pub broken_at: Option<f32>,
+}
+
+/// A fireball dragged across the canvas
+#[derive(Debug, PartialEq)]
+pub struct Fireball {
+    /// How long the fireball is shown, in seconds
+    pub duration: f32,
+    /// Where the fireball starts, relative to the center of the canvas
+    pub start: TouchPoint,
+    /// Each point the fireball moves through, with the time before it gets there, likely in milliseconds
+    pub path: Vec<(u16, TouchPoint)>,
+}
+
+impl DigitalTouchMessage {
+    /// Converts a raw byte payload from the database into a [`DigitalTouchMessage`].
+    pub fn from_payload(payload: &[u8]) -> Result<Self, DigitalTouchError> {
+        let msg =
+            BaseMessage::parse_from_bytes(payload).map_err(DigitalTouchError::ProtobufError)?;
+        let touch = &msg.TouchPayload;
+
+        let content = match msg.TouchKind.enum_value() {
+            Ok(DigitalTouch::Tap) => {
+                let tap = TapMessage::parse_from_bytes(touch)
+                    .map_err(DigitalTouchError::ProtobufError)?;
+                DigitalTouchContent::Tap(parse_taps(&tap)?)
+            }
+            Ok(DigitalTouch::Sketch) => {
+                let sketch = SketchMessage::parse_from_bytes(touch)
+                    .map_err(DigitalTouchError::ProtobufError)?;
+                DigitalTouchContent::Sketch(parse_strokes(&sketch)?)
+            }
+            Ok(DigitalTouch::Kiss) => {
+                let kiss = KissMessage::parse_from_bytes(touch)
+                    .map_err(DigitalTouchError::ProtobufError)?;
+                DigitalTouchContent::Kiss(parse_kisses(&kiss)?)
+            }
+            Ok(DigitalTouch::Heartbeat) => {
+                let heartbeat = HeartbeatMessage::parse_from_bytes(touch)
+                    .map_err(DigitalTouchError::ProtobufError)?;
+                DigitalTouchContent::Heartbeat(Heartbeat {
+                    bpm: heartbeat.BPM,
+                    duration: heartbeat.Duration,
+                    broken_at: (heartbeat.HeartBrokenAt > 0.).then_some(heartbeat.HeartBrokenAt),
+                })
+            }
+            Ok(DigitalTouch::Fireball) => {
+                let fireball = FireballMessage::parse_from_bytes(touch)
+                    .map_err(DigitalTouchError::ProtobufError)?;
+                DigitalTouchContent::Fireball(parse_fireball(&fireball)?)
+            }
+            Ok(DigitalTouch::Unknown) => return Err(DigitalTouchError::UnknownKind(0)),
+            Err(kind) => return Err(DigitalTouchError::UnknownKind(kind)),
+        };
+
+        Ok(Self {
+            id: msg.ID,
+            content,
+        })
+    }
+
+    /// The kind of Digital Touch message
+    #[must_use]
+    pub fn kind(&self) -> DigitalTouch {
+        match self.content {
+            DigitalTouchContent::Tap(_) => DigitalTouch::Tap,
+            DigitalTouchContent::Sketch(_) => DigitalTouch::Sketch,
+            DigitalTouchContent::Kiss(_) => DigitalTouch::Kiss,
+            DigitalTouchContent::Heartbeat(_) => DigitalTouch::Heartbeat,
+            DigitalTouchContent::Fireball(_) => DigitalTouch::Fireball,
+        }
+    }
+
+    /// Renders taps and sketches as an `svg` graphic.
+    ///
+    /// Other kinds are animations that do not have a still image, so they return `None`.
+    #[must_use]
+    pub fn render_svg(&self) -> Option<String> {
+        let shapes = match &self.content {
+            DigitalTouchContent::Tap(taps) => generate_taps(taps),
+            DigitalTouchContent::Sketch(strokes) => generate_strokes(strokes),
+            _ => return None,
+        };
+
+        let mut svg = String::new();
+        svg.push('\n');
+        svg.push_str(format!(r#"<svg viewBox="0 0 {CANVAS_SIZE} {CANVAS_SIZE}" preserveAspectRatio="xMidYMid meet" width="100%" height="100%" xmlns="http://www.w3.org/2000/svg">"#).as_str());
+        svg.push('\n');
+        svg.push_str(&format!("<title>{}</title>\n", self.id));
+        svg.push_str("<metadata>\n");
+        svg.push_str(&format!("<id>{}</id>\n", self.id));
+        svg.push_str(&format!("<kind>{:?}</kind>\n", self.kind()));
+        svg.push_str("</metadata>\n");
+        svg.push_str(&format!(
+            r#"<rect width="{CANVAS_SIZE}" height="{CANVAS_SIZE}" fill="black" />"#
+        ));
+        svg.push('\n');
+        svg.push_str(&shapes);
+        svg.push_str("</svg>\n");
+        Some(svg)
+    }
+}
+
+impl Display for DigitalTouchMessage {
+    /// Summarizes the message as text
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        match &self.content {
+            DigitalTouchContent::Tap(taps) => write!(fmt, "{}", plural(taps.len(), "tap")),
+            DigitalTouchContent::Sketch(strokes) => {
+                write!(fmt, "Sketch with {}", plural(strokes.len(), "line"))
+            }
+            DigitalTouchContent::Kiss(kisses) => write!(fmt, "{}", plural(kisses.len(), "kiss")),
+            DigitalTouchContent::Heartbeat(heartbeat) => write!(fmt, "{heartbeat}"),
+            DigitalTouchContent::Fireball(fireball) => {
+                write!(fmt, "Fireball for {:.1} seconds", fireball.duration)
+            }
+        }
+    }
+}
+
+impl Display for Heartbeat {
+    /// Summarizes the heartbeat as text, i.e. `Heartbeat at 84 BPM for 2 seconds`
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        match self.broken_at {
+            Some(broken_at) => write!(
+                fmt,
+                "Heartbreak at {:.0} BPM, broken after {broken_at:.1} seconds",
+                self.bpm
+            ),
+            None => write!(
+                fmt,
+                "Heartbeat at {:.0} BPM for {}",
+                self.bpm,
+                plural(self.duration as usize, "second")
+            ),
+        }
+    }
+}
+
+impl Color {
+    /// The color as a hex string, i.e. `#ff00fc`
+    #[must_use]
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+
+    /// The color's opacity, from `0.0` to `1.0`
+    #[must_use]
+    pub fn opacity(&self) -> f32 {
+        f32::from(self.alpha) / f32::from(u8::MAX)
+    }
+}
+
+/// Formats a count with a singular or plural noun
+fn plural(count: usize, noun: &str) -> String {
+    match (count, noun.ends_with('s')) {
+        (1, _) => format!("1 {noun}"),
+        (_, true) => format!("{count} {noun}es"),
+        (_, false) => format!("{count} {noun}s"),
+    }
+}
+
+/// Generates svg circles for each tap.
+fn generate_taps(taps: &[Tap]) -> String {
+    let mut svg = String::new();
+    for tap in taps {
+        svg.push_str(&format!(
+            r#"<circle cx="{:.1}" cy="{:.1}" r="{TAP_RADIUS}" fill="{}" fill-opacity="{:.2}" />"#,
+            tap.location.x * CANVAS_SIZE,
+            tap.location.y * CANVAS_SIZE,
+            tap.color.to_hex(),
+            tap.color.opacity()
+        ));
+        svg.push('\n');
+    }
+    svg
+}
+
+/// Generates svg lines for each stroke in a sketch.
+fn generate_strokes(strokes: &[Stroke]) -> String {
+    let mut svg = String::new();
+    for stroke in strokes {
+        let points: Vec<String> = stroke
+            .points
+            .iter()
+            .map(|point| format!("{:.1},{:.1}", point.x * CANVAS_SIZE, point.y * CANVAS_SIZE))
+            .collect();
+        svg.push_str(&format!(
+            r#"<polyline points="{}" fill="none" stroke="{}" stroke-opacity="{:.2}" stroke-width="{STROKE_WIDTH}" stroke-linecap="round" stroke-linejoin="round" />"#,
+            points.join(" "),
+            stroke.color.to_hex(),
+            stroke.color.opacity()
+        ));
+        svg.push('\n');
+    }
+    svg
+}
+
+/// Splits a packed byte array into chunks of `stride` bytes, failing if there are bytes left over.
+fn chunks<'a>(
+    data: &'a [u8],
+    stride: usize,
+    field: &'static str,
+) -> Result<impl Iterator<Item = &'a [u8]>, DigitalTouchError> {
+    if !data.len().is_multiple_of(stride) {
+        return Err(DigitalTouchError::InvalidLength(field, stride, data.len()));
+    }
+    Ok(data.chunks_exact(stride))
+}
+
+/// Parses a little endian `u16`.
+fn parse_u16(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+/// Parses a point from a pair of little endian `u16`s.
+fn parse_point(bytes: &[u8]) -> TouchPoint {
+    TouchPoint {
+        x: f32::from(parse_u16(&bytes[0..2])) / f32::from(u16::MAX),
+        y: f32::from(parse_u16(&bytes[2..4])) / f32::from(u16::MAX),
+    }
+}
+
+/// Parses a color from `RGBA` bytes.
+fn parse_color(bytes: &[u8]) -> Color {
+    Color {
+        red: bytes[0],
+        green: bytes[1],
+        blue: bytes[2],
+        alpha: bytes[3],
+    }
+}
+
+/// Parses packed arrays that must all have the same number of items.
+fn parse_packed<T>(
+    arrays: [(&[u8], usize, &'static str); 3],
+    build: impl Fn(&[u8], &[u8], &[u8]) -> T,
+) -> Result<Vec<T>, DigitalTouchError> {
+    let [
+        (first, first_stride, first_name),
+        (second, second_stride, second_name),
+        (third, third_stride, third_name),
+    ] = arrays;
+    let first: Vec<&[u8]> = chunks(first, first_stride, first_name)?.collect();
+    let second: Vec<&[u8]> = chunks(second, second_stride, second_name)?.collect();
+    let third: Vec<&[u8]> = chunks(third, third_stride, third_name)?.collect();
+
+    if second.len() != first.len() {
+        return Err(DigitalTouchError::MismatchedCounts(
+            second_name,
+            first.len(),
+            second.len(),
+        ));
+    }
+    if third.len() != first.len() {
+        return Err(DigitalTouchError::MismatchedCounts(
+            third_name,
+            first.len(),
+            third.len(),
+        ));
+    }
+
+    Ok(first
+        .iter()
+        .zip(&second)
+        .zip(&third)
+        .map(|((a, b), c)| build(a, b, c))
+        .collect())
+}
+
+/// Parses the taps of a tap message.
+fn parse_taps(msg: &TapMessage) -> Result<Vec<Tap>, DigitalTouchError> {
+    parse_packed(
+        [
+            (&msg.Delays, 2, "delays"),
+            (&msg.Location, 4, "locations"),
+            (&msg.Color, 4, "colors"),
+        ],
+        |delay, location, color| Tap {
+            delay: parse_u16(delay),
+            location: parse_point(location),
+            color: parse_color(color),
+        },
+    )
+}
+
+/// Parses the kisses of a kiss message.
+fn parse_kisses(msg: &KissMessage) -> Result<Vec<Kiss>, DigitalTouchError> {
+    parse_packed(
+        [
+            (&msg.Delays, 2, "delays"),
+            (&msg.Points, 4, "points"),
+            (&msg.Rotations, 2, "rotations"),
+        ],
+        |delay, location, rotation| Kiss {
+            delay: parse_u16(delay),
+            location: parse_point(location),
+            rotation: f32::from(parse_u16(rotation)) / f32::from(u16::MAX),
+        },
+    )
+}
+
+/// Parses the path of a fireball message.
+fn parse_fireball(msg: &FireballMessage) -> Result<Fireball, DigitalTouchError> {
+    let delays: Vec<u16> = chunks(&msg.Delays, 2, "delays")?.map(parse_u16).collect();
+    let points: Vec<TouchPoint> = chunks(&msg.Points, 4, "points")?.map(parse_point).collect();
+    if points.len() != delays.len() {
+        return Err(DigitalTouchError::MismatchedCounts(
+            "points",
+            delays.len(),
+            points.len(),
+        ));
+    }
+
+    Ok(Fireball {
+        duration: msg.Duration,
+        start: TouchPoint {
+            x: msg.StartX,
+            y: msg.StartY,
+        },
+        path: delays.into_iter().zip(points).collect(),
+    })
+}
+
+/// Parses the strokes of a sketch message.
+///
+/// Each stroke is stored as a `u16` delay and a `u16` number of points, followed by the points.
+fn parse_strokes(msg: &SketchMessage) -> Result<Vec<Stroke>, DigitalTouchError> {
+    let colors: Vec<Color> = chunks(&msg.Colors, 4, "colors")?.map(parse_color).collect();
+
+    let data = &msg.Strokes;
+    let mut strokes = Vec::with_capacity(colors.len());
+    let mut idx = 0;
+    while idx < data.len() {
+        if idx + 4 > data.len() {
+            return Err(DigitalTouchError::InvalidLength("strokes", 4, data.len()));
+        }
+        let delay = parse_u16(&data[idx..idx + 2]);
+        let num_points = parse_u16(&data[idx + 2..idx + 4]) as usize;
+        idx += 4;
+
+        let end = idx + num_points * 4;
+        if end > data.len() {
+            return Err(DigitalTouchError::InvalidLength("strokes", 4, data.len()));
+        }
+        let points = data[idx..end].chunks_exact(4).map(parse_point).collect();
+        idx = end;
+
+        let color =
+            colors
+                .get(strokes.len())
+                .copied()
+                .ok_or(DigitalTouchError::MismatchedCounts(
+                    "colors",
+                    strokes.len() + 1,
+                    colors.len(),
+                ))?;
+        strokes.push(Stroke {
+            delay,
+            points,
+            color,
+        });
+    }
+
+    if strokes.len() as i64 != msg.StrokesCount {
+        return Err(DigitalTouchError::MismatchedCounts(
+            "strokes",
+            msg.StrokesCount as usize,
+            strokes.len(),
+        ));
+    }
+    Ok(strokes)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::message_types::digital_touch::{DigitalTouch, from_payload};
+    use crate::{
+        error::digital_touch::DigitalTouchError,
+        message_types::digital_touch::{
+            DigitalTouch, DigitalTouchMessage,
+            digital_touch_proto::TapMessage,
+            from_payload,
+            models::{Color, DigitalTouchContent, Heartbeat, Kiss, Tap, TouchPoint, parse_taps},
+        },
+    };
 
     use std::env::current_dir;
-    use std::fs::File;
+    use std::fs::{File, read_to_string};
     use std::io::Read;
 
     #[test]
@@ -108,4 +583,179 @@ mod tests {
         let expected = from_payload(&data);
         assert_eq!(expected, Some(DigitalTouch::Fireball));
     }
+
+    fn read_payload(name: &str) -> Vec<u8> {
+        let protobuf_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/digital_touch_message")
+            .join(name);
+        let mut proto_data = File::open(protobuf_path).unwrap();
+        let mut data = vec![];
+        proto_data.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn can_decode_tap() {
+        let balloon = DigitalTouchMessage::from_payload(&read_payload("tap.bin")).unwrap();
+
+        assert_eq!(balloon.id, "E3F4E72A-A863-43C3-8277-E17680251B06");
+        assert_eq!(balloon.kind(), DigitalTouch::Tap);
+        assert_eq!(
+            balloon.content,
+            DigitalTouchContent::Tap(vec![Tap {
+                delay: 0,
+                location: TouchPoint {
+                    x: 30809. / 65535.,
+                    y: 37418. / 65535.,
+                },
+                color: Color {
+                    red: 255,
+                    green: 0,
+                    blue: 252,
+                    alpha: 255,
+                },
+            }])
+        );
+        assert_eq!(balloon.to_string(), "1 tap");
+    }
+
+    #[test]
+    fn can_decode_sketch() {
+        let balloon = DigitalTouchMessage::from_payload(&read_payload("sketch.bin")).unwrap();
+
+        assert_eq!(balloon.kind(), DigitalTouch::Sketch);
+        let DigitalTouchContent::Sketch(strokes) = &balloon.content else {
+            panic!("expected a sketch, got {:?}", balloon.content);
+        };
+        assert_eq!(strokes.len(), 1);
+        assert_eq!(strokes[0].delay, 0);
+        assert_eq!(strokes[0].points.len(), 81);
+        assert_eq!(
+            strokes[0].points[0],
+            TouchPoint {
+                x: 14168. / 65535.,
+                y: 43154. / 65535.,
+            }
+        );
+        assert_eq!(strokes[0].color.to_hex(), "#ff00fc");
+        assert_eq!(balloon.to_string(), "Sketch with 1 line");
+    }
+
+    #[test]
+    fn can_decode_kiss() {
+        let balloon = DigitalTouchMessage::from_payload(&read_payload("kiss.bin")).unwrap();
+
+        assert_eq!(
+            balloon.content,
+            DigitalTouchContent::Kiss(vec![Kiss {
+                delay: 0,
+                location: TouchPoint {
+                    x: 33913. / 65535.,
+                    y: 34117. / 65535.,
+                },
+                rotation: 294. / 65535.,
+            }])
+        );
+        assert_eq!(balloon.to_string(), "1 kiss");
+        assert!(balloon.render_svg().is_none());
+    }
+
+    #[test]
+    fn can_decode_heartbeat() {
+        let balloon = DigitalTouchMessage::from_payload(&read_payload("heartbeat.bin")).unwrap();
+
+        assert_eq!(
+            balloon.content,
+            DigitalTouchContent::Heartbeat(Heartbeat {
+                bpm: 84.,
+                duration: 2,
+                broken_at: None,
+            })
+        );
+        assert_eq!(balloon.to_string(), "Heartbeat at 84 BPM for 2 seconds");
+    }
+
+    #[test]
+    fn can_decode_heartbreak() {
+        let balloon = DigitalTouchMessage::from_payload(&read_payload("heartbreak.bin")).unwrap();
+
+        let DigitalTouchContent::Heartbeat(heartbeat) = &balloon.content else {
+            panic!("expected a heartbeat, got {:?}", balloon.content);
+        };
+        assert_eq!(heartbeat.bpm, 84.);
+        assert!((heartbeat.broken_at.unwrap() - 1.7146).abs() < 0.001);
+        assert_eq!(
+            balloon.to_string(),
+            "Heartbreak at 84 BPM, broken after 1.7 seconds"
+        );
+    }
+
+    #[test]
+    fn can_decode_fireball() {
+        let balloon = DigitalTouchMessage::from_payload(&read_payload("fireball.bin")).unwrap();
+
+        let DigitalTouchContent::Fireball(fireball) = &balloon.content else {
+            panic!("expected a fireball, got {:?}", balloon.content);
+        };
+        assert!((fireball.duration - 2.0799).abs() < 0.001);
+        assert_eq!(
+            fireball
+                .path
+                .iter()
+                .map(|(delay, _)| *delay)
+                .collect::<Vec<_>>(),
+            vec![859, 0, 83]
+        );
+        assert_eq!(
+            fireball.path[2].1,
+            TouchPoint {
+                x: 32278. / 65535.,
+                y: 31366. / 65535.,
+            }
+        );
+        assert_eq!(balloon.to_string(), "Fireball for 2.1 seconds");
+    }
+
+    #[test]
+    fn can_render_tap_svg() {
+        let balloon = DigitalTouchMessage::from_payload(&read_payload("tap.bin")).unwrap();
+        let expected = read_to_string(
+            current_dir()
+                .unwrap()
+                .join("test_data/digital_touch_message/tap.svg"),
+        )
+        .unwrap();
+
+        assert_eq!(balloon.render_svg().unwrap(), expected);
+    }
+
+    #[test]
+    fn can_render_sketch_svg() {
+        let balloon = DigitalTouchMessage::from_payload(&read_payload("sketch.bin")).unwrap();
+        let expected = read_to_string(
+            current_dir()
+                .unwrap()
+                .join("test_data/digital_touch_message/sketch.svg"),
+        )
+        .unwrap();
+
+        assert_eq!(balloon.render_svg().unwrap(), expected);
+    }
+
+    #[test]
+    fn cant_decode_mismatched_taps() {
+        let tap = TapMessage {
+            Delays: vec![0, 0, 0, 0],
+            Location: vec![0, 0, 0, 0],
+            Color: vec![255, 255, 255, 255],
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            parse_taps(&tap),
+            Err(DigitalTouchError::MismatchedCounts("locations", 2, 1))
+        ));
+    }
 }
@@ -27,7 +27,12 @@ use crate::{
 };
 
 /// The type of edit performed to a message body part
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum EditStatus {
     /// The content of the message body part was altered
     Edited,
@@ -38,7 +43,8 @@ pub enum EditStatus {
 }
 
 /// Represents a single edit event for a message part
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EditedEvent {
     /// The date the message part was edited
     pub date: i64,
@@ -81,7 +87,8 @@ impl AttributedBody for EditedEvent {
 }
 
 /// Tracks the edit status and history for a specific part of a message
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EditedMessagePart {
     /// The type of edit made to the given message part
     pub status: EditStatus,
@@ -127,7 +134,8 @@ impl Default for EditedMessagePart {
 /// # Documentation
 ///
 /// Apple describes editing and unsending messages [here](https://support.apple.com/guide/iphone/unsend-and-edit-messages-iphe67195653/ios).
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EditedMessage {
     /// Contains data representing each part of an edited message
     pub parts: Vec<EditedMessagePart>,
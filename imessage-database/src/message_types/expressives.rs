@@ -5,7 +5,12 @@
 /// Bubble effects are effects that alter the display of the chat bubble.
 ///
 /// Read more [here](https://www.imore.com/how-to-use-bubble-and-screen-effects-imessage-iphone-ipad).
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum BubbleEffect {
     Slam,
     Loud,
@@ -16,7 +21,12 @@ pub enum BubbleEffect {
 /// Screen effects are effects that alter the entire background of the message view.
 ///
 /// Read more [here](https://www.imore.com/how-to-use-bubble-and-screen-effects-imessage-iphone-ipad).
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum ScreenEffect {
     Confetti,
     Echo,
@@ -50,6 +60,11 @@ pub enum ScreenEffect {
 /// - `com.apple.messages.effect.CKSparklesEffect`
 /// - `com.apple.messages.effect.CKSpotlightEffect`
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Expressive<'a> {
     /// Effects that use the entire screen
     Screen(ScreenEffect),
@@ -60,3 +75,45 @@ pub enum Expressive<'a> {
     /// Message is not an expressive
     None,
 }
+
+/// Owned version of [`Expressive`], used when the parsed data must outlive its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OwnedExpressive {
+    /// See [`Expressive::Screen`]
+    Screen(ScreenEffect),
+    /// See [`Expressive::Bubble`]
+    Bubble(BubbleEffect),
+    /// See [`Expressive::Unknown`]
+    Unknown(String),
+    /// See [`Expressive::None`]
+    None,
+}
+
+impl From<&Expressive<'_>> for OwnedExpressive {
+    fn from(expressive: &Expressive<'_>) -> Self {
+        match expressive {
+            Expressive::Screen(effect) => Self::Screen(effect.clone()),
+            Expressive::Bubble(effect) => Self::Bubble(effect.clone()),
+            Expressive::Unknown(id) => Self::Unknown((*id).to_string()),
+            Expressive::None => Self::None,
+        }
+    }
+}
+
+impl OwnedExpressive {
+    /// Borrow the owned data as an [`Expressive`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> Expressive<'_> {
+        match self {
+            Self::Screen(effect) => Expressive::Screen(effect.clone()),
+            Self::Bubble(effect) => Expressive::Bubble(effect.clone()),
+            Self::Unknown(id) => Expressive::Unknown(id),
+            Self::None => Expressive::None,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+/*!
+ Find My messages are sent when someone starts or stops sharing their location.
+*/
+
+use plist::Value;
+
+use crate::{
+    error::plist::PlistParseError,
+    message_types::{app::AppMessage, variants::BalloonProvider},
+};
+
+/// Whether the sender's location is shared, parsed from the message's text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum LocationSharing {
+    /// The sender started sharing their location
+    Started,
+    /// The sender stopped sharing their location
+    Stopped,
+    /// The text was missing or not recognized
+    Unknown,
+}
+
+/// This struct is not documented by Apple, but represents messages displayed as
+/// `com.apple.findmy.FindMyMessagesApp`
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FindMyMessage<'a> {
+    /// Whether the sender's location is shared
+    pub sharing: LocationSharing,
+    /// The text displayed in the message bubble, i.e. `Started Sharing Location`
+    pub description: Option<&'a str>,
+    /// The version of the payload's data
+    pub version: Option<&'a str>,
+    /// The compressed data `Find My` uses to show the shared location
+    pub data: Option<&'a str>,
+}
+
+impl<'a> BalloonProvider<'a> for FindMyMessage<'a> {
+    fn from_map(payload: &'a Value) -> Result<Self, PlistParseError> {
+        AppMessage::from_map(payload).map(|balloon| Self::from_app(&balloon))
+    }
+}
+
+impl<'a> FindMyMessage<'a> {
+    /// Read a Find My message from the app message it was sent as
+    #[must_use]
+    pub fn from_app(balloon: &AppMessage<'a>) -> Self {
+        let metadata = balloon.parse_query_string();
+
+        let sharing = match balloon.ldtext {
+            Some(text) if text.starts_with("Started Sharing") => LocationSharing::Started,
+            Some(text) if text.starts_with("Stopped Sharing") => LocationSharing::Stopped,
+            _ => LocationSharing::Unknown,
+        };
+
+        Self {
+            sharing,
+            description: balloon.ldtext,
+            version: metadata.get("FindMyMessagePayloadVersionKey").copied(),
+            data: metadata.get("FindMyMessagePayloadZippedDataKey").copied(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        message_types::{
+            find_my::{FindMyMessage, LocationSharing},
+            variants::BalloonProvider,
+        },
+        util::plist::parse_ns_keyed_archiver,
+    };
+    use plist::Value;
+    use std::env::current_dir;
+    use std::fs::File;
+
+    #[test]
+    fn test_parse_find_my() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/FindMy.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        let balloon = FindMyMessage::from_map(&parsed).unwrap();
+        let expected = FindMyMessage {
+            sharing: LocationSharing::Started,
+            description: Some("Started Sharing Location"),
+            version: Some("v0"),
+            data: Some("FAKEDATA"),
+        };
+
+        assert_eq!(balloon, expected);
+    }
+}
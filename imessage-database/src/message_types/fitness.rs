@@ -0,0 +1,116 @@
+/*!
+ Fitness messages share activity, like closed rings, workouts, and awards, with friends in the Fitness app.
+*/
+
+use plist::Value;
+
+use crate::{
+    error::plist::PlistParseError,
+    message_types::{app::AppMessage, variants::BalloonProvider},
+    tables::table::FITNESS_RECEIVER,
+};
+
+/// This struct is not documented by Apple, but represents messages displayed as
+/// `com.apple.ActivityMessagesApp.MessagesExtension`
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FitnessMessage<'a> {
+    /// The activity being shared, i.e. `Outdoor Walk`
+    pub activity: Option<&'a str>,
+    /// The statistics displayed for the activity, i.e. `1.2 MI` or `45 MIN`, in the order they are displayed
+    pub metrics: Vec<&'a str>,
+    /// The text displayed in the message bubble, where [`FITNESS_RECEIVER`] stands in for the recipient
+    pub summary: Option<&'a str>,
+}
+
+impl<'a> BalloonProvider<'a> for FitnessMessage<'a> {
+    fn from_map(payload: &'a Value) -> Result<Self, PlistParseError> {
+        let balloon = AppMessage::from_map(payload)?;
+
+        Ok(Self {
+            activity: balloon.title.or(balloon.caption),
+            metrics: [
+                balloon.title.and(balloon.caption),
+                balloon.subtitle,
+                balloon.subcaption,
+                balloon.trailing_caption,
+                balloon.trailing_subcaption,
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            summary: balloon.ldtext,
+        })
+    }
+}
+
+impl FitnessMessage<'_> {
+    /// The summary, with the recipient's placeholder replaced by `recipient`
+    #[must_use]
+    pub fn summary_for(&self, recipient: &str) -> Option<String> {
+        self.summary
+            .map(|summary| summary.replace(FITNESS_RECEIVER, recipient))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        message_types::{fitness::FitnessMessage, variants::BalloonProvider},
+        tables::table::FITNESS_RECEIVER,
+    };
+    use plist::{Dictionary, Value};
+
+    fn payload(user_info: &[(&str, &str)], ldtext: &str) -> Value {
+        let mut info = Dictionary::new();
+        for (key, value) in user_info {
+            info.insert(key.to_string(), Value::String(value.to_string()));
+        }
+        let mut root = Dictionary::new();
+        root.insert("userInfo".to_string(), Value::Dictionary(info));
+        root.insert("ldtext".to_string(), Value::String(ldtext.to_string()));
+        root.insert("an".to_string(), Value::String("Fitness".to_string()));
+        Value::Dictionary(root)
+    }
+
+    #[test]
+    fn test_parse_fitness_workout() {
+        let summary = format!("{FITNESS_RECEIVER} completed an Outdoor Walk");
+        let payload = payload(
+            &[
+                ("image-title", "Outdoor Walk"),
+                ("caption", "1.21 MI"),
+                ("subcaption", "32:10"),
+                ("secondary-subcaption", ""),
+            ],
+            &summary,
+        );
+
+        let balloon = FitnessMessage::from_map(&payload).unwrap();
+        let expected = FitnessMessage {
+            activity: Some("Outdoor Walk"),
+            metrics: vec!["1.21 MI", "32:10"],
+            summary: Some(&summary),
+        };
+
+        assert_eq!(balloon, expected);
+        assert_eq!(
+            balloon.summary_for("Jenny").as_deref(),
+            Some("Jenny completed an Outdoor Walk")
+        );
+    }
+
+    #[test]
+    fn test_parse_fitness_caption_only() {
+        let payload = payload(&[("caption", "Closed all three rings")], "");
+
+        let balloon = FitnessMessage::from_map(&payload).unwrap();
+        let expected = FitnessMessage {
+            activity: Some("Closed all three rings"),
+            metrics: vec![],
+            summary: None,
+        };
+
+        assert_eq!(balloon, expected);
+    }
+}
@@ -10,8 +10,16 @@ use crate::{
     message_types::handwriting::handwriting_proto::{BaseMessage, Compression},
 };
 
+use png::{BitDepth, ColorType, Encoder};
 use protobuf::Message;
 
+/// Speed, in canvas units per second, that animated `svg` strokes are drawn at
+const DRAW_SPEED: f32 = 400.;
+/// Seconds between the end of one animated `svg` stroke and the start of the next
+const STROKE_PAUSE: f32 = 0.15;
+/// Largest `png` that can be rendered, in pixels, so a large canvas or scale cannot exhaust memory
+pub const MAX_PNG_PIXELS: u64 = 4096 * 4096;
+
 /// Parser for [handwritten](https://support.apple.com/en-us/HT206894) iMessages.
 ///
 /// This message type is not documented by Apple, but represents messages displayed as
@@ -116,6 +124,134 @@ impl HandwrittenMessage {
 
         output
     }
+
+    /// Renders the handwriting message as an animated `svg` graphic that replays the strokes in the order they were drawn.
+    ///
+    /// Each line is revealed by animating its `stroke-dashoffset`, so it is drawn at a constant speed.
+    #[must_use]
+    pub fn render_animated_svg(&self) -> String {
+        let mut svg = String::new();
+        svg.push('\n');
+        svg.push_str(format!(r#"<svg viewBox="0 0 {} {}" preserveAspectRatio="xMidYMid meet" width="100%" height="100%" xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">"#, self.width, self.height).as_str());
+        svg.push('\n');
+        svg.push_str(&format!("<title>{}</title>\n", self.id));
+        svg.push_str("<metadata>\n");
+        svg.push_str(&format!("<id>{}</id>\n", self.id));
+        svg.push_str(&format!("<createdAt>{}</createdAt>\n", self.created_at));
+        svg.push_str("</metadata>\n");
+        svg.push_str("<style>\n");
+        svg.push_str(
+            r"    .line {
+        fill: none;
+        stroke: black;
+        stroke-linecap: round;
+        stroke-linejoin: round;
+    }
+",
+        );
+        svg.push_str("</style>\n");
+        generate_animated_strokes(&mut svg, &self.strokes);
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Renders the handwriting message as a `png` image with a transparent background.
+    ///
+    /// The image is `scale` times the size of the message's canvas, and each line is as wide as its points.
+    /// Scales that would make the image larger than [`MAX_PNG_PIXELS`] are invalid.
+    pub fn render_png(&self, scale: f32) -> Result<Vec<u8>, HandwritingError> {
+        if !scale.is_finite() || scale <= 0. {
+            return Err(HandwritingError::InvalidScale(scale));
+        }
+        let width = ((f32::from(self.width) * scale).ceil() as u32).max(1);
+        let height = ((f32::from(self.height) * scale).ceil() as u32).max(1);
+        if u64::from(width) * u64::from(height) > MAX_PNG_PIXELS {
+            return Err(HandwritingError::InvalidScale(scale));
+        }
+
+        // Draw the coverage of each pixel, then fill the covered pixels with black
+        let mut canvas = Canvas::new(width, height);
+        for stroke in &self.strokes {
+            match stroke.as_slice() {
+                [] => {}
+                [point] => canvas.draw_segment(point, point, scale),
+                _ => stroke.windows(2).for_each(|window| {
+                    canvas.draw_segment(&window[0], &window[1], scale);
+                }),
+            }
+        }
+        let pixels: Vec<u8> = canvas
+            .coverage
+            .iter()
+            .flat_map(|&alpha| [0, 0, 0, alpha])
+            .collect();
+
+        let mut png = vec![];
+        let mut encoder = Encoder::new(&mut png, width, height);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(HandwritingError::PngError)?;
+        writer
+            .write_image_data(&pixels)
+            .map_err(HandwritingError::PngError)?;
+        writer.finish().map_err(HandwritingError::PngError)?;
+        Ok(png)
+    }
+}
+
+/// A grid of pixels that stores how much of each pixel is covered by a line.
+struct Canvas {
+    width: u32,
+    height: u32,
+    /// Coverage of each pixel, row by row, from `0` to `u8::MAX`
+    coverage: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            coverage: vec![0; width as usize * height as usize],
+        }
+    }
+
+    /// Draws an anti-aliased line with round ends whose width changes from `start` to `end`.
+    fn draw_segment(&mut self, start: &Point, end: &Point, scale: f32) {
+        let (x0, y0) = (f32::from(start.x) * scale, f32::from(start.y) * scale);
+        let (x1, y1) = (f32::from(end.x) * scale, f32::from(end.y) * scale);
+        // Keep hairlines at least one pixel wide so they do not disappear
+        let r0 = (f32::from(start.width) * scale / 2.).max(0.5);
+        let r1 = (f32::from(end.width) * scale / 2.).max(0.5);
+
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let length_squared = dx * dx + dy * dy;
+        let reach = r0.max(r1) + 1.;
+
+        let min_x = (x0.min(x1) - reach).floor().max(0.) as u32;
+        let min_y = (y0.min(y1) - reach).floor().max(0.) as u32;
+        let max_x = ((x0.max(x1) + reach).ceil().max(0.) as u32).min(self.width);
+        let max_y = ((y0.max(y1) + reach).ceil().max(0.) as u32).min(self.height);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                // Find the closest point on the segment and the line's radius there
+                let t = if length_squared > 0. {
+                    (((px - x0) * dx + (py - y0) * dy) / length_squared).clamp(0., 1.)
+                } else {
+                    0.
+                };
+                let distance = (px - (x0 + t * dx)).hypot(py - (y0 + t * dy));
+                let radius = r0 + (r1 - r0) * t;
+
+                let alpha =
+                    ((radius - distance + 0.5).clamp(0., 1.) * f32::from(u8::MAX)).round() as u8;
+                let pixel = &mut self.coverage[y as usize * self.width as usize + x as usize];
+                *pixel = (*pixel).max(alpha);
+            }
+        }
+    }
 }
 
 /// Draws a line on a 2d character grid using Bresenham's line algorithm.
@@ -177,6 +313,59 @@ fn generate_strokes(svg: &mut String, strokes: &[Vec<Point>]) {
     }
 }
 
+/// Generates svg lines from an array of strokes, each animated to draw after the lines before it.
+fn generate_animated_strokes(svg: &mut String, strokes: &[Vec<Point>]) {
+    let mut begin = 0.;
+    for stroke in strokes {
+        for (width, points) in &group_points(stroke) {
+            let mut points_svg = String::with_capacity(points.len() * 3);
+            for point in points {
+                points_svg.push_str(&format!(" {},{}", point.x, point.y));
+            }
+
+            // Round the length up so the dash gap always covers the whole line
+            let length = (polyline_length(points) * 100.).ceil() / 100.;
+            let duration = (length / DRAW_SPEED).max(0.01);
+            svg.push_str(&format!(
+                r#"<polyline class="line" points="{}" stroke-width="{}" visibility="hidden""#,
+                points_svg.trim_start(),
+                width
+            ));
+            if length > 0. {
+                svg.push_str(&format!(
+                    r#" stroke-dasharray="{length:.2} {length:.2}" stroke-dashoffset="{length:.2}""#
+                ));
+            }
+            svg.push_str(">\n");
+            svg.push_str(&format!(
+                r#"<set attributeName="visibility" to="visible" begin="{begin:.2}s" fill="freeze" />"#
+            ));
+            svg.push('\n');
+            if length > 0. {
+                svg.push_str(&format!(
+                    r#"<animate attributeName="stroke-dashoffset" from="{length:.2}" to="0" begin="{begin:.2}s" dur="{duration:.2}s" fill="freeze" />"#
+                ));
+                svg.push('\n');
+            }
+            svg.push_str("</polyline>\n");
+            begin += duration;
+        }
+        begin += STROKE_PAUSE;
+    }
+}
+
+/// Measures the length of a line through each point.
+fn polyline_length(points: &[&Point]) -> f32 {
+    points
+        .windows(2)
+        .map(|window| {
+            let dx = f32::from(window[1].x) - f32::from(window[0].x);
+            let dy = f32::from(window[1].y) - f32::from(window[0].y);
+            dx.hypot(dy)
+        })
+        .sum()
+}
+
 /// Group points along a stroke together by width
 fn group_points(stroke: &[Point]) -> Vec<(u16, Vec<&Point>)> {
     let mut groups = vec![];
@@ -341,11 +530,16 @@ fn parse_coordinates(b1: u8, b2: u8) -> u16 {
 
 #[cfg(test)]
 mod tests {
-    use crate::message_types::handwriting::models::{HandwrittenMessage, Point};
+    use crate::{
+        error::handwriting::HandwritingError,
+        message_types::handwriting::models::{HandwrittenMessage, MAX_PNG_PIXELS, Point},
+    };
 
     use std::env::current_dir;
     use std::fs::File;
-    use std::io::Read;
+    use std::io::{Cursor, Read};
+
+    use png::{ColorType, Decoder};
 
     #[test]
     fn test_parse_handwritten_from_payload() {
@@ -11227,4 +11421,133 @@ mod tests {
 
         assert_eq!(balloon.render_svg(), expected);
     }
+
+    #[test]
+    fn test_parse_handwritten_as_animated_svg() {
+        let protobuf_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/handwritten_message/hello.bin");
+        let mut proto_data = File::open(protobuf_path).unwrap();
+        let mut data = vec![];
+        proto_data.read_to_end(&mut data).unwrap();
+        let balloon = HandwrittenMessage::from_payload(&data).unwrap();
+
+        let mut expected = String::new();
+        let expected_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/handwritten_message/hello_animated.svg");
+        let mut expected_data = File::open(expected_path).unwrap();
+        expected_data.read_to_string(&mut expected).unwrap();
+
+        assert_eq!(balloon.render_animated_svg(), expected);
+    }
+
+    #[test]
+    fn test_parse_handwritten_as_png() {
+        let protobuf_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/handwritten_message/hello.bin");
+        let mut proto_data = File::open(protobuf_path).unwrap();
+        let mut data = vec![];
+        proto_data.read_to_end(&mut data).unwrap();
+        let balloon = HandwrittenMessage::from_payload(&data).unwrap();
+
+        let png = balloon.render_png(2.).unwrap();
+        let mut reader = Decoder::new(Cursor::new(png)).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+
+        assert_eq!((info.width, info.height), (810, 322));
+        assert_eq!(info.color_type, ColorType::Rgba);
+        let pixel = |x: usize, y: usize| {
+            let idx = (y * info.width as usize + x) * 4;
+            &pixels[idx..idx + 4]
+        };
+        // The background is transparent
+        assert_eq!(pixel(0, 0), [0, 0, 0, 0]);
+        // The first point is at (8, 149) and 10 wide, so it covers the pixels around it
+        assert_eq!(pixel(16, 298), [0, 0, 0, 255]);
+        assert_eq!(pixel(16 + 9, 298), [0, 0, 0, 255]);
+        assert_eq!(pixel(16 - 12, 298), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_parse_handwritten_as_png_scaled() {
+        let protobuf_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/handwritten_message/handwriting.bin");
+        let mut proto_data = File::open(protobuf_path).unwrap();
+        let mut data = vec![];
+        proto_data.read_to_end(&mut data).unwrap();
+        let balloon = HandwrittenMessage::from_payload(&data).unwrap();
+
+        let png = balloon.render_png(0.5).unwrap();
+        let info = Decoder::new(Cursor::new(png)).read_info().unwrap();
+
+        assert_eq!((info.info().width, info.info().height), (377, 122));
+    }
+
+    #[test]
+    fn test_parse_handwritten_as_png_invalid_scale() {
+        let protobuf_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/handwritten_message/hello.bin");
+        let mut proto_data = File::open(protobuf_path).unwrap();
+        let mut data = vec![];
+        proto_data.read_to_end(&mut data).unwrap();
+        let balloon = HandwrittenMessage::from_payload(&data).unwrap();
+
+        assert!(matches!(
+            balloon.render_png(0.),
+            Err(HandwritingError::InvalidScale(_))
+        ));
+        assert!(matches!(
+            balloon.render_png(f32::NAN),
+            Err(HandwritingError::InvalidScale(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_handwritten_as_png_too_large() {
+        let balloon = HandwrittenMessage {
+            id: String::new(),
+            created_at: 0,
+            height: u16::MAX,
+            width: u16::MAX,
+            strokes: vec![],
+        };
+
+        // The canvas alone is larger than the limit
+        assert!(matches!(
+            balloon.render_png(1.),
+            Err(HandwritingError::InvalidScale(_))
+        ));
+        // A large scale makes even a small canvas too large
+        let small = HandwrittenMessage {
+            height: 100,
+            width: 100,
+            ..balloon
+        };
+        assert!(matches!(
+            small.render_png(1000.),
+            Err(HandwritingError::InvalidScale(_))
+        ));
+        // The largest allowed image renders
+        let side = (MAX_PNG_PIXELS as f64).sqrt() as u16;
+        let largest = HandwrittenMessage {
+            height: side,
+            width: side,
+            ..small
+        };
+        assert!(largest.render_png(1.).is_ok());
+        assert!(matches!(
+            largest.render_png(1.01),
+            Err(HandwritingError::InvalidScale(_))
+        ));
+    }
 }
@@ -4,13 +4,18 @@
 
 pub mod app;
 pub mod app_store;
+pub mod apple_pay;
+pub mod check_in;
 pub mod collaboration;
 pub mod digital_touch;
 pub mod edited;
 pub mod expressives;
+pub mod find_my;
+pub mod fitness;
 pub mod handwriting;
 pub mod music;
 pub mod placemark;
+pub mod slideshow;
 pub mod sticker;
 pub mod text_effects;
 pub mod url;
@@ -13,6 +13,7 @@ use crate::{
 /// This struct is not documented by Apple, but represents messages displayed as
 /// `com.apple.messages.URLBalloonProvider` but from the Music app
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct MusicMessage<'a> {
     /// URL in Apple Music
     pub url: Option<&'a str>,
@@ -76,6 +77,58 @@ impl<'a> MusicMessage<'a> {
     }
 }
 
+/// Owned version of [`MusicMessage`], used when the parsed data must outlive its payload
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedMusicMessage {
+    /// URL in Apple Music
+    pub url: Option<String>,
+    /// URL pointing to the track preview stream
+    pub preview: Option<String>,
+    /// Artist name
+    pub artist: Option<String>,
+    /// Album name
+    pub album: Option<String>,
+    /// Track name
+    pub track_name: Option<String>,
+    /// Included lyrics, if any
+    pub lyrics: Option<Vec<String>>,
+}
+
+impl From<&MusicMessage<'_>> for OwnedMusicMessage {
+    fn from(message: &MusicMessage<'_>) -> Self {
+        Self {
+            url: message.url.map(str::to_string),
+            preview: message.preview.map(str::to_string),
+            artist: message.artist.map(str::to_string),
+            album: message.album.map(str::to_string),
+            track_name: message.track_name.map(str::to_string),
+            lyrics: message
+                .lyrics
+                .as_ref()
+                .map(|items| items.iter().map(|item| (*item).to_string()).collect()),
+        }
+    }
+}
+
+impl OwnedMusicMessage {
+    /// Borrow the owned data as a [`MusicMessage`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> MusicMessage<'_> {
+        MusicMessage {
+            url: self.url.as_deref(),
+            preview: self.preview.as_deref(),
+            artist: self.artist.as_deref(),
+            album: self.album.as_deref(),
+            track_name: self.track_name.as_deref(),
+            lyrics: self
+                .lyrics
+                .as_ref()
+                .map(|items| items.iter().map(String::as_str).collect()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
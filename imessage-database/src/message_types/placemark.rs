@@ -12,6 +12,7 @@ use crate::{
 
 /// Representation of Apple's [`CLPlacemark`](https://developer.apple.com/documentation/corelocation/clplacemark) object
 #[derive(Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Placemark<'a> {
     /// The name of the placemark
     pub name: Option<&'a str>,
@@ -70,6 +71,7 @@ impl<'a> Placemark<'a> {
 /// This struct is not documented by Apple, but represents messages displayed as
 /// `com.apple.messages.URLBalloonProvider` but for the Maps app
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct PlacemarkMessage<'a> {
     /// The URL that ended up serving content, after all redirects
     pub url: Option<&'a str>,
@@ -129,13 +131,169 @@ impl<'a> PlacemarkMessage<'a> {
     pub fn get_url(&self) -> Option<&str> {
         self.url.or(self.original_url)
     }
+
+    /// Get the location of the placemark from its Apple Maps URL, falling back to the original URL
+    ///
+    /// The placemark's payload does not store its coordinates, but the URL it links to does.
+    #[must_use]
+    pub fn coordinates(&self) -> Option<Coordinates> {
+        [self.url, self.original_url]
+            .into_iter()
+            .flatten()
+            .find_map(Coordinates::from_map_url)
+    }
+}
+
+/// A point on the Earth, in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Coordinates {
+    /// Parse coordinates from an Apple Maps URL, like `https://maps.apple.com/?ll=33.450858,-118.508212`
+    ///
+    /// The pin's location in `ll` is preferred, then `coordinate`, then the search location in `sll`,
+    /// then a search query that is itself a pair of coordinates.
+    #[must_use]
+    pub fn from_map_url(url: &str) -> Option<Self> {
+        let (_, query) = url.split_once('?')?;
+        let query = query.split_once('#').map_or(query, |(query, _)| query);
+
+        ["ll", "coordinate", "sll", "q"]
+            .into_iter()
+            .find_map(|key| {
+                query
+                    .split('&')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(name, _)| *name == key)
+                    .and_then(|(_, value)| Self::parse(value))
+            })
+    }
+
+    /// Parse a pair like `33.450858,-118.508212`, where the comma may be percent-encoded
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.replace("%2C", ",").replace("%2c", ",");
+        let (latitude, longitude) = value.split_once(',')?;
+        let latitude: f64 = latitude.trim().parse().ok()?;
+        let longitude: f64 = longitude.trim().parse().ok()?;
+
+        ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)).then_some(
+            Self {
+                latitude,
+                longitude,
+            },
+        )
+    }
+}
+
+/// Owned version of [`Placemark`], used when the parsed data must outlive its payload
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedPlacemark {
+    /// The name of the placemark
+    pub name: Option<String>,
+    /// The full address formatted associated with the placemark
+    pub address: Option<String>,
+    /// The state or province associated with the placemark
+    pub state: Option<String>,
+    /// The city associated with the placemark
+    pub city: Option<String>,
+    /// The abbreviated country or region name
+    pub iso_country_code: Option<String>,
+    /// The postal code associated with the placemark
+    pub postal_code: Option<String>,
+    /// The name of the country or region associated with the placemark
+    pub country: Option<String>,
+    /// The street associated with the placemark
+    pub street: Option<String>,
+    /// Additional administrative area information for the placemark
+    pub sub_administrative_area: Option<String>,
+    /// Additional city-level information for the placemark
+    pub sub_locality: Option<String>,
+}
+
+impl From<&Placemark<'_>> for OwnedPlacemark {
+    fn from(placemark: &Placemark<'_>) -> Self {
+        Self {
+            name: placemark.name.map(str::to_string),
+            address: placemark.address.map(str::to_string),
+            state: placemark.state.map(str::to_string),
+            city: placemark.city.map(str::to_string),
+            iso_country_code: placemark.iso_country_code.map(str::to_string),
+            postal_code: placemark.postal_code.map(str::to_string),
+            country: placemark.country.map(str::to_string),
+            street: placemark.street.map(str::to_string),
+            sub_administrative_area: placemark.sub_administrative_area.map(str::to_string),
+            sub_locality: placemark.sub_locality.map(str::to_string),
+        }
+    }
+}
+
+impl OwnedPlacemark {
+    /// Borrow the owned data as a [`Placemark`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> Placemark<'_> {
+        Placemark {
+            name: self.name.as_deref(),
+            address: self.address.as_deref(),
+            state: self.state.as_deref(),
+            city: self.city.as_deref(),
+            iso_country_code: self.iso_country_code.as_deref(),
+            postal_code: self.postal_code.as_deref(),
+            country: self.country.as_deref(),
+            street: self.street.as_deref(),
+            sub_administrative_area: self.sub_administrative_area.as_deref(),
+            sub_locality: self.sub_locality.as_deref(),
+        }
+    }
+}
+
+/// Owned version of [`PlacemarkMessage`], used when the parsed data must outlive its payload
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedPlacemarkMessage {
+    /// The URL that ended up serving content, after all redirects
+    pub url: Option<String>,
+    /// The original url, before any redirects
+    pub original_url: Option<String>,
+    /// The full street address of the location
+    pub place_name: Option<String>,
+    /// [Placemark] data for the specified location
+    pub placemark: OwnedPlacemark,
+}
+
+impl From<&PlacemarkMessage<'_>> for OwnedPlacemarkMessage {
+    fn from(message: &PlacemarkMessage<'_>) -> Self {
+        Self {
+            url: message.url.map(str::to_string),
+            original_url: message.original_url.map(str::to_string),
+            place_name: message.place_name.map(str::to_string),
+            placemark: OwnedPlacemark::from(&message.placemark),
+        }
+    }
+}
+
+impl OwnedPlacemarkMessage {
+    /// Borrow the owned data as a [`PlacemarkMessage`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> PlacemarkMessage<'_> {
+        PlacemarkMessage {
+            url: self.url.as_deref(),
+            original_url: self.original_url.as_deref(),
+            place_name: self.place_name.as_deref(),
+            placemark: self.placemark.as_borrowed(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
         message_types::{
-            placemark::{Placemark, PlacemarkMessage},
+            placemark::{Coordinates, Placemark, PlacemarkMessage},
             variants::BalloonProvider,
         },
         util::plist::parse_ns_keyed_archiver,
@@ -208,4 +366,50 @@ mod tests {
 
         assert_eq!(placemark, expected);
     }
+
+    #[test]
+    fn can_get_placemark_coordinates() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/shared_placemark/SharedPlacemark.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        let balloon = PlacemarkMessage::from_map(&parsed).unwrap();
+        let expected = Coordinates {
+            latitude: 33.450858,
+            longitude: -118.508212,
+        };
+
+        assert_eq!(balloon.coordinates(), Some(expected));
+    }
+
+    #[test]
+    fn can_parse_map_url_coordinates() {
+        assert_eq!(
+            Coordinates::from_map_url("https://maps.apple.com/?q=Home&sll=40.7484%2C-73.9857"),
+            Some(Coordinates {
+                latitude: 40.7484,
+                longitude: -73.9857,
+            })
+        );
+        assert_eq!(
+            Coordinates::from_map_url("https://maps.apple.com/?q=51.5007,-0.1246&t=m"),
+            Some(Coordinates {
+                latitude: 51.5007,
+                longitude: -0.1246,
+            })
+        );
+        assert_eq!(
+            Coordinates::from_map_url("https://maps.apple.com/?q=Cherry%20Cove"),
+            None
+        );
+        assert_eq!(
+            Coordinates::from_map_url("https://maps.apple.com/?ll=123.4,5.6"),
+            None
+        );
+        assert_eq!(Coordinates::from_map_url("https://maps.apple.com/"), None);
+    }
 }
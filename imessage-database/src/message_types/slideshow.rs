@@ -0,0 +1,97 @@
+/*!
+ Slideshow messages share an album or memory from the Photos app as an iCloud link.
+*/
+
+use plist::Value;
+
+use crate::{
+    error::plist::PlistParseError,
+    message_types::{app::AppMessage, variants::BalloonProvider},
+};
+
+/// This struct is not documented by Apple, but represents messages displayed as
+/// `com.apple.mobileslideshow.PhotosMessagesApp`
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SlideshowMessage<'a> {
+    /// The name of the album
+    pub title: Option<&'a str>,
+    /// The number of photos in the album
+    pub photo_count: Option<u64>,
+    /// The number of videos in the album
+    pub video_count: Option<u64>,
+    /// The iCloud link to the album
+    pub url: Option<&'a str>,
+}
+
+impl<'a> BalloonProvider<'a> for SlideshowMessage<'a> {
+    fn from_map(payload: &'a Value) -> Result<Self, PlistParseError> {
+        let balloon = AppMessage::from_map(payload)?;
+        // The counts are in the subcaption, i.e. `37 Photos`, and after the title in `Home - 37 Photos`
+        let counts = balloon
+            .subcaption
+            .or_else(|| balloon.ldtext?.rsplit_once(" - ").map(|(_, counts)| counts));
+
+        Ok(Self {
+            title: balloon.caption,
+            photo_count: counts.and_then(|counts| parse_count(counts, "Photo")),
+            video_count: counts.and_then(|counts| parse_count(counts, "Video")),
+            url: balloon.url,
+        })
+    }
+}
+
+/// Find the number of a kind of item in a string like `12 Photos, 3 Videos`
+fn parse_count(counts: &str, noun: &str) -> Option<u64> {
+    counts.split(',').find_map(|count| {
+        let (number, kind) = count.trim().split_once(char::is_whitespace)?;
+        if !kind.trim().starts_with(noun) {
+            return None;
+        }
+        number.parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        message_types::{
+            slideshow::{SlideshowMessage, parse_count},
+            variants::BalloonProvider,
+        },
+        util::plist::parse_ns_keyed_archiver,
+    };
+    use plist::Value;
+    use std::env::current_dir;
+    use std::fs::File;
+
+    #[test]
+    fn test_parse_slideshow() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/Slideshow.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        let balloon = SlideshowMessage::from_map(&parsed).unwrap();
+        let expected = SlideshowMessage {
+            title: Some("Home"),
+            photo_count: Some(37),
+            video_count: None,
+            url: Some("https://share.icloud.com/photos/1337h4x0r_jk#Home"),
+        };
+
+        assert_eq!(balloon, expected);
+    }
+
+    #[test]
+    fn test_parse_slideshow_counts() {
+        assert_eq!(parse_count("1 Photo", "Photo"), Some(1));
+        assert_eq!(parse_count("12 Photos, 3 Videos", "Photo"), Some(12));
+        assert_eq!(parse_count("12 Photos, 3 Videos", "Video"), Some(3));
+        assert_eq!(parse_count("3 Videos", "Photo"), None);
+        assert_eq!(parse_count("Home", "Photo"), None);
+    }
+}
@@ -8,6 +8,11 @@
 ///
 /// Read more about text styles [here](https://www.apple.com/newsroom/2024/06/ios-18-makes-iphone-more-personal-capable-and-intelligent-than-ever/).
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum TextEffect<'a> {
     /// Default, unstyled text
     Default,
@@ -38,7 +43,12 @@ pub enum TextEffect<'a> {
 /// Unit conversion text effect container
 ///
 /// Read more about unit conversions [here](https://www.macrumors.com/how-to/convert-currencies-temperatures-more-ios-16/).
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Unit {
     Currency,
     Distance,
@@ -51,7 +61,12 @@ pub enum Unit {
 /// Traditional text effect container
 ///
 /// Read more about text styles [here](https://www.apple.com/newsroom/2024/06/ios-18-makes-iphone-more-personal-capable-and-intelligent-than-ever/).
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Style {
     /// **Bold** styled text
     Bold,
@@ -68,7 +83,12 @@ pub enum Style {
 /// A message's [`typedstream`](crate::util::typedstream) contains an [`i64`] identifier under the key `__kIMTextEffectAttributeName`.
 ///
 /// Read more about text styles [here](https://www.apple.com/newsroom/2024/06/ios-18-makes-iphone-more-personal-capable-and-intelligent-than-ever/).
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Animation {
     /// Denoted by an ID of `5`
     Big,
@@ -116,3 +136,57 @@ impl Animation {
         }
     }
 }
+
+/// Owned version of [`TextEffect`], used when the parsed data must outlive its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OwnedTextEffect {
+    /// See [`TextEffect::Default`]
+    Default,
+    /// See [`TextEffect::Mention`]
+    Mention(String),
+    /// See [`TextEffect::Link`]
+    Link(String),
+    /// See [`TextEffect::OTP`]
+    OTP,
+    /// See [`TextEffect::Styles`]
+    Styles(Vec<Style>),
+    /// See [`TextEffect::Animated`]
+    Animated(Animation),
+    /// See [`TextEffect::Conversion`]
+    Conversion(Unit),
+}
+
+impl From<&TextEffect<'_>> for OwnedTextEffect {
+    fn from(effect: &TextEffect<'_>) -> Self {
+        match effect {
+            TextEffect::Default => Self::Default,
+            TextEffect::Mention(who) => Self::Mention((*who).to_string()),
+            TextEffect::Link(url) => Self::Link((*url).to_string()),
+            TextEffect::OTP => Self::OTP,
+            TextEffect::Styles(styles) => Self::Styles(styles.clone()),
+            TextEffect::Animated(animation) => Self::Animated(animation.clone()),
+            TextEffect::Conversion(unit) => Self::Conversion(unit.clone()),
+        }
+    }
+}
+
+impl OwnedTextEffect {
+    /// Borrow the owned data as a [`TextEffect`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> TextEffect<'_> {
+        match self {
+            Self::Default => TextEffect::Default,
+            Self::Mention(who) => TextEffect::Mention(who),
+            Self::Link(url) => TextEffect::Link(url),
+            Self::OTP => TextEffect::OTP,
+            Self::Styles(styles) => TextEffect::Styles(styles.clone()),
+            Self::Animated(animation) => TextEffect::Animated(animation.clone()),
+            Self::Conversion(unit) => TextEffect::Conversion(unit.clone()),
+        }
+    }
+}
@@ -21,6 +21,7 @@ use crate::{
 /// This struct is not documented by Apple, but represents messages created by
 /// `com.apple.messages.URLBalloonProvider`.
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct URLMessage<'a> {
     /// The webpage's `<og:title>` attribute
     pub title: Option<&'a str>,
@@ -135,6 +136,71 @@ impl<'a> URLMessage<'a> {
     }
 }
 
+/// Owned version of [`URLMessage`], used when the parsed data must outlive its payload
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedURLMessage {
+    /// The webpage's `<og:title>` attribute
+    pub title: Option<String>,
+    /// The webpage's `<og:description>` attribute
+    pub summary: Option<String>,
+    /// The URL that ended up serving content, after all redirects
+    pub url: Option<String>,
+    /// The original url, before any redirects
+    pub original_url: Option<String>,
+    /// The type of webpage Apple thinks the link represents
+    pub item_type: Option<String>,
+    /// Up to 4 image previews displayed in the background of the bubble
+    pub images: Vec<String>,
+    /// Icons that represent the website, generally the favicon or apple-touch-icon
+    pub icons: Vec<String>,
+    /// The name of a website
+    pub site_name: Option<String>,
+    pub placeholder: bool,
+}
+
+impl From<&URLMessage<'_>> for OwnedURLMessage {
+    fn from(message: &URLMessage<'_>) -> Self {
+        Self {
+            title: message.title.map(str::to_string),
+            summary: message.summary.map(str::to_string),
+            url: message.url.map(str::to_string),
+            original_url: message.original_url.map(str::to_string),
+            item_type: message.item_type.map(str::to_string),
+            images: message
+                .images
+                .iter()
+                .map(|item| (*item).to_string())
+                .collect(),
+            icons: message
+                .icons
+                .iter()
+                .map(|item| (*item).to_string())
+                .collect(),
+            site_name: message.site_name.map(str::to_string),
+            placeholder: message.placeholder,
+        }
+    }
+}
+
+impl OwnedURLMessage {
+    /// Borrow the owned data as a [`URLMessage`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> URLMessage<'_> {
+        URLMessage {
+            title: self.title.as_deref(),
+            summary: self.summary.as_deref(),
+            url: self.url.as_deref(),
+            original_url: self.original_url.as_deref(),
+            item_type: self.item_type.as_deref(),
+            images: self.images.iter().map(String::as_str).collect(),
+            icons: self.icons.iter().map(String::as_str).collect(),
+            site_name: self.site_name.as_deref(),
+            placeholder: self.placeholder,
+        }
+    }
+}
+
 #[cfg(test)]
 mod url_tests {
     use crate::{
@@ -9,10 +9,14 @@ use plist::Value;
 use crate::{
     error::plist::PlistParseError,
     message_types::{
-        app_store::AppStoreMessage, collaboration::CollaborationMessage, music::MusicMessage,
-        placemark::PlacemarkMessage, url::URLMessage,
+        app::AppMessage,
+        app_store::{AppStoreMessage, OwnedAppStoreMessage},
+        collaboration::{CollaborationMessage, OwnedCollaborationMessage},
+        music::{MusicMessage, OwnedMusicMessage},
+        placemark::{OwnedPlacemarkMessage, PlacemarkMessage},
+        url::{OwnedURLMessage, URLMessage},
     },
-    tables::messages::models::GroupAction,
+    tables::messages::models::{GroupAction, OwnedGroupAction},
 };
 
 /// # Tapbacks
@@ -44,6 +48,11 @@ use crate::{
 ///
 /// In this example, a Like on `p:2/` is a like on the third image.
 #[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Tapback<'a> {
     /// Heart
     Loved,
@@ -80,6 +89,11 @@ impl Display for Tapback<'_> {
 /// Messages sent via an app's iMessage integration will send in a special balloon instead of a normal
 /// text balloon. This represents the different variants of message balloon.
 #[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum CustomBalloon<'a> {
     /// Generic third party [applications](crate::message_types::app)
     Application(&'a str),
@@ -106,6 +120,11 @@ pub enum CustomBalloon<'a> {
 /// Apple sometimes overloads `com.apple.messages.URLBalloonProvider` with
 /// other types of messages; this enum represents those variants.
 #[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum URLOverride<'a> {
     /// [`URL`](crate::message_types::url) previews
     Normal(URLMessage<'a>),
@@ -119,11 +138,49 @@ pub enum URLOverride<'a> {
     SharedPlacemark(PlacemarkMessage<'a>),
 }
 
+/// The payload of an app balloon, decoded by [`Message::balloon_payload()`](crate::tables::messages::Message::balloon_payload)
+#[derive(Debug)]
+pub struct BalloonPayload<'a> {
+    /// The kind of balloon that sent the payload
+    pub balloon: CustomBalloon<'a>,
+    /// The payload, decoded from its `NSKeyedArchiver` property list
+    pub payload: Value,
+}
+
+impl BalloonPayload<'_> {
+    /// Parse the payload with the parser for the kind of balloon that sent it
+    pub fn parse(&self) -> Result<ParsedBalloon<'_>, PlistParseError> {
+        match self.balloon {
+            CustomBalloon::URL => {
+                URLMessage::get_url_message_override(&self.payload).map(ParsedBalloon::URL)
+            }
+            _ => AppMessage::from_map(&self.payload).map(ParsedBalloon::App),
+        }
+    }
+}
+
+/// An app balloon parsed from a [`BalloonPayload`]
+#[derive(Debug)]
+pub enum ParsedBalloon<'a> {
+    /// Messages sent with `com.apple.messages.URLBalloonProvider`, see [`URLOverride`]
+    URL(URLOverride<'a>),
+    /// Messages from every other app
+    ///
+    /// Typed parsers like [`CheckInMessage`](crate::message_types::check_in::CheckInMessage) can be built from these
+    /// with `from_app()`.
+    App(AppMessage<'a>),
+}
+
 /// Announcement Message Types
 ///
 /// Announcements are messages sent to a thread for actions that are not balloons, i.e.
 /// updating the name of the group or changing the group photo
 #[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Announcement<'a> {
     /// All parts of the message were unsent
     FullyUnsent,
@@ -138,7 +195,12 @@ pub enum Announcement<'a> {
 /// Tapback Action Container
 ///
 /// Tapbacks can either be added or removed; this enum represents those states
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum TapbackAction {
     /// Tapback was added to the message
     Added,
@@ -151,6 +213,11 @@ pub enum TapbackAction {
 /// Messages can exist as one of many different variants, this encapsulates
 /// all of the possibilities.
 #[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Variant<'a> {
     /// An iMessage with a standard text body that may include attachments
     Normal,
@@ -175,3 +242,265 @@ pub trait BalloonProvider<'a> {
     where
         Self: Sized;
 }
+
+/// Owned version of [`Tapback`], used when the parsed data must outlive its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OwnedTapback {
+    /// See [`Tapback::Loved`]
+    Loved,
+    /// See [`Tapback::Liked`]
+    Liked,
+    /// See [`Tapback::Disliked`]
+    Disliked,
+    /// See [`Tapback::Laughed`]
+    Laughed,
+    /// See [`Tapback::Emphasized`]
+    Emphasized,
+    /// See [`Tapback::Questioned`]
+    Questioned,
+    /// See [`Tapback::Emoji`]
+    Emoji(Option<String>),
+    /// See [`Tapback::Sticker`]
+    Sticker,
+}
+
+impl From<&Tapback<'_>> for OwnedTapback {
+    fn from(tapback: &Tapback<'_>) -> Self {
+        match tapback {
+            Tapback::Loved => Self::Loved,
+            Tapback::Liked => Self::Liked,
+            Tapback::Disliked => Self::Disliked,
+            Tapback::Laughed => Self::Laughed,
+            Tapback::Emphasized => Self::Emphasized,
+            Tapback::Questioned => Self::Questioned,
+            Tapback::Emoji(emoji) => Self::Emoji(emoji.map(str::to_string)),
+            Tapback::Sticker => Self::Sticker,
+        }
+    }
+}
+
+impl OwnedTapback {
+    /// Borrow the owned data as a [`Tapback`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> Tapback<'_> {
+        match self {
+            Self::Loved => Tapback::Loved,
+            Self::Liked => Tapback::Liked,
+            Self::Disliked => Tapback::Disliked,
+            Self::Laughed => Tapback::Laughed,
+            Self::Emphasized => Tapback::Emphasized,
+            Self::Questioned => Tapback::Questioned,
+            Self::Emoji(emoji) => Tapback::Emoji(emoji.as_deref()),
+            Self::Sticker => Tapback::Sticker,
+        }
+    }
+}
+
+/// Owned version of [`CustomBalloon`], used when the parsed data must outlive its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OwnedCustomBalloon {
+    /// See [`CustomBalloon::Application`]
+    Application(String),
+    /// See [`CustomBalloon::URL`]
+    URL,
+    /// See [`CustomBalloon::Handwriting`]
+    Handwriting,
+    /// See [`CustomBalloon::DigitalTouch`]
+    DigitalTouch,
+    /// See [`CustomBalloon::ApplePay`]
+    ApplePay,
+    /// See [`CustomBalloon::Fitness`]
+    Fitness,
+    /// See [`CustomBalloon::Slideshow`]
+    Slideshow,
+    /// See [`CustomBalloon::CheckIn`]
+    CheckIn,
+    /// See [`CustomBalloon::FindMy`]
+    FindMy,
+}
+
+impl From<&CustomBalloon<'_>> for OwnedCustomBalloon {
+    fn from(balloon: &CustomBalloon<'_>) -> Self {
+        match balloon {
+            CustomBalloon::Application(bundle_id) => Self::Application((*bundle_id).to_string()),
+            CustomBalloon::URL => Self::URL,
+            CustomBalloon::Handwriting => Self::Handwriting,
+            CustomBalloon::DigitalTouch => Self::DigitalTouch,
+            CustomBalloon::ApplePay => Self::ApplePay,
+            CustomBalloon::Fitness => Self::Fitness,
+            CustomBalloon::Slideshow => Self::Slideshow,
+            CustomBalloon::CheckIn => Self::CheckIn,
+            CustomBalloon::FindMy => Self::FindMy,
+        }
+    }
+}
+
+impl OwnedCustomBalloon {
+    /// Borrow the owned data as a [`CustomBalloon`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> CustomBalloon<'_> {
+        match self {
+            Self::Application(bundle_id) => CustomBalloon::Application(bundle_id),
+            Self::URL => CustomBalloon::URL,
+            Self::Handwriting => CustomBalloon::Handwriting,
+            Self::DigitalTouch => CustomBalloon::DigitalTouch,
+            Self::ApplePay => CustomBalloon::ApplePay,
+            Self::Fitness => CustomBalloon::Fitness,
+            Self::Slideshow => CustomBalloon::Slideshow,
+            Self::CheckIn => CustomBalloon::CheckIn,
+            Self::FindMy => CustomBalloon::FindMy,
+        }
+    }
+}
+
+/// Owned version of [`URLOverride`], used when the parsed data must outlive its source
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OwnedURLOverride {
+    /// See [`URLOverride::Normal`]
+    Normal(OwnedURLMessage),
+    /// See [`URLOverride::AppleMusic`]
+    AppleMusic(OwnedMusicMessage),
+    /// See [`URLOverride::AppStore`]
+    AppStore(OwnedAppStoreMessage),
+    /// See [`URLOverride::Collaboration`]
+    Collaboration(OwnedCollaborationMessage),
+    /// See [`URLOverride::SharedPlacemark`]
+    SharedPlacemark(OwnedPlacemarkMessage),
+}
+
+impl From<&URLOverride<'_>> for OwnedURLOverride {
+    fn from(balloon: &URLOverride<'_>) -> Self {
+        match balloon {
+            URLOverride::Normal(message) => Self::Normal(message.into()),
+            URLOverride::AppleMusic(message) => Self::AppleMusic(message.into()),
+            URLOverride::AppStore(message) => Self::AppStore(message.into()),
+            URLOverride::Collaboration(message) => Self::Collaboration(message.into()),
+            URLOverride::SharedPlacemark(message) => Self::SharedPlacemark(message.into()),
+        }
+    }
+}
+
+impl OwnedURLOverride {
+    /// Borrow the owned data as a [`URLOverride`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> URLOverride<'_> {
+        match self {
+            Self::Normal(message) => URLOverride::Normal(message.as_borrowed()),
+            Self::AppleMusic(message) => URLOverride::AppleMusic(message.as_borrowed()),
+            Self::AppStore(message) => URLOverride::AppStore(message.as_borrowed()),
+            Self::Collaboration(message) => URLOverride::Collaboration(message.as_borrowed()),
+            Self::SharedPlacemark(message) => URLOverride::SharedPlacemark(message.as_borrowed()),
+        }
+    }
+}
+
+/// Owned version of [`Announcement`], used when the parsed data must outlive its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OwnedAnnouncement {
+    /// See [`Announcement::FullyUnsent`]
+    FullyUnsent,
+    /// See [`Announcement::GroupAction`]
+    GroupAction(OwnedGroupAction),
+    /// See [`Announcement::AudioMessageKept`]
+    AudioMessageKept,
+    /// See [`Announcement::Unknown`]
+    Unknown(i32),
+}
+
+impl From<&Announcement<'_>> for OwnedAnnouncement {
+    fn from(announcement: &Announcement<'_>) -> Self {
+        match announcement {
+            Announcement::FullyUnsent => Self::FullyUnsent,
+            Announcement::GroupAction(action) => Self::GroupAction(action.into()),
+            Announcement::AudioMessageKept => Self::AudioMessageKept,
+            Announcement::Unknown(num) => Self::Unknown(**num),
+        }
+    }
+}
+
+impl OwnedAnnouncement {
+    /// Borrow the owned data as an [`Announcement`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> Announcement<'_> {
+        match self {
+            Self::FullyUnsent => Announcement::FullyUnsent,
+            Self::GroupAction(action) => Announcement::GroupAction(action.as_borrowed()),
+            Self::AudioMessageKept => Announcement::AudioMessageKept,
+            Self::Unknown(num) => Announcement::Unknown(num),
+        }
+    }
+}
+
+/// Owned version of [`Variant`], used when the parsed data must outlive its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OwnedVariant {
+    /// See [`Variant::Normal`]
+    Normal,
+    /// See [`Variant::Edited`]
+    Edited,
+    /// See [`Variant::Tapback`]
+    Tapback(usize, TapbackAction, OwnedTapback),
+    /// See [`Variant::App`]
+    App(OwnedCustomBalloon),
+    /// See [`Variant::SharePlay`]
+    SharePlay,
+    /// See [`Variant::Unknown`]
+    Unknown(i32),
+}
+
+impl From<&Variant<'_>> for OwnedVariant {
+    fn from(variant: &Variant<'_>) -> Self {
+        match variant {
+            Variant::Normal => Self::Normal,
+            Variant::Edited => Self::Edited,
+            Variant::Tapback(idx, action, tapback) => {
+                Self::Tapback(*idx, action.clone(), tapback.into())
+            }
+            Variant::App(balloon) => Self::App(balloon.into()),
+            Variant::SharePlay => Self::SharePlay,
+            Variant::Unknown(num) => Self::Unknown(*num),
+        }
+    }
+}
+
+impl OwnedVariant {
+    /// Borrow the owned data as a [`Variant`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> Variant<'_> {
+        match self {
+            Self::Normal => Variant::Normal,
+            Self::Edited => Variant::Edited,
+            Self::Tapback(idx, action, tapback) => {
+                Variant::Tapback(*idx, action.clone(), tapback.as_borrowed())
+            }
+            Self::App(balloon) => Variant::App(balloon.as_borrowed()),
+            Self::SharePlay => Variant::SharePlay,
+            Self::Unknown(num) => Variant::Unknown(*num),
+        }
+    }
+}
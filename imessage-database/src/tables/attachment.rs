@@ -16,8 +16,13 @@ use crate::{
     error::{attachment::AttachmentError, table::TableError},
     message_types::sticker::{StickerEffect, StickerSource, get_sticker_effect},
     tables::{
+        diagnostic::AttachmentDiagnostic,
         messages::Message,
-        table::{ATTACHMENT, ATTRIBUTION_INFO, GetBlob, STICKER_USER_INFO, Table},
+        schema::{SchemaCapabilities, SchemaFeature},
+        table::{
+            ATTACHMENT, ATTRIBUTION_INFO, GetBlob, MESSAGE_ATTACHMENT_JOIN, STICKER_USER_INFO,
+            Table,
+        },
     },
     util::{
         dirs::home,
@@ -31,7 +36,18 @@ use crate::{
 
 /// The default root directory for iMessage attachment data
 pub const DEFAULT_ATTACHMENT_ROOT: &str = "~/Library/Messages/Attachments";
-const COLS: &str = "a.rowid, a.filename, a.uti, a.mime_type, a.transfer_name, a.total_bytes, a.is_sticker, a.hide_attachment, a.emoji_image_short_description";
+/// The columns read by [`Attachment::from_row()`], selected when the schema has them
+const COLS: [&str; 9] = [
+    "rowid",
+    "filename",
+    "uti",
+    "mime_type",
+    "transfer_name",
+    "total_bytes",
+    "is_sticker",
+    "hide_attachment",
+    "emoji_image_short_description",
+];
 
 /// Represents the [MIME type](https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_Types) of a message's attachment data
 ///
@@ -73,6 +89,7 @@ impl MediaType<'_> {
 
 /// Represents a single row in the `attachment` table.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Attachment {
     pub rowid: i32,
     /// The path to the file on disk
@@ -141,40 +158,48 @@ impl Attachment {
     /// Gets a Vector of attachments associated with a single message
     ///
     /// The order of the attachments aligns with the order of the [`BubbleComponent::Attachment`](crate::tables::messages::models::BubbleComponent::Attachment)s in the message's [`body()`](crate::tables::table::AttributedBody).
-    pub fn from_message(db: &Connection, msg: &Message) -> Result<Vec<Attachment>, TableError> {
-        let mut out_l = vec![];
+    pub fn from_message(
+        db: &Connection,
+        capabilities: &SchemaCapabilities,
+        msg: &Message,
+    ) -> Result<Vec<Attachment>, TableError> {
         if msg.has_attachments() {
-            let mut statement = db
-                .prepare(&format!(
-                    "
-                        SELECT {COLS}
-                        FROM message_attachment_join j 
-                        LEFT JOIN {ATTACHMENT} a ON j.attachment_id = a.ROWID
-                        WHERE j.message_id = {}
-                    ",
-                    msg.rowid
-                ))
-                .or_else(|_| {
-                    db.prepare(&format!(
-                        "
-                            SELECT *
-                            FROM message_attachment_join j 
-                            LEFT JOIN {ATTACHMENT} a ON j.attachment_id = a.ROWID
-                            WHERE j.message_id = {}
-                        ",
-                        msg.rowid
-                    ))
-                })
-                .map_err(TableError::Attachment)?;
-
-            let iter = statement
-                .query_map([], |row| Ok(Attachment::from_row(row)))
-                .map_err(TableError::Attachment)?;
-
-            for attachment in iter {
-                let m = Attachment::extract(attachment)?;
-                out_l.push(m);
-            }
+            return Self::from_message_rowid(db, capabilities, msg.rowid);
+        }
+        Ok(vec![])
+    }
+
+    /// Gets a Vector of attachments associated with a message `ROWID`, see [`Attachment::from_message()`]
+    pub(crate) fn from_message_rowid(
+        db: &Connection,
+        capabilities: &SchemaCapabilities,
+        message_rowid: i32,
+    ) -> Result<Vec<Attachment>, TableError> {
+        let mut out_l = vec![];
+        let columns = COLS
+            .iter()
+            .filter(|column| capabilities.has_column(ATTACHMENT, column))
+            .map(|column| format!("a.{column}"))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let mut statement = db
+            .prepare(&format!(
+                "
+                    SELECT {columns}
+                    FROM {MESSAGE_ATTACHMENT_JOIN} j
+                    LEFT JOIN {ATTACHMENT} a ON j.attachment_id = a.ROWID
+                    WHERE j.message_id = {message_rowid}
+                "
+            ))
+            .map_err(TableError::Attachment)?;
+
+        let iter = statement
+            .query_map([], |row| Ok(Attachment::from_row(row)))
+            .map_err(TableError::Attachment)?;
+
+        for attachment in iter {
+            let m = Attachment::extract(attachment)?;
+            out_l.push(m);
         }
         Ok(out_l)
     }
@@ -298,21 +323,34 @@ impl Attachment {
     }
 
     /// Get the total attachment bytes referenced in the table
+    ///
+    /// If the [`QueryContext`] has filters, only attachments that belong to the messages
+    /// selected by [`Message::stream_rows()`] are included.
     pub fn get_total_attachment_bytes(
         db: &Connection,
+        capabilities: &SchemaCapabilities,
         context: &QueryContext,
     ) -> Result<u64, TableError> {
-        let mut bytes_query = match context.limit {
-            Some(limit) => {
-                let statement = format!("SELECT IFNULL(SUM(total_bytes), 0) FROM {ATTACHMENT} a LIMIT {limit}"); // TODO: Who knows if this works!
-                db.prepare(&statement).map_err(TableError::Attachment)?
-            }
-            None => {
-                db.prepare(&format!(
-                    "SELECT IFNULL(SUM(total_bytes), 0) FROM {ATTACHMENT}"
-                ))
-                .map_err(TableError::Attachment)?
-            }
+        let mut bytes_query = if context.has_filters() {
+            db.prepare(&format!(
+                "SELECT IFNULL(SUM(a.total_bytes), 0)
+                     FROM {ATTACHMENT} a
+                     WHERE a.ROWID IN (
+                         SELECT j.attachment_id
+                         FROM {MESSAGE_ATTACHMENT_JOIN} j
+                         WHERE j.message_id IN ({})
+                     )",
+                Message::generate_rowid_query(
+                    context,
+                    capabilities.supports(SchemaFeature::RecentlyDeleted)
+                )
+            ))
+            .map_err(TableError::Attachment)?
+        } else {
+            db.prepare(&format!(
+                "SELECT IFNULL(SUM(total_bytes), 0) FROM {ATTACHMENT}"
+            ))
+            .map_err(TableError::Attachment)?
         };
         bytes_query
             .query_row([], |r| -> Result<i64> { r.get(0) })
@@ -351,108 +389,102 @@ impl Attachment {
         None
     }
 
-    /// Emit diagnostic data for the Attachments table
+    /// Gather diagnostic data for the Attachments table
     ///
     /// This is defined outside of [`Diagnostic`](crate::tables::table::Diagnostic) because it requires additional data.
     ///
     /// Get the number of attachments that are missing, either because the path is missing from the
-    /// table or the path does not point to a file.
+    /// table or the path does not point to a file, and the size of the missing files.
     ///
     /// # Example:
     ///
     /// ```
     /// use imessage_database::util::{dirs::default_db_path, platform::Platform};
-    /// use imessage_database::tables::table::{Diagnostic, get_connection};
+    /// use imessage_database::tables::table::get_connection;
     /// use imessage_database::tables::attachment::Attachment;
     ///
     /// let db_path = default_db_path();
     /// let conn = get_connection(&db_path).unwrap();
-    /// Attachment::run_diagnostic(&conn, &db_path, &Platform::macOS);
+    /// let report = Attachment::diagnostic(&conn, &db_path, &Platform::macOS).unwrap();
     /// ```
     ///
     /// `db_path` is the path to the root of the backup directory.
     /// This is the same path used by [`get_connection()`](crate::tables::table::get_connection).
-    pub fn run_diagnostic(
+    pub fn diagnostic(
         db: &Connection,
         db_path: &Path,
         platform: &Platform,
-    ) -> Result<(), TableError> {
-        processing();
-        let mut total_attachments = 0;
-        let mut null_attachments = 0;
-        let mut size_on_disk: u64 = 0;
+    ) -> Result<AttachmentDiagnostic, TableError> {
+        let mut report = AttachmentDiagnostic::default();
         let mut statement_paths = db
-            .prepare(&format!("SELECT filename FROM {ATTACHMENT}"))
+            .prepare(&format!("SELECT filename, total_bytes FROM {ATTACHMENT}"))
             .map_err(TableError::Attachment)?;
         let paths = statement_paths
-            .query_map([], |r| Ok(r.get(0)))
+            .query_map([], |r| {
+                Ok((r.get::<_, Option<String>>(0)?, r.get::<_, Option<i64>>(1)?))
+            })
             .map_err(TableError::Attachment)?;
 
-        let missing_files = paths
-            .filter_map(Result::ok)
-            .filter(|path: &Result<String, Error>| {
-                // Keep track of the number of attachments in the table
-                total_attachments += 1;
-                if let Ok(filepath) = path {
-                    match platform {
-                        Platform::macOS => {
-                            let path = Attachment::gen_macos_attachment(filepath);
-                            let file = Path::new(&path);
-                            if let Ok(metadata) = file.metadata() {
-                                size_on_disk += metadata.len();
-                            }
-                            !file.exists()
-                        }
-                        Platform::iOS => {
-                            if let Some(parsed_path) =
-                                Attachment::gen_ios_attachment(filepath, db_path)
-                            {
-                                let file = Path::new(&parsed_path);
-                                if let Ok(metadata) = file.metadata() {
-                                    size_on_disk += metadata.len();
-                                }
-                                return !file.exists();
-                            }
-                            // This hits if the attachment path doesn't get generated
-                            true
-                        }
-                    }
-                } else {
-                    // This hits if there is no path provided for the current attachment
-                    null_attachments += 1;
-                    true
+        for (path, bytes) in paths.filter_map(Result::ok) {
+            // Keep track of the number of attachments in the table
+            report.total_attachments += 1;
+            let file = match (path, platform) {
+                (Some(filepath), Platform::macOS) => {
+                    Some(Attachment::gen_macos_attachment(&filepath))
                 }
+                // This is `None` if the attachment path doesn't get generated
+                (Some(filepath), Platform::iOS) => {
+                    Attachment::gen_ios_attachment(&filepath, db_path)
+                }
+                // This hits if there is no path provided for the current attachment
+                (None, _) => {
+                    report.missing_paths += 1;
+                    None
+                }
+            };
+
+            match file.and_then(|file| Path::new(&file).metadata().ok()) {
+                Some(metadata) => report.bytes_on_disk += metadata.len(),
+                None => {
+                    report.missing_files += 1;
+                    report.missing_bytes += bytes.and_then(|b| u64::try_from(b).ok()).unwrap_or(0);
+                }
+            }
+        }
+
+        report.bytes_referenced = SchemaCapabilities::probe(db)
+            .and_then(|capabilities| {
+                Attachment::get_total_attachment_bytes(db, &capabilities, &QueryContext::default())
             })
-            .count();
+            .unwrap_or(0);
 
-        let total_bytes =
-            Attachment::get_total_attachment_bytes(db, &QueryContext::default()).unwrap_or(0);
+        Ok(report)
+    }
 
+    /// Emit diagnostic data for the Attachments table to `stdout`
+    ///
+    /// This is defined outside of [`Diagnostic`](crate::tables::table::Diagnostic) because it requires additional data.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::{dirs::default_db_path, platform::Platform};
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::attachment::Attachment;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// Attachment::run_diagnostic(&conn, &db_path, &Platform::macOS);
+    /// ```
+    pub fn run_diagnostic(
+        db: &Connection,
+        db_path: &Path,
+        platform: &Platform,
+    ) -> Result<(), TableError> {
+        processing();
+        let report = Attachment::diagnostic(db, db_path, platform);
         done_processing();
-
-        if total_attachments > 0 {
-            println!("\rAttachment diagnostic data:");
-            println!("    Total attachments: {total_attachments}");
-            println!(
-                "        Data referenced in table: {}",
-                format_file_size(total_bytes)
-            );
-            println!(
-                "        Data present on disk: {}",
-                format_file_size(size_on_disk)
-            );
-            if missing_files > 0 && total_attachments > 0 {
-                println!(
-                    "    Missing files: {missing_files:?} ({:.0}%)",
-                    (missing_files as f64 / f64::from(total_attachments)) * 100f64
-                );
-                println!("        No path provided: {null_attachments}");
-                println!(
-                    "        No file located: {}",
-                    missing_files.saturating_sub(null_attachments)
-                );
-            }
-        }
+        print!("{}", report?);
         Ok(())
     }
 
@@ -527,6 +559,7 @@ mod tests {
     use crate::{
         tables::{
             attachment::{Attachment, DEFAULT_ATTACHMENT_ROOT, MediaType},
+            schema::SchemaCapabilities,
             table::get_connection,
         },
         util::{platform::Platform, query_context::QueryContext},
@@ -743,10 +776,13 @@ mod tests {
             .unwrap()
             .join("imessage-database/test_data/db/test.db");
         let connection = get_connection(&db_path).unwrap();
+        let capabilities = SchemaCapabilities::probe(&connection).unwrap();
 
         let context = QueryContext::default();
 
-        assert!(Attachment::get_total_attachment_bytes(&connection, &context).is_ok());
+        assert!(
+            Attachment::get_total_attachment_bytes(&connection, &capabilities, &context).is_ok()
+        );
     }
 
     #[test]
@@ -757,11 +793,14 @@ mod tests {
             .unwrap()
             .join("imessage-database/test_data/db/test.db");
         let connection = get_connection(&db_path).unwrap();
+        let capabilities = SchemaCapabilities::probe(&connection).unwrap();
 
         let mut context = QueryContext::default();
         context.set_limit(10);
 
-        assert!(Attachment::get_total_attachment_bytes(&connection, &context).is_ok());
+        assert!(
+            Attachment::get_total_attachment_bytes(&connection, &capabilities, &context).is_ok()
+        );
     }
 
     #[test]
@@ -772,12 +811,15 @@ mod tests {
             .unwrap()
             .join("imessage-database/test_data/db/test.db");
         let connection = get_connection(&db_path).unwrap();
+        let capabilities = SchemaCapabilities::probe(&connection).unwrap();
 
         let mut context = QueryContext::default();
         context.set_selected_chat_ids(BTreeSet::from([1, 2, 3]));
         context.set_selected_handle_ids(BTreeSet::from([1, 2, 3]));
 
-        assert!(Attachment::get_total_attachment_bytes(&connection, &context).is_ok());
+        assert!(
+            Attachment::get_total_attachment_bytes(&connection, &capabilities, &context).is_ok()
+        );
     }
 
     #[test]
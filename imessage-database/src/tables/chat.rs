@@ -16,6 +16,7 @@ use crate::{
 
 /// Represents a single row in the `chat` table.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chat {
     pub rowid: i32,
     pub chat_identifier: String,
@@ -6,10 +6,12 @@ use std::collections::{BTreeSet, HashMap, HashSet};
 
 use crate::{
     error::table::TableError,
-    tables::table::{
-        CHAT_HANDLE_JOIN, CHAT_MESSAGE_JOIN, Cacheable, Deduplicate, Diagnostic, Table,
+    tables::{
+        diagnostic::ChatDiagnostic,
+        table::{
+            CHAT, CHAT_HANDLE_JOIN, CHAT_MESSAGE_JOIN, Cacheable, Deduplicate, Diagnostic, Table,
+        },
     },
-    util::output::{done_processing, processing},
 };
 use rusqlite::{Connection, Error, Result, Row, Statement};
 
@@ -127,10 +129,13 @@ impl Deduplicate for ChatToHandle {
 }
 
 impl Diagnostic for ChatToHandle {
-    /// Emit diagnostic data for the Chat to Handle join table
+    type Report = ChatDiagnostic;
+
+    /// Gather diagnostic data for the Chat to Handle join table
     ///
     /// Get the number of chats referenced in the messages table
-    /// that do not exist in this join table:
+    /// that do not exist in this join table, and the number of chats
+    /// that have the same participants as another chat:
     /// # Example:
     ///
     /// ```
@@ -140,11 +145,10 @@ impl Diagnostic for ChatToHandle {
     ///
     /// let db_path = default_db_path();
     /// let conn = get_connection(&db_path).unwrap();
+    /// let report = ChatToHandle::diagnostic(&conn).unwrap();
     /// ChatToHandle::run_diagnostic(&conn);
     /// ```
-    fn run_diagnostic(db: &Connection) -> Result<(), TableError> {
-        processing();
-
+    fn diagnostic(db: &Connection) -> Result<ChatDiagnostic, TableError> {
         // Get the Chat IDs that are associated with messages
         let mut statement_message_chats = db
             .prepare(&format!("SELECT DISTINCT chat_id from {CHAT_MESSAGE_JOIN}"))
@@ -159,32 +163,25 @@ impl Diagnostic for ChatToHandle {
             }
         });
 
-        // Get the Chat IDs that are associated with handles
-        let mut statement_handle_chats = db
-            .prepare(&format!("SELECT DISTINCT chat_id from {CHAT_HANDLE_JOIN}"))
-            .map_err(TableError::ChatToHandle)?;
-        let statement_handle_chat_rows = statement_handle_chats
-            .query_map([], |row: &Row| -> Result<i32> { row.get(0) })
-            .map_err(TableError::ChatToHandle)?;
-        let mut unique_chats_from_handles: HashSet<i32> = HashSet::new();
-        statement_handle_chat_rows.into_iter().for_each(|row| {
-            if let Ok(row) = row {
-                unique_chats_from_handles.insert(row);
-            }
-        });
-
-        done_processing();
+        // Get the participants of each chat
+        let chats = ChatToHandle::cache(db)?;
+        let unique_chats_from_handles: HashSet<i32> = chats.keys().copied().collect();
+        let deduplicated_chats: HashSet<i32> = ChatToHandle::dedupe(&chats).into_values().collect();
 
-        // Find the set difference and emit
+        // Find the set difference
         let chats_with_no_handles = unique_chats_from_messages
             .difference(&unique_chats_from_handles)
             .count();
-        if chats_with_no_handles > 0 {
-            println!("Thread diagnostic data:");
-            println!("    Chats with no handles: {chats_with_no_handles:?}");
-        }
 
-        Ok(())
+        let total_chats: u64 = db
+            .query_row(&format!("SELECT COUNT(*) FROM {CHAT}"), [], |r| r.get(0))
+            .map_err(TableError::ChatToHandle)?;
+
+        Ok(ChatDiagnostic {
+            total_chats,
+            chats_with_no_handles: chats_with_no_handles as u64,
+            duplicated_chats: (chats.len() - deduplicated_chats.len()) as u64,
+        })
     }
 }
 
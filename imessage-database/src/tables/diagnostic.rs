@@ -0,0 +1,541 @@
+/*!
+ Structured results of the table diagnostics.
+
+ Each table that implements [`Diagnostic`] gathers its data into one of the reports in this module, and
+ [`DiagnosticReport::generate()`] collects all of them, along with the schema generation of the database and the
+ [`SchemaFeature`]s it supports.
+ The reports implement [`Display`] to emit the same text that [`Diagnostic::run_diagnostic()`] prints, and
+ with the `serde` feature they can be serialized for other tools to consume.
+
+ # Example:
+
+ ```
+ use imessage_database::util::{dirs::default_db_path, platform::Platform};
+ use imessage_database::tables::{diagnostic::DiagnosticReport, table::get_connection};
+
+ let db_path = default_db_path();
+ let conn = get_connection(&db_path).unwrap();
+ let report = DiagnosticReport::generate(&conn, &db_path, &Platform::macOS).unwrap();
+ println!("{report}");
+ ```
+*/
+
+use std::{
+    fmt::{Display, Formatter, Result},
+    path::Path,
+};
+
+use rusqlite::Connection;
+
+use crate::{
+    error::table::TableError,
+    tables::{
+        attachment::Attachment,
+        chat_handle::ChatToHandle,
+        handle::Handle,
+        messages::Message,
+        schema::{SchemaCapabilities, SchemaFeature},
+        table::Diagnostic,
+    },
+    util::{platform::Platform, size::format_file_size},
+};
+
+/// Generations of the iMessage database schema, as distinguished by their [`SchemaCapabilities`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SchemaGeneration {
+    /// macOS Catalina, iOS 13 and older: no replies
+    Ios13Older,
+    /// macOS Big Sur to Monterey, iOS 14 to iOS 15: replies, but no edits or Recently Deleted
+    Ios14To15,
+    /// macOS Ventura+ and iOS 16+: edits, unsends, and Recently Deleted
+    Ios16Newer,
+    /// The database does not have the tables messages are read from
+    Unknown,
+}
+
+impl SchemaGeneration {
+    /// Determine the schema generation from the database's [`SchemaCapabilities`]
+    #[must_use]
+    pub fn detect(db: &Connection) -> Self {
+        SchemaCapabilities::probe(db).map_or(SchemaGeneration::Unknown, |capabilities| {
+            SchemaGeneration::from(&capabilities)
+        })
+    }
+}
+
+impl From<&SchemaCapabilities> for SchemaGeneration {
+    fn from(capabilities: &SchemaCapabilities) -> Self {
+        if !capabilities.can_read_messages() {
+            SchemaGeneration::Unknown
+        } else if capabilities.supports(SchemaFeature::RecentlyDeleted)
+            && capabilities.supports(SchemaFeature::Edits)
+        {
+            SchemaGeneration::Ios16Newer
+        } else if capabilities.supports(SchemaFeature::Replies) {
+            SchemaGeneration::Ios14To15
+        } else {
+            SchemaGeneration::Ios13Older
+        }
+    }
+}
+
+impl Display for SchemaGeneration {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            SchemaGeneration::Ios13Older => write!(fmt, "macOS Catalina, iOS 13 and older"),
+            SchemaGeneration::Ios14To15 => write!(fmt, "macOS Big Sur to Monterey, iOS 14 to 15"),
+            SchemaGeneration::Ios16Newer => write!(fmt, "macOS Ventura+, iOS 16+"),
+            SchemaGeneration::Unknown => write!(fmt, "Unknown"),
+        }
+    }
+}
+
+/// Diagnostic data for the `message` table
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MessageDiagnostic {
+    pub total_messages: u64,
+    /// Messages that are not in any chat or in Recently Deleted
+    pub messages_without_chat: u64,
+    /// Messages that are joined to more than one chat
+    pub messages_in_multiple_chats: u64,
+    /// Messages in Recently Deleted, or `None` if the schema does not support it
+    pub recently_deleted: Option<u64>,
+    /// Messages whose `attributedBody` cannot be deserialized as [`typedstream`](crate::util::typedstream) data
+    pub unparseable_bodies: u64,
+    /// Messages whose `message_summary_info` is not a valid `plist`
+    pub unparseable_summaries: u64,
+}
+
+impl Display for MessageDiagnostic {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        writeln!(fmt, "Message diagnostic data:")?;
+        writeln!(fmt, "    Total messages: {}", self.total_messages)?;
+        if self.messages_without_chat > 0 {
+            writeln!(
+                fmt,
+                "    Messages not associated with a chat: {}",
+                self.messages_without_chat
+            )?;
+        }
+        if self.messages_in_multiple_chats > 0 {
+            writeln!(
+                fmt,
+                "    Messages belonging to more than one chat: {}",
+                self.messages_in_multiple_chats
+            )?;
+        }
+        if let Some(deleted) = self.recently_deleted.filter(|deleted| *deleted > 0) {
+            writeln!(fmt, "    Messages in Recently Deleted: {deleted}")?;
+        }
+        if self.unparseable_bodies > 0 {
+            writeln!(
+                fmt,
+                "    Message bodies that cannot be parsed: {}",
+                self.unparseable_bodies
+            )?;
+        }
+        if self.unparseable_summaries > 0 {
+            writeln!(
+                fmt,
+                "    Edit summaries that cannot be parsed: {}",
+                self.unparseable_summaries
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Diagnostic data for the `handle` table
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HandleDiagnostic {
+    pub total_handles: u64,
+    /// Rows that repeat the phone number or email of an earlier row, usually for a different service
+    pub duplicated_handles: u64,
+    /// Contacts whose `person_centric_id` is shared by more than one handle, or `None` if the column is not available
+    pub contacts_with_multiple_ids: Option<u64>,
+}
+
+impl Display for HandleDiagnostic {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        let contacts = self.contacts_with_multiple_ids.unwrap_or(0);
+        if self.duplicated_handles > 0 || contacts > 0 {
+            writeln!(fmt, "Handle diagnostic data:")?;
+            if self.duplicated_handles > 0 {
+                writeln!(fmt, "    Duplicated handles: {}", self.duplicated_handles)?;
+            }
+            if contacts > 0 {
+                writeln!(fmt, "    Contacts with more than one ID: {contacts}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Diagnostic data for the `chat` and `chat_handle_join` tables
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChatDiagnostic {
+    pub total_chats: u64,
+    /// Chats that contain messages but have no participants
+    pub chats_with_no_handles: u64,
+    /// Chats with the same participants as an earlier chat
+    pub duplicated_chats: u64,
+}
+
+impl Display for ChatDiagnostic {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        if self.chats_with_no_handles > 0 || self.duplicated_chats > 0 {
+            writeln!(fmt, "Thread diagnostic data:")?;
+            if self.chats_with_no_handles > 0 {
+                writeln!(
+                    fmt,
+                    "    Chats with no handles: {}",
+                    self.chats_with_no_handles
+                )?;
+            }
+            if self.duplicated_chats > 0 {
+                writeln!(
+                    fmt,
+                    "    Chats with the same participants as another chat: {}",
+                    self.duplicated_chats
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Diagnostic data for the `attachment` table
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AttachmentDiagnostic {
+    pub total_attachments: u64,
+    /// Attachments whose file is not on disk, including those without a path
+    pub missing_files: u64,
+    /// Attachments without a path in the table
+    pub missing_paths: u64,
+    /// The size of the missing files, according to the table
+    pub missing_bytes: u64,
+    /// The size of every attachment, according to the table
+    pub bytes_referenced: u64,
+    /// The size of the attachment files that are on disk
+    pub bytes_on_disk: u64,
+}
+
+impl Display for AttachmentDiagnostic {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        if self.total_attachments > 0 {
+            writeln!(fmt, "Attachment diagnostic data:")?;
+            writeln!(fmt, "    Total attachments: {}", self.total_attachments)?;
+            writeln!(
+                fmt,
+                "        Data referenced in table: {}",
+                format_file_size(self.bytes_referenced)
+            )?;
+            writeln!(
+                fmt,
+                "        Data present on disk: {}",
+                format_file_size(self.bytes_on_disk)
+            )?;
+            if self.missing_files > 0 {
+                writeln!(
+                    fmt,
+                    "    Missing files: {} ({:.0}%), {}",
+                    self.missing_files,
+                    (self.missing_files as f64 / self.total_attachments as f64) * 100f64,
+                    format_file_size(self.missing_bytes)
+                )?;
+                writeln!(fmt, "        No path provided: {}", self.missing_paths)?;
+                writeln!(
+                    fmt,
+                    "        No file located: {}",
+                    self.missing_files.saturating_sub(self.missing_paths)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Diagnostic data for every table in the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiagnosticReport {
+    pub schema: SchemaGeneration,
+    /// The schema-dependent features the database supports
+    pub features: Vec<SchemaFeature>,
+    pub messages: MessageDiagnostic,
+    pub handles: HandleDiagnostic,
+    pub chats: ChatDiagnostic,
+    pub attachments: AttachmentDiagnostic,
+}
+
+impl DiagnosticReport {
+    /// Gather diagnostic data for every table
+    ///
+    /// `db_path` is the path to the root of the backup directory.
+    /// This is the same path used by [`get_connection()`](crate::tables::table::get_connection).
+    pub fn generate(
+        db: &Connection,
+        db_path: &Path,
+        platform: &Platform,
+    ) -> std::result::Result<Self, TableError> {
+        let capabilities = SchemaCapabilities::probe(db)?;
+        Ok(DiagnosticReport {
+            schema: SchemaGeneration::from(&capabilities),
+            features: capabilities.features(),
+            messages: Message::diagnostic(db)?,
+            handles: Handle::diagnostic(db)?,
+            chats: ChatToHandle::diagnostic(db)?,
+            attachments: Attachment::diagnostic(db, db_path, platform)?,
+        })
+    }
+}
+
+impl Display for DiagnosticReport {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        writeln!(fmt, "Schema: {}", self.schema)?;
+        if !self.features.is_empty() {
+            writeln!(
+                fmt,
+                "Features: {}",
+                self.features
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )?;
+        }
+        write!(fmt, "{}", self.messages)?;
+        write!(fmt, "{}", self.handles)?;
+        write!(fmt, "{}", self.chats)?;
+        write!(fmt, "{}", self.attachments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, remove_dir_all, remove_file},
+        path::PathBuf,
+    };
+
+    use rusqlite::params;
+
+    use crate::{
+        tables::{
+            diagnostic::{DiagnosticReport, SchemaGeneration},
+            schema::SchemaFeature,
+            table::{Diagnostic, get_connection},
+        },
+        util::{
+            fixture::{FixtureBuilder, NewMessage, Schema},
+            platform::Platform,
+        },
+    };
+
+    const DATE: i64 = 694_000_000_000_000_000;
+
+    fn build(name: &str, schema: Schema) -> (PathBuf, FixtureBuilder) {
+        let dir =
+            std::env::temp_dir().join(format!("imessage-diagnostic-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let fixture = FixtureBuilder::create(&dir.join("chat.db"), schema).unwrap();
+        (dir, fixture)
+    }
+
+    #[test]
+    fn can_detect_schema() {
+        for (name, schema, expected) in [
+            (
+                "schema-13",
+                Schema::Ios13Older,
+                SchemaGeneration::Ios13Older,
+            ),
+            ("schema-14", Schema::Ios14To15, SchemaGeneration::Ios14To15),
+            (
+                "schema-16",
+                Schema::Ios16Newer,
+                SchemaGeneration::Ios16Newer,
+            ),
+        ] {
+            let (dir, fixture) = build(name, schema);
+            assert_eq!(SchemaGeneration::detect(fixture.connection()), expected);
+            remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn can_generate_empty_report() {
+        let (dir, fixture) = build("empty", Schema::Ios16Newer);
+        let report =
+            DiagnosticReport::generate(fixture.connection(), &dir, &Platform::macOS).unwrap();
+
+        assert_eq!(report.schema, SchemaGeneration::Ios16Newer);
+        assert_eq!(report.features, SchemaFeature::ALL.to_vec());
+        assert_eq!(report.messages.total_messages, 0);
+        assert_eq!(report.messages.recently_deleted, Some(0));
+        assert_eq!(report.handles.total_handles, 0);
+        assert_eq!(report.chats.total_chats, 0);
+        assert_eq!(report.attachments.total_attachments, 0);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_count_orphaned_and_deleted_messages() {
+        let (dir, mut fixture) = build("orphaned", Schema::Ios16Newer);
+        let chat = fixture.chat("+15558675309", None, &[]).unwrap();
+        let other = fixture.chat("test@example.com", None, &[]).unwrap();
+        let kept = fixture
+            .message(chat, NewMessage::text("Kept", DATE))
+            .unwrap();
+        let deleted = fixture
+            .message(chat, NewMessage::text("Deleted", DATE))
+            .unwrap();
+        fixture.recently_delete(deleted.rowid, DATE).unwrap();
+        // Removing a message from its only chat deletes it, so insert the orphan directly
+        fixture
+            .connection()
+            .execute("INSERT INTO message (guid) VALUES ('orphaned')", [])
+            .unwrap();
+        fixture
+            .connection()
+            .execute(
+                "INSERT INTO chat_message_join (chat_id, message_id) VALUES (?1, ?2)",
+                params![other, kept.rowid],
+            )
+            .unwrap();
+
+        let report = crate::tables::messages::Message::diagnostic(fixture.connection()).unwrap();
+        assert_eq!(report.total_messages, 3);
+        assert_eq!(report.messages_without_chat, 1);
+        assert_eq!(report.messages_in_multiple_chats, 1);
+        assert_eq!(report.recently_deleted, Some(1));
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_count_unparseable_blobs() {
+        let (dir, mut fixture) = build("blobs", Schema::Ios16Newer);
+        let chat = fixture.chat("+15558675309", None, &[]).unwrap();
+        fixture
+            .message(chat, NewMessage::text("Fine", DATE))
+            .unwrap();
+        let broken = fixture
+            .message(chat, NewMessage::text("Broken", DATE))
+            .unwrap();
+        fixture
+            .connection()
+            .execute(
+                "UPDATE message SET attributedBody = X'0102', message_summary_info = X'0304' WHERE ROWID = ?1",
+                params![broken.rowid],
+            )
+            .unwrap();
+
+        let report = crate::tables::messages::Message::diagnostic(fixture.connection()).unwrap();
+        assert_eq!(report.unparseable_bodies, 1);
+        assert_eq!(report.unparseable_summaries, 1);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_skip_missing_columns_in_older_schema() {
+        let (dir, mut fixture) = build("older", Schema::Ios13Older);
+        let chat = fixture.chat("+15558675309", None, &[]).unwrap();
+        fixture
+            .message(chat, NewMessage::text("Old", DATE))
+            .unwrap();
+
+        let report =
+            DiagnosticReport::generate(fixture.connection(), &dir, &Platform::macOS).unwrap();
+        assert_eq!(report.schema, SchemaGeneration::Ios13Older);
+        assert!(report.features.is_empty());
+        assert_eq!(report.messages.total_messages, 1);
+        assert_eq!(report.messages.recently_deleted, None);
+        assert_eq!(report.messages.unparseable_bodies, 0);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_count_duplicated_handles_and_chats() {
+        let (dir, mut fixture) = build("duplicates", Schema::Ios16Newer);
+        let imessage = fixture.handle("+15558675309", "iMessage").unwrap();
+        let sms = fixture.handle("+15558675309", "SMS").unwrap();
+        let other = fixture.handle("test@example.com", "iMessage").unwrap();
+        fixture
+            .connection()
+            .execute(
+                "UPDATE handle SET person_centric_id = 'person' WHERE ROWID IN (?1, ?2)",
+                params![imessage, other],
+            )
+            .unwrap();
+        fixture.chat("+15558675309", None, &[imessage]).unwrap();
+        fixture.chat("+1 555-867-5309", None, &[imessage]).unwrap();
+        fixture.chat("SMS", None, &[sms]).unwrap();
+
+        let report =
+            DiagnosticReport::generate(fixture.connection(), &dir, &Platform::macOS).unwrap();
+        assert_eq!(report.handles.total_handles, 3);
+        assert_eq!(report.handles.duplicated_handles, 1);
+        assert_eq!(report.handles.contacts_with_multiple_ids, Some(1));
+        assert_eq!(report.chats.total_chats, 3);
+        assert_eq!(report.chats.duplicated_chats, 1);
+        assert_eq!(report.chats.chats_with_no_handles, 0);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_count_missing_attachments() {
+        let (dir, mut fixture) = build("attachments", Schema::Ios16Newer);
+        let chat = fixture.chat("+15558675309", None, &[]).unwrap();
+        let inserted = fixture
+            .message(
+                chat,
+                NewMessage::new(DATE)
+                    .with_attachment("a.png", "image/png", &[0; 10])
+                    .with_attachment("b.png", "image/png", &[0; 25]),
+            )
+            .unwrap();
+        remove_file(&inserted.attachments[1].path).unwrap();
+
+        let report =
+            DiagnosticReport::generate(fixture.connection(), &dir, &Platform::macOS).unwrap();
+        assert_eq!(report.attachments.total_attachments, 2);
+        assert_eq!(report.attachments.missing_files, 1);
+        assert_eq!(report.attachments.missing_paths, 0);
+        assert_eq!(report.attachments.missing_bytes, 25);
+        assert_eq!(report.attachments.bytes_referenced, 35);
+        assert_eq!(report.attachments.bytes_on_disk, 10);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_render_report() {
+        let (dir, mut fixture) = build("render", Schema::Ios16Newer);
+        let chat = fixture.chat("+15558675309", None, &[]).unwrap();
+        fixture.message(chat, NewMessage::text("Hi", DATE)).unwrap();
+
+        let report = DiagnosticReport::generate(
+            &get_connection(&dir.join("chat.db")).unwrap(),
+            &dir,
+            &Platform::macOS,
+        )
+        .unwrap()
+        .to_string();
+        assert!(report.starts_with("Schema: macOS Ventura+, iOS 16+\nFeatures: replies, edits, unsends, Recently Deleted, emoji tapbacks, scheduled messages, Genmoji\n"));
+        assert!(report.contains("    Total messages: 1\n"));
+        assert!(!report.contains("Attachment diagnostic data"));
+
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -7,12 +7,15 @@ use std::collections::{BTreeSet, HashMap};
 
 use crate::{
     error::table::TableError,
-    tables::table::{Cacheable, Deduplicate, Diagnostic, HANDLE, ME, Table},
-    util::output::{done_processing, processing},
+    tables::{
+        diagnostic::HandleDiagnostic,
+        table::{Cacheable, Deduplicate, Diagnostic, HANDLE, ME, Table},
+    },
 };
 
 /// Represents a single row in the `handle` table.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Handle {
     pub rowid: i32,
     /// Identifier for a contact, i.e. a phone number or email address
@@ -141,7 +144,9 @@ impl Deduplicate for Handle {
 }
 
 impl Diagnostic for Handle {
-    /// Emit diagnostic data for the Handles table
+    type Report = HandleDiagnostic;
+
+    /// Gather diagnostic data for the Handles table
     ///
     /// Get the number of handles that are duplicated
     ///
@@ -160,33 +165,39 @@ impl Diagnostic for Handle {
     ///
     /// let db_path = default_db_path();
     /// let conn = get_connection(&db_path).unwrap();
+    /// let report = Handle::diagnostic(&conn).unwrap();
     /// Handle::run_diagnostic(&conn);
     /// ```
-    fn run_diagnostic(db: &Connection) -> Result<(), TableError> {
+    fn diagnostic(db: &Connection) -> Result<HandleDiagnostic, TableError> {
+        let (total_handles, duplicated_handles): (u64, u64) = db
+            .query_row(
+                &format!("SELECT COUNT(*), COUNT(*) - COUNT(DISTINCT id) FROM {HANDLE}"),
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .map_err(TableError::Handle)?;
+
         let query = concat!(
-            "SELECT COUNT(DISTINCT person_centric_id) ",
+            "SELECT COUNT(*) FROM (",
+            "SELECT person_centric_id ",
             "FROM handle ",
-            "WHERE person_centric_id NOT NULL"
+            "WHERE person_centric_id NOT NULL ",
+            "GROUP BY person_centric_id ",
+            "HAVING COUNT(DISTINCT id) > 1)"
         );
-
-        if let Ok(mut rows) = db.prepare(query).map_err(TableError::Handle) {
-            processing();
-
-            let count_dupes: Option<i32> = rows
-                .query_row([], |r| r.get(0))
-                .map_err(TableError::Handle)?;
-
-            done_processing();
-
-            if let Some(dupes) = count_dupes {
-                if dupes > 0 {
-                    println!("Handle diagnostic data:");
-                    println!("    Contacts with more than one ID: {dupes}");
-                }
-            }
-        }
-
-        Ok(())
+        let contacts_with_multiple_ids = match db.prepare(query) {
+            Ok(mut rows) => Some(
+                rows.query_row([], |r| r.get(0))
+                    .map_err(TableError::Handle)?,
+            ),
+            Err(_) => None,
+        };
+
+        Ok(HandleDiagnostic {
+            total_handles,
+            duplicated_handles,
+            contacts_with_multiple_ids,
+        })
     }
 }
 
@@ -0,0 +1,611 @@
+/*!
+ A navigable tree of the messages in a chat, with replies, tapbacks, and edits attached to the parts they apply to.
+
+ Replies, [tapbacks](crate::message_types::variants::Tapback), and [edits](crate::message_types::edited) are
+ stored as separate rows or blobs that point back at a part of another message's
+ [`body()`](crate::tables::table::AttributedBody::body). A [`Conversation`] resolves those pointers once:
+ each [`Thread`] owns a message and one [`MessagePart`] per body part, and each part holds its
+ [`Reaction`]s, the replies that quote it, and its edit history.
+
+ # Example
+
+ ```rust
+ use imessage_database::{
+     tables::{
+         messages::conversation::Conversation, schema::SchemaCapabilities, table::get_connection,
+     },
+     util::dirs::default_db_path,
+ };
+
+ let db_path = default_db_path();
+ let conn = get_connection(&db_path).unwrap();
+ let capabilities = SchemaCapabilities::probe(&conn).unwrap();
+
+ let conversation = Conversation::load(&conn, &capabilities, 1).unwrap();
+ for thread in &conversation.threads {
+     for part in &thread.parts {
+         let active = part.active_reactions().count();
+         println!("{} part {}: {active} tapbacks, {} replies", thread.message.guid, part.index, part.replies.len());
+     }
+ }
+ ```
+*/
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use rusqlite::Connection;
+
+use crate::{
+    error::table::TableError,
+    message_types::{
+        edited::EditedMessagePart,
+        variants::{Tapback, TapbackAction, Variant},
+    },
+    tables::{
+        attachment::Attachment,
+        messages::Message,
+        schema::SchemaCapabilities,
+        table::{AttributedBody, Table},
+    },
+    util::query_context::QueryContext,
+};
+
+/// The kind of tapback a message adds or removes, used to pair removals with the tapbacks they undo
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ReactionKind {
+    /// One of the classic tapbacks, identified by its offset from `2000` or `3000`
+    Classic(i32),
+    /// A custom emoji tapback
+    Emoji(Option<String>),
+    /// A sticker placed on the message
+    Sticker,
+}
+
+impl ReactionKind {
+    fn from_message(message: &Message) -> Option<Self> {
+        match message.associated_message_type? {
+            1000 | 2007 | 3007 => Some(Self::Sticker),
+            2006 | 3006 => Some(Self::Emoji(message.associated_message_emoji.clone())),
+            kind @ (2000..=2005 | 3000..=3005) => Some(Self::Classic(kind % 1000)),
+            _ => None,
+        }
+    }
+}
+
+/// A tapback left by one person on a part of a message, after applying each time they added or removed it
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Reaction {
+    /// The messages that added and removed the tapback, in the order they were sent; never empty
+    pub actions: Vec<Message>,
+    /// The sticker images placed by sticker tapbacks, resolved by [`Conversation::load()`]
+    pub stickers: Vec<Attachment>,
+}
+
+impl Reaction {
+    /// The most recent message that added or removed the tapback
+    #[must_use]
+    pub fn latest(&self) -> &Message {
+        // `actions` is never empty, as a reaction is only created from a tapback message
+        &self.actions[self.actions.len() - 1]
+    }
+
+    /// `true` if the tapback is still applied, i.e. it was not removed after it was last added
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self.latest().variant(),
+            Variant::Tapback(_, TapbackAction::Added, _)
+        )
+    }
+
+    /// The tapback, taken from the message that added it
+    #[must_use]
+    pub fn tapback(&self) -> Option<Tapback<'_>> {
+        let added = self.actions.iter().rev().find(|message| {
+            matches!(
+                message.variant(),
+                Variant::Tapback(_, TapbackAction::Added, _)
+            )
+        });
+        match added.unwrap_or(self.latest()).variant() {
+            Variant::Tapback(_, _, tapback) => Some(tapback),
+            _ => None,
+        }
+    }
+
+    /// `true` if the database owner left the tapback, else `false`
+    #[must_use]
+    pub fn is_from_me(&self) -> bool {
+        self.latest().is_from_me()
+    }
+
+    /// The ID of the person who left the tapback
+    #[must_use]
+    pub fn handle_id(&self) -> Option<i32> {
+        self.latest().handle_id
+    }
+}
+
+/// A part of a message's body, with everything that points at it
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MessagePart {
+    /// The index of the part in the message's [`body()`](crate::tables::table::AttributedBody::body)
+    pub index: usize,
+    /// Tapbacks left on the part, in the order they were first added
+    pub reactions: Vec<Reaction>,
+    /// Replies that quote the part, in the order they were sent
+    pub replies: Vec<Thread>,
+    /// The part's edit history, if it was edited or unsent
+    pub edit: Option<EditedMessagePart>,
+}
+
+impl MessagePart {
+    fn new(index: usize) -> Self {
+        Self {
+            index,
+            reactions: vec![],
+            replies: vec![],
+            edit: None,
+        }
+    }
+
+    /// The tapbacks that are still applied to the part
+    pub fn active_reactions(&self) -> impl Iterator<Item = &Reaction> {
+        self.reactions
+            .iter()
+            .filter(|reaction| reaction.is_active())
+    }
+}
+
+/// A message and its parts, each carrying the tapbacks, replies, and edits that point at it
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Thread {
+    pub message: Message,
+    /// One entry per part of the message's body, in the same order
+    pub parts: Vec<MessagePart>,
+}
+
+impl Thread {
+    /// The replies to every part of the message, in order of the part they quote
+    pub fn replies(&self) -> impl Iterator<Item = &Thread> {
+        self.parts.iter().flat_map(|part| &part.replies)
+    }
+
+    /// The number of replies in the thread, including replies to replies
+    #[must_use]
+    pub fn reply_count(&self) -> usize {
+        self.replies().map(|reply| 1 + reply.reply_count()).sum()
+    }
+
+    /// Find the message with `guid` in the thread
+    #[must_use]
+    pub fn find(&self, guid: &str) -> Option<&Thread> {
+        if self.message.guid == guid {
+            return Some(self);
+        }
+        self.replies().find_map(|reply| reply.find(guid))
+    }
+
+    fn reactions_mut(&mut self) -> Vec<&mut Reaction> {
+        let mut out_v = vec![];
+        for part in &mut self.parts {
+            out_v.extend(part.reactions.iter_mut());
+            for reply in &mut part.replies {
+                out_v.extend(reply.reactions_mut());
+            }
+        }
+        out_v
+    }
+}
+
+/// The messages in a chat, arranged as a tree of threads
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Conversation {
+    /// The ID of the chat the messages were loaded from
+    pub chat_id: i32,
+    /// Messages that do not reply to another message in the chat, in the order they were sent
+    pub threads: Vec<Thread>,
+    /// Tapbacks whose message is not in the chat, i.e. because it was deleted
+    pub unattached_tapbacks: Vec<Message>,
+}
+
+impl Conversation {
+    /// Load every message in a chat and arrange them as threads
+    ///
+    /// The text of each message is generated, so edit histories are available, and sticker
+    /// tapbacks are resolved to their attachments.
+    pub fn load(
+        db: &Connection,
+        capabilities: &SchemaCapabilities,
+        chat_id: i32,
+    ) -> Result<Self, TableError> {
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids(BTreeSet::from([chat_id]));
+
+        let mut statement = Message::stream_rows(db, capabilities, &context)?;
+        let rows = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(TableError::Messages)?;
+
+        let mut messages = vec![];
+        for row in rows {
+            let mut message = Message::extract(row)?;
+            // Messages without a body, like tapbacks, have no text to generate
+            let _ = message.generate_text(db);
+            messages.push(message);
+        }
+
+        let mut conversation = Self::from_messages(chat_id, messages);
+        for thread in &mut conversation.threads {
+            for reaction in thread.reactions_mut() {
+                if matches!(reaction.tapback(), Some(Tapback::Sticker)) {
+                    for message in &reaction.actions {
+                        reaction.stickers.extend(Attachment::from_message(
+                            db,
+                            capabilities,
+                            message,
+                        )?);
+                    }
+                }
+            }
+        }
+        Ok(conversation)
+    }
+
+    /// Arrange messages that were already loaded from a chat as threads
+    ///
+    /// Replies whose original message is missing are kept as threads of their own.
+    #[must_use]
+    pub fn from_messages(chat_id: i32, mut messages: Vec<Message>) -> Self {
+        // Tapbacks must be applied in the order they were sent
+        messages.sort_by_key(|message| (message.date, message.rowid));
+
+        // Early escape if we try and render the same message GUID twice
+        // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+        let mut seen = HashSet::new();
+        messages.retain(|message| seen.insert(message.rowid));
+
+        let (tapbacks, messages): (Vec<Message>, Vec<Message>) =
+            messages.into_iter().partition(Message::is_tapback);
+
+        // Map each message GUID to its tapbacks, grouped by the part they apply to
+        let mut reactions: HashMap<String, HashMap<usize, Vec<Reaction>>> = HashMap::new();
+        let mut unattached_tapbacks = vec![];
+        let guids: HashSet<&str> = messages
+            .iter()
+            .map(|message| message.guid.as_str())
+            .collect();
+        for tapback in tapbacks {
+            let Some((idx, target)) = tapback
+                .clean_associated_guid()
+                .filter(|(_, target)| guids.contains(target))
+                .map(|(idx, target)| (idx, target.to_string()))
+            else {
+                unattached_tapbacks.push(tapback);
+                continue;
+            };
+            let part = reactions.entry(target).or_default().entry(idx).or_default();
+            apply_tapback(part, tapback);
+        }
+
+        // Map each message GUID to the messages that reply to it
+        let mut replies: HashMap<&str, Vec<usize>> = HashMap::new();
+        let mut roots = vec![];
+        for (idx, message) in messages.iter().enumerate() {
+            match message.thread_originator_guid.as_deref() {
+                Some(originator) if originator != message.guid && guids.contains(originator) => {
+                    replies.entry(originator).or_default().push(idx);
+                }
+                _ => roots.push(idx),
+            }
+        }
+        let replies: HashMap<String, Vec<usize>> = replies
+            .into_iter()
+            .map(|(guid, children)| (guid.to_string(), children))
+            .collect();
+
+        let mut builder = Builder {
+            messages: messages.into_iter().map(Some).collect(),
+            replies,
+            reactions,
+        };
+        let mut threads: Vec<Thread> = roots
+            .into_iter()
+            .filter_map(|idx| builder.build(idx))
+            .collect();
+
+        // Replies that form a cycle are never reached from a root, so keep them as threads of their own
+        for idx in 0..builder.messages.len() {
+            threads.extend(builder.build(idx));
+        }
+        threads.sort_by_key(|thread| (thread.message.date, thread.message.rowid));
+
+        Self {
+            chat_id,
+            threads,
+            unattached_tapbacks,
+        }
+    }
+
+    /// Find the message with `guid`, whether it starts a thread or replies to another message
+    #[must_use]
+    pub fn find(&self, guid: &str) -> Option<&Thread> {
+        self.threads.iter().find_map(|thread| thread.find(guid))
+    }
+
+    /// The number of messages in the conversation, excluding tapbacks
+    #[must_use]
+    pub fn message_count(&self) -> usize {
+        self.threads
+            .iter()
+            .map(|thread| 1 + thread.reply_count())
+            .sum()
+    }
+}
+
+/// Add a tapback to the reactions on a part, or record that it undoes an earlier one
+fn apply_tapback(reactions: &mut Vec<Reaction>, tapback: Message) {
+    let kind = ReactionKind::from_message(&tapback);
+    let is_added = matches!(
+        tapback.variant(),
+        Variant::Tapback(_, TapbackAction::Added, _)
+    );
+    let same_sender = |reaction: &Reaction| {
+        reaction.is_from_me() == tapback.is_from_me()
+            && (tapback.is_from_me() || reaction.handle_id() == tapback.handle_id)
+            && ReactionKind::from_message(reaction.latest()) == kind
+    };
+
+    // Each sticker is a separate reaction, and removing one removes the latest sticker still applied
+    let existing = if kind == Some(ReactionKind::Sticker) {
+        if is_added {
+            None
+        } else {
+            reactions
+                .iter_mut()
+                .rev()
+                .find(|reaction| same_sender(reaction) && reaction.is_active())
+        }
+    } else {
+        reactions.iter_mut().find(|reaction| same_sender(reaction))
+    };
+
+    match existing {
+        Some(reaction) => reaction.actions.push(tapback),
+        None => reactions.push(Reaction {
+            actions: vec![tapback],
+            stickers: vec![],
+        }),
+    }
+}
+
+/// Moves messages out of a flat list into the threads they belong to
+struct Builder {
+    messages: Vec<Option<Message>>,
+    /// Message GUID to the indexes of the messages that reply to it
+    replies: HashMap<String, Vec<usize>>,
+    /// Message GUID to the reactions on each of its parts
+    reactions: HashMap<String, HashMap<usize, Vec<Reaction>>>,
+}
+
+impl Builder {
+    /// Build the thread for the message at `idx`, or `None` if it is already part of another thread
+    fn build(&mut self, idx: usize) -> Option<Thread> {
+        let message = self.messages.get_mut(idx)?.take()?;
+
+        let mut reactions = self.reactions.remove(&message.guid).unwrap_or_default();
+        let mut replies: Vec<(usize, Thread)> = vec![];
+        for child in self.replies.remove(&message.guid).unwrap_or_default() {
+            if let Some(reply) = self.build(child) {
+                replies.push((reply.message.get_reply_index(), reply));
+            }
+        }
+
+        let edits = message.edited_parts.as_ref();
+        let num_parts = [
+            message.body().len(),
+            edits.map_or(0, |edits| edits.items()),
+            reactions.keys().max().map_or(0, |idx| idx + 1),
+            replies.iter().map(|(idx, _)| idx + 1).max().unwrap_or(0),
+            1,
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or(1);
+
+        let mut parts: Vec<MessagePart> = (0..num_parts).map(MessagePart::new).collect();
+        for part in &mut parts {
+            part.reactions = reactions.remove(&part.index).unwrap_or_default();
+            part.edit = edits.and_then(|edits| edits.part(part.index)).cloned();
+        }
+        for (idx, reply) in replies {
+            parts[idx].replies.push(reply);
+        }
+
+        Some(Thread { message, parts })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::current_dir;
+
+    use crate::tables::{
+        messages::{
+            Message,
+            conversation::{Conversation, Thread},
+        },
+        schema::SchemaCapabilities,
+        table::get_connection,
+    };
+
+    fn message(rowid: i32, guid: &str) -> Message {
+        let mut message = Message::blank();
+        message.rowid = rowid;
+        message.guid = guid.to_string();
+        message.date = i64::from(rowid);
+        message.text = Some("Hello".to_string());
+        message
+    }
+
+    fn tapback(rowid: i32, target: &str, part: usize, kind: i32, handle_id: i32) -> Message {
+        let mut tapback = message(rowid, &format!("tapback-{rowid}"));
+        tapback.associated_message_guid = Some(format!("p:{part}/{target}"));
+        tapback.associated_message_type = Some(kind);
+        tapback.handle_id = Some(handle_id);
+        tapback
+    }
+
+    fn reply(rowid: i32, guid: &str, originator: &str, part: usize) -> Message {
+        let mut reply = message(rowid, guid);
+        reply.thread_originator_guid = Some(originator.to_string());
+        reply.thread_originator_part = Some(format!("{part}:0:5"));
+        reply
+    }
+
+    const ROOT: &str = "00000000-0000-0000-0000-000000000001";
+    const REPLY: &str = "00000000-0000-0000-0000-000000000002";
+    const MISSING: &str = "00000000-0000-0000-0000-000000000009";
+
+    fn root(conversation: &Conversation) -> &Thread {
+        conversation.find(ROOT).unwrap()
+    }
+
+    #[test]
+    fn can_apply_tapback_removals() {
+        let conversation = Conversation::from_messages(
+            1,
+            vec![
+                message(1, ROOT),
+                tapback(2, ROOT, 0, 2000, 5),
+                tapback(3, ROOT, 0, 2001, 6),
+                tapback(4, ROOT, 0, 3000, 5),
+                tapback(5, ROOT, 0, 2000, 6),
+            ],
+        );
+
+        let part = &root(&conversation).parts[0];
+        assert_eq!(part.reactions.len(), 3);
+        assert_eq!(part.reactions[0].actions.len(), 2);
+        assert!(!part.reactions[0].is_active());
+        assert!(part.reactions[1].is_active());
+        assert!(part.reactions[2].is_active());
+        assert_eq!(part.active_reactions().count(), 2);
+    }
+
+    #[test]
+    fn can_re_add_tapback() {
+        let conversation = Conversation::from_messages(
+            1,
+            vec![
+                message(1, ROOT),
+                tapback(2, ROOT, 0, 2003, 5),
+                tapback(3, ROOT, 0, 3003, 5),
+                tapback(4, ROOT, 0, 2003, 5),
+            ],
+        );
+
+        let part = &root(&conversation).parts[0];
+        assert_eq!(part.reactions.len(), 1);
+        assert_eq!(part.reactions[0].actions.len(), 3);
+        assert!(part.reactions[0].is_active());
+    }
+
+    #[test]
+    fn can_attach_tapbacks_to_parts() {
+        let conversation =
+            Conversation::from_messages(1, vec![message(1, ROOT), tapback(2, ROOT, 2, 2004, 5)]);
+
+        let thread = root(&conversation);
+        assert_eq!(thread.parts.len(), 3);
+        assert!(thread.parts[0].reactions.is_empty());
+        assert_eq!(thread.parts[2].reactions.len(), 1);
+    }
+
+    #[test]
+    fn can_keep_stickers_separate() {
+        let conversation = Conversation::from_messages(
+            1,
+            vec![
+                message(1, ROOT),
+                tapback(2, ROOT, 0, 2007, 5),
+                tapback(3, ROOT, 0, 2007, 5),
+                tapback(4, ROOT, 0, 3007, 5),
+            ],
+        );
+
+        let part = &root(&conversation).parts[0];
+        assert_eq!(part.reactions.len(), 2);
+        assert!(part.reactions[0].is_active());
+        assert!(!part.reactions[1].is_active());
+    }
+
+    #[test]
+    fn can_attach_replies_to_parts() {
+        let mut original = message(1, ROOT);
+        original.num_replies = 2;
+        let conversation = Conversation::from_messages(
+            1,
+            vec![
+                original,
+                reply(2, REPLY, ROOT, 1),
+                reply(3, "00000000-0000-0000-0000-000000000003", ROOT, 0),
+                tapback(4, REPLY, 0, 2000, 5),
+            ],
+        );
+
+        assert_eq!(conversation.threads.len(), 1);
+        assert_eq!(conversation.message_count(), 3);
+
+        let thread = root(&conversation);
+        assert_eq!(thread.reply_count(), 2);
+        assert_eq!(thread.parts[0].replies.len(), 1);
+        assert_eq!(thread.parts[1].replies[0].message.guid, REPLY);
+
+        let reply = conversation.find(REPLY).unwrap();
+        assert_eq!(reply.parts[0].reactions.len(), 1);
+    }
+
+    #[test]
+    fn can_keep_orphans() {
+        let conversation = Conversation::from_messages(
+            1,
+            vec![
+                message(1, ROOT),
+                reply(2, REPLY, MISSING, 0),
+                tapback(3, MISSING, 0, 2000, 5),
+            ],
+        );
+
+        assert_eq!(conversation.threads.len(), 2);
+        assert_eq!(conversation.threads[1].message.guid, REPLY);
+        assert_eq!(conversation.unattached_tapbacks.len(), 1);
+    }
+
+    #[test]
+    fn can_skip_duplicate_rows() {
+        let conversation = Conversation::from_messages(1, vec![message(1, ROOT), message(1, ROOT)]);
+
+        assert_eq!(conversation.threads.len(), 1);
+    }
+
+    #[test]
+    fn can_load_empty_chat() {
+        let db_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/db/test.db");
+        let conn = get_connection(&db_path).unwrap();
+        let capabilities = SchemaCapabilities::probe(&conn).unwrap();
+
+        let conversation = Conversation::load(&conn, &capabilities, 1).unwrap();
+
+        assert!(conversation.threads.is_empty());
+        assert!(conversation.unattached_tapbacks.is_empty());
+    }
+}
@@ -0,0 +1,492 @@
+/*!
+ Resumable cursors for reading messages that changed since a previous point in time.
+
+ Applications that watch the database for changes need to know which rows are new, which rows were
+ [edited or unsent](crate::message_types::edited), and which rows were moved into or out of the
+ recently deleted collection. A [`MessageCursor`] records how far a consumer has read, and
+ [`Message::changes_since()`] returns every change after that point alongside the next cursor.
+
+ # Example
+
+ ```rust
+ use imessage_database::{
+     tables::{
+         messages::{Message, cursor::{MessageChange, MessageCursor}},
+         schema::SchemaCapabilities,
+         table::get_connection,
+     },
+     util::{dirs::default_db_path, query_context::QueryContext},
+ };
+
+ let db_path = default_db_path();
+ let conn = get_connection(&db_path).unwrap();
+ let capabilities = SchemaCapabilities::probe(&conn).unwrap();
+ let context = QueryContext::default();
+
+ // Start from the current state of the database
+ let cursor = MessageCursor::latest(&conn, &capabilities, &context).unwrap();
+
+ // Later, read everything that changed since then
+ let changes = Message::changes_since(&conn, &capabilities, &context, &cursor).unwrap();
+ for change in &changes.changes {
+     if let MessageChange::Added(message) = change {
+         println!("New message: {}", message.guid);
+     }
+ }
+
+ // Persist the token to resume after a restart
+ let token = changes.cursor.to_string();
+ let restored: MessageCursor = token.parse().unwrap();
+ ```
+*/
+
+use std::{collections::BTreeSet, fmt::Display, str::FromStr};
+
+use rusqlite::Connection;
+
+use crate::{
+    error::{cursor::CursorError, table::TableError},
+    tables::{
+        messages::{Message, query_parts::message_query},
+        schema::{SchemaCapabilities, SchemaFeature},
+        table::{CHAT_MESSAGE_JOIN, MESSAGE, RECENTLY_DELETED, Table},
+    },
+    util::query_context::QueryContext,
+};
+
+/// The version of the cursor token format emitted by [`MessageCursor`]'s [`Display`] implementation
+const TOKEN_VERSION: &str = "1";
+
+/// Represents the position a consumer has read the `message` table up to.
+///
+/// A cursor can be persisted with its [`Display`] implementation and restored with [`FromStr`]. The
+/// [`Default`] cursor starts at the beginning of the table, so every existing row is reported as new.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MessageCursor {
+    /// The largest message `ROWID` that has been read
+    pub last_rowid: i32,
+    /// The most recent `date_edited` or `date_retracted` value that has been read
+    pub last_modified: i64,
+    /// The message `ROWID`s that were in the recently deleted collection when the cursor was created
+    pub recoverable: BTreeSet<i32>,
+}
+
+/// Represents a single change to a message row observed by [`Message::changes_since()`]
+#[derive(Debug)]
+pub enum MessageChange {
+    /// A row that was written after the cursor was created
+    Added(Message),
+    /// An existing row that was edited or unsent after the cursor was created
+    Modified(Message),
+    /// An existing row that was moved to the recently deleted collection
+    Deleted(Message),
+    /// A row that left the recently deleted collection and is still present in the table
+    Restored(Message),
+    /// The `ROWID` of a row that left the recently deleted collection and was removed from the table
+    Purged(i32),
+}
+
+/// The result of reading changes with [`Message::changes_since()`]
+#[derive(Debug)]
+pub struct MessageChanges {
+    /// Every change since the previous cursor, ordered by kind and then by `ROWID`
+    pub changes: Vec<MessageChange>,
+    /// The cursor to resume from on the next read
+    pub cursor: MessageCursor,
+}
+
+impl MessageCursor {
+    /// Create a cursor positioned at the current state of the database, so only changes
+    /// that happen after this call are reported by [`Message::changes_since()`].
+    pub fn latest(
+        db: &Connection,
+        capabilities: &SchemaCapabilities,
+        context: &QueryContext,
+    ) -> Result<Self, TableError> {
+        Ok(Self {
+            last_rowid: Self::max_rowid(db)?,
+            last_modified: Self::max_modified(db, capabilities).map_or(0, |(date, _)| date),
+            recoverable: Self::recoverable_ids(db, capabilities, context)?.unwrap_or_default(),
+        })
+    }
+
+    /// Get the largest `ROWID` in the `message` table
+    fn max_rowid(db: &Connection) -> Result<i32, TableError> {
+        db.query_row(
+            &format!("SELECT IFNULL(MAX(ROWID), 0) FROM {MESSAGE}"),
+            [],
+            |row| row.get(0),
+        )
+        .map_err(TableError::Messages)
+    }
+
+    /// Get the most recent edit or retraction date in the `message` table and whether the
+    /// `date_retracted` column exists, or `None` if the schema predates edited messages
+    fn max_modified(db: &Connection, capabilities: &SchemaCapabilities) -> Option<(i64, bool)> {
+        if !capabilities.supports(SchemaFeature::Edits) {
+            return None;
+        }
+        let has_retracted = capabilities.supports(SchemaFeature::Unsends);
+        let query = if has_retracted {
+            format!(
+                "SELECT IFNULL(MAX(MAX(IFNULL(date_edited, 0), IFNULL(date_retracted, 0))), 0) FROM {MESSAGE}"
+            )
+        } else {
+            format!("SELECT IFNULL(MAX(date_edited), 0) FROM {MESSAGE}")
+        };
+        db.query_row(&query, [], |row| row.get(0))
+            .map(|date| (date, has_retracted))
+            .ok()
+    }
+
+    /// Get the `ROWID`s of messages in the recently deleted collection that match the [`QueryContext`],
+    /// or `None` if the schema does not have a recently deleted collection
+    fn recoverable_ids(
+        db: &Connection,
+        capabilities: &SchemaCapabilities,
+        context: &QueryContext,
+    ) -> Result<Option<BTreeSet<i32>>, TableError> {
+        if !capabilities.supports(SchemaFeature::RecentlyDeleted) {
+            return Ok(None);
+        }
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT DISTINCT
+                 d.message_id
+             FROM {RECENTLY_DELETED} as d
+             LEFT JOIN {MESSAGE} as m ON m.ROWID = d.message_id
+             LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
+             {}",
+                Message::generate_filter_statement(context, true)
+            ))
+            .map_err(TableError::Messages)?;
+
+        let ids = statement
+            .query_map([], |row| row.get(0))
+            .map_err(TableError::Messages)?
+            .collect::<Result<BTreeSet<i32>, _>>()
+            .map_err(TableError::Messages)?;
+
+        Ok(Some(ids))
+    }
+
+    /// Read every change after this cursor, see [`Message::changes_since()`]
+    pub(crate) fn changes(
+        &self,
+        db: &Connection,
+        capabilities: &SchemaCapabilities,
+        context: &QueryContext,
+    ) -> Result<MessageChanges, TableError> {
+        // Determine the new watermarks first so rows written while we read are left for the next call
+        let max_rowid = Self::max_rowid(db)?;
+        let max_modified = Self::max_modified(db, capabilities);
+        let recoverable = Self::recoverable_ids(db, capabilities, context)?;
+
+        let mut changes = vec![];
+
+        // Rows written since the last read
+        let added = query_messages(
+            db,
+            capabilities,
+            context,
+            &format!("m.ROWID > {} AND m.ROWID <= {max_rowid}", self.last_rowid),
+        )?;
+        changes.extend(added.into_iter().map(MessageChange::Added));
+
+        // Existing rows that were edited or unsent since the last read
+        if let Some((modified, has_retracted)) = max_modified {
+            let last = self.last_modified;
+            let clause = if has_retracted {
+                format!(
+                    "m.ROWID <= {} AND ((m.date_edited > {last} AND m.date_edited <= {modified}) OR (m.date_retracted > {last} AND m.date_retracted <= {modified}))",
+                    self.last_rowid
+                )
+            } else {
+                format!(
+                    "m.ROWID <= {} AND m.date_edited > {last} AND m.date_edited <= {modified}",
+                    self.last_rowid
+                )
+            };
+            let modified = query_messages(db, capabilities, context, &clause)?;
+            changes.extend(modified.into_iter().map(MessageChange::Modified));
+        }
+
+        // Rows that entered or left the recently deleted collection
+        if let Some(current) = &recoverable {
+            let entered = current
+                .difference(&self.recoverable)
+                .copied()
+                .collect::<BTreeSet<i32>>();
+            let left = self
+                .recoverable
+                .difference(current)
+                .copied()
+                .collect::<BTreeSet<i32>>();
+
+            changes.extend(
+                messages_by_rowid(db, capabilities, &entered)?
+                    .into_iter()
+                    .map(MessageChange::Deleted),
+            );
+
+            let restored = messages_by_rowid(db, capabilities, &left)?;
+            let purged = left
+                .iter()
+                .filter(|rowid| !restored.iter().any(|message| message.rowid == **rowid))
+                .copied()
+                .collect::<Vec<i32>>();
+            changes.extend(restored.into_iter().map(MessageChange::Restored));
+            changes.extend(purged.into_iter().map(MessageChange::Purged));
+        }
+
+        Ok(MessageChanges {
+            changes,
+            cursor: MessageCursor {
+                last_rowid: max_rowid.max(self.last_rowid),
+                last_modified: max_modified
+                    .map_or(self.last_modified, |(date, _)| date.max(self.last_modified)),
+                recoverable: recoverable.unwrap_or_default(),
+            },
+        })
+    }
+}
+
+impl Display for MessageCursor {
+    /// Serialize the cursor to a token like `1:452567:760569924490443648:12,15`
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let recoverable = self
+            .recoverable
+            .iter()
+            .map(std::string::ToString::to_string)
+            .collect::<Vec<String>>()
+            .join(",");
+        write!(
+            fmt,
+            "{TOKEN_VERSION}:{}:{}:{recoverable}",
+            self.last_rowid, self.last_modified
+        )
+    }
+}
+
+impl FromStr for MessageCursor {
+    type Err = CursorError;
+
+    /// Restore a cursor from a token created by its [`Display`] implementation
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        let mut sections = token.trim().split(':');
+        let (Some(version), Some(rowid), Some(modified), Some(recoverable), None) = (
+            sections.next(),
+            sections.next(),
+            sections.next(),
+            sections.next(),
+            sections.next(),
+        ) else {
+            return Err(CursorError::MalformedToken(token.to_string()));
+        };
+
+        if version != TOKEN_VERSION {
+            return Err(CursorError::UnsupportedVersion(version.to_string()));
+        }
+
+        Ok(Self {
+            last_rowid: parse_number(rowid)?,
+            last_modified: parse_number(modified)?,
+            recoverable: recoverable
+                .split(',')
+                .filter(|id| !id.is_empty())
+                .map(parse_number)
+                .collect::<Result<BTreeSet<i32>, CursorError>>()?,
+        })
+    }
+}
+
+/// Parse a single numeric section of a cursor token
+fn parse_number<T: FromStr>(value: &str) -> Result<T, CursorError> {
+    value
+        .parse()
+        .map_err(|_| CursorError::InvalidNumber(value.to_string()))
+}
+
+/// Add a clause to the filters generated for a [`QueryContext`]
+fn with_clause(filters: &str, clause: &str) -> String {
+    if filters.is_empty() {
+        format!("WHERE {clause}")
+    } else {
+        format!("{filters} AND {clause}")
+    }
+}
+
+/// Query the messages that match both the [`QueryContext`] and `clause`, sorted by `ROWID`
+/// with rows that belong to more than one chat collapsed
+fn query_messages(
+    db: &Connection,
+    capabilities: &SchemaCapabilities,
+    context: &QueryContext,
+    clause: &str,
+) -> Result<Vec<Message>, TableError> {
+    let filters = with_clause(
+        &Message::generate_filter_statement(
+            context,
+            capabilities.supports(SchemaFeature::RecentlyDeleted),
+        ),
+        clause,
+    );
+    let mut statement = db
+        .prepare(&message_query(capabilities, Some(&filters), None))
+        .map_err(TableError::Messages)?;
+
+    let mut messages = statement
+        .query_map([], |row| Ok(Message::from_row(row)))
+        .map_err(TableError::Messages)?
+        .map(Message::extract)
+        .collect::<Result<Vec<Message>, TableError>>()?;
+
+    messages.sort_by_key(|message| message.rowid);
+    messages.dedup_by_key(|message| message.rowid);
+    Ok(messages)
+}
+
+/// Query messages by `ROWID`, regardless of any [`QueryContext`]
+fn messages_by_rowid(
+    db: &Connection,
+    capabilities: &SchemaCapabilities,
+    rowids: &BTreeSet<i32>,
+) -> Result<Vec<Message>, TableError> {
+    if rowids.is_empty() {
+        return Ok(vec![]);
+    }
+    let ids = rowids
+        .iter()
+        .map(std::string::ToString::to_string)
+        .collect::<Vec<String>>()
+        .join(", ");
+    query_messages(
+        db,
+        capabilities,
+        &QueryContext::default(),
+        &format!("m.ROWID IN ({ids})"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, env::current_dir};
+
+    use rusqlite::Connection;
+
+    use crate::{
+        tables::{
+            messages::{
+                Message,
+                cursor::{MessageChange, MessageCursor},
+            },
+            schema::SchemaCapabilities,
+            table::get_connection,
+        },
+        util::query_context::QueryContext,
+    };
+
+    /// The only message in the test database
+    const ROWID: i32 = 452567;
+
+    fn connection() -> (Connection, SchemaCapabilities) {
+        let db_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/db/test.db");
+        let conn = get_connection(&db_path).unwrap();
+        let capabilities = SchemaCapabilities::probe(&conn).unwrap();
+        (conn, capabilities)
+    }
+
+    #[test]
+    fn can_read_all_from_default() {
+        let (conn, capabilities) = connection();
+        let context = QueryContext::default();
+
+        let changes =
+            Message::changes_since(&conn, &capabilities, &context, &MessageCursor::default())
+                .unwrap();
+
+        assert_eq!(changes.changes.len(), 1);
+        assert!(matches!(&changes.changes[0], MessageChange::Added(m) if m.rowid == ROWID));
+        assert_eq!(changes.cursor.last_rowid, ROWID);
+    }
+
+    #[test]
+    fn can_resume_from_latest() {
+        let (conn, capabilities) = connection();
+        let context = QueryContext::default();
+
+        let cursor = MessageCursor::latest(&conn, &capabilities, &context).unwrap();
+        let changes = Message::changes_since(&conn, &capabilities, &context, &cursor).unwrap();
+
+        assert!(changes.changes.is_empty());
+        assert_eq!(changes.cursor, cursor);
+    }
+
+    #[test]
+    fn can_respect_query_context() {
+        let (conn, capabilities) = connection();
+        let mut context = QueryContext::default();
+        context.set_selected_handle_ids(BTreeSet::from([2]));
+
+        let changes =
+            Message::changes_since(&conn, &capabilities, &context, &MessageCursor::default())
+                .unwrap();
+
+        assert!(changes.changes.is_empty());
+        assert_eq!(changes.cursor.last_rowid, ROWID);
+    }
+
+    #[test]
+    fn can_detect_left_recoverable() {
+        let (conn, capabilities) = connection();
+        let context = QueryContext::default();
+
+        let cursor = MessageCursor {
+            last_rowid: ROWID,
+            last_modified: 0,
+            recoverable: BTreeSet::from([ROWID, 1]),
+        };
+        let changes = Message::changes_since(&conn, &capabilities, &context, &cursor).unwrap();
+
+        assert_eq!(changes.changes.len(), 2);
+        assert!(matches!(&changes.changes[0], MessageChange::Restored(m) if m.rowid == ROWID));
+        assert!(matches!(changes.changes[1], MessageChange::Purged(1)));
+        assert!(changes.cursor.recoverable.is_empty());
+    }
+
+    #[test]
+    fn can_round_trip_token() {
+        let cursor = MessageCursor {
+            last_rowid: ROWID,
+            last_modified: 760569924490443648,
+            recoverable: BTreeSet::from([12, 15]),
+        };
+
+        let token = cursor.to_string();
+        assert_eq!(token, "1:452567:760569924490443648:12,15");
+        assert_eq!(token.parse::<MessageCursor>().unwrap(), cursor);
+    }
+
+    #[test]
+    fn can_round_trip_default_token() {
+        let cursor = MessageCursor::default();
+
+        let token = cursor.to_string();
+        assert_eq!(token, "1:0:0:");
+        assert_eq!(token.parse::<MessageCursor>().unwrap(), cursor);
+    }
+
+    #[test]
+    fn cant_parse_bad_tokens() {
+        assert!("".parse::<MessageCursor>().is_err());
+        assert!("1:2:3".parse::<MessageCursor>().is_err());
+        assert!("2:0:0:".parse::<MessageCursor>().is_err());
+        assert!("1:a:0:".parse::<MessageCursor>().is_err());
+        assert!("1:0:0:1,b".parse::<MessageCursor>().is_err());
+        assert!("1:0:0::".parse::<MessageCursor>().is_err());
+    }
+}
@@ -109,49 +109,88 @@
  ```
 */
 
-use std::{collections::HashMap, io::Read};
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{Cursor, Read},
+};
 
 use chrono::{DateTime, offset::Local};
 use plist::Value;
 use rusqlite::{Connection, Error, Result, Row, Statement, blob::Blob};
 
 use crate::{
-    error::{message::MessageError, table::TableError},
+    error::{message::MessageError, plist::PlistParseError, table::TableError},
     message_types::{
         edited::{EditStatus, EditedMessage},
         expressives::{BubbleEffect, Expressive, ScreenEffect},
-        variants::{Announcement, BalloonProvider, CustomBalloon, Tapback, TapbackAction, Variant},
+        variants::{
+            Announcement, BalloonPayload, BalloonProvider, CustomBalloon, Tapback, TapbackAction,
+            Variant,
+        },
     },
     tables::{
+        diagnostic::MessageDiagnostic,
         messages::{
             body::{parse_body_legacy, parse_body_typedstream},
+            cursor::{MessageChanges, MessageCursor},
             models::{BubbleComponent, GroupAction, Service},
-            query_parts::{ios_13_older_query, ios_14_15_query, ios_16_newer_query},
+            query_parts::{message_columns, message_query},
         },
+        schema::{SchemaCapabilities, SchemaFeature},
         table::{
-            ATTRIBUTED_BODY, AttributedBody, CHAT_MESSAGE_JOIN, Cacheable, Diagnostic, GetBlob,
-            MESSAGE, MESSAGE_ATTACHMENT_JOIN, MESSAGE_PAYLOAD, MESSAGE_SUMMARY_INFO,
-            RECENTLY_DELETED, Table,
+            ATTRIBUTED_BODY, AttributedBody, CHAT_HANDLE_JOIN, CHAT_MESSAGE_JOIN, Cacheable,
+            Diagnostic, GetBlob, MESSAGE, MESSAGE_ATTACHMENT_JOIN, MESSAGE_PAYLOAD,
+            MESSAGE_SUMMARY_INFO, RECENTLY_DELETED, Table,
         },
     },
     util::{
         bundle_id::parse_balloon_bundle_id,
         dates::{get_local_time, readable_diff},
-        output::{done_processing, processing},
+        plist::parse_ns_keyed_archiver,
         query_context::QueryContext,
         streamtyped,
         typedstream::{models::Archivable, parser::TypedStreamReader},
     },
 };
 
-/// The required columns, interpolated into the most recent schema due to performance considerations
-pub(crate) const COLS: &str = "rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, associated_message_emoji";
+/// The required columns, selected by name instead of `*` due to performance considerations; columns the schema does not have are skipped
+pub(crate) const COLS: [&str; 28] = [
+    "rowid",
+    "guid",
+    "text",
+    "service",
+    "handle_id",
+    "destination_caller_id",
+    "subject",
+    "date",
+    "date_read",
+    "date_delivered",
+    "is_from_me",
+    "is_read",
+    "item_type",
+    "other_handle",
+    "share_status",
+    "share_direction",
+    "group_title",
+    "group_action_type",
+    "associated_message_guid",
+    "associated_message_type",
+    "balloon_bundle_id",
+    "expressive_send_style_id",
+    "thread_originator_guid",
+    "thread_originator_part",
+    "date_edited",
+    "associated_message_emoji",
+    "date_retracted",
+    "schedule_type",
+];
 
 /// Represents a single row in the `message` table.
 ///
 /// Additional information is available in the [parent](crate::tables::messages::message) module.
 #[derive(Debug)]
 #[allow(non_snake_case)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Message {
     pub rowid: i32,
     pub guid: String,
@@ -203,6 +242,10 @@ pub struct Message {
     pub date_edited: i64,
     /// If present, this is the emoji associated with a custom emoji tapback
     pub associated_message_emoji: Option<String>,
+    /// The date the message was unsent
+    pub date_retracted: i64,
+    /// Nonzero if the message was scheduled to be sent later
+    pub schedule_type: i32,
     /// The [`identifier`](crate::tables::chat::Chat::chat_identifier) of the chat the message belongs to
     pub chat_id: Option<i32>,
     /// The number of attached files included in the message
@@ -246,6 +289,8 @@ impl Table for Message {
             thread_originator_part: row.get("thread_originator_part").unwrap_or(None),
             date_edited: row.get("date_edited").unwrap_or(0),
             associated_message_emoji: row.get("associated_message_emoji").unwrap_or(None),
+            date_retracted: row.get("date_retracted").unwrap_or(0),
+            schedule_type: row.get("schedule_type").unwrap_or(0),
             chat_id: row.get("chat_id").unwrap_or(None),
             num_attachments: row.get("num_attachments")?,
             deleted_from: row.get("deleted_from").unwrap_or(None),
@@ -255,12 +300,11 @@ impl Table for Message {
         })
     }
 
-    /// Convert data from the messages table to native Rust data structures, falling back to
-    /// more compatible queries to ensure compatibility with older database schemas
+    /// Convert data from the messages table to native Rust data structures, selecting
+    /// the columns present in the database schema, see [`SchemaCapabilities`]
     fn get(db: &Connection) -> Result<Statement, TableError> {
-        db.prepare(&ios_16_newer_query(None, None))
-            .or_else(|_| db.prepare(&ios_14_15_query(None, None)))
-            .or_else(|_| db.prepare(&ios_13_older_query(None, None)))
+        let capabilities = SchemaCapabilities::probe(db)?;
+        db.prepare(&message_query(&capabilities, None, None))
             .map_err(TableError::Messages)
     }
 
@@ -273,7 +317,9 @@ impl Table for Message {
 }
 
 impl Diagnostic for Message {
-    /// Emit diagnostic data for the Messages table
+    type Report = MessageDiagnostic;
+
+    /// Gather diagnostic data for the Messages table
     ///
     /// # Example:
     ///
@@ -284,10 +330,22 @@ impl Diagnostic for Message {
     ///
     /// let db_path = default_db_path();
     /// let conn = get_connection(&db_path).unwrap();
+    /// let report = Message::diagnostic(&conn).unwrap();
     /// Message::run_diagnostic(&conn);
     /// ```
-    fn run_diagnostic(db: &Connection) -> Result<(), TableError> {
-        processing();
+    fn diagnostic(db: &Connection) -> Result<MessageDiagnostic, TableError> {
+        let capabilities = SchemaCapabilities::probe(db)?;
+        let recoverable = capabilities.supports(SchemaFeature::RecentlyDeleted);
+
+        // Messages in Recently Deleted are not joined to a chat, so do not count them as orphaned
+        let (recoverable_join, recoverable_filter) = if recoverable {
+            (
+                format!("LEFT JOIN {RECENTLY_DELETED} as d ON m.rowid = d.message_id"),
+                "AND d.chat_id is NULL",
+            )
+        } else {
+            (String::new(), "")
+        };
         let mut messages_without_chat = db
             .prepare(&format!(
                 "
@@ -296,15 +354,15 @@ impl Diagnostic for Message {
             FROM
             {MESSAGE} as m
             LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.rowid = c.message_id
+            {recoverable_join}
             WHERE
                 c.chat_id is NULL
-            ORDER BY
-                m.date
+                {recoverable_filter}
             "
             ))
             .map_err(TableError::Messages)?;
 
-        let num_dangling: i32 = messages_without_chat
+        let num_dangling: u64 = messages_without_chat
             .query_row([], |r| r.get(0))
             .unwrap_or(0);
 
@@ -325,7 +383,7 @@ impl Diagnostic for Message {
             ))
             .map_err(TableError::Messages)?;
 
-        let messages_in_more_than_one_chat: i32 = messages_in_more_than_one_chat_q
+        let messages_in_more_than_one_chat: u64 = messages_in_more_than_one_chat_q
             .query_row([], |r| r.get(0))
             .unwrap_or(0);
 
@@ -340,21 +398,59 @@ impl Diagnostic for Message {
             ))
             .map_err(TableError::Messages)?;
 
-        let total_messages: i64 = messages_count.query_row([], |r| r.get(0)).unwrap_or(0);
+        let total_messages: u64 = messages_count.query_row([], |r| r.get(0)).unwrap_or(0);
 
-        done_processing();
+        // Older schemas do not have Recently Deleted
+        let recently_deleted: Option<u64> = if recoverable {
+            db.query_row(
+                &format!("SELECT COUNT(DISTINCT message_id) FROM {RECENTLY_DELETED}"),
+                [],
+                |r| r.get(0),
+            )
+            .ok()
+        } else {
+            None
+        };
 
-        println!("Message diagnostic data:");
-        println!("    Total messages: {total_messages}");
-        if num_dangling > 0 {
-            println!("    Messages not associated with a chat: {num_dangling}");
-        }
-        if messages_in_more_than_one_chat > 0 {
-            println!(
-                "    Messages belonging to more than one chat: {messages_in_more_than_one_chat}"
-            );
+        // Older schemas do not have `message_summary_info`
+        let query = if capabilities.has_column(MESSAGE, MESSAGE_SUMMARY_INFO) {
+            format!(
+                "SELECT {ATTRIBUTED_BODY}, {MESSAGE_SUMMARY_INFO} FROM {MESSAGE} WHERE {ATTRIBUTED_BODY} IS NOT NULL OR {MESSAGE_SUMMARY_INFO} IS NOT NULL"
+            )
+        } else {
+            format!(
+                "SELECT {ATTRIBUTED_BODY}, NULL FROM {MESSAGE} WHERE {ATTRIBUTED_BODY} IS NOT NULL"
+            )
+        };
+        let mut blobs = db.prepare(&query).map_err(TableError::Messages)?;
+        let rows = blobs
+            .query_map([], |r| {
+                Ok((
+                    r.get::<_, Option<Vec<u8>>>(0)?,
+                    r.get::<_, Option<Vec<u8>>>(1)?,
+                ))
+            })
+            .map_err(TableError::Messages)?;
+
+        let mut unparseable_bodies = 0;
+        let mut unparseable_summaries = 0;
+        for (body, summary) in rows.filter_map(Result::ok) {
+            if body.is_some_and(|body| TypedStreamReader::from(&body).parse().is_err()) {
+                unparseable_bodies += 1;
+            }
+            if summary.is_some_and(|summary| Value::from_reader(Cursor::new(summary)).is_err()) {
+                unparseable_summaries += 1;
+            }
         }
-        Ok(())
+
+        Ok(MessageDiagnostic {
+            total_messages,
+            messages_without_chat: num_dangling,
+            messages_in_multiple_chats: messages_in_more_than_one_chat,
+            recently_deleted,
+            unparseable_bodies,
+            unparseable_summaries,
+        })
     }
 }
 
@@ -380,21 +476,10 @@ impl Cacheable for Message {
         let mut map: HashMap<Self::K, Self::V> = HashMap::new();
 
         // Create query
+        let capabilities = SchemaCapabilities::probe(db)?;
         let statement = db.prepare(&format!(
             "SELECT
-                 {COLS},
-                 c.chat_id,
-                 (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
-                 NULL as deleted_from,
-                 0 as num_replies
-             FROM
-                 {MESSAGE} as m
-             LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
-             WHERE m.associated_message_guid IS NOT NULL
-            "
-        )).or_else(|_| db.prepare(&format!(
-            "SELECT
-                 *,
+                 {},
                  c.chat_id,
                  (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
                  NULL as deleted_from,
@@ -403,8 +488,9 @@ impl Cacheable for Message {
                  {MESSAGE} as m
              LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
              WHERE m.associated_message_guid IS NOT NULL
-            "
-        )));
+            ",
+            message_columns(&capabilities)
+        ));
 
         if let Ok(mut statement) = statement {
             // Execute query to build the message tapback map
@@ -711,7 +797,7 @@ impl Message {
     }
 
     /// Get the index of the part of a message a reply is pointing to
-    fn get_reply_index(&self) -> usize {
+    pub(crate) fn get_reply_index(&self) -> usize {
         if let Some(parts) = &self.thread_originator_part {
             return match parts.split(':').next() {
                 Some(part) => str::parse::<usize>(part).unwrap_or(0),
@@ -726,31 +812,73 @@ impl Message {
     /// If `include_recoverable` is `true`, the filter includes messages from the recently deleted messages
     /// table that match the chat IDs. This allows recovery of deleted messages that are still
     /// present in the database but no longer visible in the Messages app.
+    ///
+    /// Handle filters match messages sent by those handles as well as messages the database owner
+    /// sent to any chat those handles participate in.
     pub(crate) fn generate_filter_statement(
         context: &QueryContext,
         include_recoverable: bool,
     ) -> String {
-        let mut filters = String::new();
+        let mut filters: Vec<String> = vec![];
+
+        // Date range filters
+        if let Some(start) = context.start_date {
+            filters.push(format!("m.date >= {start}"));
+        }
+        if let Some(end) = context.end_date {
+            filters.push(format!("m.date < {end}"));
+        }
 
         // Chat ID filter, optionally including recoverable messages
         if let Some(chat_ids) = &context.selected_chat_ids {
-            // Allocate the filter string for interpolation
-            let ids = chat_ids
-                .iter()
-                .map(std::string::ToString::to_string)
-                .collect::<Vec<String>>()
-                .join(", ");
+            let ids = Self::join_ids(chat_ids);
 
             if include_recoverable {
-                filters.push_str(&format!(
-                    "WHERE (c.chat_id IN ({ids}) OR d.chat_id IN ({ids}))"
+                filters.push(format!("(c.chat_id IN ({ids}) OR d.chat_id IN ({ids}))"));
+            } else {
+                filters.push(format!("c.chat_id IN ({ids})"));
+            }
+        }
+
+        // Handle ID filter, including messages we sent to chats those handles belong to
+        if let Some(handle_ids) = &context.selected_handle_ids {
+            let ids = Self::join_ids(handle_ids);
+            let chats =
+                format!("SELECT chat_id FROM {CHAT_HANDLE_JOIN} WHERE handle_id IN ({ids})");
+
+            if include_recoverable {
+                filters.push(format!(
+                    "(m.handle_id IN ({ids}) OR (m.is_from_me = 1 AND (c.chat_id IN ({chats}) OR d.chat_id IN ({chats}))))"
                 ));
             } else {
-                filters.push_str(&format!("WHERE c.chat_id IN ({ids})"));
+                filters.push(format!(
+                    "(m.handle_id IN ({ids}) OR (m.is_from_me = 1 AND c.chat_id IN ({chats})))"
+                ));
             }
         }
 
-        filters
+        // Service filter
+        if let Some(services) = &context.selected_services {
+            let names = services
+                .iter()
+                .map(|service| format!("'{}'", service.replace('\'', "''")))
+                .collect::<Vec<String>>()
+                .join(", ");
+            filters.push(format!("m.service IN ({names})"));
+        }
+
+        if filters.is_empty() {
+            return String::new();
+        }
+        format!("WHERE {}", filters.join(" AND "))
+    }
+
+    /// Allocate a set of IDs as a string for interpolation into an `IN` clause
+    fn join_ids(ids: &BTreeSet<i32>) -> String {
+        ids.iter()
+            .map(std::string::ToString::to_string)
+            .collect::<Vec<String>>()
+            .join(", ")
     }
 
     pub(crate) fn generate_limit_statement(context: &QueryContext) -> String {
@@ -763,6 +891,34 @@ impl Message {
         limit_statement
     }
 
+    /// Generate a query that selects the `ROWID` of every message row described by a [`QueryContext`].
+    ///
+    /// This selects the same rows as [`Message::stream_rows()`], so aggregates built on top of it
+    /// (i.e. [`Message::get_count()`] or [`Attachment::get_total_attachment_bytes()`](crate::tables::attachment::Attachment::get_total_attachment_bytes))
+    /// agree with the messages that get streamed.
+    pub(crate) fn generate_rowid_query(
+        context: &QueryContext,
+        include_recoverable: bool,
+    ) -> String {
+        let recoverable_join = if include_recoverable {
+            format!("LEFT JOIN {RECENTLY_DELETED} as d ON m.ROWID = d.message_id")
+        } else {
+            String::new()
+        };
+        format!(
+            "SELECT
+                 m.ROWID
+             FROM {MESSAGE} as m
+             LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
+             {recoverable_join}
+             {}
+             ORDER BY m.date DESC
+             {}",
+            Self::generate_filter_statement(context, include_recoverable),
+            Self::generate_limit_statement(context)
+        )
+    }
+
     /// Get the number of messages in the database
     ///
     /// # Example:
@@ -770,35 +926,28 @@ impl Message {
     /// ```
     /// use imessage_database::util::dirs::default_db_path;
     /// use imessage_database::tables::table::{Diagnostic, get_connection};
-    /// use imessage_database::tables::messages::Message;
+    /// use imessage_database::tables::{messages::Message, schema::SchemaCapabilities};
     /// use imessage_database::util::query_context::QueryContext;
     ///
     /// let db_path = default_db_path();
     /// let conn = get_connection(&db_path).unwrap();
+    /// let capabilities = SchemaCapabilities::probe(&conn).unwrap();
     /// let context = QueryContext::default();
-    /// Message::get_count(&conn, &context);
+    /// Message::get_count(&conn, &capabilities, &context);
     /// ```
-    pub fn get_count(db: &Connection, context: &QueryContext) -> Result<u64, TableError> {
+    pub fn get_count(
+        db: &Connection,
+        capabilities: &SchemaCapabilities,
+        context: &QueryContext,
+    ) -> Result<u64, TableError> {
         let mut statement = if context.has_filters() {
             db.prepare(&format!(
-                "SELECT
-                     COUNT(*)
-                 FROM {MESSAGE} as m
-                 LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
-                 LEFT JOIN {RECENTLY_DELETED} as d ON m.ROWID = d.message_id
-                 {}",
-                Self::generate_filter_statement(context, true)
+                "SELECT COUNT(*) FROM ({})",
+                Self::generate_rowid_query(
+                    context,
+                    capabilities.supports(SchemaFeature::RecentlyDeleted)
+                )
             ))
-            .or_else(|_| {
-                db.prepare(&format!(
-                    "SELECT
-                         COUNT(*)
-                     FROM {MESSAGE} as m
-                     LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
-                    {}",
-                    Self::generate_filter_statement(context, false)
-                ))
-            })
             .map_err(TableError::Messages)?
         } else {
             db.prepare(&format!("SELECT COUNT(*) FROM {MESSAGE}"))
@@ -817,14 +966,15 @@ impl Message {
     /// ```
     /// use imessage_database::util::dirs::default_db_path;
     /// use imessage_database::tables::table::{Diagnostic, get_connection};
-    /// use imessage_database::tables::{messages::Message, table::Table};
+    /// use imessage_database::tables::{messages::Message, schema::SchemaCapabilities, table::Table};
     /// use imessage_database::util::query_context::QueryContext;
     ///
     /// let db_path = default_db_path();
     /// let conn = get_connection(&db_path).unwrap();
+    /// let capabilities = SchemaCapabilities::probe(&conn).unwrap();
     /// let context = QueryContext::default();
     ///
-    /// let mut statement = Message::stream_rows(&conn, &context).unwrap();
+    /// let mut statement = Message::stream_rows(&conn, &capabilities, &context).unwrap();
     ///
     /// let messages = statement.query_map([], |row| Ok(Message::from_row(row))).unwrap();
     ///
@@ -832,30 +982,57 @@ impl Message {
     /// ```
     pub fn stream_rows<'a>(
         db: &'a Connection,
+        capabilities: &SchemaCapabilities,
         context: &'a QueryContext,
     ) -> Result<Statement<'a>, TableError> {
         if !context.has_filters() {
-            return Self::get(db);
+            return db
+                .prepare(&message_query(capabilities, None, None))
+                .map_err(TableError::Messages);
         }
-        db.prepare(&ios_16_newer_query(
-            Some(&Self::generate_filter_statement(context, true)),
+        db.prepare(&message_query(
+            capabilities,
+            Some(&Self::generate_filter_statement(
+                context,
+                capabilities.supports(SchemaFeature::RecentlyDeleted),
+            )),
             Some(&Self::generate_limit_statement(context)),
         ))
-        .or_else(|_| {
-            db.prepare(&ios_14_15_query(
-                Some(&Self::generate_filter_statement(context, false)),
-                Some(&Self::generate_limit_statement(context)),
-            ))
-        })
-        .or_else(|_| {
-            db.prepare(&ios_13_older_query(
-                Some(&Self::generate_filter_statement(context, false)),
-                Some(&Self::generate_limit_statement(context)),
-            ))
-        })
         .map_err(TableError::Messages)
     }
 
+    /// Get the messages that were added, edited, unsent, deleted, or restored since a [`MessageCursor`]
+    ///
+    /// Changes are filtered by the [`QueryContext`], but its limit is ignored so that no change is skipped.
+    /// See [`cursor`](crate::tables::messages::cursor) for details.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::messages::{Message, cursor::MessageCursor};
+    /// use imessage_database::tables::schema::SchemaCapabilities;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let capabilities = SchemaCapabilities::probe(&conn).unwrap();
+    /// let context = QueryContext::default();
+    ///
+    /// let changes =
+    ///     Message::changes_since(&conn, &capabilities, &context, &MessageCursor::default()).unwrap();
+    /// println!("{} changes, resume from {}", changes.changes.len(), changes.cursor);
+    /// ```
+    pub fn changes_since(
+        db: &Connection,
+        capabilities: &SchemaCapabilities,
+        context: &QueryContext,
+        cursor: &MessageCursor,
+    ) -> Result<MessageChanges, TableError> {
+        cursor.changes(db, capabilities, context)
+    }
+
     /// See [`Tapback`] for details on this data.
     #[must_use]
     pub fn clean_associated_guid(&self) -> Option<(usize, &str)> {
@@ -884,17 +1061,23 @@ impl Message {
     }
 
     /// Build a `HashMap` of message component index to messages that reply to that component
-    pub fn get_replies(&self, db: &Connection) -> Result<HashMap<usize, Vec<Self>>, TableError> {
+    pub fn get_replies(
+        &self,
+        db: &Connection,
+        capabilities: &SchemaCapabilities,
+    ) -> Result<HashMap<usize, Vec<Self>>, TableError> {
         let mut out_h: HashMap<usize, Vec<Self>> = HashMap::new();
 
         // No need to hit the DB if we know we don't have replies
         if self.has_replies() {
             let filters = format!("WHERE m.thread_originator_guid = \"{}\"", self.guid);
 
-            // No iOS 13 and prior used here because `thread_originator_guid` is not present in that schema
+            // Messages only have replies if the schema has `thread_originator_guid`
+            if !capabilities.supports(SchemaFeature::Replies) {
+                return Ok(out_h);
+            }
             let mut statement = db
-                .prepare(&ios_16_newer_query(Some(&filters), None))
-                .or_else(|_| db.prepare(&ios_14_15_query(Some(&filters), None)))
+                .prepare(&message_query(capabilities, Some(&filters), None))
                 .map_err(TableError::Messages)?;
 
             let iter = statement
@@ -1079,6 +1262,30 @@ impl Message {
         Value::from_reader(self.get_blob(db, MESSAGE_PAYLOAD)?).ok()
     }
 
+    /// Decode the payload of an app balloon, which can then be [parsed](BalloonPayload::parse)
+    ///
+    /// Returns `None` if the message is not an app balloon or has no payload. Handwriting and Digital Touch payloads
+    /// are not property lists, so they are also `None`; read them with [`Self::raw_payload_data()`].
+    ///
+    /// Calling this hits the database, so it is expensive and should
+    /// only get invoked when needed.
+    pub fn balloon_payload(
+        &self,
+        db: &Connection,
+    ) -> Option<Result<BalloonPayload<'_>, PlistParseError>> {
+        let Variant::App(balloon) = self.variant() else {
+            return None;
+        };
+        if matches!(
+            balloon,
+            CustomBalloon::Handwriting | CustomBalloon::DigitalTouch
+        ) {
+            return None;
+        }
+        let payload = self.payload_data(db)?;
+        Some(parse_ns_keyed_archiver(&payload).map(|payload| BalloonPayload { balloon, payload }))
+    }
+
     /// Get a message's raw data from the [`MESSAGE_PAYLOAD`] BLOB column
     ///
     /// Calling this hits the database, so it is expensive and should
@@ -1170,6 +1377,7 @@ impl Message {
     /// use imessage_database::{
     ///     tables::{
     ///         messages::Message,
+    ///         schema::SchemaCapabilities,
     ///         table::get_connection,
     ///     },
     ///     util::dirs::default_db_path,
@@ -1177,21 +1385,24 @@ impl Message {
     ///
     /// let db_path = default_db_path();
     /// let conn = get_connection(&db_path).unwrap();
+    /// let capabilities = SchemaCapabilities::probe(&conn).unwrap();
     ///
-    /// if let Ok(mut message) = Message::from_guid("example-guid", &conn) {
+    /// if let Ok(mut message) = Message::from_guid("example-guid", &conn, &capabilities) {
     ///     let _ = message.generate_text(&conn);
     ///     println!("{:#?}", message)
     /// }
     ///```
-    pub fn from_guid(guid: &str, db: &Connection) -> Result<Self, TableError> {
+    pub fn from_guid(
+        guid: &str,
+        db: &Connection,
+        capabilities: &SchemaCapabilities,
+    ) -> Result<Self, TableError> {
         // If the database has `chat_recoverable_message_join`, we can restore some deleted messages.
         // If database has `thread_originator_guid`, we can parse replies, otherwise default to 0
         let filters = format!("WHERE m.guid = \"{guid}\"");
 
         let mut statement = db
-            .prepare(&ios_16_newer_query(Some(&filters), None))
-            .or_else(|_| db.prepare(&ios_14_15_query(Some(&filters), None)))
-            .or_else(|_| db.prepare(&ios_13_older_query(Some(&filters), None)))
+            .prepare(&message_query(capabilities, Some(&filters), None))
             .map_err(TableError::Messages)?;
 
         Message::extract(statement.query_row([], |row| Ok(Message::from_row(row))))
@@ -1229,6 +1440,8 @@ impl Message {
             thread_originator_part: None,
             date_edited: 0,
             associated_message_emoji: None,
+            date_retracted: 0,
+            schedule_type: 0,
             chat_id: None,
             num_attachments: 0,
             deleted_from: None,
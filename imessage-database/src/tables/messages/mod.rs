@@ -5,6 +5,8 @@
 pub use message::Message;
 
 pub(crate) mod body;
+pub mod conversation;
+pub mod cursor;
 pub mod message;
 pub mod models;
 pub(crate) mod query_parts;
@@ -5,7 +5,8 @@
 use std::fmt::{Display, Formatter, Result};
 
 use crate::{
-    message_types::text_effects::TextEffect, tables::messages::message::Message,
+    message_types::text_effects::{OwnedTextEffect, TextEffect},
+    tables::messages::message::Message,
     util::typedstream::models::Archivable,
 };
 
@@ -17,6 +18,11 @@ use crate::{
 ///
 /// iMessage bubbles can only contain data of one variant of this enum at a time.
 #[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum BubbleComponent<'a> {
     /// A text message with associated formatting, generally representing ranges present in a `NSAttributedString`
     Text(Vec<TextAttributes<'a>>),
@@ -96,6 +102,7 @@ impl Display for Service<'_> {
 /// ])];
 /// ```
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TextAttributes<'a> {
     /// The start index of the affected range of message text
     pub start: usize,
@@ -114,6 +121,7 @@ impl<'a> TextAttributes<'a> {
 
 /// Representation of attachment metadata used for rendering message body in a conversation feed.
 #[derive(Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct AttachmentMeta<'a> {
     /// GUID of the attachment in the `attachment` table
     pub guid: Option<&'a str>,
@@ -199,6 +207,11 @@ impl<'a> AttachmentMeta<'a> {
 
 /// Represents different types of group message actions that can occur in a chat system
 #[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum GroupAction<'a> {
     /// A new participant has been added to the group
     ParticipantAdded(i32),
@@ -234,3 +247,174 @@ impl<'a> GroupAction<'a> {
         }
     }
 }
+
+/// Owned version of [`BubbleComponent`], used when the parsed data must outlive its source
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OwnedBubbleComponent {
+    /// See [`BubbleComponent::Text`]
+    Text(Vec<OwnedTextAttributes>),
+    /// See [`BubbleComponent::Attachment`]
+    Attachment(OwnedAttachmentMeta),
+    /// See [`BubbleComponent::App`]
+    App,
+    /// See [`BubbleComponent::Retracted`]
+    Retracted,
+}
+
+impl From<&BubbleComponent<'_>> for OwnedBubbleComponent {
+    fn from(component: &BubbleComponent<'_>) -> Self {
+        match component {
+            BubbleComponent::Text(attributes) => {
+                Self::Text(attributes.iter().map(OwnedTextAttributes::from).collect())
+            }
+            BubbleComponent::Attachment(meta) => Self::Attachment(OwnedAttachmentMeta::from(meta)),
+            BubbleComponent::App => Self::App,
+            BubbleComponent::Retracted => Self::Retracted,
+        }
+    }
+}
+
+impl OwnedBubbleComponent {
+    /// Borrow the owned data as a [`BubbleComponent`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> BubbleComponent<'_> {
+        match self {
+            Self::Text(attributes) => BubbleComponent::Text(
+                attributes
+                    .iter()
+                    .map(OwnedTextAttributes::as_borrowed)
+                    .collect(),
+            ),
+            Self::Attachment(meta) => BubbleComponent::Attachment(meta.as_borrowed()),
+            Self::App => BubbleComponent::App,
+            Self::Retracted => BubbleComponent::Retracted,
+        }
+    }
+}
+
+/// Owned version of [`TextAttributes`], used when the parsed data must outlive its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedTextAttributes {
+    /// The start index of the affected range of message text
+    pub start: usize,
+    /// The end index of the affected range of message text
+    pub end: usize,
+    /// The effects applied to the specified range
+    pub effect: OwnedTextEffect,
+}
+
+impl From<&TextAttributes<'_>> for OwnedTextAttributes {
+    fn from(attributes: &TextAttributes<'_>) -> Self {
+        Self {
+            start: attributes.start,
+            end: attributes.end,
+            effect: OwnedTextEffect::from(&attributes.effect),
+        }
+    }
+}
+
+impl OwnedTextAttributes {
+    /// Borrow the owned data as [`TextAttributes`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> TextAttributes<'_> {
+        TextAttributes::new(self.start, self.end, self.effect.as_borrowed())
+    }
+}
+
+/// Owned version of [`GroupAction`], used when the parsed data must outlive its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OwnedGroupAction {
+    /// See [`GroupAction::ParticipantAdded`]
+    ParticipantAdded(i32),
+    /// See [`GroupAction::ParticipantRemoved`]
+    ParticipantRemoved(i32),
+    /// See [`GroupAction::NameChange`]
+    NameChange(String),
+    /// See [`GroupAction::ParticipantLeft`]
+    ParticipantLeft,
+    /// See [`GroupAction::GroupIconChanged`]
+    GroupIconChanged,
+    /// See [`GroupAction::GroupIconRemoved`]
+    GroupIconRemoved,
+}
+
+impl From<&GroupAction<'_>> for OwnedGroupAction {
+    fn from(action: &GroupAction<'_>) -> Self {
+        match action {
+            GroupAction::ParticipantAdded(who) => Self::ParticipantAdded(*who),
+            GroupAction::ParticipantRemoved(who) => Self::ParticipantRemoved(*who),
+            GroupAction::NameChange(name) => Self::NameChange((*name).to_string()),
+            GroupAction::ParticipantLeft => Self::ParticipantLeft,
+            GroupAction::GroupIconChanged => Self::GroupIconChanged,
+            GroupAction::GroupIconRemoved => Self::GroupIconRemoved,
+        }
+    }
+}
+
+impl OwnedGroupAction {
+    /// Borrow the owned data as a [`GroupAction`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> GroupAction<'_> {
+        match self {
+            Self::ParticipantAdded(who) => GroupAction::ParticipantAdded(*who),
+            Self::ParticipantRemoved(who) => GroupAction::ParticipantRemoved(*who),
+            Self::NameChange(name) => GroupAction::NameChange(name),
+            Self::ParticipantLeft => GroupAction::ParticipantLeft,
+            Self::GroupIconChanged => GroupAction::GroupIconChanged,
+            Self::GroupIconRemoved => GroupAction::GroupIconRemoved,
+        }
+    }
+}
+
+/// Owned version of [`AttachmentMeta`], used when the parsed data must outlive its payload
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedAttachmentMeta {
+    /// GUID of the attachment in the `attachment` table
+    pub guid: Option<String>,
+    /// The transcription, if the attachment was an [audio message](https://support.apple.com/guide/iphone/send-and-receive-audio-messages-iph2e42d3117/ios) sent from or received on a [supported platform](https://www.apple.com/ios/feature-availability/#messages-audio-message-transcription).
+    pub transcription: Option<String>,
+    /// The height of the attachment in points
+    pub height: Option<f64>,
+    /// The width of the attachment in points
+    pub width: Option<f64>,
+    /// The attachment's original filename
+    pub name: Option<String>,
+}
+
+impl From<&AttachmentMeta<'_>> for OwnedAttachmentMeta {
+    fn from(meta: &AttachmentMeta<'_>) -> Self {
+        Self {
+            guid: meta.guid.map(str::to_string),
+            transcription: meta.transcription.map(str::to_string),
+            height: meta.height.copied(),
+            width: meta.width.copied(),
+            name: meta.name.map(str::to_string),
+        }
+    }
+}
+
+impl OwnedAttachmentMeta {
+    /// Borrow the owned data as an [`AttachmentMeta`]
+    #[must_use]
+    pub fn as_borrowed(&self) -> AttachmentMeta<'_> {
+        AttachmentMeta {
+            guid: self.guid.as_deref(),
+            transcription: self.transcription.as_deref(),
+            height: self.height.as_ref(),
+            width: self.width.as_ref(),
+            name: self.name.as_deref(),
+        }
+    }
+}
@@ -1,97 +1,66 @@
 /*!
- Query data to ensure query compatibility with older known schemas
+ Build message queries from the columns present in the database schema, see [`SchemaCapabilities`]
 
  - If the database has `chat_recoverable_message_join`, we can restore some deleted messages.
  - If database has `thread_originator_guid`, we can parse replies, otherwise default to 0
+ - Columns the schema does not have are not selected, so [`Message::from_row()`](crate::tables::table::Table::from_row) uses their defaults
 */
 
-use std::sync::LazyLock;
-
 use crate::tables::{
     messages::message::COLS,
+    schema::{SchemaCapabilities, SchemaFeature},
     table::{CHAT_MESSAGE_JOIN, MESSAGE, MESSAGE_ATTACHMENT_JOIN, RECENTLY_DELETED},
 };
 
-/// macOS Ventura+ and i0S 16+ schema, interpolated with required columns for performance
-static IOS_16_NEWER_HEAD: LazyLock<String> = LazyLock::new(|| {
-    format!("
-SELECT
-    {COLS},
-    c.chat_id,
-    (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
-    d.chat_id as deleted_from,
-    (SELECT COUNT(*) FROM {MESSAGE} m2 WHERE m2.thread_originator_guid = m.guid) as num_replies
-FROM
-    {MESSAGE} as m
-LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
-LEFT JOIN {RECENTLY_DELETED} as d ON m.ROWID = d.message_id
-")
-});
-
-/// macOS Big Sur to Monterey, iOS 14 to iOS 15 schema
-static IOS_14_15_HEAD: LazyLock<String> = LazyLock::new(|| {
-    format!("
-SELECT
-    *,
-    c.chat_id,
-    (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
-    NULL as deleted_from,
-    (SELECT COUNT(*) FROM {MESSAGE} m2 WHERE m2.thread_originator_guid = m.guid) as num_replies
-FROM
-    {MESSAGE} as m
-LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
-")
-});
-
-/// macOS Catalina, iOS 13 and older schema
-static IOS_13_OLDER_HEAD: LazyLock<String> = LazyLock::new(|| {
-    format!("
-SELECT
-    *,
-    c.chat_id,
-    (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
-    NULL as deleted_from,
-    0 as num_replies
-FROM
-    {MESSAGE} as m
-LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
-")
-});
-
 const ORDER_BY: &str = "
 ORDER BY
     m.date DESC
 ";
 
-/// Generate a SQL Query compatible with the macOS Ventura+ and i0S 16+ schema
-pub(crate) fn ios_16_newer_query(filters: Option<&str>, limit: Option<&str>) -> String {
-    format!(
-        "{}{}{}{};",
-        *IOS_16_NEWER_HEAD,
-        filters.unwrap_or_default(),
-        ORDER_BY,
-        limit.unwrap_or_default()
-    )
+/// The required columns that are present in the `message` table
+pub(crate) fn message_columns(capabilities: &SchemaCapabilities) -> String {
+    COLS.iter()
+        .filter(|column| capabilities.has_column(MESSAGE, column))
+        .copied()
+        .collect::<Vec<&str>>()
+        .join(", ")
 }
 
-/// Generate a SQL Query compatible with the macOS Big Sur to Monterey, iOS 14 to iOS 15 schema
-pub(crate) fn ios_14_15_query(filters: Option<&str>, limit: Option<&str>) -> String {
-    format!(
-        "{}{}{}{};",
-        *IOS_14_15_HEAD,
-        filters.unwrap_or_default(),
-        ORDER_BY,
-        limit.unwrap_or_default()
-    )
-}
+/// Generate a SQL Query that selects every message column the schema supports
+pub(crate) fn message_query(
+    capabilities: &SchemaCapabilities,
+    filters: Option<&str>,
+    limit: Option<&str>,
+) -> String {
+    let (deleted_from, recoverable_join) = if capabilities.supports(SchemaFeature::RecentlyDeleted)
+    {
+        (
+            "d.chat_id",
+            format!("LEFT JOIN {RECENTLY_DELETED} as d ON m.ROWID = d.message_id\n"),
+        )
+    } else {
+        ("NULL", String::new())
+    };
+    let num_replies = if capabilities.supports(SchemaFeature::Replies) {
+        format!("(SELECT COUNT(*) FROM {MESSAGE} m2 WHERE m2.thread_originator_guid = m.guid)")
+    } else {
+        String::from("0")
+    };
 
-/// Generate a SQL Query compatible with the macOS Catalina, iOS 13 and older schema
-pub(crate) fn ios_13_older_query(filters: Option<&str>, limit: Option<&str>) -> String {
     format!(
-        "{}{}{}{};",
-        *IOS_13_OLDER_HEAD,
+        "
+SELECT
+    {},
+    c.chat_id,
+    (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
+    {deleted_from} as deleted_from,
+    {num_replies} as num_replies
+FROM
+    {MESSAGE} as m
+LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
+{recoverable_join}{}{ORDER_BY}{};",
+        message_columns(capabilities),
         filters.unwrap_or_default(),
-        ORDER_BY,
         limit.unwrap_or_default()
     )
 }
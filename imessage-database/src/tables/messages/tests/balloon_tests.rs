@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::{current_dir, temp_dir},
+        fs::{create_dir_all, read, remove_dir_all},
+        path::{Path, PathBuf},
+    };
+
+    use rusqlite::{Connection, params};
+
+    use crate::{
+        message_types::{
+            check_in::{CheckInMessage, CheckInStatus},
+            variants::{CustomBalloon, ParsedBalloon, URLOverride},
+        },
+        tables::{messages::Message, schema::SchemaCapabilities, table::get_connection},
+        util::fixture::{FixtureBuilder, NewMessage, Schema},
+    };
+
+    const DATE: i64 = 694_000_000_000_000_000;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-balloon-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Create a database with a message sent by `bundle_id`, with the contents of `plist` as its payload
+    fn balloon_db(
+        dir: &Path,
+        bundle_id: &str,
+        plist: Option<&str>,
+    ) -> (Connection, SchemaCapabilities, String) {
+        let db_path = dir.join("chat.db");
+        let mut fixture = FixtureBuilder::create(&db_path, Schema::Ios16Newer).unwrap();
+        let handle = fixture.handle("+15558675309", "iMessage").unwrap();
+        let chat = fixture.chat("+15558675309", None, &[handle]).unwrap();
+        let message = fixture
+            .message(chat, NewMessage::new(DATE).from_handle(handle))
+            .unwrap();
+        let payload = plist.map(|plist| read(current_dir().unwrap().join(plist)).unwrap());
+        fixture
+            .connection()
+            .execute(
+                "UPDATE message SET balloon_bundle_id = ?1, payload_data = ?2 WHERE ROWID = ?3",
+                params![bundle_id, payload, message.rowid],
+            )
+            .unwrap();
+        let conn = get_connection(&db_path).unwrap();
+        let capabilities = SchemaCapabilities::probe(&conn).unwrap();
+        (conn, capabilities, message.guid)
+    }
+
+    #[test]
+    fn can_parse_url_balloon_payload() {
+        let dir = temp("url");
+        let (conn, capabilities, guid) = balloon_db(
+            &dir,
+            "com.apple.messages.URLBalloonProvider",
+            Some("test_data/url_message/URL.plist"),
+        );
+        let message = Message::from_guid(&guid, &conn, &capabilities).unwrap();
+
+        let payload = message.balloon_payload(&conn).unwrap().unwrap();
+        assert!(matches!(payload.balloon, CustomBalloon::URL));
+        let ParsedBalloon::URL(URLOverride::Normal(balloon)) = payload.parse().unwrap() else {
+            panic!("expected a URL preview");
+        };
+        assert_eq!(balloon.title, Some("Christopher Sardegna"));
+        assert_eq!(balloon.url, Some("https://chrissardegna.com/"));
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_parse_app_balloon_payload() {
+        let dir = temp("app");
+        let (conn, capabilities, guid) = balloon_db(
+            &dir,
+            "com.apple.messages.MSMessageExtensionBalloonPlugin:0000000000:com.apple.SafetyMonitorApp.SafetyMonitorMessages",
+            Some("test_data/app_message/CheckinTimer.plist"),
+        );
+        let message = Message::from_guid(&guid, &conn, &capabilities).unwrap();
+
+        let payload = message.balloon_payload(&conn).unwrap().unwrap();
+        assert!(matches!(payload.balloon, CustomBalloon::CheckIn));
+        let ParsedBalloon::App(balloon) = payload.parse().unwrap() else {
+            panic!("expected an app message");
+        };
+        let check_in = CheckInMessage::from_app(&balloon);
+        assert_eq!(check_in.status, CheckInStatus::TimerStarted);
+        assert_eq!(check_in.sent, Some(1697316869.688709));
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cannot_parse_missing_balloon_payload() {
+        let dir = temp("missing");
+        let (conn, capabilities, guid) =
+            balloon_db(&dir, "com.apple.messages.URLBalloonProvider", None);
+        let message = Message::from_guid(&guid, &conn, &capabilities).unwrap();
+
+        assert!(message.balloon_payload(&conn).is_none());
+
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,7 +1,9 @@
 mod announcement;
+mod balloon_tests;
 mod date_tests;
 mod edited_tests;
 mod expressive_tests;
 mod guid_tests;
 mod query_tests;
+mod serde_tests;
 mod variant;
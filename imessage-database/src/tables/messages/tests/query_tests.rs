@@ -36,10 +36,52 @@ mod exclude_recoverable_tests {
         context.set_limit(10);
         context.set_selected_chat_ids(BTreeSet::from([1, 2, 3]));
 
+        let statement = Message::generate_filter_statement(&context, false);
+        assert_eq!(statement, "WHERE c.chat_id IN (1, 2, 3)");
+    }
+
+    #[test]
+    fn can_generate_filter_statement_dates() {
+        let mut context = QueryContext::default();
+        context.set_start_date(100);
+        context.set_end_date(200);
+
+        let statement = Message::generate_filter_statement(&context, false);
+        assert_eq!(statement, "WHERE m.date >= 100 AND m.date < 200");
+    }
+
+    #[test]
+    fn can_generate_filter_statement_handle_ids() {
+        let mut context = QueryContext::default();
+        context.set_selected_handle_ids(BTreeSet::from([1, 2]));
+
+        let statement = Message::generate_filter_statement(&context, false);
+        assert_eq!(
+            statement,
+            "WHERE (m.handle_id IN (1, 2) OR (m.is_from_me = 1 AND c.chat_id IN (SELECT chat_id FROM chat_handle_join WHERE handle_id IN (1, 2))))"
+        );
+    }
+
+    #[test]
+    fn can_generate_filter_statement_services() {
+        let mut context = QueryContext::default();
+        context.set_selected_services(BTreeSet::from(["SMS".to_string(), "it's".to_string()]));
+
+        let statement = Message::generate_filter_statement(&context, false);
+        assert_eq!(statement, "WHERE m.service IN ('SMS', 'it''s')");
+    }
+
+    #[test]
+    fn can_generate_filter_statement_all() {
+        let mut context = QueryContext::default();
+        context.set_start_date(100);
+        context.set_selected_chat_ids(BTreeSet::from([3]));
+        context.set_selected_services(BTreeSet::from(["iMessage".to_string()]));
+
         let statement = Message::generate_filter_statement(&context, false);
         assert_eq!(
             statement,
-            "WHERE c.chat_id IN (1, 2, 3)"
+            "WHERE m.date >= 100 AND c.chat_id IN (3) AND m.service IN ('iMessage')"
         );
     }
 
@@ -100,13 +142,148 @@ mod include_recoverable_tests {
             "WHERE (c.chat_id IN (1, 2, 3) OR d.chat_id IN (1, 2, 3))"
         );
     }
+
+    #[test]
+    fn can_generate_filter_statement_handle_ids() {
+        let mut context = QueryContext::default();
+        context.set_selected_handle_ids(BTreeSet::from([1]));
+
+        let statement = Message::generate_filter_statement(&context, true);
+        assert_eq!(
+            statement,
+            "WHERE (m.handle_id IN (1) OR (m.is_from_me = 1 AND (c.chat_id IN (SELECT chat_id FROM chat_handle_join WHERE handle_id IN (1)) OR d.chat_id IN (SELECT chat_id FROM chat_handle_join WHERE handle_id IN (1)))))"
+        );
+    }
+}
+
+#[cfg(test)]
+mod filter_db_tests {
+    use std::{collections::BTreeSet, env::current_dir};
+
+    use rusqlite::Connection;
+
+    use crate::{
+        tables::{
+            attachment::Attachment,
+            messages::Message,
+            schema::SchemaCapabilities,
+            table::{Table, get_connection},
+        },
+        util::query_context::QueryContext,
+    };
+
+    /// The only message in the test database, sent by handle `1` with one attachment
+    const MESSAGE_DATE: i64 = 760569924490443648;
+    const ATTACHMENT_BYTES: u64 = 24560;
+
+    fn connection() -> Connection {
+        let db_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/db/test.db");
+        get_connection(&db_path).unwrap()
+    }
+
+    fn stream_count(
+        conn: &Connection,
+        capabilities: &SchemaCapabilities,
+        context: &QueryContext,
+    ) -> u64 {
+        let mut statement = Message::stream_rows(conn, capabilities, context).unwrap();
+        statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .unwrap()
+            .count() as u64
+    }
+
+    /// Ensure every API that takes a [`QueryContext`] selects the same data
+    fn assert_consistent(context: &QueryContext, expected_messages: u64, expected_bytes: u64) {
+        let conn = connection();
+        let capabilities = SchemaCapabilities::probe(&conn).unwrap();
+        assert_eq!(
+            stream_count(&conn, &capabilities, context),
+            expected_messages
+        );
+        assert_eq!(
+            Message::get_count(&conn, &capabilities, context).unwrap(),
+            expected_messages
+        );
+        assert_eq!(
+            Attachment::get_total_attachment_bytes(&conn, &capabilities, context).unwrap(),
+            expected_bytes
+        );
+    }
+
+    #[test]
+    fn can_filter_selected_handle() {
+        let mut context = QueryContext::default();
+        context.set_selected_handle_ids(BTreeSet::from([1]));
+        assert_consistent(&context, 1, ATTACHMENT_BYTES);
+    }
+
+    #[test]
+    fn can_filter_unselected_handle() {
+        let mut context = QueryContext::default();
+        context.set_selected_handle_ids(BTreeSet::from([2]));
+        assert_consistent(&context, 0, 0);
+    }
+
+    #[test]
+    fn can_filter_start_date() {
+        let mut context = QueryContext::default();
+        context.set_start_date(MESSAGE_DATE);
+        assert_consistent(&context, 1, ATTACHMENT_BYTES);
+
+        context.set_start_date(MESSAGE_DATE + 1);
+        assert_consistent(&context, 0, 0);
+    }
+
+    #[test]
+    fn can_filter_end_date() {
+        let mut context = QueryContext::default();
+        context.set_end_date(MESSAGE_DATE + 1);
+        assert_consistent(&context, 1, ATTACHMENT_BYTES);
+
+        context.set_end_date(MESSAGE_DATE);
+        assert_consistent(&context, 0, 0);
+    }
+
+    #[test]
+    fn can_filter_service() {
+        let mut context = QueryContext::default();
+        context.set_selected_services(BTreeSet::from(["iMessage".to_string()]));
+        assert_consistent(&context, 1, ATTACHMENT_BYTES);
+
+        context.set_selected_services(BTreeSet::from(["SMS".to_string()]));
+        assert_consistent(&context, 0, 0);
+    }
+
+    #[test]
+    fn can_filter_limit() {
+        let mut context = QueryContext::default();
+        context.set_limit(1);
+        assert_consistent(&context, 1, ATTACHMENT_BYTES);
+
+        context.set_limit(0);
+        assert_consistent(&context, 0, 0);
+    }
+
+    #[test]
+    fn can_filter_combined() {
+        let mut context = QueryContext::default();
+        context.set_selected_handle_ids(BTreeSet::from([1]));
+        context.set_start_date(MESSAGE_DATE);
+        context.set_selected_services(BTreeSet::from(["SMS".to_string()]));
+        assert_consistent(&context, 0, 0);
+    }
 }
 
 #[cfg(test)]
 mod guid_query_tests {
     use std::env::current_dir;
 
-    use crate::tables::{messages::Message, table::get_connection};
+    use crate::tables::{messages::Message, schema::SchemaCapabilities, table::get_connection};
 
     #[test]
     fn test_cant_query_bad_guid() {
@@ -116,8 +293,9 @@ mod guid_query_tests {
             .unwrap()
             .join("imessage-database/test_data/db/test.db");
         let conn = get_connection(&db_path).unwrap();
+        let capabilities = SchemaCapabilities::probe(&conn).unwrap();
 
-        let message = Message::from_guid("fake-guid", &conn);
+        let message = Message::from_guid("fake-guid", &conn, &capabilities);
 
         assert!(message.is_err());
     }
@@ -130,9 +308,11 @@ mod guid_query_tests {
             .unwrap()
             .join("imessage-database/test_data/db/test.db");
         let conn = get_connection(&db_path).unwrap();
+        let capabilities = SchemaCapabilities::probe(&conn).unwrap();
 
         let mut message =
-            Message::from_guid("0355C6E1-D0C8-4212-AA87-DD8AE4FD1203", &conn).unwrap();
+            Message::from_guid("0355C6E1-D0C8-4212-AA87-DD8AE4FD1203", &conn, &capabilities)
+                .unwrap();
         let _ = message.generate_text(&conn);
         println!("{message:#?}");
         assert!(message.components.is_some());
@@ -146,8 +326,9 @@ mod guid_query_tests {
             .unwrap()
             .join("imessage-database/test_data/db/test.db");
         let conn = get_connection(&db_path).unwrap();
+        let capabilities = SchemaCapabilities::probe(&conn).unwrap();
 
-        let message = Message::from_guid("", &conn);
+        let message = Message::from_guid("", &conn, &capabilities);
         assert!(message.is_err());
     }
 
@@ -159,23 +340,41 @@ mod guid_query_tests {
             .unwrap()
             .join("imessage-database/test_data/db/test.db");
         let conn = get_connection(&db_path).unwrap();
+        let capabilities = SchemaCapabilities::probe(&conn).unwrap();
 
-        let message = Message::from_guid("not-a-valid-guid-format", &conn);
+        let message = Message::from_guid("not-a-valid-guid-format", &conn, &capabilities);
         assert!(message.is_err());
     }
 }
 
 #[cfg(test)]
 mod query_string_tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all},
+        path::PathBuf,
+    };
+
     use crate::{
-        tables::messages::query_parts,
+        tables::{messages::query_parts, schema::SchemaCapabilities},
+        util::fixture::{FixtureBuilder, Schema},
     };
 
+    fn probe(name: &str, schema: Schema) -> (PathBuf, SchemaCapabilities) {
+        let dir = temp_dir().join(format!("imessage-query-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let fixture = FixtureBuilder::create(&dir.join("chat.db"), schema).unwrap();
+        let capabilities = SchemaCapabilities::probe(fixture.connection()).unwrap();
+        (dir, capabilities)
+    }
+
     #[test]
     fn can_generate_no_filters_16() {
-        let query_string = query_parts::ios_16_newer_query(None, Some("LIMIT 10"));
+        let (dir, capabilities) = probe("can_generate_no_filters_16", Schema::Ios16Newer);
+        let query_string = query_parts::message_query(&capabilities, None, Some("LIMIT 10"));
         let expected = "\nSELECT
-    rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, associated_message_emoji,
+    rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, associated_message_emoji, date_retracted, schedule_type,
     c.chat_id,
     (SELECT COUNT(*) FROM message_attachment_join a WHERE m.ROWID = a.message_id) as num_attachments,
     d.chat_id as deleted_from,
@@ -187,15 +386,21 @@ LEFT JOIN chat_recoverable_message_join as d ON m.ROWID = d.message_id
 
 ORDER BY
     m.date DESC
-LIMIT 10;"; 
+LIMIT 10;";
         assert_eq!(query_string, expected);
+        remove_dir_all(dir).unwrap();
     }
 
     #[test]
     fn can_generate_filters_16() {
-        let query_string = query_parts::ios_16_newer_query(Some("WHERE m.guid = \"fake\""), Some("LIMIT 10"));
+        let (dir, capabilities) = probe("can_generate_filters_16", Schema::Ios16Newer);
+        let query_string = query_parts::message_query(
+            &capabilities,
+            Some("WHERE m.guid = \"fake\""),
+            Some("LIMIT 10"),
+        );
         let expected = "\nSELECT
-    rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, associated_message_emoji,
+    rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, associated_message_emoji, date_retracted, schedule_type,
     c.chat_id,
     (SELECT COUNT(*) FROM message_attachment_join a WHERE m.ROWID = a.message_id) as num_attachments,
     d.chat_id as deleted_from,
@@ -209,13 +414,15 @@ ORDER BY
     m.date DESC
 LIMIT 10;";
         assert_eq!(query_string, expected);
+        remove_dir_all(dir).unwrap();
     }
 
     #[test]
     fn can_generate_no_filters_14_15() {
-        let query_string = query_parts::ios_14_15_query(None, None);
+        let (dir, capabilities) = probe("can_generate_no_filters_14_15", Schema::Ios14To15);
+        let query_string = query_parts::message_query(&capabilities, None, None);
         let expected = "\nSELECT
-    *,
+    rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part,
     c.chat_id,
     (SELECT COUNT(*) FROM message_attachment_join a WHERE m.ROWID = a.message_id) as num_attachments,
     NULL as deleted_from,
@@ -227,15 +434,20 @@ LEFT JOIN chat_message_join as c ON m.ROWID = c.message_id
 ORDER BY
     m.date DESC
 ;";
-        println!("{query_string}");
         assert_eq!(query_string, expected);
+        remove_dir_all(dir).unwrap();
     }
 
     #[test]
     fn can_generate_filters_14_15() {
-        let query_string = query_parts::ios_14_15_query(Some("WHERE m.guid = \"fake\""), Some("LIMIT 10"));
+        let (dir, capabilities) = probe("can_generate_filters_14_15", Schema::Ios14To15);
+        let query_string = query_parts::message_query(
+            &capabilities,
+            Some("WHERE m.guid = \"fake\""),
+            Some("LIMIT 10"),
+        );
         let expected = "\nSELECT
-    *,
+    rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part,
     c.chat_id,
     (SELECT COUNT(*) FROM message_attachment_join a WHERE m.ROWID = a.message_id) as num_attachments,
     NULL as deleted_from,
@@ -248,13 +460,15 @@ ORDER BY
     m.date DESC
 LIMIT 10;";
         assert_eq!(query_string, expected);
+        remove_dir_all(dir).unwrap();
     }
 
     #[test]
     fn can_generate_no_filters_13() {
-        let query_string = query_parts::ios_13_older_query(None, Some("LIMIT 10"));
+        let (dir, capabilities) = probe("can_generate_no_filters_13", Schema::Ios13Older);
+        let query_string = query_parts::message_query(&capabilities, None, Some("LIMIT 10"));
         let expected = "\nSELECT
-    *,
+    rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id,
     c.chat_id,
     (SELECT COUNT(*) FROM message_attachment_join a WHERE m.ROWID = a.message_id) as num_attachments,
     NULL as deleted_from,
@@ -266,15 +480,20 @@ LEFT JOIN chat_message_join as c ON m.ROWID = c.message_id
 ORDER BY
     m.date DESC
 LIMIT 10;";
-        println!("{query_string}");
         assert_eq!(query_string, expected);
+        remove_dir_all(dir).unwrap();
     }
 
     #[test]
     fn can_generate_filters_13() {
-        let query_string = query_parts::ios_13_older_query(Some("WHERE m.guid = \"fake\""), Some("LIMIT 10"));
+        let (dir, capabilities) = probe("can_generate_filters_13", Schema::Ios13Older);
+        let query_string = query_parts::message_query(
+            &capabilities,
+            Some("WHERE m.guid = \"fake\""),
+            Some("LIMIT 10"),
+        );
         let expected = "\nSELECT
-    *,
+    rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id,
     c.chat_id,
     (SELECT COUNT(*) FROM message_attachment_join a WHERE m.ROWID = a.message_id) as num_attachments,
     NULL as deleted_from,
@@ -287,5 +506,6 @@ ORDER BY
     m.date DESC
 LIMIT 10;";
         assert_eq!(query_string, expected);
+        remove_dir_all(dir).unwrap();
     }
 }
@@ -0,0 +1,201 @@
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use serde_json::{from_str, json, to_string, to_value};
+
+    use crate::{
+        message_types::{
+            edited::{EditStatus, EditedEvent, EditedMessage, EditedMessagePart},
+            expressives::{BubbleEffect, Expressive, OwnedExpressive},
+            placemark::{OwnedPlacemarkMessage, Placemark, PlacemarkMessage},
+            text_effects::{Style, TextEffect},
+            url::{OwnedURLMessage, URLMessage},
+            variants::{
+                Announcement, CustomBalloon, OwnedAnnouncement, OwnedVariant, Tapback,
+                TapbackAction, Variant,
+            },
+        },
+        tables::{
+            attachment::Attachment,
+            chat::Chat,
+            handle::Handle,
+            messages::{
+                Message,
+                models::{AttachmentMeta, BubbleComponent, OwnedBubbleComponent, TextAttributes},
+            },
+        },
+    };
+
+    #[test]
+    fn can_round_trip_message() {
+        let mut message = Message::blank();
+        message.rowid = 10;
+        message.guid = "ABC".to_string();
+        message.text = Some("Hello".to_string());
+        message.chat_id = Some(2);
+        message.edited_parts = Some(EditedMessage {
+            parts: vec![EditedMessagePart {
+                status: EditStatus::Edited,
+                edit_history: vec![EditedEvent::new(1, Some("Helo".to_string()), None, None)],
+            }],
+        });
+
+        let serialized = to_string(&message).unwrap();
+        let deserialized: Message = from_str(&serialized).unwrap();
+
+        assert_eq!(deserialized.rowid, 10);
+        assert_eq!(deserialized.text.as_deref(), Some("Hello"));
+        assert_eq!(deserialized.edited_parts, message.edited_parts);
+        assert_eq!(to_string(&deserialized).unwrap(), serialized);
+    }
+
+    #[test]
+    fn can_serialize_edit_status() {
+        let part = EditedMessagePart {
+            status: EditStatus::Unsent,
+            edit_history: vec![],
+        };
+
+        assert_eq!(
+            to_value(&part).unwrap(),
+            json!({"status": "unsent", "edit_history": []})
+        );
+    }
+
+    #[test]
+    fn can_serialize_body() {
+        let body = vec![
+            BubbleComponent::Attachment(AttachmentMeta::default()),
+            BubbleComponent::Text(vec![
+                TextAttributes::new(0, 3, TextEffect::Mention("+5558675309")),
+                TextAttributes::new(3, 5, TextEffect::Styles(vec![Style::Bold])),
+            ]),
+            BubbleComponent::Retracted,
+        ];
+
+        let value = to_value(&body).unwrap();
+        assert_eq!(
+            value,
+            json!([
+                {"attachment": {"guid": null, "transcription": null, "height": null, "width": null, "name": null}},
+                {"text": [
+                    {"start": 0, "end": 3, "effect": {"mention": "+5558675309"}},
+                    {"start": 3, "end": 5, "effect": {"styles": ["bold"]}}
+                ]},
+                "retracted"
+            ])
+        );
+
+        let owned: Vec<OwnedBubbleComponent> = serde_json::from_value(value).unwrap();
+        assert_eq!(
+            owned,
+            body.iter()
+                .map(OwnedBubbleComponent::from)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            owned
+                .iter()
+                .map(OwnedBubbleComponent::as_borrowed)
+                .collect::<Vec<_>>(),
+            body
+        );
+    }
+
+    #[test]
+    fn can_serialize_variant() {
+        let variant = Variant::Tapback(1, TapbackAction::Added, Tapback::Emoji(Some("🔥")));
+
+        let value = to_value(&variant).unwrap();
+        assert_eq!(value, json!({"tapback": [1, "added", {"emoji": "🔥"}]}));
+
+        let owned: OwnedVariant = serde_json::from_value(value).unwrap();
+        assert_eq!(owned, OwnedVariant::from(&variant));
+
+        let app = to_value(Variant::App(CustomBalloon::CheckIn)).unwrap();
+        assert_eq!(app, json!({"app": "check_in"}));
+    }
+
+    #[test]
+    fn can_serialize_announcement() {
+        let announcement = Announcement::Unknown(&5);
+
+        let value = to_value(&announcement).unwrap();
+        assert_eq!(value, json!({"unknown": 5}));
+
+        let owned: OwnedAnnouncement = serde_json::from_value(value).unwrap();
+        assert_eq!(owned, OwnedAnnouncement::Unknown(5));
+    }
+
+    #[test]
+    fn can_serialize_expressive() {
+        let expressive = Expressive::Bubble(BubbleEffect::InvisibleInk);
+
+        let value = to_value(&expressive).unwrap();
+        assert_eq!(value, json!({"bubble": "invisible_ink"}));
+
+        let owned: OwnedExpressive = serde_json::from_value(value).unwrap();
+        assert_eq!(owned.as_borrowed(), expressive);
+    }
+
+    #[test]
+    fn can_round_trip_url_message() {
+        let message = URLMessage {
+            title: Some("Title"),
+            summary: None,
+            url: Some("https://example.com"),
+            original_url: None,
+            item_type: None,
+            images: vec!["https://example.com/1.png"],
+            icons: vec![],
+            site_name: Some("Example"),
+            placeholder: false,
+        };
+
+        let serialized = to_string(&message).unwrap();
+        let owned: OwnedURLMessage = from_str(&serialized).unwrap();
+
+        assert_eq!(owned.as_borrowed(), message);
+        assert_eq!(to_string(&owned).unwrap(), serialized);
+    }
+
+    #[test]
+    fn can_round_trip_placemark_message() {
+        let message = PlacemarkMessage {
+            url: Some("https://maps.apple.com/?q=Home"),
+            original_url: None,
+            place_name: Some("Home"),
+            placemark: Placemark {
+                city: Some("Cupertino"),
+                ..Default::default()
+            },
+        };
+
+        let serialized = to_string(&message).unwrap();
+        let owned: OwnedPlacemarkMessage = from_str(&serialized).unwrap();
+
+        assert_eq!(owned.as_borrowed(), message);
+    }
+
+    #[test]
+    fn can_round_trip_tables() {
+        let chat: Chat = from_str(
+            r#"{"rowid": 1, "chat_identifier": "chat0", "service_name": "iMessage", "display_name": null}"#,
+        )
+        .unwrap();
+        assert_eq!(chat.chat_identifier, "chat0");
+
+        let handle: Handle =
+            from_str(r#"{"rowid": 2, "id": "+15558675309", "person_centric_id": null}"#).unwrap();
+        assert_eq!(handle.id, "+15558675309");
+
+        let attachment: Attachment = from_str(
+            r#"{"rowid": 3, "filename": "a.png", "uti": null, "mime_type": "image/png", "transfer_name": null, "total_bytes": 10, "is_sticker": false, "hide_attachment": 0, "emoji_description": null, "copied_path": null}"#,
+        )
+        .unwrap();
+        assert_eq!(attachment.total_bytes, 10);
+        assert_eq!(
+            to_value(&attachment).unwrap()["mime_type"],
+            json!("image/png")
+        );
+    }
+}
@@ -0,0 +1,284 @@
+/*!
+ Introspection of the columns and tables available in an iMessage database.
+
+ The schema of the iMessage database changes between versions of macOS and iOS, and even databases from the
+ same version may differ. Instead of trying queries until one of them prepares, [`SchemaCapabilities`] reads
+ `PRAGMA table_info` for each table the crate queries, and the message queries are built from the columns
+ that are actually present. Consumers can ask which [`SchemaFeature`]s a database supports.
+
+ Probe a database once, when its connection is opened, and pass the capabilities to the functions that query it.
+
+ # Example:
+
+ ```
+ use imessage_database::util::dirs::default_db_path;
+ use imessage_database::tables::{
+     schema::{SchemaCapabilities, SchemaFeature},
+     table::get_connection,
+ };
+
+ let db_path = default_db_path();
+ let conn = get_connection(&db_path).unwrap();
+ let capabilities = SchemaCapabilities::probe(&conn).unwrap();
+
+ if !capabilities.supports(SchemaFeature::RecentlyDeleted) {
+     println!("Deleted messages cannot be recovered from this database");
+ }
+ for feature in capabilities.features() {
+     println!("Supports {feature}");
+ }
+ ```
+*/
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Formatter, Result},
+};
+
+use rusqlite::Connection;
+
+use crate::{
+    error::table::TableError,
+    tables::table::{
+        ATTACHMENT, CHAT, CHAT_HANDLE_JOIN, CHAT_MESSAGE_JOIN, HANDLE, MESSAGE,
+        MESSAGE_ATTACHMENT_JOIN, RECENTLY_DELETED,
+    },
+};
+
+/// Tables whose columns are read by [`SchemaCapabilities::probe()`]
+const PROBED_TABLES: [&str; 8] = [
+    MESSAGE,
+    CHAT,
+    HANDLE,
+    ATTACHMENT,
+    CHAT_MESSAGE_JOIN,
+    CHAT_HANDLE_JOIN,
+    MESSAGE_ATTACHMENT_JOIN,
+    RECENTLY_DELETED,
+];
+
+/// Features of the iMessage database that depend on its schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SchemaFeature {
+    /// Threaded replies, from `message.thread_originator_guid`
+    Replies,
+    /// Edited messages, from `message.date_edited`
+    Edits,
+    /// Unsent messages, from `message.date_retracted`
+    Unsends,
+    /// Deleted messages that can be recovered, from the `chat_recoverable_message_join` table
+    RecentlyDeleted,
+    /// Tapbacks with any emoji, from `message.associated_message_emoji`
+    EmojiTapbacks,
+    /// Messages scheduled with Send Later, from `message.schedule_type`
+    ScheduledMessages,
+    /// Descriptions of generated emoji, from `attachment.emoji_image_short_description`
+    Genmoji,
+}
+
+impl SchemaFeature {
+    /// Every feature, in the order they are reported
+    pub const ALL: [SchemaFeature; 7] = [
+        SchemaFeature::Replies,
+        SchemaFeature::Edits,
+        SchemaFeature::Unsends,
+        SchemaFeature::RecentlyDeleted,
+        SchemaFeature::EmojiTapbacks,
+        SchemaFeature::ScheduledMessages,
+        SchemaFeature::Genmoji,
+    ];
+}
+
+impl Display for SchemaFeature {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            SchemaFeature::Replies => write!(fmt, "replies"),
+            SchemaFeature::Edits => write!(fmt, "edits"),
+            SchemaFeature::Unsends => write!(fmt, "unsends"),
+            SchemaFeature::RecentlyDeleted => write!(fmt, "Recently Deleted"),
+            SchemaFeature::EmojiTapbacks => write!(fmt, "emoji tapbacks"),
+            SchemaFeature::ScheduledMessages => write!(fmt, "scheduled messages"),
+            SchemaFeature::Genmoji => write!(fmt, "Genmoji"),
+        }
+    }
+}
+
+/// The columns of each table in an iMessage database, see the [module](crate::tables::schema) docs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SchemaCapabilities {
+    /// Lowercase column names of each probed table that exists
+    tables: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl SchemaCapabilities {
+    /// Read the columns of every table the crate queries, skipping tables that do not exist
+    pub fn probe(db: &Connection) -> std::result::Result<Self, TableError> {
+        let mut tables = BTreeMap::new();
+        for table in PROBED_TABLES {
+            let mut statement = db
+                .prepare(&format!("PRAGMA table_info({table})"))
+                .map_err(TableError::Schema)?;
+            let columns = statement
+                .query_map([], |row| row.get::<_, String>("name"))
+                .map_err(TableError::Schema)?
+                .map(|name| name.map(|name| name.to_lowercase()))
+                .collect::<std::result::Result<BTreeSet<String>, _>>()
+                .map_err(TableError::Schema)?;
+
+            // `table_info` returns no rows for tables that do not exist
+            if !columns.is_empty() {
+                tables.insert(table.to_string(), columns);
+            }
+        }
+        Ok(Self { tables })
+    }
+
+    /// Determine whether a table exists
+    #[must_use]
+    pub fn has_table(&self, table: &str) -> bool {
+        self.tables.contains_key(table)
+    }
+
+    /// Determine whether a table exists and has a column, ignoring case
+    ///
+    /// Every table that exists has a `rowid`, even if it is not declared.
+    #[must_use]
+    pub fn has_column(&self, table: &str, column: &str) -> bool {
+        self.tables.get(table).is_some_and(|columns| {
+            column.eq_ignore_ascii_case("rowid") || columns.contains(&column.to_lowercase())
+        })
+    }
+
+    /// Get the lowercase column names of a table, or `None` if it does not exist
+    #[must_use]
+    pub fn columns(&self, table: &str) -> Option<&BTreeSet<String>> {
+        self.tables.get(table)
+    }
+
+    /// Determine whether the database supports a [`SchemaFeature`]
+    #[must_use]
+    pub fn supports(&self, feature: SchemaFeature) -> bool {
+        match feature {
+            SchemaFeature::Replies => self.has_column(MESSAGE, "thread_originator_guid"),
+            SchemaFeature::Edits => self.has_column(MESSAGE, "date_edited"),
+            SchemaFeature::Unsends => self.has_column(MESSAGE, "date_retracted"),
+            SchemaFeature::RecentlyDeleted => self.has_table(RECENTLY_DELETED),
+            SchemaFeature::EmojiTapbacks => self.has_column(MESSAGE, "associated_message_emoji"),
+            SchemaFeature::ScheduledMessages => self.has_column(MESSAGE, "schedule_type"),
+            SchemaFeature::Genmoji => self.has_column(ATTACHMENT, "emoji_image_short_description"),
+        }
+    }
+
+    /// Get every [`SchemaFeature`] the database supports
+    #[must_use]
+    pub fn features(&self) -> Vec<SchemaFeature> {
+        SchemaFeature::ALL
+            .into_iter()
+            .filter(|feature| self.supports(*feature))
+            .collect()
+    }
+
+    /// Get every [`SchemaFeature`] the database does not support
+    #[must_use]
+    pub fn missing_features(&self) -> Vec<SchemaFeature> {
+        SchemaFeature::ALL
+            .into_iter()
+            .filter(|feature| !self.supports(*feature))
+            .collect()
+    }
+
+    /// Determine whether the tables every message query reads from exist
+    #[must_use]
+    pub fn can_read_messages(&self) -> bool {
+        self.has_table(MESSAGE)
+            && self.has_table(CHAT_MESSAGE_JOIN)
+            && self.has_table(MESSAGE_ATTACHMENT_JOIN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use rusqlite::Connection;
+
+    use crate::{
+        tables::{
+            schema::{SchemaCapabilities, SchemaFeature},
+            table::{ATTACHMENT, CHAT, MESSAGE, RECENTLY_DELETED},
+        },
+        util::fixture::{FixtureBuilder, Schema},
+    };
+
+    fn build(name: &str, schema: Schema) -> (PathBuf, FixtureBuilder) {
+        let dir = temp_dir().join(format!("imessage-schema-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let fixture = FixtureBuilder::create(&dir.join("chat.db"), schema).unwrap();
+        (dir, fixture)
+    }
+
+    #[test]
+    fn can_probe_each_schema() {
+        for (name, schema, expected) in [
+            ("13", Schema::Ios13Older, vec![]),
+            ("14", Schema::Ios14To15, vec![SchemaFeature::Replies]),
+            ("16", Schema::Ios16Newer, SchemaFeature::ALL.to_vec()),
+        ] {
+            let (dir, fixture) = build(name, schema);
+            let capabilities = SchemaCapabilities::probe(fixture.connection()).unwrap();
+            assert!(capabilities.can_read_messages());
+            assert_eq!(capabilities.features(), expected);
+            remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn can_report_missing_features() {
+        let (dir, fixture) = build("missing", Schema::Ios14To15);
+        let capabilities = SchemaCapabilities::probe(fixture.connection()).unwrap();
+        assert_eq!(
+            capabilities.missing_features(),
+            vec![
+                SchemaFeature::Edits,
+                SchemaFeature::Unsends,
+                SchemaFeature::RecentlyDeleted,
+                SchemaFeature::EmojiTapbacks,
+                SchemaFeature::ScheduledMessages,
+                SchemaFeature::Genmoji,
+            ]
+        );
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_check_columns() {
+        let (dir, fixture) = build("columns", Schema::Ios16Newer);
+        let capabilities = SchemaCapabilities::probe(fixture.connection()).unwrap();
+
+        assert!(capabilities.has_column(MESSAGE, "rowid"));
+        assert!(capabilities.has_column(MESSAGE, "attributedBody"));
+        assert!(capabilities.has_column(MESSAGE, "DATE_RETRACTED"));
+        assert!(!capabilities.has_column(MESSAGE, "not_a_column"));
+        assert!(capabilities.has_column(CHAT, "chat_identifier"));
+        assert!(capabilities.has_column(ATTACHMENT, "emoji_image_short_description"));
+        assert!(capabilities.has_table(RECENTLY_DELETED));
+        assert!(!capabilities.has_column("not_a_table", "rowid"));
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_probe_empty_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        let capabilities = SchemaCapabilities::probe(&conn).unwrap();
+        assert!(!capabilities.can_read_messages());
+        assert!(capabilities.features().is_empty());
+        assert!(capabilities.columns(MESSAGE).is_none());
+    }
+}
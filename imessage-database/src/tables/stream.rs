@@ -0,0 +1,331 @@
+/*!
+ Asynchronous [`Stream`] adapters for table data, available with the `async` feature.
+
+ `SQLite` reads block the calling thread, so each stream opens its own connection on Tokio's
+ [blocking thread pool](tokio::task::spawn_blocking) and sends rows back over a bounded channel.
+ When the channel is full, the reading thread waits for the consumer to catch up, so memory use is
+ limited by the channel's `capacity` regardless of the size of the table. Dropping a stream stops
+ the reading thread after its current row.
+
+ # Example
+
+ ```rust,no_run
+ use futures::StreamExt;
+ use imessage_database::{
+     tables::{messages::Message, stream::DEFAULT_CAPACITY},
+     util::{dirs::default_db_path, query_context::QueryContext},
+ };
+
+ # async fn example() {
+ let db_path = default_db_path();
+ let context = QueryContext::default();
+
+ let mut messages = Message::stream_rows_async(&db_path, &context, DEFAULT_CAPACITY);
+ while let Some(message) = messages.next().await {
+     println!("{:#?}", message);
+ }
+ # }
+ ```
+*/
+
+use std::{
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use rusqlite::Connection;
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+
+use crate::{
+    error::table::TableError,
+    tables::{
+        attachment::Attachment,
+        messages::Message,
+        schema::SchemaCapabilities,
+        table::{Cacheable, Table, get_connection},
+    },
+    util::query_context::QueryContext,
+};
+
+/// The default number of rows buffered between the reading thread and the consumer
+pub const DEFAULT_CAPACITY: usize = 64;
+
+/// A [`Stream`] of rows read from the database on a blocking thread
+///
+/// Created by [`Message::stream_rows_async()`], [`Attachment::from_message_async()`], or [`StreamTable::stream_async()`].
+#[derive(Debug)]
+pub struct TableStream<T> {
+    receiver: Receiver<Result<T, TableError>>,
+}
+
+impl<T> TableStream<T> {
+    /// Open a connection to `db_path` on the blocking thread pool and run `read`, which sends rows to the stream
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime.
+    fn spawn<F>(db_path: &Path, capacity: usize, read: F) -> Self
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection, &Sender<Result<T, TableError>>) -> Result<(), TableError>
+            + Send
+            + 'static,
+    {
+        let (sender, receiver) = channel(capacity.max(1));
+        let db_path = db_path.to_path_buf();
+
+        tokio::task::spawn_blocking(move || {
+            let result = get_connection(&db_path).and_then(|db| read(&db, &sender));
+            if let Err(why) = result {
+                // The consumer may have already dropped the stream, so there is nobody to notify
+                let _ = sender.blocking_send(Err(why));
+            }
+        });
+
+        Self { receiver }
+    }
+}
+
+impl<T> Stream for TableStream<T> {
+    type Item = Result<T, TableError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Send each item to the stream, stopping early if the consumer has dropped it
+fn send_all<T>(
+    sender: &Sender<Result<T, TableError>>,
+    items: impl Iterator<Item = Result<T, TableError>>,
+) {
+    for item in items {
+        if sender.blocking_send(item).is_err() {
+            return;
+        }
+    }
+}
+
+impl Message {
+    /// Asynchronous version of [`Message::stream_rows()`] that yields extracted messages
+    ///
+    /// At most `capacity` messages are buffered before the reading thread waits for the consumer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime.
+    #[must_use]
+    pub fn stream_rows_async(
+        db_path: &Path,
+        context: &QueryContext,
+        capacity: usize,
+    ) -> TableStream<Message> {
+        let context = context.clone();
+        TableStream::spawn(db_path, capacity, move |db, sender| {
+            let capabilities = SchemaCapabilities::probe(db)?;
+            let mut statement = Message::stream_rows(db, &capabilities, &context)?;
+            let messages = statement
+                .query_map([], |row| Ok(Message::from_row(row)))
+                .map_err(TableError::Messages)?;
+            send_all(sender, messages.map(Message::extract));
+            Ok(())
+        })
+    }
+}
+
+impl Attachment {
+    /// Asynchronous version of [`Attachment::from_message()`] that yields each attachment in order
+    ///
+    /// At most `capacity` attachments are buffered before the reading thread waits for the consumer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime.
+    #[must_use]
+    pub fn from_message_async(
+        db_path: &Path,
+        msg: &Message,
+        capacity: usize,
+    ) -> TableStream<Attachment> {
+        let message_rowid = msg.has_attachments().then_some(msg.rowid);
+        TableStream::spawn(db_path, capacity, move |db, sender| {
+            if let Some(message_rowid) = message_rowid {
+                let capabilities = SchemaCapabilities::probe(db)?;
+                let attachments = Attachment::from_message_rowid(db, &capabilities, message_rowid)?;
+                send_all(sender, attachments.into_iter().map(Ok));
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Defines behavior for streaming every row of a [`Cacheable`] table asynchronously
+pub trait StreamTable: Table + Cacheable + Sized + Send + 'static {
+    /// Stream every row returned by [`Table::get()`]
+    ///
+    /// At most `capacity` rows are buffered before the reading thread waits for the consumer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime.
+    ///
+    /// # Example:
+    ///
+    /// ```rust,no_run
+    /// use futures::StreamExt;
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::{chat::Chat, stream::{DEFAULT_CAPACITY, StreamTable}};
+    ///
+    /// # async fn example() {
+    /// let db_path = default_db_path();
+    /// let chats = Chat::stream_async(&db_path, DEFAULT_CAPACITY).collect::<Vec<_>>().await;
+    /// # }
+    /// ```
+    #[must_use]
+    fn stream_async(db_path: &Path, capacity: usize) -> TableStream<Self> {
+        TableStream::spawn(db_path, capacity, |db, sender| {
+            let mut statement = Self::get(db)?;
+            match statement.query_map([], |row| Ok(Self::from_row(row))) {
+                Ok(rows) => send_all(sender, rows.map(Self::extract)),
+                // Use the table's own error variant for query failures
+                Err(why) => send_all(sender, std::iter::once(Self::extract(Err(why)))),
+            }
+            Ok(())
+        })
+    }
+}
+
+impl<T: Table + Cacheable + Sized + Send + 'static> StreamTable for T {}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet, env::current_dir, iter::repeat_with, path::PathBuf,
+        sync::mpsc::channel, time::Duration,
+    };
+
+    use futures::StreamExt;
+
+    use crate::{
+        tables::{
+            attachment::Attachment,
+            chat::Chat,
+            chat_handle::ChatToHandle,
+            handle::Handle,
+            messages::Message,
+            schema::SchemaCapabilities,
+            stream::{DEFAULT_CAPACITY, StreamTable, TableStream, send_all},
+            table::{Cacheable, Table, get_connection},
+        },
+        util::query_context::QueryContext,
+    };
+
+    fn db_path() -> PathBuf {
+        current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/db/test.db")
+    }
+
+    #[tokio::test]
+    async fn can_stream_messages() {
+        let context = QueryContext::default();
+        let messages = Message::stream_rows_async(&db_path(), &context, DEFAULT_CAPACITY)
+            .collect::<Vec<_>>()
+            .await;
+
+        let conn = get_connection(&db_path()).unwrap();
+        let capabilities = SchemaCapabilities::probe(&conn).unwrap();
+        assert_eq!(
+            messages.len() as u64,
+            Message::get_count(&conn, &capabilities, &context).unwrap()
+        );
+        assert!(messages.iter().all(Result::is_ok));
+    }
+
+    #[tokio::test]
+    async fn can_stream_messages_with_context() {
+        let mut context = QueryContext::default();
+        context.set_selected_handle_ids(BTreeSet::from([2]));
+
+        let messages = Message::stream_rows_async(&db_path(), &context, 1)
+            .collect::<Vec<_>>()
+            .await;
+
+        assert!(messages.is_empty());
+    }
+
+    #[tokio::test]
+    async fn can_stream_attachments() {
+        let conn = get_connection(&db_path()).unwrap();
+        let capabilities = SchemaCapabilities::probe(&conn).unwrap();
+        let message = Message::stream_rows(&conn, &capabilities, &QueryContext::default())
+            .unwrap()
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .unwrap()
+            .map(Message::extract)
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let expected = Attachment::from_message(&conn, &capabilities, &message).unwrap();
+        let attachments = Attachment::from_message_async(&db_path(), &message, 1)
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(attachments.len(), expected.len());
+        for (streamed, expected) in attachments.into_iter().zip(expected) {
+            assert_eq!(streamed.unwrap().rowid, expected.rowid);
+        }
+    }
+
+    #[tokio::test]
+    async fn can_stream_cacheable_tables() {
+        let conn = get_connection(&db_path()).unwrap();
+
+        let chats = Chat::stream_async(&db_path(), 1).collect::<Vec<_>>().await;
+        assert_eq!(chats.len(), Chat::cache(&conn).unwrap().len());
+
+        let handles = Handle::stream_async(&db_path(), 1)
+            .collect::<Vec<_>>()
+            .await;
+        assert!(handles.iter().all(Result::is_ok));
+
+        let chat_to_handles = ChatToHandle::stream_async(&db_path(), 1)
+            .collect::<Vec<_>>()
+            .await;
+        assert!(chat_to_handles.iter().all(Result::is_ok));
+    }
+
+    #[tokio::test]
+    async fn can_drop_stream_early() {
+        let (done, finished) = channel();
+        let mut rows = TableStream::spawn(&db_path(), 1, move |_, sender| {
+            // The rows never run out, so the reader only stops once the stream is dropped
+            send_all(sender, repeat_with(|| Ok(0)));
+            let _ = done.send(());
+            Ok(())
+        });
+        assert!(rows.next().await.is_some());
+        drop(rows);
+
+        assert!(finished.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn cant_stream_missing_database() {
+        let messages = Message::stream_rows_async(
+            &PathBuf::from("fake/path/chat.db"),
+            &QueryContext::default(),
+            DEFAULT_CAPACITY,
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].is_err());
+    }
+}
@@ -2,11 +2,15 @@
  This module defines traits for table representations and stores some shared table constants.
 */
 
-use std::{collections::HashMap, fs::metadata, path::Path};
+use std::{collections::HashMap, fmt::Display, fs::metadata, path::Path};
 
 use rusqlite::{Connection, Error, OpenFlags, Result, Row, Statement, blob::Blob};
 
-use crate::{error::table::TableError, tables::messages::models::BubbleComponent};
+use crate::{
+    error::table::TableError,
+    tables::messages::models::BubbleComponent,
+    util::output::{done_processing, processing},
+};
 
 /// Defines behavior for SQL Table data
 pub trait Table {
@@ -36,10 +40,22 @@ pub trait Deduplicate {
     fn dedupe(duplicated_data: &HashMap<i32, Self::T>) -> HashMap<i32, i32>;
 }
 
-/// Defines behavior for printing diagnostic information for a table
+/// Defines behavior for gathering and printing diagnostic information for a table
 pub trait Diagnostic {
+    /// The diagnostic data gathered about the table, from [`diagnostic`](crate::tables::diagnostic)
+    type Report: Display;
+
+    /// Gather diagnostic data about the table
+    fn diagnostic(db: &Connection) -> Result<Self::Report, TableError>;
+
     /// Emit diagnostic data about the table to `stdout`
-    fn run_diagnostic(db: &Connection) -> Result<(), TableError>;
+    fn run_diagnostic(db: &Connection) -> Result<(), TableError> {
+        processing();
+        let report = Self::diagnostic(db);
+        done_processing();
+        print!("{}", report?);
+        Ok(())
+    }
 }
 
 /// Defines behavior for getting BLOB data from from a table
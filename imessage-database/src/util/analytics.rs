@@ -0,0 +1,520 @@
+/*!
+ Aggregate statistics about how people message, computed per chat and per handle.
+
+ An [`Analyzer`] reads messages one at a time, in the order they were sent, and builds an [`Analysis`]
+ with a [`Stats`] for the whole database, for the database owner, for each chat, and for each handle:
+
+ - How long people take to respond, as a [`Distribution`] of the time between a message and the next message
+   in the same chat from someone else
+ - How long messages wait to be read, from their read receipts
+ - When people message, as a [`Heatmap`] of weekday by hour of the day in local time
+ - How many messages, tapbacks, and attachments are sent
+ - How often messages are edited or unsent
+
+ Messages from the database owner are counted in [`Analysis::me`] rather than in [`Analysis::handles`], as the
+ handle of a sent message is its recipient.
+
+ # Example
+
+ ```rust
+ use imessage_database::{
+     tables::{messages::Message, table::{get_connection, Table}},
+     util::{analytics::Analyzer, dates::get_offset, dirs::default_db_path},
+ };
+
+ let db_path = default_db_path();
+ let conn = get_connection(&db_path).unwrap();
+
+ let mut analyzer = Analyzer::new(get_offset());
+ let mut statement = Message::get(&conn).unwrap();
+ let messages = statement.query_map([], |row| Ok(Message::from_row(row))).unwrap();
+ for message in messages {
+     analyzer.add(&Message::extract(message).unwrap());
+ }
+
+ let analysis = analyzer.finish();
+ if let Some(latency) = analysis.overall.response_latency {
+     println!("Median response time: {} seconds", latency.median);
+ }
+ ```
+*/
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Datelike, Timelike};
+
+use crate::{
+    tables::messages::Message,
+    util::dates::{TIMESTAMP_FACTOR, get_local_time},
+};
+
+/// Messages sent more than this many seconds after the previous message start a new exchange instead of responding to it
+pub const RESPONSE_WINDOW: i64 = 24 * 60 * 60;
+
+/// Summary statistics of a set of durations, in seconds
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Distribution {
+    /// The number of durations measured
+    pub count: usize,
+    pub min: f64,
+    /// The 50th percentile
+    pub median: f64,
+    /// The 75th percentile
+    pub p75: f64,
+    /// The 90th percentile
+    pub p90: f64,
+    /// The 99th percentile
+    pub p99: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl Distribution {
+    /// Summarize a set of durations, or `None` if there are none
+    #[must_use]
+    pub fn from_samples(mut samples: Vec<f64>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_by(f64::total_cmp);
+
+        Some(Self {
+            count: samples.len(),
+            min: samples[0],
+            median: percentile(&samples, 50.),
+            p75: percentile(&samples, 75.),
+            p90: percentile(&samples, 90.),
+            p99: percentile(&samples, 99.),
+            max: samples[samples.len() - 1],
+            mean: samples.iter().sum::<f64>() / samples.len() as f64,
+        })
+    }
+}
+
+/// Get the `p`th percentile of sorted, non-empty samples, interpolating between the closest ranks
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p / 100.) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+/// The number of messages sent in each hour of each day of the week, in local time
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Heatmap {
+    /// Indexed by the day of the week, starting on Monday, then by the hour of the day
+    pub counts: [[u64; 24]; 7],
+}
+
+impl Heatmap {
+    /// Count a message sent on `weekday`, where Monday is `0`, during `hour`
+    pub fn record(&mut self, weekday: usize, hour: usize) {
+        if let Some(count) = self
+            .counts
+            .get_mut(weekday)
+            .and_then(|day| day.get_mut(hour))
+        {
+            *count += 1;
+        }
+    }
+
+    /// The weekday, where Monday is `0`, and hour with the most messages, or `None` if there are no messages
+    #[must_use]
+    pub fn busiest(&self) -> Option<(usize, usize)> {
+        let mut busiest = None;
+        let mut most = 0;
+        for (weekday, day) in self.counts.iter().enumerate() {
+            for (hour, &count) in day.iter().enumerate() {
+                if count > most {
+                    most = count;
+                    busiest = Some((weekday, hour));
+                }
+            }
+        }
+        busiest
+    }
+}
+
+/// Statistics about the messages in a chat, from a handle, or from the database owner
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats {
+    /// The number of messages, excluding tapbacks and announcements other than unsent messages
+    pub messages: u64,
+    /// The number of tapbacks added or removed
+    pub tapbacks: u64,
+    /// The number of files attached to messages
+    pub attachments: u64,
+    /// The number of messages that were edited but not fully unsent
+    pub edited: u64,
+    /// The number of messages where every part was unsent
+    pub unsent: u64,
+    /// The share of messages that were edited, from `0` to `1`
+    pub edit_rate: f64,
+    /// The share of messages that were unsent, from `0` to `1`
+    pub unsend_rate: f64,
+    /// Seconds between a message and the previous message from someone else, within [`RESPONSE_WINDOW`]
+    pub response_latency: Option<Distribution>,
+    /// Seconds between a message being sent and being read
+    pub read_delay: Option<Distribution>,
+    /// When the messages were sent
+    pub heatmap: Heatmap,
+}
+
+/// The statistics computed by an [`Analyzer`]
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Analysis {
+    /// Every message that was analyzed
+    pub overall: Stats,
+    /// Messages sent by the database owner
+    pub me: Stats,
+    /// Messages in each chat, keyed by chat ID
+    pub chats: BTreeMap<i32, Stats>,
+    /// Messages received from each handle, keyed by handle ID
+    pub handles: BTreeMap<i32, Stats>,
+}
+
+/// Who sent a message, used to find when a different person responds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sender {
+    Me,
+    Handle(Option<i32>),
+}
+
+/// The measurements taken from a single message
+struct Measurement {
+    is_tapback: bool,
+    attachments: u64,
+    is_edited: bool,
+    is_unsent: bool,
+    response_latency: Option<f64>,
+    read_delay: Option<f64>,
+    /// The weekday, where Monday is `0`, and hour the message was sent
+    sent_at: Option<(usize, usize)>,
+}
+
+/// Running totals for a [`Stats`]
+#[derive(Debug, Default)]
+struct Accumulator {
+    stats: Stats,
+    response_latency: Vec<f64>,
+    read_delay: Vec<f64>,
+}
+
+impl Accumulator {
+    fn finish(mut self) -> Stats {
+        if self.stats.messages > 0 {
+            self.stats.edit_rate = self.stats.edited as f64 / self.stats.messages as f64;
+            self.stats.unsend_rate = self.stats.unsent as f64 / self.stats.messages as f64;
+        }
+        self.stats.response_latency = Distribution::from_samples(self.response_latency);
+        self.stats.read_delay = Distribution::from_samples(self.read_delay);
+        self.stats
+    }
+
+    fn record(&mut self, measurement: &Measurement) {
+        if measurement.is_tapback {
+            self.stats.tapbacks += 1;
+            return;
+        }
+        self.stats.messages += 1;
+        self.stats.attachments += measurement.attachments;
+        if measurement.is_unsent {
+            self.stats.unsent += 1;
+        } else if measurement.is_edited {
+            self.stats.edited += 1;
+        }
+        self.response_latency.extend(measurement.response_latency);
+        self.read_delay.extend(measurement.read_delay);
+        if let Some((weekday, hour)) = measurement.sent_at {
+            self.stats.heatmap.record(weekday, hour);
+        }
+    }
+}
+
+/// Builds an [`Analysis`] from messages read in the order they were sent
+#[derive(Debug)]
+pub struct Analyzer {
+    /// The offset used to convert message dates to local time, from [`get_offset`](crate::util::dates::get_offset)
+    offset: i64,
+    overall: Accumulator,
+    me: Accumulator,
+    chats: HashMap<i32, Accumulator>,
+    handles: HashMap<i32, Accumulator>,
+    /// The sender and date of the most recent message in each chat
+    previous: HashMap<i32, (Sender, i64)>,
+}
+
+impl Analyzer {
+    #[must_use]
+    pub fn new(offset: i64) -> Self {
+        Self {
+            offset,
+            overall: Accumulator::default(),
+            me: Accumulator::default(),
+            chats: HashMap::new(),
+            handles: HashMap::new(),
+            previous: HashMap::new(),
+        }
+    }
+
+    /// Add a message to the analysis
+    ///
+    /// Messages must be added in the order they were sent for response times to be measured. Unsent messages are
+    /// only detected if their [`edited_parts`](Message::edited_parts) were populated by
+    /// [`generate_text()`](Message::generate_text).
+    pub fn add(&mut self, message: &Message) {
+        // Unsent messages are announcements too, but they were messages before they were unsent
+        if message.group_action().is_some() || message.is_kept_audio_message() {
+            return;
+        }
+        let is_tapback = message.is_tapback();
+        let sender = if message.is_from_me() {
+            Sender::Me
+        } else {
+            Sender::Handle(message.handle_id)
+        };
+
+        // Tapbacks react to a message rather than respond to it, so they do not start or end an exchange
+        let mut response_latency = None;
+        if let (false, Some(chat_id)) = (is_tapback, message.chat_id)
+            && let Some((previous_sender, previous_date)) =
+                self.previous.insert(chat_id, (sender, message.date))
+        {
+            let gap = (message.date - previous_date) / TIMESTAMP_FACTOR;
+            if previous_sender != sender && (0..=RESPONSE_WINDOW).contains(&gap) {
+                response_latency = Some(gap as f64);
+            }
+        }
+
+        let read_delay = (message.date_read != 0 && message.date_read >= message.date)
+            .then(|| ((message.date_read - message.date) / TIMESTAMP_FACTOR) as f64);
+
+        let sent_at = get_local_time(&message.date, &self.offset)
+            .ok()
+            .map(|date| {
+                (
+                    date.weekday().num_days_from_monday() as usize,
+                    date.hour() as usize,
+                )
+            });
+
+        let measurement = Measurement {
+            is_tapback,
+            attachments: u64::try_from(message.num_attachments).unwrap_or(0),
+            is_edited: message.is_edited(),
+            is_unsent: message.is_fully_unsent(),
+            response_latency,
+            read_delay,
+            sent_at,
+        };
+
+        self.overall.record(&measurement);
+        if let Some(chat_id) = message.chat_id {
+            self.chats.entry(chat_id).or_default().record(&measurement);
+        }
+        match sender {
+            Sender::Me => self.me.record(&measurement),
+            Sender::Handle(Some(handle_id)) if handle_id != 0 => {
+                self.handles
+                    .entry(handle_id)
+                    .or_default()
+                    .record(&measurement);
+            }
+            Sender::Handle(_) => {}
+        }
+    }
+
+    /// Compute the statistics for every message added
+    #[must_use]
+    pub fn finish(self) -> Analysis {
+        Analysis {
+            overall: self.overall.finish(),
+            me: self.me.finish(),
+            chats: self
+                .chats
+                .into_iter()
+                .map(|(id, stats)| (id, stats.finish()))
+                .collect(),
+            handles: self
+                .handles
+                .into_iter()
+                .map(|(id, stats)| (id, stats.finish()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        message_types::edited::{EditStatus, EditedMessage, EditedMessagePart},
+        tables::messages::Message,
+        util::{
+            analytics::{Analyzer, Distribution, Heatmap, RESPONSE_WINDOW},
+            dates::{TIMESTAMP_FACTOR, get_offset},
+        },
+    };
+
+    fn message(seconds: i64, handle_id: i32, is_from_me: bool) -> Message {
+        let mut message = Message::blank();
+        message.date = seconds * TIMESTAMP_FACTOR;
+        message.handle_id = Some(handle_id);
+        message.is_from_me = is_from_me;
+        message.chat_id = Some(1);
+        message
+    }
+
+    #[test]
+    fn can_summarize_distribution() {
+        let distribution = Distribution::from_samples(vec![4., 1., 3., 2., 5.]).unwrap();
+
+        assert_eq!(distribution.count, 5);
+        assert_eq!(distribution.min, 1.);
+        assert_eq!(distribution.median, 3.);
+        assert_eq!(distribution.p75, 4.);
+        assert!((distribution.p90 - 4.6).abs() < f64::EPSILON);
+        assert_eq!(distribution.max, 5.);
+        assert_eq!(distribution.mean, 3.);
+    }
+
+    #[test]
+    fn cant_summarize_empty_distribution() {
+        assert_eq!(Distribution::from_samples(vec![]), None);
+    }
+
+    #[test]
+    fn can_find_busiest_hour() {
+        let mut heatmap = Heatmap::default();
+        assert_eq!(heatmap.busiest(), None);
+
+        heatmap.record(2, 14);
+        heatmap.record(2, 14);
+        heatmap.record(6, 23);
+        heatmap.record(7, 0);
+
+        assert_eq!(heatmap.busiest(), Some((2, 14)));
+        assert_eq!(heatmap.counts.iter().flatten().sum::<u64>(), 3);
+    }
+
+    #[test]
+    fn can_measure_response_latency() {
+        let mut analyzer = Analyzer::new(0);
+        analyzer.add(&message(0, 5, false));
+        analyzer.add(&message(30, 5, false));
+        analyzer.add(&message(90, 5, true));
+        analyzer.add(&message(100, 5, false));
+        analyzer.add(&message(100 + RESPONSE_WINDOW + 1, 5, true));
+
+        let analysis = analyzer.finish();
+
+        let me = analysis.me.response_latency.unwrap();
+        assert_eq!(me.count, 1);
+        assert_eq!(me.median, 60.);
+
+        let handle = analysis.handles[&5].response_latency.as_ref().unwrap();
+        assert_eq!(handle.count, 1);
+        assert_eq!(handle.median, 10.);
+
+        assert_eq!(
+            analysis.chats[&1].response_latency.as_ref().unwrap().count,
+            2
+        );
+        assert_eq!(analysis.overall.messages, 5);
+    }
+
+    #[test]
+    fn can_ignore_tapbacks_for_responses() {
+        let mut tapback = message(10, 5, true);
+        tapback.associated_message_guid =
+            Some("p:0/00000000-0000-0000-0000-000000000000".to_string());
+        tapback.associated_message_type = Some(2000);
+
+        let mut analyzer = Analyzer::new(0);
+        analyzer.add(&message(0, 5, false));
+        analyzer.add(&tapback);
+        analyzer.add(&message(20, 5, true));
+
+        let analysis = analyzer.finish();
+
+        assert_eq!(analysis.me.tapbacks, 1);
+        assert_eq!(analysis.me.messages, 1);
+        assert_eq!(analysis.me.response_latency.unwrap().median, 20.);
+    }
+
+    #[test]
+    fn can_measure_read_delay() {
+        let mut read = message(0, 5, false);
+        read.date_read = 45 * TIMESTAMP_FACTOR;
+        let unread = message(10, 5, false);
+
+        let mut analyzer = Analyzer::new(0);
+        analyzer.add(&read);
+        analyzer.add(&unread);
+
+        let analysis = analyzer.finish();
+        let delay = analysis.handles[&5].read_delay.as_ref().unwrap();
+        assert_eq!(delay.count, 1);
+        assert_eq!(delay.median, 45.);
+    }
+
+    #[test]
+    fn can_count_edits_and_unsends() {
+        let mut edited = message(0, 5, true);
+        edited.date_edited = TIMESTAMP_FACTOR;
+        edited.edited_parts = Some(EditedMessage {
+            parts: vec![EditedMessagePart {
+                status: EditStatus::Edited,
+                edit_history: vec![],
+            }],
+        });
+        let mut unsent = message(10, 5, true);
+        unsent.date_edited = 11 * TIMESTAMP_FACTOR;
+        unsent.edited_parts = Some(EditedMessage {
+            parts: vec![EditedMessagePart {
+                status: EditStatus::Unsent,
+                edit_history: vec![],
+            }],
+        });
+        let mut attachment = message(20, 5, true);
+        attachment.num_attachments = 2;
+
+        let mut analyzer = Analyzer::new(0);
+        analyzer.add(&edited);
+        analyzer.add(&unsent);
+        analyzer.add(&attachment);
+        analyzer.add(&message(30, 5, true));
+
+        let me = analyzer.finish().me;
+        assert_eq!(me.messages, 4);
+        assert_eq!(me.edited, 1);
+        assert_eq!(me.unsent, 1);
+        assert_eq!(me.attachments, 2);
+        assert_eq!(me.edit_rate, 0.25);
+        assert_eq!(me.unsend_rate, 0.25);
+    }
+
+    #[test]
+    fn can_build_heatmap() {
+        let mut analyzer = Analyzer::new(get_offset());
+        // Noon on 2001-01-01 UTC, a Monday, is Monday or Tuesday in every timezone
+        analyzer.add(&message(3600 * 12, 5, false));
+
+        let analysis = analyzer.finish();
+        let (weekday, _) = analysis.handles[&5].heatmap.busiest().unwrap();
+        assert!(weekday <= 1);
+        assert_eq!(
+            analysis
+                .overall
+                .heatmap
+                .counts
+                .iter()
+                .flatten()
+                .sum::<u64>(),
+            1
+        );
+    }
+}
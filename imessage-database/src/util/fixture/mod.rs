@@ -0,0 +1,1159 @@
+/*!
+ Build synthetic iMessage databases for tests, available with the `test-util` feature.
+
+ [`FixtureBuilder`] creates a database with the tables, indexes, and triggers of a chosen [`Schema`] generation, the
+ same generations that the message queries distinguish. It can then insert chats, handles, messages with `typedstream`
+ bodies, tapbacks, attachments with files on disk, edits and unsends recorded in `message_summary_info`, and messages
+ moved to Recently Deleted.
+
+ # Example
+
+ ```rust
+ use imessage_database::util::{
+     fixture::{FixtureBuilder, NewMessage, Schema},
+     typedstream::models::AttributeRun,
+ };
+
+ let dir = std::env::temp_dir().join("imessage-fixture-example");
+ std::fs::create_dir_all(&dir).unwrap();
+ # let _ = std::fs::remove_file(dir.join("chat.db"));
+
+ let mut fixture = FixtureBuilder::create(&dir.join("chat.db"), Schema::Ios16Newer).unwrap();
+ let handle = fixture.handle("+15558675309", "iMessage").unwrap();
+ let chat = fixture.chat("+15558675309", None, &[handle]).unwrap();
+
+ let message = fixture
+     .message(
+         chat,
+         NewMessage::new(694_000_000_000_000_000)
+             .from_handle(handle)
+             .with_text(vec![AttributeRun::new("Hello "), AttributeRun::new("world").with_link("https://example.com")]),
+     )
+     .unwrap();
+ fixture.edit(message.rowid, 0, "Hello there", 694_000_060_000_000_000).unwrap();
+ ```
+*/
+
+mod schema;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{create_dir_all, write},
+    io,
+    path::{Path, PathBuf},
+};
+
+use plist::{Dictionary, Value};
+use rusqlite::{Connection, params};
+
+use crate::{
+    error::table::TableError,
+    message_types::variants::{Tapback, TapbackAction},
+    tables::table::{
+        ATTACHMENT, CHAT, CHAT_HANDLE_JOIN, CHAT_MESSAGE_JOIN, HANDLE, MESSAGE,
+        MESSAGE_ATTACHMENT_JOIN, RECENTLY_DELETED,
+    },
+    util::{
+        dates::TIMESTAMP_FACTOR,
+        typedstream::{
+            models::{AttributeRun, AttributedString},
+            writer::TypedStreamWriter,
+        },
+    },
+};
+
+/// Name of the directory, next to the database, where attachment files are written
+const ATTACHMENTS_DIR: &str = "Attachments";
+
+/// Generations of the iMessage database schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schema {
+    /// macOS Catalina, iOS 13 and older: no replies
+    Ios13Older,
+    /// macOS Big Sur to Monterey, iOS 14 to iOS 15: replies, but no edits or Recently Deleted
+    Ios14To15,
+    /// macOS Ventura+ and iOS 16+: edits, unsends, and Recently Deleted
+    Ios16Newer,
+}
+
+/// One part of a [`NewMessage`]
+#[derive(Debug, Clone)]
+pub enum Part {
+    /// Text and its attributes
+    Text(Vec<AttributeRun>),
+    /// A file that is written to disk and linked to the message
+    Attachment {
+        /// The attachment's original filename
+        name: String,
+        /// The attachment's MIME type, i.e. `image/png`
+        mime_type: String,
+        /// The contents of the file
+        contents: Vec<u8>,
+    },
+}
+
+/// A message to insert with [`FixtureBuilder::message()`]
+///
+/// Dates are stored like the `date` column: nanoseconds since 2001-01-01 in UTC.
+#[derive(Debug, Clone)]
+pub struct NewMessage {
+    /// The parts of the message, in order
+    pub parts: Vec<Part>,
+    /// The handle that sent the message, or `0` if it was sent from this device
+    pub handle_id: i32,
+    /// `true` if the message was sent from this device
+    pub is_from_me: bool,
+    /// When the message was sent
+    pub date: i64,
+    /// The service the message was sent with, i.e. `iMessage` or `SMS`
+    pub service: String,
+    /// The GUID and part index of the message this message replies to
+    pub reply_to: Option<(String, usize)>,
+    /// The GUID and part index of the message a tapback applies to
+    pub associated_message: Option<(String, usize)>,
+    /// The tapback code, see [`Message::variant()`](crate::tables::messages::Message::variant)
+    pub associated_message_type: i32,
+    /// The emoji of a custom emoji tapback
+    pub associated_message_emoji: Option<String>,
+}
+
+impl NewMessage {
+    /// Create a message without any parts, sent from this device
+    #[must_use]
+    pub fn new(date: i64) -> Self {
+        Self {
+            parts: vec![],
+            handle_id: 0,
+            is_from_me: true,
+            date,
+            service: "iMessage".to_string(),
+            reply_to: None,
+            associated_message: None,
+            associated_message_type: 0,
+            associated_message_emoji: None,
+        }
+    }
+
+    /// Create a message with a single part of plain text
+    #[must_use]
+    pub fn text(text: &str, date: i64) -> Self {
+        Self::new(date).with_text(vec![AttributeRun::new(text)])
+    }
+
+    /// Create a tapback on a part of another message
+    #[must_use]
+    pub fn tapback(
+        target_guid: &str,
+        part: usize,
+        tapback: &Tapback,
+        action: &TapbackAction,
+        date: i64,
+    ) -> Self {
+        let (code, emoji) = match tapback {
+            Tapback::Loved => (0, None),
+            Tapback::Liked => (1, None),
+            Tapback::Disliked => (2, None),
+            Tapback::Laughed => (3, None),
+            Tapback::Emphasized => (4, None),
+            Tapback::Questioned => (5, None),
+            Tapback::Emoji(emoji) => (6, emoji.map(String::from)),
+            Tapback::Sticker => (7, None),
+        };
+        let offset = match action {
+            TapbackAction::Added => 2000,
+            TapbackAction::Removed => 3000,
+        };
+        Self {
+            associated_message: Some((target_guid.to_string(), part)),
+            associated_message_type: offset + code,
+            associated_message_emoji: emoji,
+            ..Self::new(date)
+        }
+    }
+
+    /// Add a part that contains text
+    #[must_use]
+    pub fn with_text(mut self, runs: Vec<AttributeRun>) -> Self {
+        self.parts.push(Part::Text(runs));
+        self
+    }
+
+    /// Add a part that contains an attachment
+    #[must_use]
+    pub fn with_attachment(mut self, name: &str, mime_type: &str, contents: &[u8]) -> Self {
+        self.parts.push(Part::Attachment {
+            name: name.to_string(),
+            mime_type: mime_type.to_string(),
+            contents: contents.to_vec(),
+        });
+        self
+    }
+
+    /// Mark the message as received from a handle
+    #[must_use]
+    pub fn from_handle(mut self, handle_id: i32) -> Self {
+        self.handle_id = handle_id;
+        self.is_from_me = false;
+        self
+    }
+
+    /// Set the service the message was sent with
+    #[must_use]
+    pub fn with_service(mut self, service: &str) -> Self {
+        self.service = service.to_string();
+        self
+    }
+
+    /// Mark the message as a reply to a part of another message
+    #[must_use]
+    pub fn reply_to(mut self, guid: &str, part: usize) -> Self {
+        self.reply_to = Some((guid.to_string(), part));
+        self
+    }
+}
+
+/// A message inserted by [`FixtureBuilder::message()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InsertedMessage {
+    /// The message's `ROWID`
+    pub rowid: i32,
+    /// The message's GUID
+    pub guid: String,
+    /// The attachments inserted with the message, in order
+    pub attachments: Vec<InsertedAttachment>,
+}
+
+/// An attachment inserted with a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InsertedAttachment {
+    /// The attachment's `ROWID`
+    pub rowid: i32,
+    /// The attachment's GUID
+    pub guid: String,
+    /// Where the attachment's file was written
+    pub path: PathBuf,
+}
+
+/// The content of a message, tracked so edits and unsends can rewrite it
+#[derive(Debug)]
+struct MessageState {
+    guid: String,
+    date: i64,
+    /// The runs of each part, as currently displayed
+    parts: Vec<Vec<AttributeRun>>,
+    /// Each version of the edited parts, including the original
+    history: BTreeMap<usize, Vec<(i64, Vec<AttributeRun>)>>,
+    /// Indexes of parts that were unsent
+    retracted: Vec<usize>,
+}
+
+impl MessageState {
+    /// Build the message body, skipping parts that were unsent
+    fn body(&self) -> AttributedString {
+        AttributedString::new(
+            self.parts
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| !self.retracted.contains(idx))
+                .flat_map(|(idx, runs)| {
+                    runs.iter()
+                        .map(move |run| run.clone().with_part(idx as i32))
+                })
+                .collect(),
+        )
+    }
+
+    /// Build the `message_summary_info` plist that records edits and unsends
+    fn summary_info(&self) -> Result<Vec<u8>, TableError> {
+        let mut ranges = Dictionary::new();
+        let mut offset = 0;
+        for (idx, runs) in self.parts.iter().enumerate() {
+            let length: usize = runs.iter().map(AttributeRun::len).sum();
+            let mut range = Dictionary::new();
+            range.insert("lo".to_string(), Value::from(offset as i64));
+            range.insert("le".to_string(), Value::from(length as i64));
+            ranges.insert(idx.to_string(), Value::Dictionary(range));
+            offset += length;
+        }
+
+        let mut edits = Dictionary::new();
+        for (idx, versions) in &self.history {
+            let mut events = vec![];
+            for (date, runs) in versions {
+                let mut event = Dictionary::new();
+                event.insert(
+                    "d".to_string(),
+                    Value::Real(*date as f64 / TIMESTAMP_FACTOR as f64),
+                );
+                event.insert(
+                    "t".to_string(),
+                    Value::Data(write_body(&AttributedString {
+                        runs: runs.clone(),
+                        mutable: false,
+                    })?),
+                );
+                events.push(Value::Dictionary(event));
+            }
+            edits.insert(idx.to_string(), Value::Array(events));
+        }
+
+        let mut root = Dictionary::new();
+        root.insert("ust".to_string(), Value::Boolean(true));
+        root.insert("otr".to_string(), Value::Dictionary(ranges));
+        if !edits.is_empty() {
+            root.insert(
+                "ep".to_string(),
+                Value::Array(
+                    self.history
+                        .keys()
+                        .map(|idx| Value::from(*idx as i64))
+                        .collect(),
+                ),
+            );
+            root.insert("ec".to_string(), Value::Dictionary(edits));
+        }
+        if !self.retracted.is_empty() {
+            root.insert(
+                "rp".to_string(),
+                Value::Array(
+                    self.retracted
+                        .iter()
+                        .map(|idx| Value::from(*idx as i64))
+                        .collect(),
+                ),
+            );
+        }
+
+        let mut bytes = vec![];
+        Value::Dictionary(root)
+            .to_writer_binary(&mut bytes)
+            .map_err(|why| TableError::CannotRead(io::Error::other(why)))?;
+        Ok(bytes)
+    }
+}
+
+/// Creates a synthetic iMessage database and inserts rows into it
+///
+/// Rows are written as they are inserted, so the database can be read at any point, for example by a
+/// [`Message`](crate::tables::messages::Message) query on another connection.
+#[derive(Debug)]
+pub struct FixtureBuilder {
+    /// Connection used to write to the database
+    conn: Connection,
+    /// The schema generation of the database
+    schema: Schema,
+    /// Directory where attachment files are written
+    attachment_root: PathBuf,
+    /// Used to generate unique GUIDs
+    guid_counter: u64,
+    /// The content of each message inserted by the builder, keyed by `ROWID`
+    messages: HashMap<i32, MessageState>,
+}
+
+impl FixtureBuilder {
+    /// Create a new database at `path` with the tables of a [`Schema`] generation
+    ///
+    /// Attachment files are written to an `Attachments` directory next to the database.
+    pub fn create(path: &Path, schema: Schema) -> Result<Self, TableError> {
+        let conn = Connection::open(path).map_err(|why| {
+            TableError::CannotConnect(format!(
+                "Unable to create fixture database at {}: {why}",
+                path.display()
+            ))
+        })?;
+        conn.execute_batch(&schema::create_statements(&schema))
+            .map_err(|why| {
+                TableError::CannotConnect(format!(
+                    "Unable to create tables in {}: {why}",
+                    path.display()
+                ))
+            })?;
+
+        Ok(Self {
+            conn,
+            schema,
+            attachment_root: path
+                .parent()
+                .unwrap_or_else(|| Path::new("."))
+                .join(ATTACHMENTS_DIR),
+            guid_counter: 0,
+            messages: HashMap::new(),
+        })
+    }
+
+    /// The connection used to write to the database
+    #[must_use]
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// The schema generation of the database
+    #[must_use]
+    pub fn schema(&self) -> Schema {
+        self.schema
+    }
+
+    /// The directory where attachment files are written
+    #[must_use]
+    pub fn attachment_root(&self) -> &Path {
+        &self.attachment_root
+    }
+
+    /// Insert a handle, i.e. a phone number or email address, and get its `ROWID`
+    pub fn handle(&mut self, id: &str, service: &str) -> Result<i32, TableError> {
+        self.conn
+            .execute(
+                &format!(
+                    "INSERT INTO {HANDLE} (id, service, uncanonicalized_id) VALUES (?1, ?2, ?1)"
+                ),
+                params![id, service],
+            )
+            .map_err(TableError::Handle)?;
+        Ok(self.conn.last_insert_rowid() as i32)
+    }
+
+    /// Insert a chat with some participants and get its `ROWID`
+    ///
+    /// Chats with more than one participant are group chats.
+    pub fn chat(
+        &mut self,
+        identifier: &str,
+        display_name: Option<&str>,
+        handles: &[i32],
+    ) -> Result<i32, TableError> {
+        let (separator, style) = if handles.len() > 1 {
+            ('+', 43)
+        } else {
+            ('-', 45)
+        };
+        self.conn
+            .execute(
+                &format!(
+                    "INSERT INTO {CHAT} (guid, style, state, chat_identifier, service_name, display_name) VALUES (?1, ?2, 3, ?3, 'iMessage', ?4)"
+                ),
+                params![
+                    format!("iMessage;{separator};{identifier}"),
+                    style,
+                    identifier,
+                    display_name
+                ],
+            )
+            .map_err(TableError::Chat)?;
+        let chat_id = self.conn.last_insert_rowid() as i32;
+
+        for handle_id in handles {
+            self.conn
+                .execute(
+                    &format!("INSERT INTO {CHAT_HANDLE_JOIN} (chat_id, handle_id) VALUES (?1, ?2)"),
+                    params![chat_id, handle_id],
+                )
+                .map_err(TableError::ChatToHandle)?;
+        }
+        Ok(chat_id)
+    }
+
+    /// Insert a message into a chat, writing any attachments to disk
+    pub fn message(
+        &mut self,
+        chat_id: i32,
+        message: NewMessage,
+    ) -> Result<InsertedMessage, TableError> {
+        let guid = self.next_guid();
+
+        // Attachments are represented in the body by a placeholder that points to their file transfer
+        let mut parts = Vec::with_capacity(message.parts.len());
+        let mut files = vec![];
+        for (idx, part) in message.parts.into_iter().enumerate() {
+            match part {
+                Part::Text(runs) => parts.push(runs),
+                Part::Attachment {
+                    name,
+                    mime_type,
+                    contents,
+                } => {
+                    parts.push(vec![AttributeRun::attachment(
+                        &format!("at_{idx}_{guid}"),
+                        Some(&name),
+                    )]);
+                    files.push((name, mime_type, contents));
+                }
+            }
+        }
+
+        let state = MessageState {
+            guid: guid.clone(),
+            date: message.date,
+            parts,
+            history: BTreeMap::new(),
+            retracted: vec![],
+        };
+        let body = state.body();
+        let (text, attributed_body) = if body.runs.is_empty() {
+            (None, None)
+        } else {
+            (Some(body.text()), Some(write_body(&body)?))
+        };
+
+        let mut columns = vec![
+            "guid",
+            "text",
+            "attributedBody",
+            "handle_id",
+            "service",
+            "date",
+            "date_read",
+            "date_delivered",
+            "is_from_me",
+            "is_read",
+            "is_sent",
+            "is_delivered",
+            "is_finished",
+            "associated_message_type",
+            "cache_has_attachments",
+        ];
+        let mut values: Vec<rusqlite::types::Value> = vec![
+            guid.clone().into(),
+            text.into(),
+            attributed_body.into(),
+            message.handle_id.into(),
+            message.service.into(),
+            message.date.into(),
+            (if message.is_from_me { 0 } else { message.date }).into(),
+            message.date.into(),
+            message.is_from_me.into(),
+            (!message.is_from_me).into(),
+            message.is_from_me.into(),
+            true.into(),
+            true.into(),
+            message.associated_message_type.into(),
+            (!files.is_empty()).into(),
+        ];
+        // Only reference the columns a message needs, so older schemas can hold messages that do not use them
+        if let Some((target, part)) = message.reply_to {
+            let length: usize = self
+                .messages
+                .values()
+                .find(|state| state.guid == target)
+                .and_then(|state| state.parts.get(part))
+                .map(|runs| runs.iter().map(AttributeRun::len).sum())
+                .unwrap_or_default();
+            columns.extend(["thread_originator_guid", "thread_originator_part"]);
+            values.extend([target.into(), format!("{part}:0:{length}").into()]);
+        }
+        if let Some((target, part)) = message.associated_message {
+            columns.push("associated_message_guid");
+            values.push(format!("p:{part}/{target}").into());
+        }
+        if let Some(emoji) = message.associated_message_emoji {
+            columns.push("associated_message_emoji");
+            values.push(emoji.into());
+        }
+
+        let placeholders: Vec<String> = (1..=columns.len()).map(|idx| format!("?{idx}")).collect();
+        self.conn
+            .execute(
+                &format!(
+                    "INSERT INTO {MESSAGE} ({}) VALUES ({})",
+                    columns.join(", "),
+                    placeholders.join(", ")
+                ),
+                rusqlite::params_from_iter(values),
+            )
+            .map_err(TableError::Messages)?;
+        let rowid = self.conn.last_insert_rowid() as i32;
+
+        self.conn
+            .execute(
+                &format!(
+                    "INSERT INTO {CHAT_MESSAGE_JOIN} (chat_id, message_id, message_date) VALUES (?1, ?2, ?3)"
+                ),
+                params![chat_id, rowid, message.date],
+            )
+            .map_err(TableError::Messages)?;
+
+        let mut attachments = Vec::with_capacity(files.len());
+        for (name, mime_type, contents) in files {
+            attachments.push(self.attachment(
+                rowid,
+                &name,
+                &mime_type,
+                &contents,
+                message.is_from_me,
+                message.date,
+            )?);
+        }
+
+        self.messages.insert(rowid, state);
+        Ok(InsertedMessage {
+            rowid,
+            guid,
+            attachments,
+        })
+    }
+
+    /// Edit the text of a part of a message, like `Messages.app` does when a sent message is edited
+    ///
+    /// Requires [`Schema::Ios16Newer`].
+    pub fn edit(
+        &mut self,
+        message_rowid: i32,
+        part: usize,
+        text: &str,
+        date: i64,
+    ) -> Result<(), TableError> {
+        let state = self.state(message_rowid)?;
+        if let Some(runs) = state.parts.get_mut(part) {
+            let original = std::mem::replace(runs, vec![AttributeRun::new(text)]);
+            let history = state.history.entry(part).or_default();
+            if history.is_empty() {
+                history.push((state.date, original));
+            }
+            history.push((date, vec![AttributeRun::new(text)]));
+        }
+        self.update_body(message_rowid, date, false)
+    }
+
+    /// Unsend a part of a message, removing it from the message body
+    ///
+    /// Requires [`Schema::Ios16Newer`].
+    pub fn unsend(&mut self, message_rowid: i32, part: usize, date: i64) -> Result<(), TableError> {
+        let state = self.state(message_rowid)?;
+        if part < state.parts.len() && !state.retracted.contains(&part) {
+            state.retracted.push(part);
+        }
+        self.update_body(message_rowid, date, true)
+    }
+
+    /// Move a message from its chat to Recently Deleted
+    ///
+    /// Requires [`Schema::Ios16Newer`].
+    pub fn recently_delete(&mut self, message_rowid: i32, date: i64) -> Result<(), TableError> {
+        // The message must be recoverable before it leaves its chat, otherwise the triggers delete it
+        self.conn
+            .execute(
+                &format!(
+                    "INSERT INTO {RECENTLY_DELETED} (chat_id, message_id, delete_date) SELECT chat_id, message_id, ?2 FROM {CHAT_MESSAGE_JOIN} WHERE message_id = ?1"
+                ),
+                params![message_rowid, date],
+            )
+            .map_err(TableError::Messages)?;
+        self.conn
+            .execute(
+                &format!("DELETE FROM {CHAT_MESSAGE_JOIN} WHERE message_id = ?1"),
+                params![message_rowid],
+            )
+            .map_err(TableError::Messages)?;
+        Ok(())
+    }
+
+    /// Move every message in a chat to Recently Deleted, like deleting a conversation does
+    ///
+    /// Older schemas do not have Recently Deleted, so the chat and its messages are deleted instead.
+    pub fn delete_chat(&mut self, chat_id: i32, date: i64) -> Result<(), TableError> {
+        if self.schema == Schema::Ios16Newer {
+            self.conn
+                .execute(
+                    &format!(
+                        "INSERT INTO {RECENTLY_DELETED} (chat_id, message_id, delete_date) SELECT chat_id, message_id, ?2 FROM {CHAT_MESSAGE_JOIN} WHERE chat_id = ?1"
+                    ),
+                    params![chat_id, date],
+                )
+                .map_err(TableError::Messages)?;
+            self.conn
+                .execute(
+                    &format!("DELETE FROM {CHAT_MESSAGE_JOIN} WHERE chat_id = ?1"),
+                    params![chat_id],
+                )
+                .map_err(TableError::Messages)?;
+        } else {
+            self.conn
+                .execute(
+                    &format!("DELETE FROM {CHAT} WHERE ROWID = ?1"),
+                    params![chat_id],
+                )
+                .map_err(TableError::Chat)?;
+        }
+        Ok(())
+    }
+
+    /// Permanently delete a message, whether it is in its chat or in Recently Deleted
+    ///
+    /// The triggers delete the message row and any attachment rows that are no longer referenced. Files on disk are
+    /// left in place.
+    pub fn purge(&mut self, message_rowid: i32) -> Result<(), TableError> {
+        self.conn
+            .execute(
+                &format!("DELETE FROM {MESSAGE_ATTACHMENT_JOIN} WHERE message_id = ?1"),
+                params![message_rowid],
+            )
+            .map_err(TableError::Attachment)?;
+        if self.schema == Schema::Ios16Newer {
+            self.conn
+                .execute(
+                    &format!("DELETE FROM {RECENTLY_DELETED} WHERE message_id = ?1"),
+                    params![message_rowid],
+                )
+                .map_err(TableError::Messages)?;
+        }
+        self.conn
+            .execute(
+                &format!("DELETE FROM {CHAT_MESSAGE_JOIN} WHERE message_id = ?1"),
+                params![message_rowid],
+            )
+            .map_err(TableError::Messages)?;
+        self.messages.remove(&message_rowid);
+        Ok(())
+    }
+
+    /// Write an attachment's file to disk and link it to a message
+    fn attachment(
+        &mut self,
+        message_rowid: i32,
+        name: &str,
+        mime_type: &str,
+        contents: &[u8],
+        is_outgoing: bool,
+        date: i64,
+    ) -> Result<InsertedAttachment, TableError> {
+        let guid = self.next_guid();
+
+        // Mirror the layout of `~/Library/Messages/Attachments`
+        let directory = self
+            .attachment_root
+            .join(guid[guid.len() - 2..].to_lowercase())
+            .join(format!("{:02}", self.guid_counter % 100))
+            .join(&guid);
+        let path = directory.join(name);
+        create_dir_all(&directory).map_err(TableError::CannotRead)?;
+        write(&path, contents).map_err(TableError::CannotRead)?;
+
+        self.conn
+            .execute(
+                &format!(
+                    "INSERT INTO {ATTACHMENT} (guid, original_guid, created_date, start_date, filename, mime_type, transfer_state, is_outgoing, transfer_name, total_bytes) VALUES (?1, ?1, ?2, ?2, ?3, ?4, 5, ?5, ?6, ?7)"
+                ),
+                params![
+                    guid,
+                    date / TIMESTAMP_FACTOR,
+                    path.to_string_lossy(),
+                    mime_type,
+                    is_outgoing,
+                    name,
+                    contents.len() as i64
+                ],
+            )
+            .map_err(TableError::Attachment)?;
+        let rowid = self.conn.last_insert_rowid() as i32;
+
+        self.conn
+            .execute(
+                &format!(
+                    "INSERT INTO {MESSAGE_ATTACHMENT_JOIN} (message_id, attachment_id) VALUES (?1, ?2)"
+                ),
+                params![message_rowid, rowid],
+            )
+            .map_err(TableError::Attachment)?;
+
+        Ok(InsertedAttachment { rowid, guid, path })
+    }
+
+    /// Rewrite a message's body and `message_summary_info` after an edit or unsend
+    fn update_body(
+        &mut self,
+        message_rowid: i32,
+        date: i64,
+        retracted: bool,
+    ) -> Result<(), TableError> {
+        let state = self.state(message_rowid)?;
+        let body = state.body();
+        let summary_info = state.summary_info()?;
+        let (text, attributed_body) = if body.runs.is_empty() {
+            (None, None)
+        } else {
+            (Some(body.text()), Some(write_body(&body)?))
+        };
+
+        let date_column = if retracted {
+            "date_retracted"
+        } else {
+            "date_edited"
+        };
+        self.conn
+            .execute(
+                &format!(
+                    "UPDATE {MESSAGE} SET text = ?1, attributedBody = ?2, message_summary_info = ?3, date_edited = ?4, {date_column} = ?4 WHERE ROWID = ?5"
+                ),
+                params![text, attributed_body, summary_info, date, message_rowid],
+            )
+            .map_err(TableError::Messages)?;
+        Ok(())
+    }
+
+    /// Get the content of a message inserted by this builder
+    fn state(&mut self, message_rowid: i32) -> Result<&mut MessageState, TableError> {
+        self.messages
+            .get_mut(&message_rowid)
+            .ok_or(TableError::Messages(rusqlite::Error::QueryReturnedNoRows))
+    }
+
+    /// Generate a GUID that is unique within the database
+    fn next_guid(&mut self) -> String {
+        self.guid_counter += 1;
+        format!("F1C7DB00-0000-4000-8000-{:012X}", self.guid_counter)
+    }
+}
+
+/// Serialize a message body into a `typedstream`
+fn write_body(body: &AttributedString) -> Result<Vec<u8>, TableError> {
+    TypedStreamWriter::new()
+        .write_attributed_string(body)
+        .map_err(|why| TableError::CannotRead(io::Error::other(why.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use crate::{
+        message_types::{
+            edited::EditStatus,
+            variants::{Tapback, TapbackAction, Variant},
+        },
+        tables::{attachment::Attachment, messages::Message, schema::SchemaCapabilities},
+        util::{
+            fixture::{FixtureBuilder, NewMessage, Schema},
+            platform::Platform,
+            typedstream::models::AttributeRun,
+        },
+    };
+
+    const DATE: i64 = 694_000_000_000_000_000;
+
+    fn fixture_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("imessage-fixture-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn build(name: &str, schema: Schema) -> (PathBuf, FixtureBuilder) {
+        let dir = fixture_dir(name);
+        let fixture = FixtureBuilder::create(&dir.join("chat.db"), schema).unwrap();
+        (dir, fixture)
+    }
+
+    #[test]
+    fn can_insert_text_message() {
+        for (name, schema) in [
+            ("text-13", Schema::Ios13Older),
+            ("text-14", Schema::Ios14To15),
+            ("text-16", Schema::Ios16Newer),
+        ] {
+            let (dir, mut fixture) = build(name, schema);
+            let handle = fixture.handle("+15558675309", "iMessage").unwrap();
+            let chat = fixture.chat("+15558675309", None, &[handle]).unwrap();
+            let inserted = fixture
+                .message(
+                    chat,
+                    NewMessage::new(DATE).from_handle(handle).with_text(vec![
+                        AttributeRun::new("Check "),
+                        AttributeRun::new("this").with_link("https://example.com"),
+                    ]),
+                )
+                .unwrap();
+
+            let db = fixture.connection();
+            let capabilities = SchemaCapabilities::probe(db).unwrap();
+            let mut message = Message::from_guid(&inserted.guid, db, &capabilities).unwrap();
+            assert_eq!(message.generate_text(db).unwrap(), "Check this");
+            assert_eq!(message.handle_id, Some(handle));
+            assert_eq!(message.chat_id, Some(chat));
+            assert_eq!(message.date, DATE);
+            assert!(!message.is_from_me());
+
+            remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn can_insert_group_chat() {
+        let (dir, mut fixture) = build("group", Schema::Ios16Newer);
+        let first = fixture.handle("+15558675309", "iMessage").unwrap();
+        let second = fixture.handle("test@example.com", "iMessage").unwrap();
+        let chat = fixture
+            .chat("chat123", Some("Friends"), &[first, second])
+            .unwrap();
+
+        let (guid, style, participants): (String, i32, i32) = fixture
+            .connection()
+            .query_row(
+                "SELECT guid, style, (SELECT COUNT(*) FROM chat_handle_join WHERE chat_id = ?1) FROM chat WHERE ROWID = ?1",
+                [chat],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(guid, "iMessage;+;chat123");
+        assert_eq!(style, 43);
+        assert_eq!(participants, 2);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_insert_attachment() {
+        let (dir, mut fixture) = build("attachment", Schema::Ios16Newer);
+        let chat = fixture.chat("+15558675309", None, &[]).unwrap();
+        let inserted = fixture
+            .message(
+                chat,
+                NewMessage::new(DATE)
+                    .with_attachment("photo.png", "image/png", b"not really a png")
+                    .with_text(vec![AttributeRun::new("Look")]),
+            )
+            .unwrap();
+
+        let db = fixture.connection();
+        let capabilities = SchemaCapabilities::probe(db).unwrap();
+        let mut message = Message::from_guid(&inserted.guid, db, &capabilities).unwrap();
+        assert_eq!(message.generate_text(db).unwrap(), "\u{FFFC}Look");
+        assert!(message.has_attachments());
+        assert_eq!(message.num_attachments, 1);
+
+        let attachments = Attachment::from_message(db, &capabilities, &message).unwrap();
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].mime_type.as_deref(), Some("image/png"));
+        assert_eq!(attachments[0].transfer_name.as_deref(), Some("photo.png"));
+
+        let path = attachments[0]
+            .resolved_attachment_path(&Platform::macOS, &dir.join("chat.db"), None)
+            .unwrap();
+        assert_eq!(PathBuf::from(path), inserted.attachments[0].path);
+        assert_eq!(
+            std::fs::read(&inserted.attachments[0].path).unwrap(),
+            b"not really a png"
+        );
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_insert_tapbacks() {
+        let (dir, mut fixture) = build("tapback", Schema::Ios16Newer);
+        let chat = fixture.chat("+15558675309", None, &[]).unwrap();
+        let target = fixture
+            .message(chat, NewMessage::text("Hello", DATE))
+            .unwrap();
+        let loved = fixture
+            .message(
+                chat,
+                NewMessage::tapback(
+                    &target.guid,
+                    0,
+                    &Tapback::Loved,
+                    &TapbackAction::Added,
+                    DATE,
+                ),
+            )
+            .unwrap();
+        let emoji = fixture
+            .message(
+                chat,
+                NewMessage::tapback(
+                    &target.guid,
+                    0,
+                    &Tapback::Emoji(Some("🦀")),
+                    &TapbackAction::Removed,
+                    DATE,
+                ),
+            )
+            .unwrap();
+
+        let db = fixture.connection();
+        let capabilities = SchemaCapabilities::probe(db).unwrap();
+        let message = Message::from_guid(&loved.guid, db, &capabilities).unwrap();
+        assert!(matches!(
+            message.variant(),
+            Variant::Tapback(0, TapbackAction::Added, Tapback::Loved)
+        ));
+        assert_eq!(
+            message.clean_associated_guid(),
+            Some((0, target.guid.as_str()))
+        );
+
+        let message = Message::from_guid(&emoji.guid, db, &capabilities).unwrap();
+        assert!(matches!(
+            message.variant(),
+            Variant::Tapback(0, TapbackAction::Removed, Tapback::Emoji(Some("🦀")))
+        ));
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_insert_reply() {
+        let (dir, mut fixture) = build("reply", Schema::Ios14To15);
+        let chat = fixture.chat("+15558675309", None, &[]).unwrap();
+        let target = fixture
+            .message(chat, NewMessage::text("Question?", DATE))
+            .unwrap();
+        let reply = fixture
+            .message(
+                chat,
+                NewMessage::text("Answer", DATE).reply_to(&target.guid, 0),
+            )
+            .unwrap();
+
+        let db = fixture.connection();
+        let capabilities = SchemaCapabilities::probe(db).unwrap();
+        let message = Message::from_guid(&reply.guid, db, &capabilities).unwrap();
+        assert!(message.is_reply());
+        assert_eq!(message.thread_originator_part.as_deref(), Some("0:0:9"));
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_edit_and_unsend() {
+        let (dir, mut fixture) = build("edit", Schema::Ios16Newer);
+        let chat = fixture.chat("+15558675309", None, &[]).unwrap();
+        let inserted = fixture
+            .message(
+                chat,
+                NewMessage::new(DATE)
+                    .with_text(vec![AttributeRun::new("Frist")])
+                    .with_text(vec![AttributeRun::new("Oops")]),
+            )
+            .unwrap();
+        fixture
+            .edit(inserted.rowid, 0, "First", DATE + 30_000_000_000)
+            .unwrap();
+        fixture
+            .unsend(inserted.rowid, 1, DATE + 60_000_000_000)
+            .unwrap();
+
+        let db = fixture.connection();
+        let capabilities = SchemaCapabilities::probe(db).unwrap();
+        let mut message = Message::from_guid(&inserted.guid, db, &capabilities).unwrap();
+        assert_eq!(message.generate_text(db).unwrap(), "First");
+        assert_eq!(message.date_edited, DATE + 60_000_000_000);
+
+        let edited = message.edited_parts.unwrap();
+        assert_eq!(edited.parts[0].status, EditStatus::Edited);
+        assert_eq!(edited.parts[0].edit_history.len(), 2);
+        assert_eq!(
+            edited.parts[0].edit_history[0].text.as_deref(),
+            Some("Frist")
+        );
+        assert_eq!(
+            edited.parts[0].edit_history[1].text.as_deref(),
+            Some("First")
+        );
+        assert_eq!(edited.parts[0].edit_history[1].date, DATE + 30_000_000_000);
+        assert_eq!(edited.parts[1].status, EditStatus::Unsent);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_recently_delete() {
+        let (dir, mut fixture) = build("delete", Schema::Ios16Newer);
+        let chat = fixture.chat("+15558675309", None, &[]).unwrap();
+        let inserted = fixture
+            .message(chat, NewMessage::text("Gone soon", DATE))
+            .unwrap();
+        fixture.recently_delete(inserted.rowid, DATE).unwrap();
+
+        let db = fixture.connection();
+        let capabilities = SchemaCapabilities::probe(db).unwrap();
+        let message = Message::from_guid(&inserted.guid, db, &capabilities).unwrap();
+        assert!(message.is_deleted());
+        assert_eq!(message.deleted_from, Some(chat));
+        assert_eq!(message.chat_id, None);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_purge() {
+        for (name, schema) in [
+            ("purge-14", Schema::Ios14To15),
+            ("purge-16", Schema::Ios16Newer),
+        ] {
+            let (dir, mut fixture) = build(name, schema);
+            let chat = fixture.chat("+15558675309", None, &[]).unwrap();
+            let kept = fixture
+                .message(chat, NewMessage::text("Kept", DATE))
+                .unwrap();
+            let purged = fixture
+                .message(
+                    chat,
+                    NewMessage::new(DATE).with_attachment("a.txt", "text/plain", b"a"),
+                )
+                .unwrap();
+            if schema == Schema::Ios16Newer {
+                fixture.recently_delete(purged.rowid, DATE).unwrap();
+            }
+            fixture.purge(purged.rowid).unwrap();
+
+            let db = fixture.connection();
+            let capabilities = SchemaCapabilities::probe(db).unwrap();
+            assert!(Message::from_guid(&kept.guid, db, &capabilities).is_ok());
+            assert!(Message::from_guid(&purged.guid, db, &capabilities).is_err());
+            let attachments: i32 = db
+                .query_row("SELECT COUNT(*) FROM attachment", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(attachments, 0);
+
+            remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn can_delete_chat() {
+        let (dir, mut fixture) = build("delete-chat", Schema::Ios16Newer);
+        let chat = fixture.chat("+15558675309", None, &[]).unwrap();
+        let other = fixture.chat("+15555555555", None, &[]).unwrap();
+        let first = fixture
+            .message(chat, NewMessage::text("One", DATE))
+            .unwrap();
+        let second = fixture
+            .message(chat, NewMessage::text("Two", DATE))
+            .unwrap();
+        let untouched = fixture
+            .message(other, NewMessage::text("Three", DATE))
+            .unwrap();
+        fixture.delete_chat(chat, DATE).unwrap();
+
+        let db = fixture.connection();
+        let capabilities = SchemaCapabilities::probe(db).unwrap();
+        for guid in [&first.guid, &second.guid] {
+            let message = Message::from_guid(guid, db, &capabilities).unwrap();
+            assert_eq!(message.deleted_from, Some(chat));
+        }
+        assert!(
+            !Message::from_guid(&untouched.guid, db, &capabilities)
+                .unwrap()
+                .is_deleted()
+        );
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cant_recently_delete_older_schema() {
+        let (dir, mut fixture) = build("delete-old", Schema::Ios14To15);
+        let chat = fixture.chat("+15558675309", None, &[]).unwrap();
+        let inserted = fixture
+            .message(chat, NewMessage::text("Here to stay", DATE))
+            .unwrap();
+
+        assert!(fixture.recently_delete(inserted.rowid, DATE).is_err());
+        assert!(fixture.edit(inserted.rowid, 0, "Edited", DATE).is_err());
+
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -2,13 +2,17 @@
  This module defines common utilities used across table queries.
 */
 
+pub mod analytics;
 pub mod bundle_id;
 pub mod dates;
 pub mod dirs;
+#[cfg(any(test, feature = "test-util"))]
+pub mod fixture;
 pub mod output;
 pub mod platform;
 pub mod plist;
 pub mod query_context;
+pub mod render;
 pub mod size;
 pub mod streamtyped;
 pub mod typedstream;
@@ -3,13 +3,20 @@
 */
 use std::collections::BTreeSet;
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 /// Represents filter configurations for a SQL query.
 pub struct QueryContext {
     pub limit: Option<i32>,
+    /// The start date filter, stored with an epoch of `2001-01-01 00:00:00`. Only messages sent on or after this date will be included.
+    pub start_date: Option<i64>,
+    /// The end date filter, stored with an epoch of `2001-01-01 00:00:00`. Only messages sent before this date will be included.
+    pub end_date: Option<i64>,
+    /// Selected handle IDs, including messages the database owner sent to chats with those handles
     pub selected_handle_ids: Option<BTreeSet<i32>>,
     /// Selected chat IDs
     pub selected_chat_ids: Option<BTreeSet<i32>>,
+    /// Selected services, as stored in the `service` column, i.e. `iMessage` or `SMS`
+    pub selected_services: Option<BTreeSet<String>>,
 }
 
 impl QueryContext {
@@ -26,6 +33,40 @@ impl QueryContext {
         self.limit = Some(limit);
     }
 
+    /// Populate a [`QueryContext`] with a start date
+    ///
+    /// The date is a nanosecond-precision timestamp with an epoch of `2001-01-01 00:00:00`,
+    /// the same format as [`Message::date`](crate::tables::messages::Message::date).
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_start_date(674526582885055488);
+    /// ```
+    pub fn set_start_date(&mut self, start_date: i64) {
+        self.start_date = Some(start_date);
+    }
+
+    /// Populate a [`QueryContext`] with an end date
+    ///
+    /// The date is a nanosecond-precision timestamp with an epoch of `2001-01-01 00:00:00`,
+    /// the same format as [`Message::date`](crate::tables::messages::Message::date).
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_end_date(674526582885055488);
+    /// ```
+    pub fn set_end_date(&mut self, end_date: i64) {
+        self.end_date = Some(end_date);
+    }
+
     /// Populate a [`QueryContext`] with a list of handle IDs to select
     ///
     /// # Example:
@@ -56,6 +97,21 @@ impl QueryContext {
         self.selected_chat_ids = (!selected_chat_ids.is_empty()).then_some(selected_chat_ids);
     }
 
+    /// Populate a [`QueryContext`] with a list of services to select
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use std::collections::BTreeSet;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_selected_services(BTreeSet::from(["iMessage".to_string(), "SMS".to_string()]));
+    /// ```
+    pub fn set_selected_services(&mut self, selected_services: BTreeSet<String>) {
+        self.selected_services = (!selected_services.is_empty()).then_some(selected_services);
+    }
+
     /// Determine if the current `QueryContext` has any filters present
     ///
     /// # Example:
@@ -71,8 +127,11 @@ impl QueryContext {
     #[must_use]
     pub fn has_filters(&self) -> bool {
         self.limit.is_some()
+            || self.start_date.is_some()
+            || self.end_date.is_some()
             || self.selected_chat_ids.is_some()
             || self.selected_handle_ids.is_some()
+            || self.selected_services.is_some()
     }
 }
 
@@ -99,6 +158,16 @@ mod use_tests {
         assert!(context.has_filters());
     }
 
+    #[test]
+    fn can_create_dates() {
+        let mut context = QueryContext::default();
+        context.set_start_date(100);
+        context.set_end_date(200);
+
+        assert_eq!(context.start_date, Some(100));
+        assert_eq!(context.end_date, Some(200));
+        assert!(context.has_filters());
+    }
 }
 
 #[cfg(test)]
@@ -162,4 +231,25 @@ mod id_tests {
         assert_eq!(qc.selected_handle_ids, None);
         assert!(!qc.has_filters());
     }
+
+    #[test]
+    fn test_can_set_selected_services() {
+        let mut qc = QueryContext::default();
+        qc.set_selected_services(BTreeSet::from(["SMS".to_string()]));
+
+        assert_eq!(
+            qc.selected_services,
+            Some(BTreeSet::from(["SMS".to_string()]))
+        );
+        assert!(qc.has_filters());
+    }
+
+    #[test]
+    fn test_can_set_selected_services_empty() {
+        let mut qc = QueryContext::default();
+        qc.set_selected_services(BTreeSet::new());
+
+        assert_eq!(qc.selected_services, None);
+        assert!(!qc.has_filters());
+    }
 }
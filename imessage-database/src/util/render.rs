@@ -0,0 +1,736 @@
+/*!
+ Renders message text and its [`TextAttributes`] to Markdown, HTML, or ANSI terminal output.
+
+ Attribute ranges may overlap, so the text is split at every range boundary and each segment is rendered with
+ every effect that covers it. Adjacent segments with the same effects are merged, so the output only changes
+ style where the message does. Range indexes are byte offsets; any index that falls inside a multi-byte character
+ is moved to the nearest character boundary that keeps the character inside the range.
+
+ # Example
+
+ ```
+ use imessage_database::{
+     message_types::text_effects::{Style, TextEffect},
+     tables::messages::models::TextAttributes,
+     util::render::{RenderFormat, render_text},
+ };
+
+ let attributes = vec![
+     TextAttributes::new(0, 5, TextEffect::Styles(vec![Style::Bold])),
+     TextAttributes::new(5, 12, TextEffect::Default),
+ ];
+
+ assert_eq!(render_text("Hello, world", &attributes, RenderFormat::Markdown), "**Hello**, world");
+ assert_eq!(render_text("Hello, world", &attributes, RenderFormat::Html), "<b>Hello</b>, world");
+ ```
+*/
+
+use std::fmt::Write;
+
+use crate::{
+    message_types::text_effects::{Animation, Style, TextEffect},
+    tables::{
+        messages::{
+            Message,
+            models::{BubbleComponent, TextAttributes},
+        },
+        table::AttributedBody,
+    },
+};
+
+/// URL schemes that are safe to link to from rendered text
+///
+/// Other schemes, like `javascript:` and `data:`, can run code when the link is opened, so they are not linked.
+pub const SAFE_LINK_SCHEMES: [&str; 4] = ["http", "https", "mailto", "tel"];
+
+/// The formats text can be rendered to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderFormat {
+    /// [CommonMark](https://commonmark.org) with inline HTML for underlines
+    Markdown,
+    /// HTML fragments, using the `mention` and `animation*` classes for effects that have no tag
+    Html,
+    /// Text with [SGR](https://en.wikipedia.org/wiki/ANSI_escape_code#SGR) escape codes and `OSC 8` hyperlinks
+    Ansi,
+}
+
+/// Every effect applied to a segment of text
+#[derive(Debug, Default, PartialEq)]
+struct Effects<'a> {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+    /// One-time codes and unit conversions, which are underlined
+    detected: bool,
+    link: Option<&'a str>,
+    mention: Option<&'a str>,
+    animation: Option<&'a Animation>,
+}
+
+impl<'a> Effects<'a> {
+    /// Combine every effect in `effects`; when links, mentions, or animations overlap, the last one wins
+    fn from_effects(effects: impl Iterator<Item = &'a TextEffect<'a>>) -> Self {
+        let mut combined = Effects::default();
+        for effect in effects {
+            match effect {
+                TextEffect::Default => {}
+                TextEffect::Mention(mention) => combined.mention = Some(mention),
+                TextEffect::Link(link) => combined.link = Some(link),
+                TextEffect::OTP | TextEffect::Conversion(_) => combined.detected = true,
+                TextEffect::Styles(styles) => {
+                    for style in styles {
+                        match style {
+                            Style::Bold => combined.bold = true,
+                            Style::Italic => combined.italic = true,
+                            Style::Underline => combined.underline = true,
+                            Style::Strikethrough => combined.strikethrough = true,
+                        }
+                    }
+                }
+                TextEffect::Animated(animation) => combined.animation = Some(animation),
+            }
+        }
+        combined
+    }
+}
+
+/// Render the text covered by `attributes`, applying their effects
+///
+/// If `attributes` is empty, the entire text is rendered without effects. Text outside of the attribute ranges
+/// belongs to other parts of the message body and is not rendered.
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::{
+///     message_types::text_effects::TextEffect,
+///     tables::messages::models::TextAttributes,
+///     util::render::{RenderFormat, render_text},
+/// };
+///
+/// let attributes = vec![TextAttributes::new(0, 7, TextEffect::Link("https://example.com"))];
+/// let html = render_text("example", &attributes, RenderFormat::Html);
+/// assert_eq!(html, r#"<a href="https://example.com">example</a>"#);
+/// ```
+#[must_use]
+pub fn render_text(text: &str, attributes: &[TextAttributes], format: RenderFormat) -> String {
+    let ranges: Vec<(usize, usize, &TextEffect)> = attributes
+        .iter()
+        .filter_map(|attribute| {
+            let start = floor_char_boundary(text, attribute.start);
+            let end = ceil_char_boundary(text, attribute.end);
+            (start < end).then_some((start, end, &attribute.effect))
+        })
+        .collect();
+
+    // Only render the text spanned by the attributes
+    let (span_start, span_end) = if attributes.is_empty() {
+        (0, text.len())
+    } else {
+        (
+            ranges.iter().map(|(start, _, _)| *start).min().unwrap_or(0),
+            ranges.iter().map(|(_, end, _)| *end).max().unwrap_or(0),
+        )
+    };
+
+    let mut boundaries: Vec<usize> = ranges
+        .iter()
+        .flat_map(|(start, end, _)| [*start, *end])
+        .chain([span_start, span_end])
+        .collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut out_s = String::with_capacity(span_end - span_start);
+    let mut pending: Option<(Effects, usize, usize)> = None;
+    for window in boundaries.windows(2) {
+        let (start, end) = (window[0], window[1]);
+        let effects = Effects::from_effects(
+            ranges
+                .iter()
+                .filter(|(range_start, range_end, _)| *range_start <= start && end <= *range_end)
+                .map(|(_, _, effect)| *effect),
+        );
+
+        // Extend the previous segment if its effects are the same
+        pending = match pending {
+            Some((active, pending_start, _)) if active == effects => {
+                Some((active, pending_start, end))
+            }
+            Some((active, pending_start, pending_end)) => {
+                render_segment(
+                    &mut out_s,
+                    &text[pending_start..pending_end],
+                    &active,
+                    format,
+                );
+                Some((effects, start, end))
+            }
+            None => Some((effects, start, end)),
+        };
+    }
+    if let Some((active, start, end)) = pending {
+        render_segment(&mut out_s, &text[start..end], &active, format);
+    }
+
+    out_s
+}
+
+/// Render every part of a message body, given the message's text
+///
+/// The result has one item per component; [`BubbleComponent::Text`] parts are rendered and all other parts are `None`.
+#[must_use]
+pub fn render_body(
+    text: &str,
+    components: &[BubbleComponent],
+    format: RenderFormat,
+) -> Vec<Option<String>> {
+    components
+        .iter()
+        .map(|component| match component {
+            BubbleComponent::Text(attributes) => Some(render_text(text, attributes, format)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Determine if a URL uses one of the [`SAFE_LINK_SCHEMES`], so it can be used as a link's `href`
+///
+/// Browsers ignore leading whitespace and control characters, and tabs and newlines anywhere in a URL, so they
+/// are ignored here too. URLs without a scheme are not safe, because their meaning depends on the page.
+#[must_use]
+pub fn is_safe_link(url: &str) -> bool {
+    let url: String = url
+        .trim_start_matches(|c: char| c.is_ascii_control() || c == ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    url.split_once(':').is_some_and(|(scheme, _)| {
+        SAFE_LINK_SCHEMES
+            .iter()
+            .any(|safe| scheme.eq_ignore_ascii_case(safe))
+    })
+}
+
+impl Message {
+    /// Render every part of the message's [`body()`](AttributedBody::body), see [`render_body()`]
+    ///
+    /// The message's text must be populated by [`Message::generate_text()`] first.
+    #[must_use]
+    pub fn render(&self, format: RenderFormat) -> Vec<Option<String>> {
+        render_body(
+            self.text.as_deref().unwrap_or_default(),
+            &self.body(),
+            format,
+        )
+    }
+}
+
+/// Render a segment of text with all of its effects
+fn render_segment(out_s: &mut String, text: &str, effects: &Effects, format: RenderFormat) {
+    match format {
+        RenderFormat::Markdown => render_markdown(out_s, text, effects),
+        RenderFormat::Html => render_html(out_s, text, effects),
+        RenderFormat::Ansi => render_ansi(out_s, text, effects),
+    }
+}
+
+fn render_markdown(out_s: &mut String, text: &str, effects: &Effects) {
+    let at_line_start = out_s.is_empty() || out_s.ends_with('\n');
+    let escaped = escape_markdown(text, at_line_start);
+
+    // Emphasis delimiters cannot be next to whitespace, so leave it outside of them
+    let inner = escaped.trim();
+    if inner.is_empty() {
+        out_s.push_str(&escaped);
+        return;
+    }
+    let leading = &escaped[..escaped.len() - escaped.trim_start().len()];
+    let trailing = &escaped[escaped.trim_end().len()..];
+
+    let mut styled = inner.to_string();
+    if effects.underline || effects.detected {
+        styled = format!("<u>{styled}</u>");
+    }
+    if effects.strikethrough {
+        styled = format!("~~{styled}~~");
+    }
+    if effects.italic {
+        styled = format!("*{styled}*");
+    }
+    if effects.bold {
+        styled = format!("**{styled}**");
+    }
+    // Links with other schemes are rendered as their text
+    let link = effects.link.filter(|link| is_safe_link(link));
+    if let Some(destination) = link.map(str::to_string).or_else(|| {
+        effects.mention.map(|mention| {
+            if mention.contains('@') {
+                format!("mailto:{mention}")
+            } else {
+                format!("tel:{mention}")
+            }
+        })
+    }) {
+        styled = format!(
+            "[{styled}](<{}>)",
+            escape_markdown_destination(&destination)
+        );
+    }
+
+    out_s.push_str(leading);
+    out_s.push_str(&styled);
+    out_s.push_str(trailing);
+}
+
+fn render_html(out_s: &mut String, text: &str, effects: &Effects) {
+    let mut close = vec![];
+    // Links with other schemes are rendered as their text
+    if let Some(link) = effects.link.filter(|link| is_safe_link(link)) {
+        let _ = write!(out_s, r#"<a href="{}">"#, escape_html(link));
+        close.push("</a>");
+    }
+    if let Some(mention) = effects.mention {
+        let _ = write!(
+            out_s,
+            r#"<span class="mention" title="{}">"#,
+            escape_html(mention)
+        );
+        close.push("</span>");
+    }
+    for (enabled, open_tag, close_tag) in [
+        (effects.bold, "<b>", "</b>"),
+        (effects.italic, "<i>", "</i>"),
+        (effects.underline || effects.detected, "<u>", "</u>"),
+        (effects.strikethrough, "<s>", "</s>"),
+    ] {
+        if enabled {
+            out_s.push_str(open_tag);
+            close.push(close_tag);
+        }
+    }
+    if let Some(animation) = effects.animation {
+        let _ = write!(out_s, r#"<span class="animation{animation:?}">"#);
+        close.push("</span>");
+    }
+
+    out_s.push_str(&escape_html(text));
+    for tag in close.into_iter().rev() {
+        out_s.push_str(tag);
+    }
+}
+
+fn render_ansi(out_s: &mut String, text: &str, effects: &Effects) {
+    // Links with other schemes are rendered as their text
+    let link = effects.link.filter(|link| is_safe_link(link));
+    let mut codes = vec![];
+    if effects.bold {
+        codes.push("1");
+    }
+    if effects.italic {
+        codes.push("3");
+    }
+    if effects.underline || effects.detected || link.is_some() {
+        codes.push("4");
+    }
+    if effects.strikethrough {
+        codes.push("9");
+    }
+    if effects.mention.is_some() {
+        codes.push("36");
+    } else if link.is_some() {
+        codes.push("34");
+    }
+
+    let text = strip_control_characters(text);
+    let styled = if codes.is_empty() {
+        text
+    } else {
+        format!("\x1b[{}m{text}\x1b[0m", codes.join(";"))
+    };
+
+    match link {
+        Some(link) => {
+            let _ = write!(
+                out_s,
+                "\x1b]8;;{}\x1b\\{styled}\x1b]8;;\x1b\\",
+                strip_control_characters(link)
+            );
+        }
+        None => out_s.push_str(&styled),
+    }
+}
+
+/// Escape characters that have meaning in Markdown
+///
+/// Characters that only have meaning at the start of a line, like list markers, are escaped there.
+fn escape_markdown(text: &str, mut at_line_start: bool) -> String {
+    let mut out_s = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' | '~' | '&' => {
+                out_s.push('\\');
+                out_s.push(c);
+            }
+            '+' | '-' | '=' if at_line_start => {
+                out_s.push('\\');
+                out_s.push(c);
+            }
+            // Ordered list markers are a number followed by `.` or `)`
+            '0'..='9' if at_line_start => {
+                out_s.push(c);
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    out_s.push(digit);
+                }
+                if let Some(marker) = chars.next_if(|next| *next == '.' || *next == ')') {
+                    out_s.push('\\');
+                    out_s.push(marker);
+                }
+            }
+            c => out_s.push(c),
+        }
+
+        if c == '\n' {
+            at_line_start = true;
+        } else if !(at_line_start && c == ' ') {
+            at_line_start = false;
+        }
+    }
+    out_s
+}
+
+/// Encode the characters that cannot appear in a `<...>` link destination
+fn escape_markdown_destination(destination: &str) -> String {
+    let mut out_s = String::with_capacity(destination.len());
+    for c in destination.chars() {
+        match c {
+            '<' => out_s.push_str("%3C"),
+            '>' => out_s.push_str("%3E"),
+            '\\' => out_s.push_str("%5C"),
+            '\n' => out_s.push_str("%0A"),
+            '\r' => out_s.push_str("%0D"),
+            c => out_s.push(c),
+        }
+    }
+    out_s
+}
+
+/// Escape text so it can be placed in HTML content or attribute values
+fn escape_html(text: &str) -> String {
+    let mut out_s = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out_s.push_str("&amp;"),
+            '<' => out_s.push_str("&lt;"),
+            '>' => out_s.push_str("&gt;"),
+            '"' => out_s.push_str("&quot;"),
+            '\'' => out_s.push_str("&#39;"),
+            c => out_s.push(c),
+        }
+    }
+    out_s
+}
+
+/// Remove control characters so message content cannot emit its own escape sequences
+fn strip_control_characters(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
+        .collect()
+}
+
+/// Get the largest character boundary that is not greater than `index`
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    if index >= text.len() {
+        return text.len();
+    }
+    (0..=index)
+        .rev()
+        .find(|idx| text.is_char_boundary(*idx))
+        .unwrap_or(0)
+}
+
+/// Get the smallest character boundary that is not less than `index`
+fn ceil_char_boundary(text: &str, index: usize) -> usize {
+    if index >= text.len() {
+        return text.len();
+    }
+    (index..text.len())
+        .find(|idx| text.is_char_boundary(*idx))
+        .unwrap_or(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        message_types::text_effects::{Animation, Style, TextEffect, Unit},
+        tables::messages::models::{AttachmentMeta, BubbleComponent, TextAttributes},
+        util::render::{RenderFormat, is_safe_link, render_body, render_text},
+    };
+
+    #[test]
+    fn can_render_plain_text() {
+        let attributes = vec![TextAttributes::new(0, 5, TextEffect::Default)];
+        for format in [
+            RenderFormat::Markdown,
+            RenderFormat::Html,
+            RenderFormat::Ansi,
+        ] {
+            assert_eq!(render_text("Hello", &attributes, format), "Hello");
+        }
+    }
+
+    #[test]
+    fn can_render_without_attributes() {
+        assert_eq!(render_text("a < b", &[], RenderFormat::Html), "a &lt; b");
+    }
+
+    #[test]
+    fn can_render_styles() {
+        let attributes = vec![
+            TextAttributes::new(0, 4, TextEffect::Styles(vec![Style::Bold, Style::Italic])),
+            TextAttributes::new(4, 5, TextEffect::Default),
+            TextAttributes::new(5, 9, TextEffect::Styles(vec![Style::Strikethrough])),
+            TextAttributes::new(9, 10, TextEffect::Default),
+            TextAttributes::new(10, 14, TextEffect::Styles(vec![Style::Underline])),
+        ];
+        let text = "bold gone line";
+
+        assert_eq!(
+            render_text(text, &attributes, RenderFormat::Markdown),
+            "***bold*** ~~gone~~ <u>line</u>"
+        );
+        assert_eq!(
+            render_text(text, &attributes, RenderFormat::Html),
+            "<b><i>bold</i></b> <s>gone</s> <u>line</u>"
+        );
+        assert_eq!(
+            render_text(text, &attributes, RenderFormat::Ansi),
+            "\x1b[1;3mbold\x1b[0m \x1b[9mgone\x1b[0m \x1b[4mline\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn can_render_overlapping_ranges() {
+        // `Hello` is bold, `lo wor` is italic
+        let attributes = vec![
+            TextAttributes::new(0, 5, TextEffect::Styles(vec![Style::Bold])),
+            TextAttributes::new(3, 9, TextEffect::Styles(vec![Style::Italic])),
+            TextAttributes::new(5, 11, TextEffect::Default),
+        ];
+        let text = "Hello world";
+
+        assert_eq!(
+            render_text(text, &attributes, RenderFormat::Html),
+            "<b>Hel</b><b><i>lo</i></b><i> wor</i>ld"
+        );
+        assert_eq!(
+            render_text(text, &attributes, RenderFormat::Markdown),
+            "**Hel*****lo*** *wor*ld"
+        );
+    }
+
+    #[test]
+    fn can_render_multi_byte_characters() {
+        // `🦀` is 4 bytes and `é` is 2 bytes
+        let text = "🦀 café";
+        let attributes = vec![
+            TextAttributes::new(0, 4, TextEffect::Styles(vec![Style::Bold])),
+            TextAttributes::new(4, 5, TextEffect::Default),
+            TextAttributes::new(5, 10, TextEffect::Styles(vec![Style::Italic])),
+        ];
+
+        assert_eq!(
+            render_text(text, &attributes, RenderFormat::Html),
+            "<b>🦀</b> <i>café</i>"
+        );
+    }
+
+    #[test]
+    fn can_render_indexes_inside_characters() {
+        // Both indexes fall inside of the crab, which must stay whole
+        let text = "🦀!";
+        let attributes = vec![TextAttributes::new(
+            1,
+            3,
+            TextEffect::Styles(vec![Style::Bold]),
+        )];
+
+        assert_eq!(
+            render_text(text, &attributes, RenderFormat::Html),
+            "<b>🦀</b>"
+        );
+    }
+
+    #[test]
+    fn can_render_indexes_past_end() {
+        let attributes = vec![TextAttributes::new(2, 100, TextEffect::Default)];
+        assert_eq!(render_text("Hello", &attributes, RenderFormat::Html), "llo");
+    }
+
+    #[test]
+    fn can_escape_html() {
+        let attributes = vec![
+            TextAttributes::new(0, 8, TextEffect::Link("https://a.com/?a=1&b=\"2\"")),
+            TextAttributes::new(8, 17, TextEffect::Default),
+        ];
+
+        assert_eq!(
+            render_text("<script> & 'more'", &attributes, RenderFormat::Html),
+            r#"<a href="https://a.com/?a=1&amp;b=&quot;2&quot;">&lt;script&gt;</a> &amp; &#39;more&#39;"#
+        );
+    }
+
+    #[test]
+    fn cannot_render_unsafe_links() {
+        let attributes = vec![
+            TextAttributes::new(0, 5, TextEffect::Link("javascript:alert(1)")),
+            TextAttributes::new(5, 6, TextEffect::Default),
+            TextAttributes::new(6, 11, TextEffect::Link(" JavaScript:alert(1)")),
+        ];
+
+        for format in [
+            RenderFormat::Markdown,
+            RenderFormat::Html,
+            RenderFormat::Ansi,
+        ] {
+            assert_eq!(
+                render_text("click there", &attributes, format),
+                "click there",
+                "{format:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn can_detect_safe_links() {
+        for url in [
+            "https://a.com",
+            "HTTP://a.com",
+            "mailto:a@b.com",
+            "tel:+15558675309",
+            " \thttps://a.com",
+        ] {
+            assert!(is_safe_link(url), "{url}");
+        }
+        for url in [
+            "javascript:alert(1)",
+            "JavaScript:alert(1)",
+            "java\tscript:alert(1)",
+            "\x01javascript:alert(1)",
+            "data:text/html,<script>alert(1)</script>",
+            "vbscript:msgbox(1)",
+            "a.com/https:",
+            "//a.com",
+            "",
+        ] {
+            assert!(!is_safe_link(url), "{url}");
+        }
+    }
+
+    #[test]
+    fn can_escape_markdown() {
+        let attributes = vec![TextAttributes::new(0, 26, TextEffect::Default)];
+
+        assert_eq!(
+            render_text(
+                "1. *not* [a](link) #tag_me",
+                &attributes,
+                RenderFormat::Markdown
+            ),
+            r"1\. \*not\* \[a\](link) \#tag\_me"
+        );
+        assert_eq!(
+            render_text("- item\n+ item", &[], RenderFormat::Markdown),
+            "\\- item\n\\+ item"
+        );
+        assert_eq!(
+            render_text("a-b 2.5", &[], RenderFormat::Markdown),
+            "a-b 2.5"
+        );
+    }
+
+    #[test]
+    fn can_render_markdown_emphasis_around_whitespace() {
+        let attributes = vec![
+            TextAttributes::new(0, 2, TextEffect::Default),
+            TextAttributes::new(2, 8, TextEffect::Styles(vec![Style::Bold])),
+        ];
+
+        assert_eq!(
+            render_text("a  bold ", &attributes, RenderFormat::Markdown),
+            "a  **bold** "
+        );
+    }
+
+    #[test]
+    fn can_render_links_and_mentions() {
+        let text = "Hi Chris, see site";
+        let attributes = vec![
+            TextAttributes::new(0, 3, TextEffect::Default),
+            TextAttributes::new(3, 8, TextEffect::Mention("+15558675309")),
+            TextAttributes::new(8, 14, TextEffect::Default),
+            TextAttributes::new(14, 18, TextEffect::Link("https://example.com/a>b")),
+        ];
+
+        assert_eq!(
+            render_text(text, &attributes, RenderFormat::Markdown),
+            "Hi [Chris](<tel:+15558675309>), see [site](<https://example.com/a%3Eb>)"
+        );
+        assert_eq!(
+            render_text(text, &attributes, RenderFormat::Html),
+            r#"Hi <span class="mention" title="+15558675309">Chris</span>, see <a href="https://example.com/a&gt;b">site</a>"#
+        );
+        assert_eq!(
+            render_text(text, &attributes, RenderFormat::Ansi),
+            "Hi \x1b[36mChris\x1b[0m, see \x1b]8;;https://example.com/a>b\x1b\\\x1b[4;34msite\x1b[0m\x1b]8;;\x1b\\"
+        );
+    }
+
+    #[test]
+    fn can_render_otp_conversion_and_animation() {
+        let text = "123456 5 km big";
+        let attributes = vec![
+            TextAttributes::new(0, 6, TextEffect::OTP),
+            TextAttributes::new(6, 7, TextEffect::Default),
+            TextAttributes::new(7, 11, TextEffect::Conversion(Unit::Distance)),
+            TextAttributes::new(11, 12, TextEffect::Default),
+            TextAttributes::new(12, 15, TextEffect::Animated(Animation::Big)),
+        ];
+
+        assert_eq!(
+            render_text(text, &attributes, RenderFormat::Html),
+            r#"<u>123456</u> <u>5 km</u> <span class="animationBig">big</span>"#
+        );
+        assert_eq!(
+            render_text(text, &attributes, RenderFormat::Markdown),
+            "<u>123456</u> <u>5 km</u> big"
+        );
+    }
+
+    #[test]
+    fn can_strip_ansi_control_characters() {
+        let text = "a\x1b[31mb\tc";
+        assert_eq!(render_text(text, &[], RenderFormat::Ansi), "a[31mb\tc");
+    }
+
+    #[test]
+    fn can_render_body() {
+        let text = "\u{FFFC}Caption";
+        let body = vec![
+            BubbleComponent::Attachment(AttachmentMeta::default()),
+            BubbleComponent::Text(vec![TextAttributes::new(
+                3,
+                10,
+                TextEffect::Styles(vec![Style::Bold]),
+            )]),
+            BubbleComponent::Retracted,
+        ];
+
+        assert_eq!(
+            render_body(text, &body, RenderFormat::Html),
+            vec![None, Some("<b>Caption</b>".to_string()), None]
+        );
+    }
+}
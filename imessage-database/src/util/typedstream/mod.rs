@@ -1,5 +1,6 @@
 /*!
- Contains logic and data structures used to parse and deserialize `typedstream` data into native Rust data structures.
+ Contains logic and data structures used to parse and deserialize `typedstream` data into native Rust data structures,
+ as well as to serialize `NSAttributedString`s back into `typedstream` data.
 
  ## Overview
 
@@ -15,6 +16,7 @@
  - Pure Rust implementation for efficient and safe deserialization
  - No dependencies on Apple frameworks
  - Robust error handling for malformed or incomplete `typedstream` data
+ - Serialization of `NSAttributedString`s, for example to build `attributedBody` test fixtures
 
  ## Reverse Engineering
 
@@ -24,3 +26,4 @@
 pub mod models;
 pub mod parser;
 mod tests;
+pub mod writer;
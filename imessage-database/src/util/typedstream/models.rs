@@ -1,9 +1,12 @@
 /*!
- Data structures and models used by the `typedstream` parser.
+ Data structures and models used by the `typedstream` parser and writer.
 */
 
+use crate::message_types::text_effects::Style;
+
 /// Represents a class stored in the `typedstream`
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Class {
     /// The name of the class
     pub name: String,
@@ -19,6 +22,11 @@ impl Class {
 
 /// Rust structures containing data stored in the `typedstream`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum OutputData {
     /// Text data, denoted in the stream by [`Type::String`]
     String(String),
@@ -40,6 +48,11 @@ pub enum OutputData {
 
 /// Types of data that can be archived into the `typedstream`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Archivable {
     /// An instance of a class that may contain some embedded data. `typedstream` data doesn't include property
     /// names, so data is stored in order of appearance.
@@ -178,6 +191,11 @@ impl Archivable {
 /// These type encodings are partially documented [here](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/ObjCRuntimeGuide/Articles/ocrtTypeEncodings.html#//apple_ref/doc/uid/TP40008048-CH100-SW1) by Apple.
 // TODO: Remove clone
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Type {
     /// Encoded string data, usually embedded in an object. Denoted by:
     ///
@@ -282,3 +300,152 @@ pub(crate) enum ClassResult {
     /// A new class heirarchy to be inserted into the [`TypedStreamReader::object_table`](crate::util::typedstream::parser::TypedStreamReader::object_table)
     ClassHierarchy(Vec<Archivable>),
 }
+
+/// Attribute name for the index of the message part a range belongs to
+const MESSAGE_PART_KEY: &str = "__kIMMessagePartAttributeName";
+/// Attribute name for the contact a mention refers to
+const MENTION_KEY: &str = "__kIMMentionConfirmedMention";
+/// Attribute name for the URL a link points to
+const LINK_KEY: &str = "__kIMLinkAttributeName";
+/// Attribute name for the GUID of an attachment's file transfer
+const FILE_TRANSFER_KEY: &str = "__kIMFileTransferGUIDAttributeName";
+/// Attribute name for the name of an attachment's file
+const FILENAME_KEY: &str = "__kIMFilenameAttributeName";
+/// Character that marks the position of an attachment in the text
+const ATTACHMENT_CHAR: &str = "\u{FFFC}";
+
+/// Values that can be stored in an attribute dictionary written by [`TypedStreamWriter`](crate::util::typedstream::writer::TypedStreamWriter)
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    /// Archived as an `NSString`
+    String(String),
+    /// Archived as an `NSNumber` containing an `int`
+    Integer(i32),
+    /// Archived as an `NSNumber` containing a `double`
+    Double(f64),
+    /// Archived as an `NSURL`
+    URL(String),
+}
+
+/// A range of text that shares the same attributes
+///
+/// # Example
+///
+/// ```
+/// use imessage_database::{
+///     message_types::text_effects::Style,
+///     util::typedstream::models::AttributeRun,
+/// };
+///
+/// let run = AttributeRun::new("Hello")
+///     .with_part(0)
+///     .with_style(&Style::Bold);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeRun {
+    /// The text in the range
+    pub text: String,
+    /// Attribute names and their values, in the order they are archived
+    pub attributes: Vec<(String, AttributeValue)>,
+}
+
+impl AttributeRun {
+    /// Create a range of text without any attributes
+    #[must_use]
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            attributes: vec![],
+        }
+    }
+
+    /// Create a range for an attachment, which is a single placeholder character
+    #[must_use]
+    pub fn attachment(guid: &str, filename: Option<&str>) -> Self {
+        let run = Self::new(ATTACHMENT_CHAR)
+            .with_attribute(FILE_TRANSFER_KEY, AttributeValue::String(guid.to_string()));
+        match filename {
+            Some(filename) => {
+                run.with_attribute(FILENAME_KEY, AttributeValue::String(filename.to_string()))
+            }
+            None => run,
+        }
+    }
+
+    /// Add an attribute to the range
+    #[must_use]
+    pub fn with_attribute(mut self, key: &str, value: AttributeValue) -> Self {
+        self.attributes.push((key.to_string(), value));
+        self
+    }
+
+    /// Set the index of the message part the range belongs to
+    #[must_use]
+    pub fn with_part(self, part: i32) -> Self {
+        self.with_attribute(MESSAGE_PART_KEY, AttributeValue::Integer(part))
+    }
+
+    /// Mark the range as a mention of a contact, i.e. a phone number or email address
+    #[must_use]
+    pub fn with_mention(self, contact: &str) -> Self {
+        self.with_attribute(MENTION_KEY, AttributeValue::String(contact.to_string()))
+    }
+
+    /// Mark the range as a link to a URL
+    #[must_use]
+    pub fn with_link(self, url: &str) -> Self {
+        self.with_attribute(LINK_KEY, AttributeValue::URL(url.to_string()))
+    }
+
+    /// Apply a text style to the range
+    #[must_use]
+    pub fn with_style(self, style: &Style) -> Self {
+        let key = match style {
+            Style::Bold => "__kIMTextBoldAttributeName",
+            Style::Italic => "__kIMTextItalicAttributeName",
+            Style::Strikethrough => "__kIMTextStrikethroughAttributeName",
+            Style::Underline => "__kIMTextUnderlineAttributeName",
+        };
+        self.with_attribute(key, AttributeValue::Integer(1))
+    }
+
+    /// The length of the range, counted in UTF-16 code units like `NSString`
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.text.encode_utf16().count()
+    }
+
+    /// `true` if the range does not contain any text
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+}
+
+/// An `NSAttributedString` to write with [`TypedStreamWriter`](crate::util::typedstream::writer::TypedStreamWriter)
+///
+/// The text of the string is the text of each of its runs, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributedString {
+    /// The ranges of text and their attributes
+    pub runs: Vec<AttributeRun>,
+    /// `true` to archive an `NSMutableAttributedString`, `false` to archive an `NSAttributedString`
+    pub mutable: bool,
+}
+
+impl AttributedString {
+    /// Create an `NSMutableAttributedString`, the class used for message bodies
+    #[must_use]
+    pub fn new(runs: Vec<AttributeRun>) -> Self {
+        Self {
+            runs,
+            mutable: true,
+        }
+    }
+
+    /// The full text of the string
+    #[must_use]
+    pub fn text(&self) -> String {
+        self.runs.iter().map(|run| run.text.as_str()).collect()
+    }
+}
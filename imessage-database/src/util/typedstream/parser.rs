@@ -158,7 +158,11 @@ impl<'a> TypedStreamReader<'a> {
             }
             I_16 | I_32 => Ok(self.read_signed_int()? as f32),
             _ => {
-                self.idx += 1;
+                // A reference to the type may precede the value, see `read_signed_int()`
+                if self.get_current_byte()? > REFERENCE_TAG as u8 && self.get_next_byte()? != END {
+                    self.idx += 1;
+                    return self.read_float();
+                }
                 Ok(self.read_signed_int()? as f32)
             }
         }
@@ -178,7 +182,11 @@ impl<'a> TypedStreamReader<'a> {
             }
             I_16 | I_32 => Ok(self.read_signed_int()? as f64),
             _ => {
-                self.idx += 1;
+                // A reference to the type may precede the value, see `read_signed_int()`
+                if self.get_current_byte()? > REFERENCE_TAG as u8 && self.get_next_byte()? != END {
+                    self.idx += 1;
+                    return self.read_double();
+                }
                 Ok(self.read_signed_int()? as f64)
             }
         }
@@ -361,7 +369,7 @@ impl<'a> TypedStreamReader<'a> {
                         .push(Archivable::Type(object_types.clone()));
                     // We only want to include the first embedded reference tag, not subsequent references to the same embed
                     self.seen_embedded_types
-                        .insert(self.object_table.len().saturating_sub(1) as u32);
+                        .insert(self.types_table.len() as u32);
                 }
                 self.types_table.push(object_types);
                 Ok(self.types_table.last().cloned())
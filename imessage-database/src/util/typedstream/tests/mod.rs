@@ -1,2 +1,3 @@
 mod test_parser;
 mod test_type;
+mod test_writer;
@@ -0,0 +1,295 @@
+#[cfg(test)]
+mod writer_tests {
+    use std::env::current_dir;
+    use std::fs::File;
+    use std::io::Read;
+
+    use proptest::prelude::*;
+
+    use crate::{
+        message_types::text_effects::{Style, TextEffect},
+        tables::messages::{
+            body::parse_body_typedstream,
+            models::{AttachmentMeta, BubbleComponent, TextAttributes},
+        },
+        util::typedstream::{
+            models::{
+                Archivable, AttributeRun, AttributeValue, AttributedString, Class, OutputData,
+            },
+            parser::TypedStreamReader,
+            writer::TypedStreamWriter,
+        },
+    };
+
+    fn parse_fixture(name: &str) -> Vec<Archivable> {
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream")
+            .join(name);
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        TypedStreamReader::from(&bytes).parse().unwrap()
+    }
+
+    fn write_and_parse(string: &AttributedString) -> Vec<Archivable> {
+        let bytes = TypedStreamWriter::new()
+            .write_attributed_string(string)
+            .unwrap();
+        TypedStreamReader::from(&bytes).parse().unwrap()
+    }
+
+    fn object(name: &str, version: u64, data: Vec<OutputData>) -> Archivable {
+        Archivable::Object(
+            Class {
+                name: name.to_string(),
+                version,
+            },
+            data,
+        )
+    }
+
+    fn nsstring(text: &str) -> Archivable {
+        object("NSString", 1, vec![OutputData::String(text.to_string())])
+    }
+
+    /// Build the components the reader should emit for a given string
+    fn expected_components(string: &AttributedString) -> Vec<Archivable> {
+        let text = OutputData::String(string.text());
+        let mut expected = vec![if string.mutable {
+            object("NSMutableString", 1, vec![text])
+        } else {
+            object("NSString", 1, vec![text])
+        }];
+
+        let mut dictionaries: Vec<&Vec<(String, AttributeValue)>> = vec![];
+        for run in &string.runs {
+            let index = match dictionaries.iter().position(|d| **d == run.attributes) {
+                Some(position) => position + 1,
+                None => {
+                    dictionaries.push(&run.attributes);
+                    expected.push(Archivable::Data(vec![
+                        OutputData::SignedInteger(dictionaries.len() as i64),
+                        OutputData::UnsignedInteger(run.len() as u64),
+                    ]));
+                    expected.push(object(
+                        "NSDictionary",
+                        0,
+                        vec![OutputData::SignedInteger(run.attributes.len() as i64)],
+                    ));
+                    for (key, value) in &run.attributes {
+                        expected.push(nsstring(key));
+                        match value {
+                            AttributeValue::String(s) => expected.push(nsstring(s)),
+                            AttributeValue::Integer(n) => expected.push(object(
+                                "NSNumber",
+                                0,
+                                vec![OutputData::SignedInteger(i64::from(*n))],
+                            )),
+                            AttributeValue::Double(n) => {
+                                expected.push(object("NSNumber", 0, vec![OutputData::Double(*n)]))
+                            }
+                            AttributeValue::URL(url) => {
+                                expected.push(object(
+                                    "NSURL",
+                                    0,
+                                    vec![OutputData::SignedInteger(0)],
+                                ));
+                                expected.push(nsstring(url));
+                            }
+                        }
+                    }
+                    continue;
+                }
+            };
+            expected.push(Archivable::Data(vec![
+                OutputData::SignedInteger(index as i64),
+                OutputData::UnsignedInteger(run.len() as u64),
+            ]));
+        }
+        expected
+    }
+
+    #[test]
+    fn test_write_header() {
+        let bytes = TypedStreamWriter::new()
+            .write_attributed_string(&AttributedString::new(vec![]))
+            .unwrap();
+
+        assert_eq!(
+            &bytes[..16],
+            &[
+                0x04, 0x0b, 0x73, 0x74, 0x72, 0x65, 0x61, 0x6d, 0x74, 0x79, 0x70, 0x65, 0x64, 0x81,
+                0xe8, 0x03
+            ]
+        );
+        assert!(TypedStreamReader::from(&bytes).validate_header().is_ok());
+    }
+
+    #[test]
+    fn test_write_text_basic() {
+        let string = AttributedString::new(vec![AttributeRun::new("Noter test").with_part(0)]);
+
+        assert_eq!(
+            write_and_parse(&string),
+            parse_fixture("AttributedBodyTextOnly")
+        );
+    }
+
+    #[test]
+    fn test_write_text_mention() {
+        let string = AttributedString::new(vec![
+            AttributeRun::new("Test ").with_part(0),
+            AttributeRun::new("Dad")
+                .with_mention("+15558675309")
+                .with_part(0),
+            AttributeRun::new(" ").with_part(0),
+        ]);
+
+        assert_eq!(write_and_parse(&string), parse_fixture("Mention"));
+    }
+
+    #[test]
+    fn test_write_text_styles() {
+        let string = AttributedString {
+            runs: vec![
+                AttributeRun::new("Bold")
+                    .with_style(&Style::Bold)
+                    .with_part(0),
+                AttributeRun::new(" ").with_part(0),
+                AttributeRun::new("underline")
+                    .with_part(0)
+                    .with_style(&Style::Underline),
+            ],
+            mutable: false,
+        };
+
+        let result = write_and_parse(&string);
+        assert_eq!(result, expected_components(&string));
+
+        assert_eq!(
+            parse_body_typedstream(Some(&result), Some(&string.text()), None).unwrap(),
+            vec![BubbleComponent::Text(vec![
+                TextAttributes::new(0, 4, TextEffect::Styles(vec![Style::Bold])),
+                TextAttributes::new(4, 5, TextEffect::Default),
+                TextAttributes::new(5, 14, TextEffect::Styles(vec![Style::Underline])),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_write_link_and_mention() {
+        let string = AttributedString::new(vec![
+            AttributeRun::new("Hey ").with_part(0),
+            AttributeRun::new("Chris")
+                .with_part(0)
+                .with_mention("chris@example.com"),
+            AttributeRun::new(", see ").with_part(0),
+            AttributeRun::new("example.com")
+                .with_part(0)
+                .with_link("https://example.com"),
+        ]);
+
+        let result = write_and_parse(&string);
+        assert_eq!(result, expected_components(&string));
+
+        assert_eq!(
+            parse_body_typedstream(Some(&result), Some(&string.text()), None).unwrap(),
+            vec![BubbleComponent::Text(vec![
+                TextAttributes::new(0, 4, TextEffect::Default),
+                TextAttributes::new(4, 9, TextEffect::Mention("chris@example.com")),
+                TextAttributes::new(9, 15, TextEffect::Default),
+                TextAttributes::new(15, 26, TextEffect::Link("https://example.com")),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_write_attachment() {
+        let string = AttributedString::new(vec![
+            AttributeRun::attachment("at_0_2E5F12C3", Some("image.png"))
+                .with_part(0)
+                .with_attribute(
+                    "__kIMInlineMediaWidthAttributeName",
+                    AttributeValue::Double(1139.0),
+                )
+                .with_attribute(
+                    "__kIMInlineMediaHeightAttributeName",
+                    AttributeValue::Double(640.5),
+                ),
+            AttributeRun::new("Caption").with_part(1),
+        ]);
+
+        let result = write_and_parse(&string);
+        assert_eq!(result, expected_components(&string));
+
+        assert_eq!(
+            parse_body_typedstream(Some(&result), Some(&string.text()), None).unwrap(),
+            vec![
+                BubbleComponent::Attachment(AttachmentMeta {
+                    guid: Some("at_0_2E5F12C3"),
+                    transcription: None,
+                    height: Some(&640.5),
+                    width: Some(&1139.0),
+                    name: Some("image.png"),
+                }),
+                BubbleComponent::Text(vec![TextAttributes::new(3, 10, TextEffect::Default)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_utf16_length() {
+        let string = AttributedString::new(vec![AttributeRun::new("👋🏻 hi").with_part(0)]);
+        let result = write_and_parse(&string);
+
+        assert_eq!(
+            result.get(1),
+            Some(&Archivable::Data(vec![
+                OutputData::SignedInteger(1),
+                OutputData::UnsignedInteger(7),
+            ]))
+        );
+    }
+
+    fn attribute_value() -> impl Strategy<Value = AttributeValue> {
+        prop_oneof![
+            ".{0,40}".prop_map(AttributeValue::String),
+            any::<i32>().prop_map(AttributeValue::Integer),
+            prop_oneof![
+                (-5000..5000).prop_map(f64::from),
+                any::<f64>().prop_filter("finite", |n| n.is_finite()),
+            ]
+            .prop_map(AttributeValue::Double),
+            "https://[a-z]{1,20}\\.com/.{0,20}".prop_map(AttributeValue::URL),
+        ]
+    }
+
+    fn attribute_run() -> impl Strategy<Value = AttributeRun> {
+        (
+            ".{0,200}",
+            prop::collection::vec(("__kIM[A-Za-z]{1,30}", attribute_value()), 0..4),
+        )
+            .prop_map(|(text, attributes)| AttributeRun { text, attributes })
+    }
+
+    fn attributed_string() -> impl Strategy<Value = AttributedString> {
+        (prop::collection::vec(attribute_run(), 0..8), any::<bool>())
+            .prop_map(|(runs, mutable)| AttributedString { runs, mutable })
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip(string in attributed_string()) {
+            prop_assert_eq!(write_and_parse(&string), expected_components(&string));
+        }
+
+        #[test]
+        fn test_round_trip_repeated_runs(run in attribute_run(), repeats in 1..6usize) {
+            let string = AttributedString::new(vec![run; repeats]);
+            prop_assert_eq!(write_and_parse(&string), expected_components(&string));
+        }
+    }
+}
@@ -0,0 +1,358 @@
+/*!
+ Logic used to serialize data into a `typedstream`, focussing specifically on [`NSAttributedString`](https://developer.apple.com/documentation/foundation/nsattributedstring).
+
+ The output mirrors what `NSArchiver` emits for the `attributedBody` column, so it can be read back with
+ [`TypedStreamReader`](crate::util::typedstream::parser::TypedStreamReader).
+*/
+use std::collections::HashMap;
+
+use crate::{
+    error::typedstream::TypedStreamError,
+    util::typedstream::models::{AttributeRun, AttributeValue, AttributedString},
+};
+
+/// Indicates an [`i16`] in the byte stream
+const I_16: u8 = 0x81;
+/// Indicates an [`i32`] in the byte stream
+const I_32: u8 = 0x82;
+/// Indicates an [`f32`] or [`f64`] in the byte stream; the type determines the size
+const DECIMAL: u8 = 0x83;
+/// Indicates the start of a new object
+const START: u8 = 0x84;
+/// Indicates that there is no more data to write, for example the end of a class inheritance chain
+const EMPTY: u8 = 0x85;
+/// Indicates the last byte of an object
+const END: u8 = 0x86;
+/// References are stored as signed integers offset by this value, i.e. the first reference is `0x92`
+const REFERENCE_OFFSET: i64 = -110;
+/// Smallest value that fits in a single byte
+const MIN_BYTE: i64 = -110;
+/// Largest value that fits in a single byte
+const MAX_BYTE: i64 = 127;
+
+/// Version of the `typedstream` format
+const VERSION: u64 = 4;
+/// Signature of streams written by `NSArchiver`
+const SIGNATURE: &str = "streamtyped";
+/// System version of streams written on macOS and iOS
+const SYSTEM_VERSION: i64 = 1000;
+
+/// Class inheritance chains, in descending order, with the version of each class
+const NS_MUTABLE_ATTRIBUTED_STRING: &[(&str, u64)] = &[
+    ("NSMutableAttributedString", 0),
+    ("NSAttributedString", 0),
+    ("NSObject", 0),
+];
+const NS_ATTRIBUTED_STRING: &[(&str, u64)] = &[("NSAttributedString", 0), ("NSObject", 0)];
+const NS_MUTABLE_STRING: &[(&str, u64)] =
+    &[("NSMutableString", 1), ("NSString", 1), ("NSObject", 0)];
+const NS_STRING: &[(&str, u64)] = &[("NSString", 1), ("NSObject", 0)];
+const NS_DICTIONARY: &[(&str, u64)] = &[("NSDictionary", 0), ("NSObject", 0)];
+const NS_NUMBER: &[(&str, u64)] = &[("NSNumber", 0), ("NSValue", 0), ("NSObject", 0)];
+const NS_URL: &[(&str, u64)] = &[("NSURL", 0), ("NSObject", 0)];
+
+/// Contains logic and data used to serialize data into a `typedstream`.
+///
+/// Like the [`TypedStreamReader`](crate::util::typedstream::parser::TypedStreamReader), the writer keeps a table
+/// of shared strings (type encodings and class names) and a table of archived objects. The first time an item
+/// is written it is present in the stream literally, but afterwards it is only referenced by its index in the table.
+#[derive(Debug, Default)]
+pub struct TypedStreamWriter {
+    /// The `typedstream` we are building
+    stream: Vec<u8>,
+    /// Type encodings and class names, in order of appearance
+    shared_strings: Vec<String>,
+    /// The number of objects, classes, and C strings written so far
+    objects: usize,
+    /// Index of each class in the table of archived objects
+    classes: HashMap<&'static str, usize>,
+    /// Index of each C string in the table of archived objects
+    c_strings: HashMap<&'static str, usize>,
+    /// Attribute dictionaries that were already written; runs that share attributes reuse the first dictionary
+    dictionaries: Vec<Vec<(String, AttributeValue)>>,
+}
+
+impl TypedStreamWriter {
+    /// Construct a writer with empty tables
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::typedstream::writer::TypedStreamWriter;
+    ///
+    /// let writer = TypedStreamWriter::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serialize an [`AttributedString`] into a `typedstream`, as found in the `attributedBody` column.
+    ///
+    /// Each [`AttributeRun`] is archived as a range followed by its attribute dictionary. Ranges are counted
+    /// in UTF-16 code units, like `NSString`.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::typedstream::{
+    ///     models::{AttributeRun, AttributedString},
+    ///     parser::TypedStreamReader,
+    ///     writer::TypedStreamWriter,
+    /// };
+    ///
+    /// let body = AttributedString::new(vec![
+    ///     AttributeRun::new("Hello ").with_part(0),
+    ///     AttributeRun::new("world").with_part(0).with_link("https://example.com"),
+    /// ]);
+    /// let bytes = TypedStreamWriter::new().write_attributed_string(&body).unwrap();
+    /// let result = TypedStreamReader::from(&bytes).parse();
+    /// ```
+    pub fn write_attributed_string(
+        mut self,
+        string: &AttributedString,
+    ) -> Result<Vec<u8>, TypedStreamError> {
+        self.write_header()?;
+
+        self.write_type("@")?;
+        if string.mutable {
+            self.write_object_start(NS_MUTABLE_ATTRIBUTED_STRING)?;
+        } else {
+            self.write_object_start(NS_ATTRIBUTED_STRING)?;
+        }
+
+        // The text of the string
+        self.write_type("@")?;
+        if string.mutable {
+            self.write_string_object(NS_MUTABLE_STRING, &string.text())?;
+        } else {
+            self.write_string_object(NS_STRING, &string.text())?;
+        }
+
+        for run in &string.runs {
+            self.write_run(run)?;
+        }
+
+        self.stream.push(END);
+        Ok(self.stream)
+    }
+
+    /// Write the header used by macOS and iOS
+    fn write_header(&mut self) -> Result<(), TypedStreamError> {
+        self.write_unsigned_int(VERSION)?;
+        self.write_string(SIGNATURE)?;
+        self.write_signed_int(SYSTEM_VERSION);
+        Ok(())
+    }
+
+    /// Write a range and, if its attributes were not seen before, the dictionary of attributes that applies to it
+    fn write_run(&mut self, run: &AttributeRun) -> Result<(), TypedStreamError> {
+        let (index, is_new) = match self
+            .dictionaries
+            .iter()
+            .position(|attributes| *attributes == run.attributes)
+        {
+            Some(position) => (position + 1, false),
+            None => {
+                self.dictionaries.push(run.attributes.clone());
+                (self.dictionaries.len(), true)
+            }
+        };
+
+        self.write_type("iI")?;
+        self.write_signed_int(index as i64);
+        self.write_unsigned_int(run.len() as u64)?;
+
+        if is_new {
+            self.write_type("@")?;
+            self.write_object_start(NS_DICTIONARY)?;
+            self.write_type("i")?;
+            self.write_signed_int(run.attributes.len() as i64);
+            for (key, value) in &run.attributes {
+                self.write_type("@")?;
+                self.write_string_object(NS_STRING, key)?;
+                self.write_type("@")?;
+                self.write_value(value)?;
+            }
+            self.stream.push(END);
+        }
+
+        Ok(())
+    }
+
+    /// Write the object that holds an attribute's value
+    fn write_value(&mut self, value: &AttributeValue) -> Result<(), TypedStreamError> {
+        match value {
+            AttributeValue::String(string) => self.write_string_object(NS_STRING, string)?,
+            AttributeValue::Integer(number) => {
+                self.write_number_object("i")?;
+                self.write_value_int(i64::from(*number));
+                self.stream.push(END);
+            }
+            AttributeValue::Double(number) => {
+                self.write_number_object("d")?;
+                self.write_double(*number);
+                self.stream.push(END);
+            }
+            AttributeValue::URL(url) => {
+                self.write_object_start(NS_URL)?;
+                // `NSURL`s from messages are never relative to a base URL
+                self.write_type("c")?;
+                self.write_signed_int(0);
+                self.write_type("@")?;
+                self.write_string_object(NS_STRING, url)?;
+                self.stream.push(END);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write an `NSString` or `NSMutableString`
+    fn write_string_object(
+        &mut self,
+        chain: &'static [(&'static str, u64)],
+        string: &str,
+    ) -> Result<(), TypedStreamError> {
+        self.write_object_start(chain)?;
+        self.write_type("+")?;
+        self.write_string(string)?;
+        self.stream.push(END);
+        Ok(())
+    }
+
+    /// Write the start of an `NSNumber`, which stores the type encoding of its value as a C string
+    ///
+    /// The caller is responsible for writing the value itself.
+    fn write_number_object(&mut self, encoding: &'static str) -> Result<(), TypedStreamError> {
+        self.write_object_start(NS_NUMBER)?;
+        self.write_type("*")?;
+        self.write_c_string(encoding)?;
+        self.write_type(encoding)
+    }
+
+    /// Write the start of a new object and its class
+    fn write_object_start(
+        &mut self,
+        chain: &'static [(&'static str, u64)],
+    ) -> Result<(), TypedStreamError> {
+        self.stream.push(START);
+        self.objects += 1;
+        self.write_class(chain)
+    }
+
+    /// Write a class inheritance chain, stopping at the first class that was already written
+    fn write_class(
+        &mut self,
+        chain: &'static [(&'static str, u64)],
+    ) -> Result<(), TypedStreamError> {
+        for (name, version) in chain {
+            if let Some(index) = self.classes.get(name) {
+                self.write_reference(*index);
+                return Ok(());
+            }
+            self.stream.push(START);
+            self.classes.insert(name, self.objects);
+            self.objects += 1;
+            self.write_shared_string(name)?;
+            self.write_unsigned_int(*version)?;
+        }
+        self.stream.push(EMPTY);
+        Ok(())
+    }
+
+    /// Write a C string, which is stored in the table of archived objects
+    fn write_c_string(&mut self, string: &'static str) -> Result<(), TypedStreamError> {
+        match self.c_strings.get(string) {
+            Some(index) => self.write_reference(*index),
+            None => {
+                self.stream.push(START);
+                self.c_strings.insert(string, self.objects);
+                self.objects += 1;
+                self.write_shared_string(string)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the type encoding of the next item in the stream
+    fn write_type(&mut self, encoding: &str) -> Result<(), TypedStreamError> {
+        self.write_shared_string(encoding)
+    }
+
+    /// Write a type encoding or class name, or a reference to it if it was already written
+    fn write_shared_string(&mut self, string: &str) -> Result<(), TypedStreamError> {
+        match self.shared_strings.iter().position(|item| item == string) {
+            Some(index) => self.write_reference(index),
+            None => {
+                self.stream.push(START);
+                self.write_string(string)?;
+                self.shared_strings.push(string.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a reference to an index in one of the tables
+    fn write_reference(&mut self, index: usize) {
+        self.write_signed_int(index as i64 + REFERENCE_OFFSET);
+    }
+
+    /// Write a length-prefixed UTF-8 string
+    fn write_string(&mut self, string: &str) -> Result<(), TypedStreamError> {
+        self.write_unsigned_int(string.len() as u64)?;
+        self.stream.extend_from_slice(string.as_bytes());
+        Ok(())
+    }
+
+    /// Write a signed integer using the smallest encoding that fits it
+    fn write_signed_int(&mut self, value: i64) {
+        if (MIN_BYTE..=MAX_BYTE).contains(&value) {
+            self.stream.push(value as i8 as u8);
+        } else if let Ok(value) = i16::try_from(value) {
+            self.stream.push(I_16);
+            self.stream.extend_from_slice(&value.to_le_bytes());
+        } else {
+            self.stream.push(I_32);
+            self.stream.extend_from_slice(&(value as i32).to_le_bytes());
+        }
+    }
+
+    /// Write a signed integer that belongs to an object's data
+    ///
+    /// Negative values that fit in a single byte share their encoding with references, so they are widened to
+    /// keep them distinct from the type reference that precedes them.
+    fn write_value_int(&mut self, value: i64) {
+        if (MIN_BYTE..0).contains(&value) {
+            self.stream.push(I_16);
+            self.stream.extend_from_slice(&(value as i16).to_le_bytes());
+        } else {
+            self.write_signed_int(value);
+        }
+    }
+
+    /// Write an unsigned integer using the smallest encoding that fits it
+    fn write_unsigned_int(&mut self, value: u64) -> Result<(), TypedStreamError> {
+        if value <= MAX_BYTE as u64 {
+            self.stream.push(value as u8);
+        } else if let Ok(value) = u16::try_from(value) {
+            self.stream.push(I_16);
+            self.stream.extend_from_slice(&value.to_le_bytes());
+        } else if let Ok(value) = u32::try_from(value) {
+            self.stream.push(I_32);
+            self.stream.extend_from_slice(&value.to_le_bytes());
+        } else {
+            return Err(TypedStreamError::LengthOverflow(value));
+        }
+        Ok(())
+    }
+
+    /// Write a double, which is stored as an integer if it does not have a fractional part
+    fn write_double(&mut self, value: f64) {
+        if value.fract() == 0.0 && value >= f64::from(i32::MIN) && value <= f64::from(i32::MAX) {
+            self.write_value_int(value as i64);
+        } else {
+            self.stream.push(DECIMAL);
+            self.stream.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
@@ -0,0 +1,124 @@
+/*!
+Computes messaging statistics for the `analyze` command
+
+Messages matching the options' filters are read in the order they were sent and passed to an
+[`Analyzer`]. The resulting [`Analysis`](imessage_database::util::analytics::Analysis) is keyed by
+chat and handle IDs, so the report adds the name of each chat and the phone number or email of each
+handle before it is written as JSON.
+*/
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{
+        chat::Chat,
+        handle::Handle,
+        messages::Message,
+        schema::SchemaCapabilities,
+        table::{Cacheable, Table, UNKNOWN, get_connection},
+    },
+    util::{
+        analytics::{Analyzer, Stats},
+        dates::get_offset,
+    },
+};
+
+use crate::app::{error::RuntimeError, options::AnalyzeOptions};
+
+/// Statistics for the messages in a chat
+#[derive(Debug, Serialize)]
+pub struct ChatReport {
+    pub chat_id: i32,
+    /// The chat's display name, or its identifier if it is not named
+    pub name: String,
+    #[serde(flatten)]
+    pub stats: Stats,
+}
+
+/// Statistics for the messages received from a handle
+#[derive(Debug, Serialize)]
+pub struct HandleReport {
+    pub handle_id: i32,
+    /// The phone number or email of the handle
+    pub identifier: String,
+    #[serde(flatten)]
+    pub stats: Stats,
+}
+
+/// The statistics written by the `analyze` command
+#[derive(Debug, Serialize)]
+pub struct AnalyticsReport {
+    /// Every message that was analyzed
+    pub overall: Stats,
+    /// Messages sent by the database owner
+    pub me: Stats,
+    /// Messages in each chat, ordered by chat ID
+    pub chats: Vec<ChatReport>,
+    /// Messages received from each handle, ordered by handle ID
+    pub handles: Vec<HandleReport>,
+}
+
+/// Analyze every message that matches the options' filters
+pub fn analyze(options: &AnalyzeOptions) -> Result<AnalyticsReport, RuntimeError> {
+    let db = get_connection(&options.get_db_path())?;
+    let capabilities = SchemaCapabilities::probe(&db)?;
+    let chats: HashMap<i32, Chat> = Chat::cache(&db)?;
+    let handles: HashMap<i32, String> = Handle::cache(&db)?;
+
+    let mut analyzer = Analyzer::new(get_offset());
+
+    // Keep track of current message ROWID
+    let mut current_message_row = -1;
+
+    let mut statement = Message::stream_rows(&db, &capabilities, &options.query_context)?;
+    let messages = statement
+        .query_map([], |row| Ok(Message::from_row(row)))
+        .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+    for message in messages {
+        let mut msg = Message::extract(message)?;
+
+        // Early escape if we try and render the same message GUID twice
+        // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+        if msg.rowid == current_message_row {
+            continue;
+        }
+        current_message_row = msg.rowid;
+
+        // Only edited messages need their body parsed, to tell edits apart from unsends
+        if msg.is_edited() {
+            let _ = msg.generate_text(&db);
+        }
+        analyzer.add(&msg);
+    }
+
+    let analysis = analyzer.finish();
+    Ok(AnalyticsReport {
+        overall: analysis.overall,
+        me: analysis.me,
+        chats: analysis
+            .chats
+            .into_iter()
+            .map(|(chat_id, stats)| ChatReport {
+                chat_id,
+                name: chats.get(&chat_id).map_or(UNKNOWN, Chat::name).to_string(),
+                stats,
+            })
+            .collect(),
+        handles: analysis
+            .handles
+            .into_iter()
+            .map(|(handle_id, stats)| HandleReport {
+                handle_id,
+                identifier: handles
+                    .get(&handle_id)
+                    .map_or(UNKNOWN, String::as_str)
+                    .to_string(),
+                stats,
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,58 @@
+/*!
+Errors that can happen while exporting messages
+*/
+
+use std::fmt::{Display, Formatter, Result};
+
+use imessage_database::error::table::TableError;
+
+/// Errors that can happen during the application's runtime
+#[derive(Debug)]
+pub enum RuntimeError {
+    /// The provided command line options are invalid
+    InvalidOptions(String),
+    /// Reading from or writing to the disk failed
+    DiskError(std::io::Error),
+    /// Reading from the iMessage database failed
+    DatabaseError(TableError),
+    /// Reading from the tracker's state database failed
+    StateDatabaseError(rusqlite::Error),
+    /// The file being imported is not in the expected format
+    ImportError(String),
+    /// Writing to the archive that messages are imported into failed
+    ArchiveError(rusqlite::Error),
+    /// The simulation's scenario is invalid or cannot be applied
+    #[cfg(feature = "simulate")]
+    SimulationError(String),
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            RuntimeError::InvalidOptions(why) => write!(fmt, "Invalid options!\n{why}"),
+            RuntimeError::DiskError(why) => write!(fmt, "{why}"),
+            RuntimeError::DatabaseError(why) => write!(fmt, "{why}"),
+            RuntimeError::StateDatabaseError(why) => {
+                write!(fmt, "Unable to read the tracker state database: {why}")
+            }
+            RuntimeError::ImportError(why) => write!(fmt, "Unable to import!\n{why}"),
+            RuntimeError::ArchiveError(why) => write!(fmt, "Unable to write to the archive: {why}"),
+            #[cfg(feature = "simulate")]
+            RuntimeError::SimulationError(why) => write!(fmt, "Unable to simulate!\n{why}"),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+impl From<TableError> for RuntimeError {
+    fn from(why: TableError) -> Self {
+        RuntimeError::DatabaseError(why)
+    }
+}
+
+impl From<std::io::Error> for RuntimeError {
+    fn from(why: std::io::Error) -> Self {
+        RuntimeError::DiskError(why)
+    }
+}
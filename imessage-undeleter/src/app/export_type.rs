@@ -0,0 +1,80 @@
+/*!
+The output formats supported by the `export` command
+*/
+
+use std::fmt::{Display, Formatter, Result};
+
+/// Represents the type of file to export message data to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportType {
+    /// One JSON document per conversation
+    Json,
+    /// One plain text file per conversation
+    Txt,
+    /// One HTML page per conversation, plus a report of deleted messages
+    Html,
+    /// CSV tables of every message and every deletion the tracker detected
+    Csv,
+    /// Parquet tables of every message and every deletion the tracker detected
+    Parquet,
+    /// A single XML file in the format used by the Android app SMS Backup & Restore
+    SmsBackup,
+    /// One mbox file of emails per conversation
+    Mbox,
+    /// One directory per conversation, with one email file per message
+    Eml,
+    /// A single GeoJSON file of every shared location
+    GeoJson,
+    /// A single KML file of every shared location
+    Kml,
+}
+
+impl ExportType {
+    /// Every format accepted on the command line
+    pub const ALL: &'static [ExportType] = &[
+        ExportType::Json,
+        ExportType::Txt,
+        ExportType::Html,
+        ExportType::Csv,
+        ExportType::Parquet,
+        ExportType::SmsBackup,
+        ExportType::Mbox,
+        ExportType::Eml,
+        ExportType::GeoJson,
+        ExportType::Kml,
+    ];
+
+    /// Given the user's input, return the matching export type
+    pub fn from_cli(export_type: &str) -> Option<Self> {
+        match export_type.to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "txt" => Some(Self::Txt),
+            "html" => Some(Self::Html),
+            "csv" => Some(Self::Csv),
+            "parquet" => Some(Self::Parquet),
+            "sms-backup" => Some(Self::SmsBackup),
+            "mbox" => Some(Self::Mbox),
+            "eml" => Some(Self::Eml),
+            "geojson" => Some(Self::GeoJson),
+            "kml" => Some(Self::Kml),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ExportType {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            ExportType::Json => write!(fmt, "json"),
+            ExportType::Txt => write!(fmt, "txt"),
+            ExportType::Html => write!(fmt, "html"),
+            ExportType::Csv => write!(fmt, "csv"),
+            ExportType::Parquet => write!(fmt, "parquet"),
+            ExportType::SmsBackup => write!(fmt, "sms-backup"),
+            ExportType::Mbox => write!(fmt, "mbox"),
+            ExportType::Eml => write!(fmt, "eml"),
+            ExportType::GeoJson => write!(fmt, "geojson"),
+            ExportType::Kml => write!(fmt, "kml"),
+        }
+    }
+}
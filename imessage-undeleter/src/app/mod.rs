@@ -0,0 +1,8 @@
+/*!
+Runtime configuration shared by the export commands
+*/
+
+pub mod error;
+pub mod export_type;
+pub mod options;
+pub mod runtime;
@@ -0,0 +1,713 @@
+/*!
+Command line options for the `export`, `import`, `simulate`, `analyze`, `report`, and `doctor` commands
+*/
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+#[cfg(feature = "simulate")]
+use clap::ArgAction;
+use clap::{Arg, ArgMatches, Command};
+
+use imessage_database::{
+    tables::table::DEFAULT_PATH_IOS,
+    util::{
+        dates::{TIMESTAMP_FACTOR, get_offset},
+        dirs::default_db_path,
+        platform::Platform,
+        query_context::QueryContext,
+    },
+};
+
+use crate::{
+    app::{error::RuntimeError, export_type::ExportType},
+    report::{DigestPeriod, ReportFormat},
+};
+
+/// Default export directory, relative to the current working directory
+pub const DEFAULT_EXPORT_PATH: &str = "./imessage_export";
+/// Default path to the tracker's state database, relative to the current working directory
+pub const DEFAULT_STATE_DB_PATH: &str = "./tracker_state.db";
+/// Default path to the archive that messages are imported into, relative to the current working directory
+pub const DEFAULT_ARCHIVE_PATH: &str = "./imessage_archive/chat.db";
+#[cfg(feature = "simulate")]
+/// Default path to the database a simulation creates, relative to the current working directory
+pub const DEFAULT_SIMULATION_DB_PATH: &str = "./imessage_simulation/chat.db";
+#[cfg(feature = "simulate")]
+/// Default path to the events a simulation expects the tracker to report, relative to the current working directory
+pub const DEFAULT_EXPECTED_PATH: &str = "./imessage_simulation/expected.json";
+/// Default directory that digests are written to, relative to the current working directory
+pub const DEFAULT_DIGEST_PATH: &str = "./deletion_reports";
+/// Default file the tracker logs detected deletions to, relative to the current working directory
+pub const DEFAULT_DELETIONS_PATH: &str = "./undeleted_messages/deletions.json";
+
+// Argument names
+pub const OPTION_DB_PATH: &str = "db-path";
+pub const OPTION_FORMAT: &str = "format";
+pub const OPTION_EXPORT_PATH: &str = "export-path";
+pub const OPTION_ATTACHMENT_ROOT: &str = "attachment-root";
+pub const OPTION_PLATFORM: &str = "platform";
+pub const OPTION_START_DATE: &str = "start-date";
+pub const OPTION_END_DATE: &str = "end-date";
+pub const OPTION_CUSTOM_NAME: &str = "custom-name";
+pub const OPTION_STATE_DB: &str = "state-db";
+pub const OPTION_INPUT: &str = "input";
+#[cfg(feature = "simulate")]
+pub const OPTION_SCENARIO: &str = "scenario";
+#[cfg(feature = "simulate")]
+pub const OPTION_EXPECTED: &str = "expected";
+#[cfg(feature = "simulate")]
+pub const OPTION_INSTANT: &str = "instant";
+pub const OPTION_OUTPUT: &str = "output";
+pub const OPTION_DIGEST: &str = "digest";
+
+/// Options parsed from the `export` command
+#[derive(Debug)]
+pub struct Options {
+    /// Path to the iMessage database, or the root of an iOS backup
+    pub db_path: PathBuf,
+    /// Custom root directory for attachment files
+    pub attachment_root: Option<String>,
+    /// The platform that created the database
+    pub platform: Platform,
+    /// The format to export messages to
+    pub export_type: ExportType,
+    /// Directory to write exported files to
+    pub export_path: PathBuf,
+    /// Filters applied to the exported messages
+    pub query_context: QueryContext,
+    /// Name used for messages sent by the database owner
+    pub custom_name: Option<String>,
+    /// Path to the tracker's state database, which records detected deletions
+    pub state_db_path: PathBuf,
+}
+
+impl Options {
+    /// Build the options from the `export` subcommand's matches
+    pub fn from_args(args: &ArgMatches) -> Result<Self, RuntimeError> {
+        let db_path = args
+            .get_one::<String>(OPTION_DB_PATH)
+            .map_or_else(default_db_path, PathBuf::from);
+
+        let export_type = args
+            .get_one::<String>(OPTION_FORMAT)
+            .and_then(|format| ExportType::from_cli(format))
+            .ok_or_else(|| {
+                RuntimeError::InvalidOptions(format!(
+                    "Option --{OPTION_FORMAT} must be one of: {}",
+                    ExportType::ALL
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            })?;
+
+        let platform = parse_platform(args, &db_path)?;
+        let query_context = parse_query_context(args)?;
+
+        Ok(Options {
+            db_path,
+            attachment_root: args.get_one::<String>(OPTION_ATTACHMENT_ROOT).cloned(),
+            platform,
+            export_type,
+            export_path: args
+                .get_one::<String>(OPTION_EXPORT_PATH)
+                .map_or_else(|| PathBuf::from(DEFAULT_EXPORT_PATH), PathBuf::from),
+            query_context,
+            custom_name: args.get_one::<String>(OPTION_CUSTOM_NAME).cloned(),
+            state_db_path: args
+                .get_one::<String>(OPTION_STATE_DB)
+                .map_or_else(|| PathBuf::from(DEFAULT_STATE_DB_PATH), PathBuf::from),
+        })
+    }
+
+    /// Get the path to the database file itself, accounting for iOS backups
+    pub fn get_db_path(&self) -> PathBuf {
+        match self.platform {
+            Platform::iOS => self.db_path.join(DEFAULT_PATH_IOS),
+            Platform::macOS => self.db_path.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Options {
+    /// Export every message in a macOS database at `db_path` to `export_path`
+    pub fn fake(export_type: ExportType, db_path: &Path, export_path: &Path) -> Options {
+        Options {
+            db_path: db_path.to_path_buf(),
+            attachment_root: None,
+            platform: Platform::macOS,
+            export_type,
+            export_path: export_path.to_path_buf(),
+            query_context: QueryContext::default(),
+            custom_name: None,
+            state_db_path: export_path.join("tracker_state.db"),
+        }
+    }
+}
+
+/// Options parsed from the `import` command
+#[derive(Debug)]
+pub struct ImportOptions {
+    /// Path to the SMS Backup & Restore XML file to import
+    pub input: PathBuf,
+    /// Path to the archive to import messages into, which is created if it does not exist
+    pub db_path: PathBuf,
+}
+
+impl ImportOptions {
+    /// Build the options from the `import` subcommand's matches
+    pub fn from_args(args: &ArgMatches) -> Result<Self, RuntimeError> {
+        let input = args
+            .get_one::<String>(OPTION_INPUT)
+            .map(PathBuf::from)
+            .ok_or_else(|| {
+                RuntimeError::InvalidOptions(format!("Option --{OPTION_INPUT} is required"))
+            })?;
+
+        Ok(ImportOptions {
+            input,
+            db_path: args
+                .get_one::<String>(OPTION_DB_PATH)
+                .map_or_else(|| PathBuf::from(DEFAULT_ARCHIVE_PATH), PathBuf::from),
+        })
+    }
+}
+
+#[cfg(feature = "simulate")]
+/// Options parsed from the `simulate` command
+#[derive(Debug)]
+pub struct SimulateOptions {
+    /// Path to the JSON scenario to run
+    pub scenario: PathBuf,
+    /// Path to the database to create
+    pub db_path: PathBuf,
+    /// Path to write the events the tracker is expected to report
+    pub expected_path: PathBuf,
+    /// Apply every step immediately instead of waiting between steps
+    pub instant: bool,
+}
+
+#[cfg(feature = "simulate")]
+impl SimulateOptions {
+    /// Build the options from the `simulate` subcommand's matches
+    pub fn from_args(args: &ArgMatches) -> Result<Self, RuntimeError> {
+        let scenario = args
+            .get_one::<String>(OPTION_SCENARIO)
+            .map(PathBuf::from)
+            .ok_or_else(|| {
+                RuntimeError::InvalidOptions(format!("Option --{OPTION_SCENARIO} is required"))
+            })?;
+
+        Ok(SimulateOptions {
+            scenario,
+            db_path: args
+                .get_one::<String>(OPTION_DB_PATH)
+                .map_or_else(|| PathBuf::from(DEFAULT_SIMULATION_DB_PATH), PathBuf::from),
+            expected_path: args
+                .get_one::<String>(OPTION_EXPECTED)
+                .map_or_else(|| PathBuf::from(DEFAULT_EXPECTED_PATH), PathBuf::from),
+            instant: args.get_flag(OPTION_INSTANT),
+        })
+    }
+}
+
+/// Options parsed from the `analyze` command
+#[derive(Debug)]
+pub struct AnalyzeOptions {
+    /// Path to the iMessage database, or the root of an iOS backup
+    pub db_path: PathBuf,
+    /// The platform that created the database
+    pub platform: Platform,
+    /// Filters applied to the analyzed messages
+    pub query_context: QueryContext,
+    /// Path to write the statistics to, or `None` to print them
+    pub output: Option<PathBuf>,
+}
+
+impl AnalyzeOptions {
+    /// Build the options from the `analyze` subcommand's matches
+    pub fn from_args(args: &ArgMatches) -> Result<Self, RuntimeError> {
+        let db_path = args
+            .get_one::<String>(OPTION_DB_PATH)
+            .map_or_else(default_db_path, PathBuf::from);
+
+        Ok(AnalyzeOptions {
+            platform: parse_platform(args, &db_path)?,
+            query_context: parse_query_context(args)?,
+            db_path,
+            output: args.get_one::<String>(OPTION_OUTPUT).map(PathBuf::from),
+        })
+    }
+
+    /// Get the path to the database file itself, accounting for iOS backups
+    pub fn get_db_path(&self) -> PathBuf {
+        match self.platform {
+            Platform::iOS => self.db_path.join(DEFAULT_PATH_IOS),
+            Platform::macOS => self.db_path.clone(),
+        }
+    }
+}
+
+/// Options parsed from the `report` command
+#[derive(Debug)]
+pub struct ReportOptions {
+    /// Path to the tracker's state database, which records detected deletions
+    pub state_db_path: PathBuf,
+    /// Path to the iMessage database, or the root of an iOS backup, used to name chats and find when messages were sent
+    pub db_path: PathBuf,
+    /// The platform that created the database
+    pub platform: Platform,
+    /// The format to write the report in
+    pub format: ReportFormat,
+    /// Only report deletions detected at or after this Unix timestamp
+    pub start: Option<i64>,
+    /// Only report deletions detected before this Unix timestamp
+    pub end: Option<i64>,
+    /// Report the deletions of the period that just ended, instead of a date range
+    pub digest: Option<DigestPeriod>,
+    /// Path to write the report to, or `None` to print it, or to write a digest to [`DEFAULT_DIGEST_PATH`]
+    pub output: Option<PathBuf>,
+}
+
+impl ReportOptions {
+    /// Build the options from the `report` subcommand's matches
+    pub fn from_args(args: &ArgMatches) -> Result<Self, RuntimeError> {
+        let db_path = args
+            .get_one::<String>(OPTION_DB_PATH)
+            .map_or_else(default_db_path, PathBuf::from);
+
+        let format = match args.get_one::<String>(OPTION_FORMAT) {
+            Some(format) => ReportFormat::from_cli(format).ok_or_else(|| {
+                RuntimeError::InvalidOptions(format!(
+                    "Option --{OPTION_FORMAT} must be one of: markdown, html"
+                ))
+            })?,
+            None => ReportFormat::Markdown,
+        };
+
+        let digest = match args.get_one::<String>(OPTION_DIGEST) {
+            Some(period) => Some(DigestPeriod::from_cli(period).ok_or_else(|| {
+                RuntimeError::InvalidOptions(format!(
+                    "Option --{OPTION_DIGEST} must be one of: daily, weekly"
+                ))
+            })?),
+            None => None,
+        };
+
+        let start = args
+            .get_one::<String>(OPTION_START_DATE)
+            .map(|date| parse_local_date(date, OPTION_START_DATE))
+            .transpose()?;
+        let end = args
+            .get_one::<String>(OPTION_END_DATE)
+            .map(|date| parse_local_date(date, OPTION_END_DATE))
+            .transpose()?;
+        if digest.is_some() && (start.is_some() || end.is_some()) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option --{OPTION_DIGEST} cannot be used with --{OPTION_START_DATE} or --{OPTION_END_DATE}"
+            )));
+        }
+
+        Ok(ReportOptions {
+            state_db_path: args
+                .get_one::<String>(OPTION_STATE_DB)
+                .map_or_else(|| PathBuf::from(DEFAULT_STATE_DB_PATH), PathBuf::from),
+            platform: parse_platform(args, &db_path)?,
+            db_path,
+            format,
+            start: start.map(|date| date.timestamp()),
+            end: end.map(|date| date.timestamp()),
+            digest,
+            output: args.get_one::<String>(OPTION_OUTPUT).map(PathBuf::from),
+        })
+    }
+
+    /// Get the path to the database file itself, accounting for iOS backups
+    pub fn get_db_path(&self) -> PathBuf {
+        match self.platform {
+            Platform::iOS => self.db_path.join(DEFAULT_PATH_IOS),
+            Platform::macOS => self.db_path.clone(),
+        }
+    }
+}
+
+/// Options parsed from the `doctor` command
+#[derive(Debug)]
+pub struct DoctorOptions {
+    /// Path to the iMessage database, or the root of an iOS backup
+    pub db_path: PathBuf,
+    /// The platform the user selected, or `None` to detect it from the database path
+    pub platform: Option<Platform>,
+    /// Custom root directory for attachment files
+    pub attachment_root: Option<String>,
+    /// Path to the tracker's state database, which records detected deletions
+    pub state_db_path: PathBuf,
+    /// Path to the file the tracker logs detected deletions to
+    pub output: PathBuf,
+    /// Directory the `export` command writes to
+    pub export_path: PathBuf,
+}
+
+impl DoctorOptions {
+    /// Build the options from the `doctor` subcommand's matches
+    ///
+    /// Unlike the other commands, the platform is not detected here, so the checks can report why detection fails.
+    pub fn from_args(args: &ArgMatches) -> Result<Self, RuntimeError> {
+        let platform = match args.get_one::<String>(OPTION_PLATFORM) {
+            Some(platform) => Some(Platform::from_cli(platform).ok_or_else(|| {
+                RuntimeError::InvalidOptions(format!(
+                    "{platform} is not a valid platform, use macOS or iOS"
+                ))
+            })?),
+            None => None,
+        };
+
+        Ok(DoctorOptions {
+            db_path: args
+                .get_one::<String>(OPTION_DB_PATH)
+                .map_or_else(default_db_path, PathBuf::from),
+            platform,
+            attachment_root: args.get_one::<String>(OPTION_ATTACHMENT_ROOT).cloned(),
+            state_db_path: args
+                .get_one::<String>(OPTION_STATE_DB)
+                .map_or_else(|| PathBuf::from(DEFAULT_STATE_DB_PATH), PathBuf::from),
+            output: args
+                .get_one::<String>(OPTION_OUTPUT)
+                .map_or_else(|| PathBuf::from(DEFAULT_DELETIONS_PATH), PathBuf::from),
+            export_path: args
+                .get_one::<String>(OPTION_EXPORT_PATH)
+                .map_or_else(|| PathBuf::from(DEFAULT_EXPORT_PATH), PathBuf::from),
+        })
+    }
+
+    /// Get the path to the database file itself, for the platform the checks settled on
+    pub fn get_db_path(&self, platform: &Platform) -> PathBuf {
+        match platform {
+            Platform::iOS => self.db_path.join(DEFAULT_PATH_IOS),
+            Platform::macOS => self.db_path.clone(),
+        }
+    }
+}
+
+/// Use the platform the user selected, or detect it from the database path
+fn parse_platform(args: &ArgMatches, db_path: &Path) -> Result<Platform, RuntimeError> {
+    match args.get_one::<String>(OPTION_PLATFORM) {
+        Some(platform) => Platform::from_cli(platform).ok_or_else(|| {
+            RuntimeError::InvalidOptions(format!(
+                "{platform} is not a valid platform, use macOS or iOS"
+            ))
+        }),
+        None => Ok(Platform::determine(db_path)?),
+    }
+}
+
+/// Build the filters selected by the date options
+fn parse_query_context(args: &ArgMatches) -> Result<QueryContext, RuntimeError> {
+    let mut query_context = QueryContext::default();
+    if let Some(start) = args.get_one::<String>(OPTION_START_DATE) {
+        query_context.set_start_date(parse_date(start, OPTION_START_DATE)?);
+    }
+    if let Some(end) = args.get_one::<String>(OPTION_END_DATE) {
+        query_context.set_end_date(parse_date(end, OPTION_END_DATE)?);
+    }
+    Ok(query_context)
+}
+
+/// Convert a `YYYY-MM-DD` date in the local timezone to a timestamp in the iMessage epoch
+fn parse_date(date: &str, option: &str) -> Result<i64, RuntimeError> {
+    let local = parse_local_date(date, option)?;
+    Ok((local.timestamp() - get_offset()) * TIMESTAMP_FACTOR)
+}
+
+/// Convert a `YYYY-MM-DD` date to midnight in the local timezone
+fn parse_local_date(date: &str, option: &str) -> Result<DateTime<Local>, RuntimeError> {
+    let invalid = || {
+        RuntimeError::InvalidOptions(format!(
+            "Option --{option} value {date} is not a valid YYYY-MM-DD date"
+        ))
+    };
+    let midnight = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| invalid())?
+        .and_hms_opt(0, 0, 0)
+        .ok_or_else(invalid)?;
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .ok_or_else(invalid)
+}
+
+/// Build the `export` subcommand
+pub fn export_command() -> Command {
+    Command::new("export")
+        .about("Export conversations from the iMessage database")
+        .arg(
+            Arg::new(OPTION_FORMAT)
+                .short('f')
+                .long(OPTION_FORMAT)
+                .help("The format to export messages to")
+                .value_name("FORMAT")
+                .required(true),
+        )
+        .arg(
+            Arg::new(OPTION_DB_PATH)
+                .short('p')
+                .long(OPTION_DB_PATH)
+                .help("Path to the iMessage database or the root of an iOS backup")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new(OPTION_EXPORT_PATH)
+                .short('o')
+                .long(OPTION_EXPORT_PATH)
+                .help("Directory to write exported files to")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new(OPTION_ATTACHMENT_ROOT)
+                .short('r')
+                .long(OPTION_ATTACHMENT_ROOT)
+                .help("Custom root directory for attachment files")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new(OPTION_PLATFORM)
+                .short('a')
+                .long(OPTION_PLATFORM)
+                .help("The platform that created the database, macOS or iOS")
+                .value_name("PLATFORM"),
+        )
+        .arg(
+            Arg::new(OPTION_START_DATE)
+                .short('s')
+                .long(OPTION_START_DATE)
+                .help("Only export messages sent on or after this date")
+                .value_name("YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::new(OPTION_END_DATE)
+                .short('e')
+                .long(OPTION_END_DATE)
+                .help("Only export messages sent before this date")
+                .value_name("YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::new(OPTION_CUSTOM_NAME)
+                .short('m')
+                .long(OPTION_CUSTOM_NAME)
+                .help("Name used for messages sent by the database owner")
+                .value_name("NAME"),
+        )
+        .arg(
+            Arg::new(OPTION_STATE_DB)
+                .long(OPTION_STATE_DB)
+                .help("Path to the tracker state database, used for the deletions table in csv and parquet exports")
+                .value_name("PATH"),
+        )
+}
+
+/// Build the `import` subcommand
+pub fn import_command() -> Command {
+    Command::new("import")
+        .about("Import an SMS Backup & Restore XML file into an archive that can be exported")
+        .arg(
+            Arg::new(OPTION_INPUT)
+                .short('i')
+                .long(OPTION_INPUT)
+                .help("Path to the XML file to import")
+                .value_name("PATH")
+                .required(true),
+        )
+        .arg(
+            Arg::new(OPTION_DB_PATH)
+                .short('p')
+                .long(OPTION_DB_PATH)
+                .help("Path to the archive to import into, which is created if it does not exist")
+                .value_name("PATH"),
+        )
+}
+
+#[cfg(feature = "simulate")]
+/// Build the `simulate` subcommand
+pub fn simulate_command() -> Command {
+    Command::new("simulate")
+        .about("Create a database and change it like Messages.app would, following a scripted scenario")
+        .arg(
+            Arg::new(OPTION_SCENARIO)
+                .short('s')
+                .long(OPTION_SCENARIO)
+                .help("Path to the JSON scenario to run")
+                .value_name("PATH")
+                .required(true),
+        )
+        .arg(
+            Arg::new(OPTION_DB_PATH)
+                .short('p')
+                .long(OPTION_DB_PATH)
+                .help("Path to the database to create, which must not exist")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new(OPTION_EXPECTED)
+                .short('e')
+                .long(OPTION_EXPECTED)
+                .help("Path to write the deletions the tracker is expected to report, as JSON")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new(OPTION_INSTANT)
+                .long(OPTION_INSTANT)
+                .help("Apply every step immediately instead of waiting between steps")
+                .action(ArgAction::SetTrue),
+        )
+}
+
+/// Build the `analyze` subcommand
+pub fn analyze_command() -> Command {
+    Command::new("analyze")
+        .about("Compute response times, activity, and message counts per chat and per contact, as JSON")
+        .arg(
+            Arg::new(OPTION_DB_PATH)
+                .short('p')
+                .long(OPTION_DB_PATH)
+                .help("Path to the iMessage database or the root of an iOS backup")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new(OPTION_PLATFORM)
+                .short('a')
+                .long(OPTION_PLATFORM)
+                .help("The platform that created the database, macOS or iOS")
+                .value_name("PLATFORM"),
+        )
+        .arg(
+            Arg::new(OPTION_START_DATE)
+                .short('s')
+                .long(OPTION_START_DATE)
+                .help("Only analyze messages sent on or after this date")
+                .value_name("YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::new(OPTION_END_DATE)
+                .short('e')
+                .long(OPTION_END_DATE)
+                .help("Only analyze messages sent before this date")
+                .value_name("YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::new(OPTION_OUTPUT)
+                .short('o')
+                .long(OPTION_OUTPUT)
+                .help("Path to write the statistics to, instead of printing them")
+                .value_name("PATH"),
+        )
+}
+
+/// Build the `report` subcommand
+pub fn report_command() -> Command {
+    Command::new("report")
+        .about("Summarize the deletions the tracker detected, by contact, chat, type, and time of day")
+        .arg(
+            Arg::new(OPTION_FORMAT)
+                .short('f')
+                .long(OPTION_FORMAT)
+                .help("The format to write the report in, markdown or html")
+                .value_name("FORMAT"),
+        )
+        .arg(
+            Arg::new(OPTION_STATE_DB)
+                .long(OPTION_STATE_DB)
+                .help("Path to the tracker state database")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new(OPTION_DB_PATH)
+                .short('p')
+                .long(OPTION_DB_PATH)
+                .help("Path to the iMessage database or the root of an iOS backup, used to name chats and find when messages were sent")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new(OPTION_PLATFORM)
+                .short('a')
+                .long(OPTION_PLATFORM)
+                .help("The platform that created the database, macOS or iOS")
+                .value_name("PLATFORM"),
+        )
+        .arg(
+            Arg::new(OPTION_START_DATE)
+                .short('s')
+                .long(OPTION_START_DATE)
+                .help("Only report deletions detected on or after this date")
+                .value_name("YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::new(OPTION_END_DATE)
+                .short('e')
+                .long(OPTION_END_DATE)
+                .help("Only report deletions detected before this date")
+                .value_name("YYYY-MM-DD"),
+        )
+        .arg(
+            Arg::new(OPTION_DIGEST)
+                .short('d')
+                .long(OPTION_DIGEST)
+                .help("Write a digest of the last day or week, daily or weekly, to a file named for today's date")
+                .value_name("PERIOD"),
+        )
+        .arg(
+            Arg::new(OPTION_OUTPUT)
+                .short('o')
+                .long(OPTION_OUTPUT)
+                .help("Path to write the report to, instead of printing it")
+                .value_name("PATH"),
+        )
+}
+
+/// Build the `doctor` subcommand
+pub fn doctor_command() -> Command {
+    Command::new("doctor")
+        .about("Check that the database, permissions, and outputs are ready before watching for deletions")
+        .arg(
+            Arg::new(OPTION_DB_PATH)
+                .short('p')
+                .long(OPTION_DB_PATH)
+                .help("Path to the iMessage database or the root of an iOS backup")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new(OPTION_PLATFORM)
+                .short('a')
+                .long(OPTION_PLATFORM)
+                .help("The platform that created the database, macOS or iOS")
+                .value_name("PLATFORM"),
+        )
+        .arg(
+            Arg::new(OPTION_ATTACHMENT_ROOT)
+                .short('r')
+                .long(OPTION_ATTACHMENT_ROOT)
+                .help("Custom root directory for attachment files")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new(OPTION_STATE_DB)
+                .long(OPTION_STATE_DB)
+                .help("Path to the tracker state database")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new(OPTION_OUTPUT)
+                .short('o')
+                .long(OPTION_OUTPUT)
+                .help("Path to the file the tracker logs detected deletions to")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new(OPTION_EXPORT_PATH)
+                .long(OPTION_EXPORT_PATH)
+                .help("Directory the export command writes to")
+                .value_name("PATH"),
+        )
+}
@@ -0,0 +1,242 @@
+/*!
+Database caches and helpers shared by every exporter
+*/
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::{copy, create_dir_all},
+    path::{Path, PathBuf},
+};
+
+use rusqlite::Connection;
+use tracing::{info, warn};
+
+use imessage_database::{
+    tables::{
+        attachment::Attachment,
+        chat::Chat,
+        chat_handle::ChatToHandle,
+        handle::Handle,
+        messages::Message,
+        schema::SchemaCapabilities,
+        table::{ATTACHMENTS_DIR, Cacheable, Deduplicate, ME, UNKNOWN, get_connection},
+    },
+    util::dates::get_offset,
+};
+
+use crate::{
+    app::{error::RuntimeError, export_type::ExportType, options::Options},
+    exporters::{
+        csv::CSV,
+        email::{Email, MailFormat},
+        html::HTML,
+        json::JSON,
+        locations::{LocationFormat, Locations},
+        parquet::Parquet,
+        sms_backup::SMSBackup,
+        txt::TXT,
+    },
+};
+
+/// Maximum length of a conversation's file name, before the extension
+const MAX_FILENAME_LENGTH: usize = 96;
+
+/// Stores the application state and handles application lifecycle
+pub struct Config {
+    /// Map of chatroom ID to chatroom information
+    pub chatrooms: HashMap<i32, Chat>,
+    /// Map of chatroom ID to an internal unique chatroom ID
+    pub real_chatrooms: HashMap<i32, i32>,
+    /// Map of chatroom ID to chatroom participants
+    pub chatroom_participants: HashMap<i32, BTreeSet<i32>>,
+    /// Map of participant ID to contact info
+    pub participants: HashMap<i32, String>,
+    /// Map of participant ID to an internal unique participant ID
+    pub real_participants: HashMap<i32, i32>,
+    /// Messages that are tapbacks to other messages
+    pub tapbacks: HashMap<String, HashMap<usize, Vec<Message>>>,
+    /// App configuration options
+    pub options: Options,
+    /// Global date offset used by the iMessage database
+    pub offset: i64,
+    /// The connection we use to query the database
+    db: Connection,
+    /// The columns and tables present in the database, probed when it is opened
+    capabilities: SchemaCapabilities,
+}
+
+impl Config {
+    /// Connect to the database and build the caches used by the exporters
+    pub fn new(options: Options) -> Result<Self, RuntimeError> {
+        let db = get_connection(&options.get_db_path())?;
+        let capabilities = SchemaCapabilities::probe(&db)?;
+
+        info!("Building cache...");
+        let chatroom_participants = ChatToHandle::cache(&db)?;
+        let participants = Handle::cache(&db)?;
+
+        Ok(Config {
+            chatrooms: Chat::cache(&db)?,
+            real_chatrooms: ChatToHandle::dedupe(&chatroom_participants),
+            chatroom_participants,
+            real_participants: Handle::dedupe(&participants),
+            participants,
+            tapbacks: Message::cache(&db)?,
+            options,
+            offset: get_offset(),
+            db,
+            capabilities,
+        })
+    }
+
+    /// Get a reference to the database connection
+    pub fn db(&self) -> &Connection {
+        &self.db
+    }
+
+    /// Get the schema capabilities of the database, used to build its queries
+    pub fn capabilities(&self) -> &SchemaCapabilities {
+        &self.capabilities
+    }
+
+    /// Run the exporter selected by the options
+    pub fn start(&self) -> Result<(), RuntimeError> {
+        create_dir_all(&self.options.export_path)?;
+        match self.options.export_type {
+            ExportType::Json => JSON::new(self).iter_messages()?,
+            ExportType::Txt => TXT::new(self)?.iter_messages()?,
+            ExportType::Html => HTML::new(self)?.iter_messages()?,
+            ExportType::Csv => CSV::new(self)?.iter_messages()?,
+            ExportType::Parquet => Parquet::new(self)?.iter_messages()?,
+            ExportType::SmsBackup => SMSBackup::new(self)?.iter_messages()?,
+            ExportType::Mbox => Email::new(self, MailFormat::Mbox).iter_messages()?,
+            ExportType::Eml => Email::new(self, MailFormat::Eml).iter_messages()?,
+            ExportType::GeoJson => Locations::new(self, LocationFormat::GeoJson).iter_messages()?,
+            ExportType::Kml => Locations::new(self, LocationFormat::Kml).iter_messages()?,
+        }
+        info!("Exported to {}", self.options.export_path.display());
+        Ok(())
+    }
+
+    /// Get the chatroom and deduplicated chatroom ID a message belongs to, if any
+    pub fn conversation(&self, message: &Message) -> Option<(&Chat, &i32)> {
+        let chat_id = message.chat_id.or(message.deleted_from)?;
+        Some((
+            self.chatrooms.get(&chat_id)?,
+            self.real_chatrooms.get(&chat_id)?,
+        ))
+    }
+
+    /// Get the deduplicated participants of a chatroom, sorted by their deduplicated ID
+    pub fn chatroom_participants(&self, chat_id: i32) -> Vec<(i32, &str)> {
+        let mut seen = BTreeSet::new();
+        self.chatroom_participants
+            .get(&chat_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|handle_id| {
+                let real_id = *self.real_participants.get(handle_id)?;
+                seen.insert(real_id).then(|| {
+                    (
+                        real_id,
+                        self.participants
+                            .get(handle_id)
+                            .map_or(UNKNOWN, String::as_str),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Get the name of the sender of a message
+    pub fn who(&self, handle_id: Option<i32>, is_from_me: bool) -> &str {
+        if is_from_me {
+            return self.options.custom_name.as_deref().unwrap_or(ME);
+        }
+        handle_id
+            .and_then(|id| self.participants.get(&id))
+            .map_or(UNKNOWN, String::as_str)
+    }
+
+    /// Generate a file name for a chatroom, without an extension
+    ///
+    /// The name uses the chat's display name or its participants, followed by the deduplicated chat ID
+    /// so conversations that share a name do not overwrite each other.
+    pub fn filename(&self, chatroom: &Chat, real_id: i32) -> String {
+        let name = match chatroom.display_name() {
+            Some(name) => name.to_string(),
+            None => self
+                .chatroom_participants(chatroom.rowid)
+                .into_iter()
+                .map(|(_, name)| name)
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+        let name = if name.is_empty() {
+            chatroom.chat_identifier.as_str()
+        } else {
+            name.as_str()
+        };
+        format!("{} - {real_id}", sanitize_filename(name))
+    }
+
+    /// Copy an attachment into the export's attachment directory
+    ///
+    /// Returns the path of the copy relative to the export directory, or `None` if the file is missing.
+    pub fn copy_attachment(
+        &self,
+        attachment: &mut Attachment,
+        message: &Message,
+    ) -> Option<PathBuf> {
+        let source = attachment.resolved_attachment_path(
+            &self.options.platform,
+            &self.options.db_path,
+            self.options.attachment_root.as_deref(),
+        )?;
+        let source = Path::new(&source);
+        if !source.is_file() {
+            warn!("Attachment not found at {}", source.display());
+            return None;
+        }
+
+        let file_name = attachment
+            .filename()
+            .and_then(|name| Path::new(name).file_name())
+            .map_or_else(
+                || format!("{}", attachment.rowid),
+                |name| name.to_string_lossy().to_string(),
+            );
+        // Attachments of one message can share a name, so each one gets its own directory
+        let relative = PathBuf::from(ATTACHMENTS_DIR)
+            .join(message.rowid.to_string())
+            .join(attachment.rowid.to_string())
+            .join(sanitize_filename(&file_name));
+        let destination = self.options.export_path.join(&relative);
+
+        if let Some(parent) = destination.parent() {
+            if let Err(why) = create_dir_all(parent) {
+                warn!("Unable to create {}: {why}", parent.display());
+                return None;
+            }
+        }
+        if let Err(why) = copy(source, &destination) {
+            warn!("Unable to copy {}: {why}", source.display());
+            return None;
+        }
+
+        attachment.copied_path = Some(destination);
+        Some(relative)
+    }
+}
+
+/// Remove characters that are not allowed in file names and limit the length
+pub(crate) fn sanitize_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(MAX_FILENAME_LENGTH)
+        .collect()
+}
@@ -2,12 +2,14 @@
 Persistent state management for tracking message fingerprints and deletions
 */
 
-use rusqlite::{Connection, Result as SqliteResult};
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags, Result as SqliteResult};
 use serde::{Deserialize, Serialize};
 use tracing::{info, debug};
 use blake3;
 
-use crate::core::config::StateConfig;
+use crate::core::config::{StateConfig, TrackerConfig};
 
 /// Represents a message fingerprint for deletion detection
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,6 +54,16 @@ impl StateManager {
         Ok(manager)
     }
 
+    /// Open an existing state database to read its records, without creating tables or removing old records
+    pub fn open_read_only(state_db_path: &Path) -> SqliteResult<Self> {
+        let conn = Connection::open_with_flags(state_db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let config = StateConfig {
+            state_db_path: state_db_path.to_path_buf(),
+            ..TrackerConfig::default().state
+        };
+        Ok(Self { config, conn })
+    }
+
     /// Initialize the database schema
     async fn initialize_schema(&self) -> SqliteResult<()> {
         self.conn.execute_batch(r#"
@@ -161,18 +173,20 @@ impl StateManager {
         Ok(deletion_id)
     }
 
-    /// Get all deletion records within a time range
-    pub async fn get_deletions_in_range(&self, start_time: i64, end_time: i64) -> Result<Vec<DeletionRecord>, Box<dyn std::error::Error>> {
+    /// Get all deletion records within a time range, inclusive, newest first
+    ///
+    /// This only reads from the state database, so it can be used outside of the tracker's runtime.
+    pub fn get_deletions_in_range(&self, start_time: i64, end_time: i64) -> SqliteResult<Vec<DeletionRecord>> {
         let mut stmt = self.conn.prepare(
             "SELECT id, message_id, original_fingerprint, deletion_timestamp, deletion_type, recovered_content, recovered_attachments
              FROM deletion_records 
              WHERE deletion_timestamp BETWEEN ?1 AND ?2
-             ORDER BY deletion_timestamp DESC"
+             ORDER BY deletion_timestamp DESC, id DESC"
         )?;
 
         let rows = stmt.query_map([start_time, end_time], |row| {
             let fingerprint_json: String = row.get(2)?;
-            let attachments_json: String = row.get(7)?;
+            let attachments_json: String = row.get(6)?;
             
             let original_fingerprint: MessageFingerprint = serde_json::from_str(&fingerprint_json)
                 .map_err(|_e| rusqlite::Error::InvalidColumnType(2, "fingerprint".to_string(), rusqlite::types::Type::Text))?;
@@ -187,7 +201,7 @@ impl StateManager {
                 deletion_timestamp: row.get(3)?,
                 deletion_type: row.get(4)?,
 
-                recovered_content: row.get(6)?,
+                recovered_content: row.get(5)?,
                 recovered_attachments,
             })
         })?;
@@ -263,3 +277,89 @@ impl StateManager {
         blake3::hash(input.as_bytes()).to_hex().to_string()
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all},
+        path::{Path, PathBuf},
+    };
+
+    use crate::core::{
+        config::StateConfig,
+        state_manager::{DeletionRecord, MessageFingerprint, StateManager},
+    };
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-state-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Write a state database with a deletion detected at each of `timestamps`, for messages numbered from 1
+    pub(crate) fn state_db(path: &Path, timestamps: &[i64]) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let manager = StateManager::new(StateConfig {
+                state_db_path: path.to_path_buf(),
+                retention_days: 30,
+                enable_compression: false,
+            })
+            .await
+            .unwrap();
+            for (idx, &timestamp) in timestamps.iter().enumerate() {
+                manager
+                    .store_deletion(&deletion(idx as i32 + 1, timestamp))
+                    .await
+                    .unwrap();
+            }
+        });
+    }
+
+    fn deletion(message_id: i32, timestamp: i64) -> DeletionRecord {
+        DeletionRecord {
+            id: 0,
+            message_id,
+            original_fingerprint: MessageFingerprint {
+                message_id,
+                content_hash: format!("hash{message_id}"),
+                attachment_hashes: vec![],
+                timestamp: timestamp - 60,
+                conversation_id: Some(1),
+                sender_handle: Some("+15558675309".to_string()),
+            },
+            deletion_timestamp: timestamp,
+            deletion_type: "FullMessage".to_string(),
+            recovered_content: Some("Hello".to_string()),
+            recovered_attachments: vec!["a.png".to_string()],
+        }
+    }
+
+    #[test]
+    fn can_get_deletions_in_range() {
+        let dir = temp("range");
+        let path = dir.join("tracker_state.db");
+        state_db(&path, &[300, 100, 200, 400]);
+        let manager = StateManager::open_read_only(&path).unwrap();
+
+        let deletions = manager.get_deletions_in_range(200, 300).unwrap();
+        let ids: Vec<i32> = deletions.iter().map(|deletion| deletion.message_id).collect();
+        assert_eq!(ids, [1, 3]);
+
+        let deletion = &deletions[0];
+        assert_eq!(deletion.deletion_timestamp, 300);
+        assert_eq!(deletion.deletion_type, "FullMessage");
+        assert_eq!(deletion.original_fingerprint.content_hash, "hash1");
+        assert_eq!(deletion.original_fingerprint.timestamp, 240);
+        assert_eq!(deletion.recovered_content.as_deref(), Some("Hello"));
+        assert_eq!(deletion.recovered_attachments, ["a.png"]);
+
+        assert!(manager.get_deletions_in_range(500, 600).unwrap().is_empty());
+
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,761 @@
+/*!
+Checks that the environment and database are ready to watch, for the `doctor` command
+
+Each check reports whether it passed and, when it did not, what the user can do about it. Checks that need a
+readable database are skipped when it cannot be opened, so one problem is not reported several times.
+
+Every path the checks read or write comes from [`DoctorOptions`], so they can be pointed at a fixture directory,
+like one created by the `simulate` command, as easily as at the live Messages database.
+*/
+
+use std::{
+    fmt::{Display, Formatter, Result},
+    fs::{File, OpenOptions, metadata, read_dir, remove_file},
+    io::{ErrorKind, Read},
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{Connection, OpenFlags};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{
+        attachment::DEFAULT_ATTACHMENT_ROOT,
+        diagnostic::SchemaGeneration,
+        schema::{SchemaCapabilities, SchemaFeature},
+        table::{MESSAGE, get_connection},
+    },
+    util::{
+        dirs::{default_db_path, home},
+        platform::Platform,
+        size::format_file_size,
+    },
+};
+
+use crate::{
+    DeletionEvent,
+    app::options::{
+        DEFAULT_DIGEST_PATH, DoctorOptions, OPTION_ATTACHMENT_ROOT, OPTION_DB_PATH,
+        OPTION_EXPORT_PATH, OPTION_OUTPUT, OPTION_PLATFORM, OPTION_STATE_DB,
+    },
+};
+
+/// The first bytes of every SQLite database file
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+/// Remediation for files that macOS does not let the terminal read
+const FULL_DISK_ACCESS: &str = "Grant Full Disk Access to your terminal emulator in System Settings > Privacy & Security > Full Disk Access, then restart it";
+
+/// The outcome of a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    /// The tracker can run, but may miss data
+    Warn,
+    /// The tracker cannot run until this is fixed
+    Fail,
+    /// The check depends on another check that failed
+    Skip,
+}
+
+impl Display for Status {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            Status::Pass => write!(fmt, "[ ok ]"),
+            Status::Warn => write!(fmt, "[warn]"),
+            Status::Fail => write!(fmt, "[FAIL]"),
+            Status::Skip => write!(fmt, "[skip]"),
+        }
+    }
+}
+
+/// The result of one check
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    /// What the check found
+    pub detail: String,
+    /// What the user can do to fix a warning or failure
+    pub remedy: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: String) -> Self {
+        Check {
+            name,
+            status: Status::Pass,
+            detail,
+            remedy: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: String, remedy: String) -> Self {
+        Check {
+            name,
+            status: Status::Warn,
+            detail,
+            remedy: Some(remedy),
+        }
+    }
+
+    fn fail(name: &'static str, detail: String, remedy: String) -> Self {
+        Check {
+            name,
+            status: Status::Fail,
+            detail,
+            remedy: Some(remedy),
+        }
+    }
+
+    fn skip(name: &'static str, detail: &str) -> Self {
+        Check {
+            name,
+            status: Status::Skip,
+            detail: detail.to_string(),
+            remedy: None,
+        }
+    }
+}
+
+impl Display for Check {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        write!(fmt, "{} {}: {}", self.status, self.name, self.detail)?;
+        if let Some(remedy) = &self.remedy {
+            write!(fmt, "\n       -> {remedy}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every check, in the order the tracker depends on them
+pub fn run(options: &DoctorOptions) -> Vec<Check> {
+    let (platform_check, platform) = check_platform(options);
+    let db_file = options.get_db_path(&platform);
+    let readable = check_readable(&db_file, &options.db_path);
+    let conn = match readable.status {
+        Status::Fail => None,
+        _ => get_connection(&db_file).ok(),
+    };
+
+    let mut checks = vec![platform_check, readable];
+    match &conn {
+        Some(conn) => {
+            checks.push(check_wal(conn, &db_file, &platform));
+            checks.push(check_schema(conn));
+        }
+        None => {
+            checks.push(Check::skip("WAL", "the database cannot be read"));
+            checks.push(Check::skip("Schema", "the database cannot be read"));
+        }
+    }
+    checks.push(check_attachments(options, &platform));
+    checks.push(check_state_db(&options.state_db_path));
+    checks.push(check_deletion_log(&options.output));
+    checks.push(check_output_dir(
+        "Export directory",
+        &options.export_path,
+        &format!("--{OPTION_EXPORT_PATH}"),
+    ));
+    checks.push(check_output_dir(
+        "Digest directory",
+        Path::new(DEFAULT_DIGEST_PATH),
+        &format!("report --{OPTION_OUTPUT}"),
+    ));
+    checks
+}
+
+/// Detect the platform from the database path, and compare it to the platform the user selected
+fn check_platform(options: &DoctorOptions) -> (Check, Platform) {
+    const NAME: &str = "Platform";
+    let detected = match Platform::determine(&options.db_path) {
+        Ok(platform) => platform,
+        Err(why) => {
+            let fallback = options.platform.unwrap_or_default();
+            return (
+                Check::fail(
+                    NAME,
+                    why.to_string(),
+                    format!(
+                        "Pass the root of the iOS backup with --{OPTION_DB_PATH}, not the database inside it"
+                    ),
+                ),
+                fallback,
+            );
+        }
+    };
+
+    // A missing path is detected as macOS, which the readable check reports
+    let exists = options.db_path.exists();
+    match options.platform {
+        Some(selected) if exists && selected != detected => (
+            Check::warn(
+                NAME,
+                format!(
+                    "{selected} was selected, but {} looks like a {detected} database",
+                    options.db_path.display()
+                ),
+                format!(
+                    "Remove --{OPTION_PLATFORM} to detect the platform, or pass --{OPTION_PLATFORM} {detected}"
+                ),
+            ),
+            selected,
+        ),
+        selected => {
+            let platform = selected.unwrap_or(detected);
+            let detail = match platform {
+                Platform::macOS => format!("macOS database at {}", options.db_path.display()),
+                Platform::iOS => format!("iOS backup at {}", options.db_path.display()),
+            };
+            (Check::pass(NAME, detail), platform)
+        }
+    }
+}
+
+/// Read the database file, telling a missing file apart from one macOS does not let the terminal read
+fn check_readable(db_file: &Path, db_path: &Path) -> Check {
+    const NAME: &str = "Database";
+    let display = db_file.display();
+
+    let size = match metadata(db_file) {
+        Ok(meta) if meta.is_dir() => {
+            return Check::fail(
+                NAME,
+                format!("{display} is a directory, not a database"),
+                format!(
+                    "Pass the path to chat.db with --{OPTION_DB_PATH}, or pass --{OPTION_PLATFORM} iOS for the root of an iOS backup"
+                ),
+            );
+        }
+        Ok(meta) => meta.len(),
+        Err(why) if why.kind() == ErrorKind::NotFound => {
+            let remedy = if db_path == default_db_path() {
+                format!(
+                    "Sign in to Messages on this Mac, or pass the path to a database with --{OPTION_DB_PATH}"
+                )
+            } else {
+                format!("Check the path passed with --{OPTION_DB_PATH}")
+            };
+            return Check::fail(NAME, format!("No database at {display}"), remedy);
+        }
+        Err(why) if why.kind() == ErrorKind::PermissionDenied => {
+            return Check::fail(
+                NAME,
+                format!("Not allowed to read {display}"),
+                FULL_DISK_ACCESS.to_string(),
+            );
+        }
+        Err(why) => {
+            return Check::fail(
+                NAME,
+                format!("Unable to read {display}: {why}"),
+                format!("Check the path passed with --{OPTION_DB_PATH}"),
+            );
+        }
+    };
+
+    let mut header = [0; SQLITE_HEADER.len()];
+    match File::open(db_file).and_then(|mut file| file.read_exact(&mut header)) {
+        Err(why) if why.kind() == ErrorKind::PermissionDenied => {
+            return Check::fail(
+                NAME,
+                format!("Not allowed to read {display}"),
+                FULL_DISK_ACCESS.to_string(),
+            );
+        }
+        // Files shorter than the header are not databases either
+        Err(why) if why.kind() != ErrorKind::UnexpectedEof => {
+            return Check::fail(
+                NAME,
+                format!("Unable to read {display}: {why}"),
+                format!("Check the path passed with --{OPTION_DB_PATH}"),
+            );
+        }
+        _ if &header != SQLITE_HEADER => {
+            return Check::fail(
+                NAME,
+                format!("{display} is not a SQLite database"),
+                "Pass the path to chat.db; encrypted iOS backups cannot be read, so create an unencrypted backup instead".to_string(),
+            );
+        }
+        _ => {}
+    }
+
+    let count = get_connection(db_file).and_then(|conn| {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {MESSAGE}"), [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .map_err(TableError::Messages)
+    });
+    match count {
+        Ok(count) => Check::pass(
+            NAME,
+            format!("{count} messages in {display} ({})", format_file_size(size)),
+        ),
+        Err(why) => Check::fail(
+            NAME,
+            format!("Unable to query {display}: {why}"),
+            format!(
+                "If the database is in WAL mode, the directory that contains it must be writable, or the -shm file must exist; otherwise, {}",
+                FULL_DISK_ACCESS.to_lowercase()
+            ),
+        ),
+    }
+}
+
+/// Check that the database uses a write-ahead log, which is how the tracker sees changes as Messages makes them
+fn check_wal(conn: &Connection, db_file: &Path, platform: &Platform) -> Check {
+    const NAME: &str = "WAL";
+    if *platform == Platform::iOS {
+        return Check::skip(NAME, "iOS backups are a snapshot and are not watched live");
+    }
+
+    let mode = conn
+        .query_row("PRAGMA journal_mode", [], |row| row.get::<_, String>(0))
+        .unwrap_or_default()
+        .to_lowercase();
+    if mode != "wal" {
+        return Check::warn(
+            NAME,
+            format!("The journal mode is {mode}, but Messages uses WAL"),
+            format!(
+                "This is expected for a copy of the database, but changes made in Messages will not appear in it; pass the live database with --{OPTION_DB_PATH} to watch for deletions"
+            ),
+        );
+    }
+
+    let sidecar = |suffix: &str| {
+        let mut path = db_file.as_os_str().to_owned();
+        path.push(suffix);
+        PathBuf::from(path)
+    };
+    let missing: Vec<String> = ["-wal", "-shm"]
+        .into_iter()
+        .filter(|suffix| !sidecar(suffix).is_file())
+        .map(|suffix| format!("{}{suffix}", db_file.display()))
+        .collect();
+    if missing.is_empty() {
+        let wal_size = metadata(sidecar("-wal")).map_or(0, |meta| meta.len());
+        Check::pass(
+            NAME,
+            format!(
+                "WAL mode is active, with {} in the write-ahead log",
+                format_file_size(wal_size)
+            ),
+        )
+    } else {
+        Check::warn(
+            NAME,
+            format!("WAL mode is active, but {} is missing", missing.join(" and ")),
+            "Open Messages so it keeps the write-ahead log open; if you copied the database, copy its -wal and -shm files with it".to_string(),
+        )
+    }
+}
+
+/// Find the newest message query the database supports
+fn check_schema(conn: &Connection) -> Check {
+    const NAME: &str = "Schema";
+    const UNSUPPORTED: &str = "The database schema is not supported; please open an issue that includes your macOS or iOS version";
+    let capabilities = match SchemaCapabilities::probe(conn) {
+        Ok(capabilities) => capabilities,
+        Err(why) => return Check::fail(NAME, why.to_string(), UNSUPPORTED.to_string()),
+    };
+    let list = |features: &[SchemaFeature], separator: &str| {
+        features
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>()
+            .join(separator)
+    };
+
+    match SchemaGeneration::from(&capabilities) {
+        SchemaGeneration::Unknown => Check::fail(
+            NAME,
+            "The database does not have the tables messages are read from".to_string(),
+            UNSUPPORTED.to_string(),
+        ),
+        generation => {
+            // The tracker compares `date_edited` and `date_retracted` to detect edits and unsends
+            let missing = [SchemaFeature::Edits, SchemaFeature::Unsends]
+                .into_iter()
+                .filter(|feature| !capabilities.supports(*feature))
+                .collect::<Vec<SchemaFeature>>();
+            if missing.is_empty() {
+                Check::pass(
+                    NAME,
+                    format!(
+                        "{generation}, with {}",
+                        list(&capabilities.features(), ", ")
+                    ),
+                )
+            } else {
+                Check::fail(
+                    NAME,
+                    format!("{generation} does not record {}", list(&missing, " or ")),
+                    "The tracker needs macOS Ventura or iOS 16 and newer; the export and analyze commands still work with this database".to_string(),
+                )
+            }
+        }
+    }
+}
+
+/// Check that the directory attachment files are read from exists and can be listed
+fn check_attachments(options: &DoctorOptions, platform: &Platform) -> Check {
+    const NAME: &str = "Attachments";
+    let root = match platform {
+        // iOS backups store attachments alongside the database, named by their hash
+        Platform::iOS => options.db_path.clone(),
+        Platform::macOS => {
+            let root = options
+                .attachment_root
+                .as_deref()
+                .unwrap_or(DEFAULT_ATTACHMENT_ROOT);
+            match root.strip_prefix('~') {
+                Some(rest) => PathBuf::from(format!("{}{rest}", home())),
+                None => PathBuf::from(root),
+            }
+        }
+    };
+
+    match read_dir(&root) {
+        Ok(_) => Check::pass(NAME, format!("{} is readable", root.display())),
+        Err(why) if why.kind() == ErrorKind::PermissionDenied => Check::fail(
+            NAME,
+            format!("Not allowed to read {}", root.display()),
+            FULL_DISK_ACCESS.to_string(),
+        ),
+        Err(why) if why.kind() == ErrorKind::NotFound => Check::warn(
+            NAME,
+            format!("No attachments directory at {}", root.display()),
+            format!(
+                "Deleted attachments cannot be recovered without it; if attachments are stored elsewhere, pass their directory with --{OPTION_ATTACHMENT_ROOT}"
+            ),
+        ),
+        Err(why) => Check::warn(
+            NAME,
+            format!("Unable to read {}: {why}", root.display()),
+            format!("Pass the attachments directory with --{OPTION_ATTACHMENT_ROOT}"),
+        ),
+    }
+}
+
+/// Check that the tracker can write deletion records to its state database
+fn check_state_db(path: &Path) -> Check {
+    const NAME: &str = "State database";
+    let remedy = format!(
+        "Make {} writable, or choose another location with --{OPTION_STATE_DB}",
+        path.display()
+    );
+
+    if !path.exists() {
+        let parent = parent_dir(path);
+        return match probe_dir(parent) {
+            Ok(()) => Check::pass(
+                NAME,
+                format!(
+                    "{} will be created when the first deletion is recorded",
+                    path.display()
+                ),
+            ),
+            Err(why) => Check::fail(
+                NAME,
+                format!("Unable to create files in {}: {why}", parent.display()),
+                remedy,
+            ),
+        };
+    }
+
+    // Take the write lock without changing anything, to show that writes would succeed
+    let writable =
+        Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE).and_then(|conn| {
+            conn.execute_batch("BEGIN IMMEDIATE; ROLLBACK;")
+                .map(|()| conn)
+        });
+    match writable {
+        Ok(conn) => {
+            match conn.query_row("SELECT COUNT(*) FROM deletion_records", [], |row| {
+                row.get::<_, i64>(0)
+            }) {
+                Ok(count) => Check::pass(
+                    NAME,
+                    format!(
+                        "{} is writable and has {count} deletion records",
+                        path.display()
+                    ),
+                ),
+                Err(_) => Check::warn(
+                    NAME,
+                    format!(
+                        "{} is writable, but has no deletion records table",
+                        path.display()
+                    ),
+                    format!(
+                        "Check that --{OPTION_STATE_DB} points to the tracker's state database, not another file"
+                    ),
+                ),
+            }
+        }
+        Err(why) => Check::fail(
+            NAME,
+            format!("Unable to write to {}: {why}", path.display()),
+            remedy,
+        ),
+    }
+}
+
+/// Check that the tracker can append to the file it logs deletions to
+fn check_deletion_log(path: &Path) -> Check {
+    const NAME: &str = "Deletion log";
+    let remedy = format!(
+        "Make {} writable, or choose another file with --{OPTION_OUTPUT}",
+        path.display()
+    );
+
+    if !path.exists() {
+        let ancestor = existing_ancestor(path);
+        return match probe_dir(ancestor) {
+            Ok(()) => Check::pass(
+                NAME,
+                format!(
+                    "{} will be created when the first deletion is detected",
+                    path.display()
+                ),
+            ),
+            Err(why) => Check::fail(
+                NAME,
+                format!("Unable to create files in {}: {why}", ancestor.display()),
+                remedy,
+            ),
+        };
+    }
+
+    if let Err(why) = OpenOptions::new().append(true).open(path) {
+        return Check::fail(
+            NAME,
+            format!("Unable to write to {}: {why}", path.display()),
+            remedy,
+        );
+    }
+
+    // The tracker replaces a log it cannot parse, so warn before that loses anything
+    let contents = std::fs::read_to_string(path).unwrap_or_default();
+    if contents.trim().is_empty() {
+        return Check::pass(NAME, format!("{} is writable", path.display()));
+    }
+    match serde_json::from_str::<Vec<DeletionEvent>>(&contents) {
+        Ok(events) => Check::pass(
+            NAME,
+            format!(
+                "{} is writable and has {} deletions",
+                path.display(),
+                events.len()
+            ),
+        ),
+        Err(why) => Check::warn(
+            NAME,
+            format!("{} is not a list of deletions: {why}", path.display()),
+            format!(
+                "The tracker will replace this file; move it aside or choose another file with --{OPTION_OUTPUT}"
+            ),
+        ),
+    }
+}
+
+/// Check that a directory that is created on demand can be created and written to
+///
+/// `option` is the option that chooses another location for the directory.
+fn check_output_dir(name: &'static str, path: &Path, option: &str) -> Check {
+    let ancestor = existing_ancestor(path);
+    match probe_dir(ancestor) {
+        Ok(()) if ancestor == path => Check::pass(name, format!("{} is writable", path.display())),
+        Ok(()) => Check::pass(name, format!("{} can be created", path.display())),
+        Err(why) => Check::fail(
+            name,
+            format!("Unable to create files in {}: {why}", ancestor.display()),
+            format!(
+                "Make {} writable, or choose another location with {option}",
+                ancestor.display()
+            ),
+        ),
+    }
+}
+
+/// The directory a file is created in, treating a bare file name as the current directory
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// The closest directory to `path`, including itself, that already exists
+fn existing_ancestor(path: &Path) -> &Path {
+    path.ancestors()
+        .find(|ancestor| ancestor.is_dir())
+        .unwrap_or_else(|| Path::new("."))
+}
+
+/// Create and remove an empty file in `dir`, to show that files can be created there
+fn probe_dir(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(format!(".imessage-doctor-{}", std::process::id()));
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)?;
+    remove_file(probe)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{Permissions, create_dir_all, read_dir, remove_dir_all, set_permissions, write},
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+    };
+
+    use imessage_database::util::fixture::{FixtureBuilder, NewMessage, Schema};
+
+    use crate::{
+        app::options::DoctorOptions,
+        doctor::{Check, FULL_DISK_ACCESS, Status, run},
+    };
+
+    const DATE: i64 = 694_000_000_000_000_000;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-doctor-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Create a database with one message and an attachments directory, like Messages keeps them
+    fn build(dir: &Path, schema: Schema) -> FixtureBuilder {
+        let mut fixture = FixtureBuilder::create(&dir.join("chat.db"), schema).unwrap();
+        let handle = fixture.handle("+15558675309", "iMessage").unwrap();
+        let chat = fixture.chat("+15558675309", None, &[handle]).unwrap();
+        fixture
+            .message(chat, NewMessage::text("Hello!", DATE).from_handle(handle))
+            .unwrap();
+        create_dir_all(fixture.attachment_root()).unwrap();
+        fixture
+    }
+
+    fn options(dir: &Path, attachment_root: &Path) -> DoctorOptions {
+        DoctorOptions {
+            db_path: dir.join("chat.db"),
+            platform: None,
+            attachment_root: Some(attachment_root.display().to_string()),
+            state_db_path: dir.join("tracker_state.db"),
+            output: dir.join("undeleted_messages/deletions.json"),
+            export_path: dir.join("imessage_export"),
+        }
+    }
+
+    fn find<'a>(checks: &'a [Check], name: &str) -> &'a Check {
+        checks.iter().find(|check| check.name == name).unwrap()
+    }
+
+    #[test]
+    fn can_pass_healthy_database() {
+        let dir = temp("healthy");
+        let fixture = build(&dir, Schema::Ios16Newer);
+        // Messages keeps the database in WAL mode with its connection open, so the sidecar files exist
+        fixture
+            .connection()
+            .query_row("PRAGMA journal_mode = WAL", [], |row| {
+                row.get::<_, String>(0)
+            })
+            .unwrap();
+
+        let checks = run(&options(&dir, fixture.attachment_root()));
+
+        for check in &checks {
+            assert_eq!(check.status, Status::Pass, "{check}");
+        }
+        assert!(
+            find(&checks, "Database")
+                .detail
+                .starts_with("1 messages in ")
+        );
+        assert!(find(&checks, "Schema").detail.starts_with("macOS Ventura"));
+
+        drop(fixture);
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cannot_read_missing_database() {
+        let dir = temp("missing");
+
+        let checks = run(&options(&dir, &dir));
+
+        let database = find(&checks, "Database");
+        assert_eq!(database.status, Status::Fail);
+        assert!(database.detail.starts_with("No database at "));
+        assert!(database.remedy.as_deref().unwrap().contains("--db-path"));
+        assert_eq!(find(&checks, "WAL").status, Status::Skip);
+        assert_eq!(find(&checks, "Schema").status, Status::Skip);
+        assert_eq!(find(&checks, "Attachments").status, Status::Pass);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cannot_read_attachments() {
+        let dir = temp("attachments");
+        let fixture = build(&dir, Schema::Ios16Newer);
+
+        // A file where the directory should be cannot be listed
+        let file = dir.join("Attachments.txt");
+        write(&file, "").unwrap();
+        let checks = run(&options(&dir, &file));
+        let attachments = find(&checks, "Attachments");
+        assert_eq!(attachments.status, Status::Warn);
+        assert!(attachments.detail.starts_with("Unable to read "));
+
+        let missing = run(&options(&dir, &dir.join("Missing")));
+        let attachments = find(&missing, "Attachments");
+        assert_eq!(attachments.status, Status::Warn);
+        assert!(
+            attachments
+                .detail
+                .starts_with("No attachments directory at ")
+        );
+
+        // Privileged users can list any directory, so a directory without permissions is only unreadable for others
+        let root = fixture.attachment_root();
+        set_permissions(root, Permissions::from_mode(0o000)).unwrap();
+        if read_dir(root).is_err() {
+            let denied = run(&options(&dir, root));
+            let attachments = find(&denied, "Attachments");
+            assert_eq!(attachments.status, Status::Fail);
+            assert_eq!(attachments.remedy.as_deref(), Some(FULL_DISK_ACCESS));
+        }
+        set_permissions(root, Permissions::from_mode(0o755)).unwrap();
+
+        drop(fixture);
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cannot_watch_old_schema() {
+        let dir = temp("schema");
+        let fixture = build(&dir, Schema::Ios13Older);
+
+        let checks = run(&options(&dir, fixture.attachment_root()));
+
+        assert_eq!(find(&checks, "Database").status, Status::Pass);
+        // A copy of the database is not in WAL mode
+        assert_eq!(find(&checks, "WAL").status, Status::Warn);
+        let schema = find(&checks, "Schema");
+        assert_eq!(schema.status, Status::Fail);
+        assert!(
+            schema.detail.contains("does not record edits or unsends"),
+            "{schema}"
+        );
+
+        drop(fixture);
+        remove_dir_all(dir).unwrap();
+    }
+}
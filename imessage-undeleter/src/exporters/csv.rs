@@ -0,0 +1,177 @@
+/*!
+Exports every message and every detected deletion to CSV tables
+
+See [`tabular`](crate::exporters::tabular) for the columns in each table. Dates are written as RFC 3339 strings in
+UTC, and missing values are left empty.
+*/
+
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use ::csv::{Writer, WriterBuilder};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{messages::Message, table::Table},
+};
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config},
+    exporters::tabular::{
+        DELETION_COLUMNS, DELETIONS_TABLE, DeletionRow, MESSAGE_COLUMNS, MESSAGES_TABLE, MessageRow,
+    },
+};
+
+pub struct CSV<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Table that every message is written to
+    messages: Writer<BufWriter<File>>,
+}
+
+impl<'a> CSV<'a> {
+    pub fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        Ok(CSV {
+            messages: create_table(&path(config, MESSAGES_TABLE), MESSAGE_COLUMNS)?,
+            config,
+        })
+    }
+
+    /// Write every message matching the query context, then every deletion in the tracker's state database
+    pub fn iter_messages(mut self) -> Result<(), RuntimeError> {
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        let mut statement = Message::stream_rows(
+            self.config.db(),
+            self.config.capabilities(),
+            &self.config.options.query_context,
+        )?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            self.messages
+                .serialize(MessageRow::from_message(self.config, &mut msg))
+                .map_err(io::Error::from)?;
+        }
+        self.messages.flush()?;
+
+        let mut deletions = create_table(&path(self.config, DELETIONS_TABLE), DELETION_COLUMNS)?;
+        for deletion in DeletionRow::load(&self.config.options.state_db_path)? {
+            deletions.serialize(deletion).map_err(io::Error::from)?;
+        }
+        deletions.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Build the path to a table from its name
+fn path(config: &Config, name: &str) -> PathBuf {
+    let mut path = config.options.export_path.join(name);
+    path.set_extension("csv");
+    path
+}
+
+/// Create a table and write its header row, so tables without any records still have columns
+fn create_table(path: &Path, columns: &[&str]) -> Result<Writer<BufWriter<File>>, RuntimeError> {
+    let mut writer = WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(BufWriter::new(File::create(path)?));
+    writer.write_record(columns).map_err(io::Error::from)?;
+    Ok(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use ::csv::Reader;
+
+    use imessage_database::util::fixture::{FixtureBuilder, NewMessage, Schema};
+
+    use crate::{
+        app::{export_type::ExportType, options::Options, runtime::Config},
+        core::state_manager::tests::state_db,
+        exporters::tabular::{DELETION_COLUMNS, MESSAGE_COLUMNS},
+    };
+
+    const DATE: i64 = 694_000_000_000_000_000;
+    const MINUTE: i64 = 60_000_000_000;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-csv-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn can_export_tables() {
+        let dir = temp("tables");
+        let db_path = dir.join("chat.db");
+        let mut fixture = FixtureBuilder::create(&db_path, Schema::Ios16Newer).unwrap();
+        let handle = fixture.handle("+15558675309", "iMessage").unwrap();
+        let chat = fixture
+            .chat("+15558675309", Some("Jenny"), &[handle])
+            .unwrap();
+        let text = "Commas, \"quotes\",\nand lines";
+        fixture
+            .message(chat, NewMessage::text(text, DATE).from_handle(handle))
+            .unwrap();
+        fixture
+            .message(chat, NewMessage::text("Hi", DATE + MINUTE))
+            .unwrap();
+        fixture
+            .message(chat, NewMessage::text("Bye", DATE + 2 * MINUTE))
+            .unwrap();
+
+        let export_path = dir.join("export");
+        create_dir_all(&export_path).unwrap();
+        state_db(&export_path.join("tracker_state.db"), &[100, 200]);
+        Config::new(Options::fake(ExportType::Csv, &db_path, &export_path))
+            .unwrap()
+            .start()
+            .unwrap();
+
+        let mut messages = Reader::from_path(export_path.join("messages.csv")).unwrap();
+        assert_eq!(messages.headers().unwrap(), MESSAGE_COLUMNS);
+        let rows: Vec<_> = messages.records().map(Result::unwrap).collect();
+        assert_eq!(rows.len(), 3);
+        let text_column = MESSAGE_COLUMNS.iter().position(|c| *c == "text").unwrap();
+        let chat_column = MESSAGE_COLUMNS
+            .iter()
+            .position(|c| *c == "chat_name")
+            .unwrap();
+        assert!(rows.iter().any(|row| &row[text_column] == text));
+        assert!(rows.iter().all(|row| &row[chat_column] == "Jenny"));
+
+        let mut deletions = Reader::from_path(export_path.join("deletions.csv")).unwrap();
+        assert_eq!(deletions.headers().unwrap(), DELETION_COLUMNS);
+        let rows: Vec<_> = deletions.records().map(Result::unwrap).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(&rows[0][2], "1970-01-01T00:01:40Z");
+        assert_eq!(&rows[0][9], r#"["a.png"]"#);
+
+        remove_dir_all(dir).unwrap();
+    }
+}
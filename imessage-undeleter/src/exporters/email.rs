@@ -0,0 +1,623 @@
+/*!
+Exports messages as RFC 5322 emails, either to one mbox file per conversation or to individual `.eml` files
+
+Each message becomes an email from its sender to the other participants in its conversation:
+- Handles become addresses, with the participant's contact info as the display name when it differs from the address
+- Phone numbers, which are not email addresses, use the reserved `imessage.invalid` domain
+- Message GUIDs become `Message-ID`s, so replies can point to the message they reply to with `In-Reply-To` and `References`
+- Attachments become MIME parts, read from the disk and encoded as base64
+- Message dates become `Date` headers in the local timezone
+
+Text is also encoded as base64, so no line in a message can be mistaken for the start of the next message in an mbox.
+Tapbacks are not exported.
+*/
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fs::{File, create_dir_all},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use tracing::warn;
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{
+        attachment::Attachment,
+        messages::Message,
+        table::{ME, ORPHANED, Table, UNKNOWN},
+    },
+    util::dates::get_local_time,
+};
+
+use crate::app::{error::RuntimeError, runtime::Config};
+
+/// Domain used for addresses that are not email addresses, reserved by RFC 2606 so it can never be delivered to
+const ADDRESS_DOMAIN: &str = "imessage.invalid";
+/// Maximum length of a line of base64, from RFC 2045
+const BASE64_LINE_LENGTH: usize = 76;
+/// Maximum length of an RFC 2047 encoded word
+const ENCODED_WORD_LENGTH: usize = 75;
+/// Maximum length of a header line that contains an encoded word, from RFC 2047
+const ENCODED_LINE_LENGTH: usize = 76;
+/// Length of the `=?UTF-8?B?` prefix and `?=` suffix of an encoded word
+const ENCODED_WORD_OVERHEAD: usize = 12;
+/// Content type of attachments without a known type
+const OCTET_STREAM: &str = "application/octet-stream";
+/// Subject used for messages in conversations without a name or participants
+const NO_SUBJECT: &str = "iMessage";
+
+/// The ways emails can be written to the disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MailFormat {
+    /// One mbox file per conversation, with `LF` line endings
+    Mbox,
+    /// One directory per conversation, with one `.eml` file per message and `CRLF` line endings
+    Eml,
+}
+
+impl MailFormat {
+    fn newline(self) -> &'static str {
+        match self {
+            MailFormat::Mbox => "\n",
+            MailFormat::Eml => "\r\n",
+        }
+    }
+}
+
+pub struct Email<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// How emails are written to the disk
+    format: MailFormat,
+    /// Map of deduplicated chatroom ID to the mbox its messages are written to
+    mailboxes: HashMap<i32, BufWriter<File>>,
+    /// Mbox for messages that do not belong to a chat
+    orphaned: Option<BufWriter<File>>,
+}
+
+impl<'a> Email<'a> {
+    pub fn new(config: &'a Config, format: MailFormat) -> Self {
+        Email {
+            config,
+            format,
+            mailboxes: HashMap::new(),
+            orphaned: None,
+        }
+    }
+
+    /// Write every message matching the query context as an email
+    pub fn iter_messages(mut self) -> Result<(), RuntimeError> {
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        let mut statement = Message::stream_rows(
+            self.config.db(),
+            self.config.capabilities(),
+            &self.config.options.query_context,
+        )?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            if msg.is_tapback() {
+                continue;
+            }
+
+            let Some(email) = self.format_message(&mut msg)? else {
+                continue;
+            };
+            match self.format {
+                MailFormat::Mbox => {
+                    let envelope = self.envelope(&msg);
+                    let file = self.get_or_create_mbox(&msg)?;
+                    file.write_all(envelope.as_bytes())?;
+                    file.write_all(email.as_bytes())?;
+                    // Messages in an mbox are separated by an empty line
+                    file.write_all(b"\n")?;
+                }
+                MailFormat::Eml => {
+                    let mut path = self.conversation_path(&msg);
+                    create_dir_all(&path)?;
+                    path.push(msg.rowid.to_string());
+                    path.set_extension("eml");
+                    File::create(&path)?.write_all(email.as_bytes())?;
+                }
+            }
+        }
+
+        for (_, mut file) in self.mailboxes.drain() {
+            file.flush()?;
+        }
+        if let Some(mut file) = self.orphaned.take() {
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Get the path for a message's conversation, without an extension
+    fn conversation_path(&self, msg: &Message) -> PathBuf {
+        let name = match self.config.conversation(msg) {
+            Some((chatroom, &real_id)) => self.config.filename(chatroom, real_id),
+            None => ORPHANED.to_string(),
+        };
+        self.config.options.export_path.join(name)
+    }
+
+    /// Get the mbox a message belongs in, creating it if it does not exist
+    fn get_or_create_mbox(&mut self, msg: &Message) -> Result<&mut BufWriter<File>, RuntimeError> {
+        let mut path = self.conversation_path(msg);
+        path.set_extension("mbox");
+        let file = match self.config.conversation(msg) {
+            Some((_, &real_id)) => match self.mailboxes.entry(real_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(BufWriter::new(File::create(&path)?)),
+            },
+            None => match &mut self.orphaned {
+                Some(file) => file,
+                orphaned @ None => orphaned.insert(BufWriter::new(File::create(&path)?)),
+            },
+        };
+        Ok(file)
+    }
+
+    /// Build the line that starts a message in an mbox
+    fn envelope(&self, msg: &Message) -> String {
+        let sender = self.sender(msg);
+        let date = get_local_time(&msg.date, &self.config.offset)
+            .map(|date| date.format("%a %b %e %H:%M:%S %Y").to_string())
+            .unwrap_or_default();
+        format!("From {} {date}\n", address(sender.0))
+    }
+
+    /// Build the email for a message, or `None` if it has no content
+    fn format_message(&self, msg: &mut Message) -> Result<Option<String>, RuntimeError> {
+        let db = self.config.db();
+        if let Err(why) = msg.generate_text(db) {
+            // Messages without text may still have attachments
+            if msg.num_attachments == 0 {
+                warn!("Unable to generate text for message {}: {why}", msg.rowid);
+            }
+        }
+
+        // Attachments are written as MIME parts, so remove their placeholders
+        let text = msg
+            .text
+            .as_deref()
+            .map(|text| text.replace(['\u{FFFC}', '\u{FFFD}'], ""))
+            .filter(|text| !text.trim().is_empty());
+        let mut attachments = Attachment::from_message(db, self.config.capabilities(), msg)?;
+        if text.is_none() && attachments.is_empty() {
+            return Ok(None);
+        }
+
+        let newline = self.format.newline();
+        let mut headers = vec![];
+
+        // Long headers are folded onto lines that start with a space
+        let fold = format!("{newline} ");
+        let sender = self.sender(msg);
+        headers.push(format!("From: {}", mailbox(sender.0, sender.1, &fold)));
+
+        let recipients: Vec<String> = self
+            .participants(msg)
+            .into_iter()
+            .chain((!msg.is_from_me()).then(|| self.owner(msg)))
+            .filter(|(contact, _)| *contact != sender.0)
+            .map(|(contact, name)| mailbox(contact, name, &fold))
+            .collect();
+        if !recipients.is_empty() {
+            // Fold the header so large groups do not exceed the maximum line length
+            headers.push(format!("To: {}", recipients.join(&format!(",{fold}"))));
+        }
+
+        headers.push(format!(
+            "Subject: {}",
+            encode_header(&self.subject(msg), "Subject: ".len(), &fold)
+        ));
+        if let Ok(date) = get_local_time(&msg.date, &self.config.offset) {
+            headers.push(format!("Date: {}", date.to_rfc2822()));
+        }
+        headers.push(format!("Message-ID: {}", message_id(&msg.guid)));
+        if let Some(originator) = &msg.thread_originator_guid {
+            let originator = message_id(originator);
+            headers.push(format!("In-Reply-To: {originator}"));
+            headers.push(format!("References: {originator}"));
+        }
+        headers.push("MIME-Version: 1.0".to_string());
+
+        let text_part = |lines: &mut Vec<String>| {
+            lines.push("Content-Type: text/plain; charset=utf-8".to_string());
+            lines.push("Content-Transfer-Encoding: base64".to_string());
+            lines.push(String::new());
+            lines.extend(encode_base64(
+                text.as_deref().unwrap_or_default().as_bytes(),
+            ));
+        };
+
+        let mut lines = headers;
+        if attachments.is_empty() {
+            text_part(&mut lines);
+        } else {
+            let boundary = format!("=_{}", msg.guid);
+            lines.push(format!(
+                "Content-Type: multipart/mixed; boundary=\"{boundary}\""
+            ));
+            lines.push(String::new());
+            if text.is_some() {
+                lines.push(format!("--{boundary}"));
+                text_part(&mut lines);
+            }
+            for attachment in &mut attachments {
+                if let Some(part) = self.format_attachment(attachment) {
+                    lines.push(format!("--{boundary}"));
+                    lines.extend(part);
+                }
+            }
+            lines.push(format!("--{boundary}--"));
+        }
+
+        let mut out_s = lines.join(newline);
+        out_s.push_str(newline);
+        Ok(Some(out_s))
+    }
+
+    /// Build the MIME part for an attachment, or `None` if its file cannot be read
+    fn format_attachment(&self, attachment: &mut Attachment) -> Option<Vec<String>> {
+        let options = &self.config.options;
+        let bytes = match attachment.as_bytes(
+            &options.platform,
+            &options.db_path,
+            options.attachment_root.as_deref(),
+        ) {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => {
+                warn!("Attachment {} has no file", attachment.rowid);
+                return None;
+            }
+            Err(why) => {
+                warn!("Unable to read attachment {}: {why}", attachment.rowid);
+                return None;
+            }
+        };
+
+        let name = attachment
+            .filename()
+            .and_then(|name| Path::new(name).file_name())
+            .map_or_else(
+                || attachment.rowid.to_string(),
+                |name| name.to_string_lossy().to_string(),
+            );
+        // Parameter values are quoted, and quoted strings cannot be folded
+        let name = encode_header(&name, 0, " ");
+
+        let mut lines = vec![
+            format!(
+                "Content-Type: {}; name=\"{name}\"",
+                attachment.mime_type.as_deref().unwrap_or(OCTET_STREAM)
+            ),
+            "Content-Transfer-Encoding: base64".to_string(),
+            format!("Content-Disposition: attachment; filename=\"{name}\""),
+            String::new(),
+        ];
+        lines.extend(encode_base64(&bytes));
+        Some(lines)
+    }
+
+    /// Get the contact info and display name of a message's sender
+    fn sender<'b>(&'b self, msg: &'b Message) -> (&'b str, Option<&'b str>) {
+        if msg.is_from_me() {
+            return self.owner(msg);
+        }
+        let contact = self.config.who(msg.handle_id, false);
+        (contact, None)
+    }
+
+    /// Get the contact info and display name of the database owner
+    fn owner<'b>(&'b self, msg: &'b Message) -> (&'b str, Option<&'b str>) {
+        (
+            msg.destination_caller_id.as_deref().unwrap_or(ME),
+            Some(self.config.options.custom_name.as_deref().unwrap_or(ME)),
+        )
+    }
+
+    /// Get the contact info of everyone in the conversation other than the database owner
+    fn participants(&self, msg: &Message) -> Vec<(&str, Option<&str>)> {
+        match self.config.conversation(msg) {
+            Some((chat, _)) => self
+                .config
+                .chatroom_participants(chat.rowid)
+                .into_iter()
+                .map(|(_, contact)| (contact, None))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Get the subject of a message, which is its conversation's name unless the message has its own subject
+    fn subject(&self, msg: &Message) -> String {
+        if let Some(subject) = &msg.subject {
+            return subject.clone();
+        }
+        match self.config.conversation(msg) {
+            Some((chat, _)) => match chat.display_name() {
+                Some(name) => name.to_string(),
+                None => {
+                    let participants = self
+                        .config
+                        .chatroom_participants(chat.rowid)
+                        .into_iter()
+                        .map(|(_, contact)| contact)
+                        .collect::<Vec<_>>();
+                    if participants.is_empty() {
+                        NO_SUBJECT.to_string()
+                    } else {
+                        participants.join(", ")
+                    }
+                }
+            },
+            None => NO_SUBJECT.to_string(),
+        }
+    }
+}
+
+/// Convert contact info to an email address
+///
+/// Contacts that were deduplicated have several IDs separated by spaces; the first one is used.
+fn address(contact: &str) -> String {
+    let id = contact.split_whitespace().next().unwrap_or(UNKNOWN);
+    if id.contains('@') {
+        return id.to_string();
+    }
+    let local: String = id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_'))
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if local.is_empty() {
+        format!("{}@{ADDRESS_DOMAIN}", UNKNOWN.to_lowercase())
+    } else {
+        format!("{local}@{ADDRESS_DOMAIN}")
+    }
+}
+
+/// Format an address with an optional display name, folding encoded names with `fold`
+///
+/// Contact info for a deduplicated contact, which has several IDs, is used as the display name.
+fn mailbox(contact: &str, name: Option<&str>, fold: &str) -> String {
+    let address = address(contact);
+    let name = name.or((contact.split_whitespace().count() > 1).then_some(contact));
+    match name {
+        Some(name) if name.is_ascii() => format!(
+            "\"{}\" <{address}>",
+            name.replace('\\', "\\\\").replace('"', "\\\"")
+        ),
+        // The address goes on its own line, so it does not lengthen the last line of encoded words
+        Some(name) => format!(
+            "{}{fold}<{address}>",
+            encode_header(name, "From: ".len(), fold)
+        ),
+        None => format!("<{address}>"),
+    }
+}
+
+/// Build a `Message-ID` from a message GUID
+fn message_id(guid: &str) -> String {
+    let local: String = guid
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    format!("<{local}@{ADDRESS_DOMAIN}>")
+}
+
+/// Encode header text as RFC 2047 encoded words if it is not printable ASCII
+///
+/// Each word holds whole characters and is at most [`ENCODED_WORD_LENGTH`] long. Words are joined with `separator`,
+/// which folds the header when it is a newline followed by a space. `indent` is the length of the header line before
+/// the text, which shortens the first word so its line stays within [`ENCODED_LINE_LENGTH`].
+fn encode_header(text: &str, indent: usize, separator: &str) -> String {
+    if text.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        return text.to_string();
+    }
+
+    let encoded_length = |bytes: usize| ENCODED_WORD_OVERHEAD + bytes.div_ceil(3) * 4;
+    let mut words = vec![];
+    let mut limit = ENCODED_WORD_LENGTH.min(ENCODED_LINE_LENGTH.saturating_sub(indent));
+    let mut start = 0;
+    for (idx, c) in text.char_indices() {
+        if idx > start && encoded_length(idx + c.len_utf8() - start) > limit {
+            words.push(&text[start..idx]);
+            start = idx;
+            limit = ENCODED_WORD_LENGTH;
+        }
+    }
+    words.push(&text[start..]);
+
+    words
+        .into_iter()
+        .map(|word| format!("=?UTF-8?B?{}?=", STANDARD.encode(word)))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Encode data as base64, split into lines
+fn encode_base64(data: &[u8]) -> Vec<String> {
+    STANDARD
+        .encode(data)
+        .as_bytes()
+        .chunks(BASE64_LINE_LENGTH)
+        .map(|line| String::from_utf8_lossy(line).to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_dir, read_to_string, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use base64::{Engine, engine::general_purpose::STANDARD};
+
+    use imessage_database::util::fixture::{FixtureBuilder, NewMessage, Schema};
+
+    use crate::{
+        app::{export_type::ExportType, options::Options, runtime::Config},
+        exporters::email::{
+            ENCODED_LINE_LENGTH, ENCODED_WORD_LENGTH, encode_header, mailbox, message_id,
+        },
+    };
+
+    const DATE: i64 = 694_000_000_000_000_000;
+    const MINUTE: i64 = 60_000_000_000;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-email-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Decode a header made of encoded words
+    fn decode_header(header: &str) -> String {
+        header
+            .split_whitespace()
+            .map(|word| {
+                let encoded = word
+                    .strip_prefix("=?UTF-8?B?")
+                    .and_then(|word| word.strip_suffix("?="))
+                    .unwrap();
+                String::from_utf8(STANDARD.decode(encoded).unwrap()).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn can_encode_ascii_header() {
+        assert_eq!(encode_header("Weekend plans", 0, "\n "), "Weekend plans");
+    }
+
+    #[test]
+    fn can_encode_short_header() {
+        assert_eq!(
+            encode_header("Café", 0, "\n "),
+            format!("=?UTF-8?B?{}?=", STANDARD.encode("Café"))
+        );
+        // Control characters are not allowed in headers either
+        assert_eq!(decode_header(&encode_header("a\nb", 0, "\n ")), "a\nb");
+    }
+
+    #[test]
+    fn can_encode_long_header() {
+        let subject = "Plans for the weekend 🏖️ with everyone from the café — ".repeat(4);
+        let header = format!("Subject: {}", encode_header(&subject, 9, "\r\n "));
+
+        let lines: Vec<&str> = header.split("\r\n").collect();
+        assert!(lines.len() > 1);
+        for line in &lines {
+            assert!(line.len() <= ENCODED_LINE_LENGTH, "{line}");
+        }
+        let words: Vec<&str> = header["Subject: ".len()..].split_whitespace().collect();
+        for word in &words {
+            assert!(word.len() <= ENCODED_WORD_LENGTH, "{word}");
+        }
+        // Every word decodes on its own, so characters are not split between words
+        assert_eq!(decode_header(&header["Subject: ".len()..]), subject);
+    }
+
+    #[test]
+    fn can_encode_mailbox() {
+        assert_eq!(
+            mailbox("+15558675309", None, "\n "),
+            "<+15558675309@imessage.invalid>"
+        );
+        assert_eq!(
+            mailbox("a@b.com", Some("Jenny \"J\" Doe"), "\n "),
+            r#""Jenny \"J\" Doe" <a@b.com>"#
+        );
+        assert_eq!(
+            mailbox("a@b.com", Some("Zoë"), "\n "),
+            format!("=?UTF-8?B?{}?=\n <a@b.com>", STANDARD.encode("Zoë"))
+        );
+    }
+
+    #[test]
+    fn can_build_message_id() {
+        assert_eq!(message_id("p:0/ABC-123"), "<p.0.ABC-123@imessage.invalid>");
+    }
+
+    #[test]
+    fn can_escape_mbox_from_lines() {
+        let dir = temp("mbox");
+        let db_path = dir.join("chat.db");
+        let mut fixture = FixtureBuilder::create(&db_path, Schema::Ios16Newer).unwrap();
+        let handle = fixture.handle("+15558675309", "iMessage").unwrap();
+        let chat = fixture
+            .chat("+15558675309", Some("Café crew"), &[handle])
+            .unwrap();
+        let text = "From the top\nFrom here on\n>From quoted";
+        fixture
+            .message(chat, NewMessage::text(text, DATE).from_handle(handle))
+            .unwrap();
+        fixture
+            .message(chat, NewMessage::text("From me", DATE + MINUTE))
+            .unwrap();
+
+        let export_path = dir.join("export");
+        Config::new(Options::fake(ExportType::Mbox, &db_path, &export_path))
+            .unwrap()
+            .start()
+            .unwrap();
+        let mbox = read_dir(&export_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.to_string_lossy().contains("Café crew"))
+            .unwrap();
+        let mbox = read_to_string(mbox).unwrap();
+
+        // Only the envelopes start with `From `, because message text is encoded
+        let envelopes: Vec<&str> = mbox
+            .lines()
+            .filter(|line| line.starts_with("From "))
+            .collect();
+        assert_eq!(envelopes.len(), 2);
+        assert!(
+            envelopes
+                .iter()
+                .any(|line| line.starts_with("From +15558675309@imessage.invalid "))
+        );
+        assert!(
+            envelopes
+                .iter()
+                .any(|line| line.starts_with("From me@imessage.invalid "))
+        );
+        assert!(!mbox.lines().any(|line| line.starts_with(">From")));
+        assert!(mbox.contains(&format!("\n{}\n", STANDARD.encode(text))));
+        assert!(mbox.contains(&format!(
+            "Subject: =?UTF-8?B?{}?=\n",
+            STANDARD.encode("Café crew")
+        )));
+
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,1225 @@
+/*!
+Exports each conversation to a self-contained HTML page, plus a report of deleted, unsent, and edited messages
+*/
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fmt::Write as _,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config},
+    exporters::exporter::{ATTACHMENT_NO_FILENAME, Writer},
+};
+
+use imessage_database::{
+    error::{plist::PlistParseError, table::TableError},
+    message_types::{
+        app::AppMessage,
+        check_in::{CheckInMessage, CheckInStatus},
+        digital_touch::DigitalTouchMessage,
+        edited::{EditStatus, EditedMessage},
+        expressives::{BubbleEffect, Expressive, ScreenEffect},
+        handwriting::HandwrittenMessage,
+        variants::{
+            Announcement, CustomBalloon, ParsedBalloon, Tapback, TapbackAction, URLOverride,
+            Variant,
+        },
+    },
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::{
+            Message,
+            models::{AttachmentMeta, BubbleComponent, GroupAction, TextAttributes},
+        },
+        table::{AttributedBody, FITNESS_RECEIVER, ME, ORPHANED, Table, YOU},
+    },
+    util::{
+        dates::{TIMESTAMP_FACTOR, format, get_local_time, readable_diff},
+        render::{RenderFormat, is_safe_link, render_text},
+    },
+};
+
+/// Stylesheet embedded in every page so the export does not depend on any other files
+const STYLE: &str = include_str!("resources/style.css");
+/// File name of the deletions report, without an extension
+const DELETIONS_REPORT: &str = "deletions";
+
+pub struct HTML<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: HashMap<String, BufWriter<File>>,
+    /// Writer instance for orphaned messages
+    pub orphaned: BufWriter<File>,
+    /// Rows of the deletions report, in the order the messages were sent
+    deletions: Vec<Deletion>,
+}
+
+/// A message that appears in the deletions report
+struct Deletion {
+    /// The formatted date the message was sent
+    date: String,
+    /// The name of the conversation's file, without an extension
+    file: String,
+    /// The GUID of the message, used as the anchor in the conversation's file
+    guid: String,
+    /// The name of the sender
+    sender: String,
+    /// What happened to the message
+    kind: &'static str,
+    /// The recovered content of the message, as HTML
+    content: String,
+}
+
+/// The fields of a rich link or app message card
+#[derive(Default)]
+struct AppCard<'b> {
+    /// Where the card links to
+    url: Option<&'b str>,
+    name: Option<&'b str>,
+    title: Option<&'b str>,
+    subtitle: Option<&'b str>,
+    ldtext: Option<&'b str>,
+    caption: Option<&'b str>,
+    subcaption: Option<&'b str>,
+    trailing_caption: Option<&'b str>,
+    trailing_subcaption: Option<&'b str>,
+}
+
+impl AppCard<'_> {
+    /// Render the card using the `app` classes from the stylesheet
+    ///
+    /// Preview images and audio are hosted remotely, so they are left out to keep the export self-contained.
+    fn render(&self) -> String {
+        let mut header = String::new();
+        for (class, value) in [
+            ("name", self.name),
+            ("image_title", self.title),
+            ("image_subtitle", self.subtitle),
+            ("ldtext", self.ldtext),
+        ] {
+            if let Some(value) = value {
+                let _ = write!(header, r#"<div class="{class}">{}</div>"#, escape(value));
+            }
+        }
+
+        let mut footer = String::new();
+        for (class, value) in [
+            ("caption", self.caption),
+            ("subcaption", self.subcaption),
+            ("trailing_caption", self.trailing_caption),
+            ("trailing_subcaption", self.trailing_subcaption),
+        ] {
+            if let Some(value) = value {
+                let _ = write!(
+                    footer,
+                    r#"<div class="{class}"><p>{}</p></div>"#,
+                    escape(value)
+                );
+            }
+        }
+
+        let body = format!(
+            r#"<div class="app_header">{header}</div><div class="app_footer">{footer}</div>"#
+        );
+        // Links that are not safe to open are left as plain cards
+        match self.url.filter(|url| is_safe_link(url)) {
+            Some(url) => format!(
+                r#"<div class="app"><a href="{}">{body}</a></div>"#,
+                escape(url)
+            ),
+            None => format!(r#"<div class="app">{body}</div>"#),
+        }
+    }
+}
+
+impl<'a> HTML<'a> {
+    pub fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut orphaned = config.options.export_path.clone();
+        orphaned.push(ORPHANED);
+        orphaned.set_extension("html");
+
+        let mut orphaned = BufWriter::new(File::create(orphaned)?);
+        HTML::write_to_file(&mut orphaned, &header(ORPHANED))?;
+
+        Ok(HTML {
+            config,
+            files: HashMap::new(),
+            orphaned,
+            deletions: vec![],
+        })
+    }
+
+    /// Write every message matching the query context to its conversation's page, then write the deletions report
+    pub fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        let mut statement = Message::stream_rows(
+            self.config.db(),
+            self.config.capabilities(),
+            &self.config.options.query_context,
+        )?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message; messages without text may still have attachments or app data
+            let _ = msg.generate_text(self.config.db());
+
+            // Render the announcement in-line
+            if msg.is_announcement() {
+                let announcement = self.format_announcement(&msg);
+                HTML::write_to_file(self.get_or_create_file(&msg)?, &announcement)?;
+            }
+            // Tapbacks are rendered under the messages they refer to
+            else if !msg.is_tapback() {
+                let message = self.format_message(&msg, 0)?;
+                HTML::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+            }
+
+            self.record_deletions(&msg);
+        }
+
+        for file in self.files.values_mut() {
+            HTML::write_to_file(file, FOOTER)?;
+            file.flush()?;
+        }
+        HTML::write_to_file(&mut self.orphaned, FOOTER)?;
+        self.orphaned.flush()?;
+
+        self.write_deletions_report()
+    }
+
+    /// Get the name of the page a message belongs on, without an extension
+    fn file_name(&self, message: &Message) -> String {
+        match self.config.conversation(message) {
+            Some((chatroom, &real_id)) => self.config.filename(chatroom, real_id),
+            None => ORPHANED.to_string(),
+        }
+    }
+
+    /// Get the page a message belongs on, creating it if it does not exist
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        if self.config.conversation(message).is_none() {
+            return Ok(&mut self.orphaned);
+        }
+        match self.files.entry(self.file_name(message)) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let mut path = self.config.options.export_path.join(entry.key());
+                path.set_extension("html");
+                let mut file = BufWriter::new(File::create(path)?);
+                HTML::write_to_file(&mut file, &header(entry.key()))?;
+                Ok(entry.insert(file))
+            }
+        }
+    }
+
+    /// Add a message to the deletions report if it was deleted, unsent, or edited
+    fn record_deletions(&mut self, message: &Message) {
+        let mut kinds = vec![];
+        if message.is_deleted() {
+            kinds.push((
+                "Deleted",
+                message.text.as_deref().map(escape).unwrap_or_default(),
+            ));
+        }
+        if message.is_fully_unsent() {
+            kinds.push(("Unsent", String::new()));
+        } else if let Some(edited_parts) = &message.edited_parts {
+            for (idx, part) in edited_parts.parts.iter().enumerate() {
+                let kind = match part.status {
+                    EditStatus::Edited => "Edited",
+                    EditStatus::Unsent => "Unsent part",
+                    EditStatus::Original => continue,
+                };
+                kinds.push((
+                    kind,
+                    self.format_edited(message, edited_parts, idx, "")
+                        .unwrap_or_default(),
+                ));
+            }
+        }
+
+        for (kind, content) in kinds {
+            self.deletions.push(Deletion {
+                date: format(&message.date(&self.config.offset)),
+                file: self.file_name(message),
+                guid: message.guid.clone(),
+                sender: self
+                    .config
+                    .who(message.handle_id, message.is_from_me())
+                    .to_string(),
+                kind,
+                content,
+            });
+        }
+    }
+
+    /// Write the page listing every deleted, unsent, and edited message
+    fn write_deletions_report(&self) -> Result<(), RuntimeError> {
+        let mut path = self.config.options.export_path.join(DELETIONS_REPORT);
+        path.set_extension("html");
+        let mut file = BufWriter::new(File::create(path)?);
+
+        let mut counts: Vec<(&str, usize)> = vec![];
+        for deletion in &self.deletions {
+            match counts.iter_mut().find(|(kind, _)| *kind == deletion.kind) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((deletion.kind, 1)),
+            }
+        }
+
+        let mut out_s = header("Deletions Report");
+        out_s.push_str("<h1>Deletions Report</h1>");
+        if self.deletions.is_empty() {
+            out_s.push_str("<p>No deleted, unsent, or edited messages were found.</p>");
+        } else {
+            out_s.push_str(r#"<ul class="report_summary">"#);
+            for (kind, count) in counts {
+                let _ = write!(out_s, "<li>{kind}: {count}</li>");
+            }
+            out_s.push_str("</ul>");
+
+            out_s.push_str(r#"<table class="report"><thead><tr><th>Date</th><th>Conversation</th><th>Sender</th><th>What happened</th><th>Content</th></tr></thead><tbody>"#);
+            for deletion in &self.deletions {
+                let _ = write!(
+                    out_s,
+                    r#"<tr class="{}"><td><a href="{}.html#{}">{}</a></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+                    deletion.kind.to_lowercase().replace(' ', "_"),
+                    encode_uri(&deletion.file),
+                    escape(&deletion.guid),
+                    escape(&deletion.date),
+                    escape(&deletion.file),
+                    escape(&deletion.sender),
+                    deletion.kind,
+                    deletion.content
+                );
+            }
+            out_s.push_str("</tbody></table>");
+        }
+        out_s.push_str(FOOTER);
+
+        HTML::write_to_file(&mut file, &out_s)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Get the formatted date of a message, annotated with how long it took to be read
+    pub fn get_time(&self, message: &Message) -> String {
+        let mut date = format(&message.date(&self.config.offset));
+        let read_after = message.time_until_read(&self.config.offset);
+        if let Some(time) = read_after {
+            if !time.is_empty() {
+                let who = if message.is_from_me() {
+                    "them"
+                } else {
+                    self.config.options.custom_name.as_deref().unwrap_or("you")
+                };
+                date.push_str(&format!(" (Read by {who} after {time})"));
+            }
+        }
+        date
+    }
+
+    /// Get the name used when the database owner performed an action
+    fn you(&self) -> &str {
+        self.config.options.custom_name.as_deref().unwrap_or(YOU)
+    }
+
+    /// Format a Check In message, including when the sender is expected or checked in
+    fn format_check_in(&self, balloon: &CheckInMessage) -> String {
+        // The query string stores unix timestamps, so no offset is applied
+        let date = |stamp: f64| format(&get_local_time(&(stamp as i64 * TIMESTAMP_FACTOR), &0));
+
+        let status = if let Some(expected) = balloon.estimated_arrival {
+            // Before manual check-in
+            Some(format!("Expected at {}", date(expected)))
+        } else if let Some(expected) = balloon.expected {
+            // Expired check-in
+            Some(format!("Was expected at {}", date(expected)))
+        } else if let (CheckInStatus::TimerStarted, Some(sent)) = (balloon.status, balloon.sent) {
+            // Started timer
+            Some(format!("Started at {}", date(sent)))
+        } else {
+            // Accepted check-in
+            balloon
+                .sent
+                .map(|sent| format!("Checked in at {}", date(sent)))
+        };
+
+        AppCard {
+            name: Some("Check In"),
+            caption: Some(balloon.caption.unwrap_or("Check In")),
+            subcaption: status.as_deref(),
+            ..Default::default()
+        }
+        .render()
+    }
+
+    /// Format a generic app message, falling back to `name` if the app does not provide one
+    fn format_generic_app(&self, balloon: &AppMessage, name: &str) -> String {
+        AppCard {
+            url: balloon.url,
+            name: Some(balloon.app_name.unwrap_or(name)),
+            title: balloon.title,
+            subtitle: balloon.subtitle,
+            ldtext: balloon.ldtext,
+            caption: balloon.caption,
+            subcaption: balloon.subcaption,
+            trailing_caption: balloon.trailing_caption,
+            trailing_subcaption: balloon.trailing_subcaption,
+        }
+        .render()
+    }
+
+    /// Format a rich link card for messages sent with the URL balloon provider
+    fn format_url_override(&self, message: &Message, balloon: &URLOverride) -> String {
+        match balloon {
+            URLOverride::Normal(balloon) => {
+                let url = balloon.get_url().or(message.text.as_deref());
+                AppCard {
+                    url,
+                    name: balloon.site_name.or(url),
+                    title: balloon.title,
+                    subtitle: balloon.summary,
+                    ..Default::default()
+                }
+                .render()
+            }
+            URLOverride::AppleMusic(balloon) => {
+                let mut out_s = AppCard {
+                    url: balloon.url,
+                    name: balloon.track_name,
+                    caption: balloon.album,
+                    subcaption: balloon.artist,
+                    ..Default::default()
+                }
+                .render();
+                if let Some(lyrics) = &balloon.lyrics {
+                    out_s.push_str(r#"<div class="lyrics">"#);
+                    for line in lyrics {
+                        let _ = write!(out_s, "<p>{}</p>", escape(line));
+                    }
+                    out_s.push_str("</div>");
+                }
+                out_s
+            }
+            URLOverride::AppStore(balloon) => AppCard {
+                url: balloon.url,
+                name: balloon.app_name,
+                caption: balloon.description,
+                subcaption: balloon.genre,
+                trailing_caption: balloon.platform,
+                ..Default::default()
+            }
+            .render(),
+            URLOverride::Collaboration(balloon) => AppCard {
+                url: balloon.get_url(),
+                name: balloon.app_name.or(balloon.bundle_id),
+                title: balloon.title,
+                ..Default::default()
+            }
+            .render(),
+            URLOverride::SharedPlacemark(balloon) => {
+                let locality = [balloon.placemark.city, balloon.placemark.state]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                AppCard {
+                    url: balloon.get_url(),
+                    name: balloon.place_name.or(balloon.placemark.name),
+                    caption: balloon.placemark.address.or(balloon.placemark.street),
+                    subcaption: (!locality.is_empty()).then_some(locality.as_str()),
+                    trailing_caption: balloon.placemark.postal_code,
+                    trailing_subcaption: balloon.placemark.country,
+                    ..Default::default()
+                }
+                .render()
+            }
+        }
+    }
+}
+
+impl<'a> Writer<'a> for HTML<'a> {
+    fn format_message(&self, message: &Message, indent_size: usize) -> Result<String, TableError> {
+        // Data we want to write to a file
+        let mut formatted_message = String::new();
+
+        let mut classes = String::from("message");
+        if message.is_deleted() {
+            classes.push_str(" deleted");
+        }
+        if message.is_edited() {
+            classes.push_str(" edited_message");
+        }
+        // Replies are also rendered at the top level, which is where links to the message point
+        if indent_size == 0 {
+            let _ = write!(
+                formatted_message,
+                r#"<div class="{classes}" id="{}">"#,
+                escape(&message.guid)
+            );
+        } else {
+            let _ = write!(formatted_message, r#"<div class="{classes}">"#);
+        }
+
+        // Messages we sent are colored by the service they were sent with
+        if message.is_from_me() {
+            let service = message
+                .service
+                .as_deref()
+                .unwrap_or_default()
+                .chars()
+                .filter(char::is_ascii_alphanumeric)
+                .collect::<String>();
+            let _ = write!(formatted_message, r#"<div class="sent {service}">"#);
+        } else {
+            formatted_message.push_str(r#"<div class="received">"#);
+        }
+
+        // Add message date and sender
+        let _ = write!(
+            formatted_message,
+            r##"<p><span class="timestamp"><a href="#{}">{}</a></span> <span class="sender">{}</span></p>"##,
+            escape(&message.guid),
+            escape(&self.get_time(message)),
+            escape(self.config.who(message.handle_id, message.is_from_me()))
+        );
+
+        // If message was deleted, annotate it
+        if message.is_deleted() {
+            formatted_message.push_str(
+                r#"<p><span class="deleted">This message was deleted from the conversation!</span></p>"#,
+            );
+        }
+
+        // Add a note if the message is a reply
+        if message.is_reply() && indent_size == 0 {
+            if let Some(originator) = &message.thread_originator_guid {
+                let _ = write!(
+                    formatted_message,
+                    r##"<p><span class="reply_context">This message responded to an <a href="#{}">earlier message</a>.</span></p>"##,
+                    escape(originator)
+                );
+            }
+        }
+
+        // Useful message metadata
+        let message_parts = message.body();
+        let mut attachments =
+            Attachment::from_message(self.config.db(), self.config.capabilities(), message)?;
+        let mut replies = message.get_replies(self.config.db(), self.config.capabilities())?;
+
+        // Index of where we are in the attachment Vector
+        let mut attachment_index: usize = 0;
+
+        // Render subject
+        if let Some(subject) = &message.subject {
+            let _ = write!(
+                formatted_message,
+                r#"Subject: <span class="subject">{}</span>"#,
+                escape(subject)
+            );
+        }
+
+        // Handle SharePlay
+        if message.is_shareplay() {
+            let _ = write!(
+                formatted_message,
+                r#"<hr><div class="message_part"><span class="bubble">{}</span></div>"#,
+                self.format_shareplay()
+            );
+        }
+
+        // Handle Shared Location
+        if message.started_sharing_location() || message.stopped_sharing_location() {
+            let _ = write!(
+                formatted_message,
+                r#"<hr><div class="message_part"><span class="bubble">{}</span></div>"#,
+                self.format_shared_location(message)
+            );
+        }
+
+        // Generate the message body from its components
+        for (idx, message_part) in message_parts.iter().enumerate() {
+            formatted_message.push_str(r#"<hr><div class="message_part">"#);
+            match message_part {
+                BubbleComponent::Text(text_attrs) => {
+                    if let Some(text) = &message.text {
+                        // Render edited message content, if applicable
+                        if message.is_part_edited(idx) {
+                            if let Some(edited_parts) = &message.edited_parts {
+                                if let Some(edited) =
+                                    self.format_edited(message, edited_parts, idx, "")
+                                {
+                                    formatted_message.push_str(&edited);
+                                }
+                            }
+                        } else {
+                            let mut formatted_text = self.format_attributes(text, text_attrs);
+
+                            // If we failed to parse any text above, use the original text
+                            if formatted_text.is_empty() {
+                                formatted_text.push_str(&escape(text));
+                            }
+
+                            // Fitness messages have a prefix that we need to replace with the sender
+                            if formatted_text.starts_with(FITNESS_RECEIVER) {
+                                formatted_text =
+                                    formatted_text.replace(FITNESS_RECEIVER, &escape(self.you()));
+                            }
+
+                            let _ = write!(
+                                formatted_message,
+                                r#"<span class="bubble">{formatted_text}</span>"#
+                            );
+                        }
+                    }
+                }
+                BubbleComponent::Attachment(metadata) => {
+                    match attachments.get_mut(attachment_index) {
+                        Some(attachment) => {
+                            attachment_index += 1;
+                            let result = if attachment.is_sticker {
+                                self.format_sticker(attachment, message)
+                            } else {
+                                self.format_attachment(attachment, message, metadata)
+                                    .unwrap_or_else(|missing| {
+                                        format!(
+                                            r#"<span class="attachment_error">Unable to locate attachment: {}</span>"#,
+                                            escape(missing)
+                                        )
+                                    })
+                            };
+                            formatted_message.push_str(&result);
+                        }
+                        // Attachment does not exist in attachments table
+                        None => formatted_message.push_str(
+                            r#"<span class="attachment_error">Attachment does not exist!</span>"#,
+                        ),
+                    }
+                }
+                BubbleComponent::App => match self.format_app(message, &mut attachments, "") {
+                    Ok(app_bubble) => formatted_message.push_str(&app_bubble),
+                    Err(why) => {
+                        let _ = write!(
+                            formatted_message,
+                            r#"<span class="bubble">Unable to format app message: {}</span>"#,
+                            escape(&why.to_string())
+                        );
+                    }
+                },
+                BubbleComponent::Retracted => {
+                    if let Some(edited_parts) = &message.edited_parts {
+                        if let Some(edited) = self.format_edited(message, edited_parts, idx, "") {
+                            formatted_message.push_str(&edited);
+                        }
+                    }
+                }
+            }
+            formatted_message.push_str("</div>");
+
+            // Handle expressives
+            if message.expressive_send_style_id.is_some() {
+                let _ = write!(
+                    formatted_message,
+                    r#"<span class="expressive">{}</span>"#,
+                    escape(self.format_expressive(message))
+                );
+            }
+
+            // Handle tapbacks
+            if let Some(tapbacks) = self
+                .config
+                .tapbacks
+                .get(&message.guid)
+                .and_then(|tapbacks| tapbacks.get(&idx))
+            {
+                let mut formatted_tapbacks = String::new();
+                for tapback in tapbacks {
+                    formatted_tapbacks.push_str(&self.format_tapback(tapback)?);
+                }
+
+                if !formatted_tapbacks.is_empty() {
+                    let _ = write!(
+                        formatted_message,
+                        r#"<div class="tapbacks"><hr><p>Tapbacks:</p>{formatted_tapbacks}</div>"#
+                    );
+                }
+            }
+
+            // Handle replies
+            if let Some(replies) = replies.get_mut(&idx) {
+                formatted_message.push_str(r#"<div class="replies">"#);
+                for reply in replies {
+                    let _ = reply.generate_text(self.config.db());
+                    if !reply.is_tapback() {
+                        let _ = write!(
+                            formatted_message,
+                            r#"<div class="reply">{}</div>"#,
+                            self.format_message(reply, indent_size + 1)?
+                        );
+                    }
+                }
+                formatted_message.push_str("</div>");
+            }
+        }
+
+        formatted_message.push_str("</div></div>\n");
+        Ok(formatted_message)
+    }
+
+    fn format_attachment(
+        &self,
+        attachment: &'a mut Attachment,
+        msg: &'a Message,
+        metadata: &AttachmentMeta,
+    ) -> Result<String, &'a str> {
+        let path = self.config.copy_attachment(attachment, msg);
+        let attachment: &'a Attachment = attachment;
+        let path = path.ok_or_else(|| attachment.filename().unwrap_or(ATTACHMENT_NO_FILENAME))?;
+        let path = encode_path(&path);
+
+        let mut out_s = match attachment.mime_type() {
+            MediaType::Image(_) => format!(r#"<img src="{path}" loading="lazy">"#),
+            MediaType::Video(_) => format!(
+                r#"<video controls src="{path}" type="{}"></video>"#,
+                escape(&attachment.mime_type().as_mime_type())
+            ),
+            MediaType::Audio(_) => format!(r#"<audio controls src="{path}"></audio>"#),
+            _ => format!(
+                r#"<a href="{path}">{}</a>"#,
+                escape(attachment.filename().unwrap_or(ATTACHMENT_NO_FILENAME))
+            ),
+        };
+
+        // Append the transcription if one is provided
+        if let Some(transcription) = metadata.transcription {
+            let _ = write!(
+                out_s,
+                r#"<span class="transcription">Transcription: {}</span>"#,
+                escape(transcription)
+            );
+        }
+        Ok(out_s)
+    }
+
+    fn format_sticker(&self, sticker: &'a mut Attachment, msg: &'a Message) -> String {
+        let who = escape(self.config.who(msg.handle_id, msg.is_from_me()));
+        match self.config.copy_attachment(sticker, msg) {
+            Some(path) => {
+                let mut out_s = format!(
+                    r#"<div class="sticker"><img src="{}" loading="lazy">"#,
+                    encode_path(&path)
+                );
+                if let Some(prompt) = &sticker.emoji_description {
+                    let _ = write!(
+                        out_s,
+                        r#"<div class="genmoji_prompt">Genmoji prompt: {}</div>"#,
+                        escape(prompt)
+                    );
+                }
+                let _ = write!(
+                    out_s,
+                    r#"<div class="sticker_name">Sticker from {who}</div></div>"#
+                );
+                out_s
+            }
+            None => format!(
+                r#"<span class="attachment_error">Sticker from {who} not found: {}</span>"#,
+                escape(sticker.filename().unwrap_or(ATTACHMENT_NO_FILENAME))
+            ),
+        }
+    }
+
+    fn format_app(
+        &self,
+        message: &'a Message,
+        _: &mut Vec<Attachment>,
+        _: &str,
+    ) -> Result<String, PlistParseError> {
+        let Variant::App(balloon) = message.variant() else {
+            return Err(PlistParseError::WrongMessageType);
+        };
+
+        // Handwriting and Digital Touch messages use a different payload type, so handle them first
+        match balloon {
+            CustomBalloon::Handwriting => {
+                let payload = message
+                    .raw_payload_data(self.config.db())
+                    .ok_or(PlistParseError::NoPayload)?;
+                let handwriting = HandwrittenMessage::from_payload(&payload)
+                    .map_err(PlistParseError::HandwritingError)?;
+                return Ok(handwriting.render_animated_svg());
+            }
+            CustomBalloon::DigitalTouch => {
+                let payload = message
+                    .raw_payload_data(self.config.db())
+                    .ok_or(PlistParseError::NoPayload)?;
+                let touch = DigitalTouchMessage::from_payload(&payload)
+                    .map_err(|_| PlistParseError::DigitalTouchError)?;
+                return Ok(touch.render_svg().unwrap_or_else(|| {
+                    format!(r#"<span class="bubble">Digital Touch: {touch}</span>"#)
+                }));
+            }
+            _ => {}
+        }
+
+        let Some(payload) = message.balloon_payload(self.config.db()) else {
+            // Sometimes, URL messages are missing their payloads
+            if message.is_url() {
+                if let Some(text) = &message.text {
+                    if !is_safe_link(text) {
+                        return Ok(format!(r#"<span class="bubble">{}</span>"#, escape(text)));
+                    }
+                    return Ok(format!(
+                        r#"<a href="{}"><span class="bubble">{}</span></a>"#,
+                        escape(text),
+                        escape(text)
+                    ));
+                }
+            }
+            return Err(PlistParseError::NoPayload);
+        };
+        let payload = payload?;
+
+        let bubble = match payload.parse()? {
+            // URL messages are a special sub-case of regular app messages
+            ParsedBalloon::URL(balloon) => return Ok(self.format_url_override(message, &balloon)),
+            ParsedBalloon::App(bubble) => bubble,
+        };
+        Ok(match balloon {
+            CustomBalloon::Application(bundle_id) => self.format_generic_app(&bubble, bundle_id),
+            CustomBalloon::ApplePay => self.format_generic_app(&bubble, "Apple Pay"),
+            CustomBalloon::Fitness => self.format_generic_app(&bubble, "Fitness"),
+            CustomBalloon::Slideshow => self.format_generic_app(&bubble, "Photos"),
+            CustomBalloon::FindMy => self.format_generic_app(&bubble, "Find My"),
+            CustomBalloon::CheckIn => self.format_check_in(&CheckInMessage::from_app(&bubble)),
+            CustomBalloon::URL | CustomBalloon::Handwriting | CustomBalloon::DigitalTouch => {
+                unreachable!("handled above")
+            }
+        })
+    }
+
+    fn format_tapback(&self, msg: &Message) -> Result<String, TableError> {
+        let Variant::Tapback(_, action, tapback) = msg.variant() else {
+            return Ok(String::new());
+        };
+        // Removed tapbacks are no longer visible in the conversation
+        if action == TapbackAction::Removed {
+            return Ok(String::new());
+        }
+
+        let who = escape(self.config.who(msg.handle_id, msg.is_from_me()));
+        if let Tapback::Sticker = tapback {
+            let mut stickers =
+                Attachment::from_message(self.config.db(), self.config.capabilities(), msg)?;
+            // Sticker tapbacks have only one attachment, the sticker image
+            return Ok(match stickers.get_mut(0) {
+                Some(sticker) => format!(
+                    r#"<div class="tapback">{}</div>"#,
+                    self.format_sticker(sticker, msg)
+                ),
+                None => format!(
+                    r#"<div class="tapback"><span>Sticker from {who} not found!</span></div>"#
+                ),
+            });
+        }
+        Ok(format!(
+            r#"<div class="tapback"><span><b>{}</b> by {who}</span></div>"#,
+            escape(&tapback.to_string())
+        ))
+    }
+
+    fn format_expressive(&self, msg: &'a Message) -> &'a str {
+        match msg.get_expressive() {
+            Expressive::Screen(effect) => match effect {
+                ScreenEffect::Confetti => "Sent with Confetti",
+                ScreenEffect::Echo => "Sent with Echo",
+                ScreenEffect::Fireworks => "Sent with Fireworks",
+                ScreenEffect::Balloons => "Sent with Balloons",
+                ScreenEffect::Heart => "Sent with Heart",
+                ScreenEffect::Lasers => "Sent with Lasers",
+                ScreenEffect::ShootingStar => "Sent with Shooting Star",
+                ScreenEffect::Sparkles => "Sent with Sparkles",
+                ScreenEffect::Spotlight => "Sent with Spotlight",
+            },
+            Expressive::Bubble(effect) => match effect {
+                BubbleEffect::Slam => "Sent with Slam",
+                BubbleEffect::Loud => "Sent with Loud",
+                BubbleEffect::Gentle => "Sent with Gentle",
+                BubbleEffect::InvisibleInk => "Sent with Invisible Ink",
+            },
+            Expressive::Unknown(effect) => effect,
+            Expressive::None => "",
+        }
+    }
+
+    fn format_announcement(&self, msg: &'a Message) -> String {
+        let mut who = self.config.who(msg.handle_id, msg.is_from_me());
+        // Rename yourself so we render the proper grammar here
+        if who == ME {
+            who = self.you();
+        }
+        let who = escape(who);
+        let timestamp = format(&msg.date(&self.config.offset));
+
+        let action = match msg.get_announcement() {
+            Some(Announcement::GroupAction(action)) => match action {
+                GroupAction::ParticipantAdded(person) => format!(
+                    "added <b>{}</b> to the conversation.",
+                    escape(self.config.who(Some(person), false))
+                ),
+                GroupAction::ParticipantRemoved(person) => format!(
+                    "removed <b>{}</b> from the conversation.",
+                    escape(self.config.who(Some(person), false))
+                ),
+                GroupAction::NameChange(name) => {
+                    format!("renamed the conversation to <b>{}</b>", escape(name))
+                }
+                GroupAction::ParticipantLeft => String::from("left the conversation."),
+                GroupAction::GroupIconChanged => String::from("changed the group photo."),
+                GroupAction::GroupIconRemoved => String::from("removed the group photo."),
+            },
+            Some(Announcement::AudioMessageKept) => String::from("kept an audio message."),
+            Some(Announcement::FullyUnsent) => {
+                String::from(r#"<span class="unsent">unsent a message!</span>"#)
+            }
+            Some(Announcement::Unknown(num)) => format!("performed unknown action {num}."),
+            None => String::from("performed an action that could not be formatted."),
+        };
+
+        format!(
+            r##"<div class="announcement" id="{}"><p><span class="timestamp"><a href="#{}">{}</a></span> {who} {action}</p></div>
+"##,
+            escape(&msg.guid),
+            escape(&msg.guid),
+            escape(&timestamp)
+        )
+    }
+
+    fn format_shareplay(&self) -> &str {
+        "SharePlay Message Ended"
+    }
+
+    fn format_shared_location(&self, msg: &'a Message) -> &str {
+        // Handle Shared Location
+        if msg.started_sharing_location() {
+            return "Started sharing location!";
+        } else if msg.stopped_sharing_location() {
+            return "Stopped sharing location!";
+        }
+        "Shared location!"
+    }
+
+    fn format_edited(
+        &self,
+        msg: &'a Message,
+        edited_message: &'a EditedMessage,
+        message_part_idx: usize,
+        _: &str,
+    ) -> Option<String> {
+        let edited_message_part = edited_message.part(message_part_idx)?;
+        let mut out_s = String::new();
+
+        match edited_message_part.status {
+            EditStatus::Edited => {
+                let history = &edited_message_part.edit_history;
+                out_s.push_str(r#"<div class="edited"><table><tbody>"#);
+                let mut previous_timestamp: Option<&i64> = None;
+                for (idx, event) in history.iter().enumerate() {
+                    // The latest version of the message is the footer of the table
+                    if idx + 1 == history.len() {
+                        out_s.push_str("</tbody><tfoot>");
+                    }
+
+                    let timestamp = match previous_timestamp {
+                        // The original message gets an absolute timestamp
+                        None => format(&get_local_time(&event.date, &self.config.offset)),
+                        // Subsequent edits get a relative timestamp
+                        Some(previous_timestamp) => readable_diff(
+                            get_local_time(previous_timestamp, &self.config.offset),
+                            get_local_time(&event.date, &self.config.offset),
+                        )
+                        .map(|diff| format!("Edited {diff} later"))
+                        .unwrap_or_default(),
+                    };
+                    previous_timestamp = Some(&event.date);
+
+                    let text = event.text.as_deref().map(escape).unwrap_or_default();
+                    let _ = write!(
+                        out_s,
+                        r#"<tr><td><span class="timestamp">{}</span></td><td>{text}</td></tr>"#,
+                        escape(&timestamp)
+                    );
+
+                    if idx + 1 == history.len() {
+                        out_s.push_str("</tfoot>");
+                    }
+                }
+                if history.is_empty() {
+                    out_s.push_str("</tbody>");
+                }
+                out_s.push_str("</table></div>");
+            }
+            EditStatus::Unsent => {
+                let who = escape(if msg.is_from_me() { self.you() } else { "They" });
+                let _ = match readable_diff(
+                    msg.date(&self.config.offset),
+                    msg.date_edited(&self.config.offset),
+                ) {
+                    Some(diff) => write!(
+                        out_s,
+                        r#"<span class="unsent">{who} unsent this message part {diff} after sending!</span>"#
+                    ),
+                    None => write!(
+                        out_s,
+                        r#"<span class="unsent">{who} unsent this message part!</span>"#
+                    ),
+                };
+            }
+            EditStatus::Original => return None,
+        }
+
+        Some(out_s)
+    }
+
+    fn format_attributes(&'a self, text: &'a str, attributes: &'a [TextAttributes]) -> String {
+        render_text(text, attributes, RenderFormat::Html)
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+/// Closing tags written at the end of every page
+const FOOTER: &str = "</body></html>\n";
+
+/// Build the start of a page, including the embedded stylesheet
+fn header(title: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="UTF-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{}</title>
+<style>
+{STYLE}
+</style>
+</head>
+<body>
+"#,
+        escape(title)
+    )
+}
+
+/// Escape text so it can be placed in HTML content or attribute values
+fn escape(text: &str) -> String {
+    let mut out_s = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out_s.push_str("&amp;"),
+            '<' => out_s.push_str("&lt;"),
+            '>' => out_s.push_str("&gt;"),
+            '"' => out_s.push_str("&quot;"),
+            '\'' => out_s.push_str("&#39;"),
+            c => out_s.push(c),
+        }
+    }
+    out_s
+}
+
+/// Build a relative link to a file in the export directory
+fn encode_path(path: &Path) -> String {
+    path.components()
+        .map(|component| encode_uri(&component.as_os_str().to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Percent-encode a file name so it can be used in a relative link
+fn encode_uri(text: &str) -> String {
+    let mut out_s = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out_s.push(byte as char);
+            }
+            byte => {
+                let _ = write!(out_s, "%{byte:02X}");
+            }
+        }
+    }
+    out_s
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_dir, read_to_string, remove_dir_all},
+        path::{Path, PathBuf},
+    };
+
+    use imessage_database::{
+        message_types::variants::{Tapback, TapbackAction},
+        util::{
+            fixture::{FixtureBuilder, NewMessage, Schema},
+            typedstream::models::AttributeRun,
+        },
+    };
+
+    use crate::{
+        app::{export_type::ExportType, options::Options, runtime::Config},
+        exporters::html::AppCard,
+    };
+
+    const DATE: i64 = 694_000_000_000_000_000;
+    const MINUTE: i64 = 60_000_000_000;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-html-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Export a database and read the page of its only conversation
+    fn export(db_path: &Path, export_path: &Path) -> String {
+        Config::new(Options::fake(ExportType::Html, db_path, export_path))
+            .unwrap()
+            .start()
+            .unwrap();
+        let page = read_dir(export_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.to_string_lossy().contains("Jenny"))
+            .unwrap();
+        read_to_string(page).unwrap()
+    }
+
+    #[test]
+    fn can_render_conversation() {
+        let dir = temp("conversation");
+        let db_path = dir.join("chat.db");
+        let mut fixture = FixtureBuilder::create(&db_path, Schema::Ios16Newer).unwrap();
+        let handle = fixture.handle("+15558675309", "iMessage").unwrap();
+        let chat = fixture
+            .chat("+15558675309", Some("Jenny"), &[handle])
+            .unwrap();
+        let original = fixture
+            .message(
+                chat,
+                NewMessage::text("<script>alert('hi')</script> & more", DATE).from_handle(handle),
+            )
+            .unwrap();
+        fixture
+            .message(
+                chat,
+                NewMessage::text("A reply", DATE + MINUTE).reply_to(&original.guid, 0),
+            )
+            .unwrap();
+        fixture
+            .message(
+                chat,
+                NewMessage::tapback(
+                    &original.guid,
+                    0,
+                    &Tapback::Loved,
+                    &TapbackAction::Added,
+                    DATE + 2 * MINUTE,
+                ),
+            )
+            .unwrap();
+        fixture
+            .message(
+                chat,
+                NewMessage::new(DATE + 3 * MINUTE).with_text(vec![
+                    AttributeRun::new("Site").with_link("https://a.com/?a=1&b=2"),
+                ]),
+            )
+            .unwrap();
+
+        let page = export(&db_path, &dir.join("export"));
+
+        // Message text is escaped
+        assert!(page.contains(
+            r#"<span class="bubble">&lt;script&gt;alert(&#39;hi&#39;)&lt;/script&gt; &amp; more</span>"#
+        ));
+        assert!(!page.contains("<script>"));
+        assert!(page.contains(r#"<a href="https://a.com/?a=1&amp;b=2">Site</a>"#));
+
+        // The tapback and the reply are rendered under the message they refer to, in that order
+        let original_at = page.find(&format!(r#"id="{}""#, original.guid)).unwrap();
+        let tapback_at = page
+            .find(r#"<div class="tapbacks"><hr><p>Tapbacks:</p><div class="tapback"><span><b>Loved</b> by Me</span></div></div>"#)
+            .unwrap();
+        let reply_at = page
+            .find(r#"<div class="replies"><div class="reply">"#)
+            .unwrap();
+        assert!(original_at < tapback_at && tapback_at < reply_at);
+        // Replies also appear in the conversation, with a link back to the message they responded to
+        assert_eq!(page.matches(">A reply</span>").count(), 2);
+        assert!(page.contains(&format!(
+            r##"This message responded to an <a href="#{}">earlier message</a>."##,
+            original.guid
+        )));
+        // Tapbacks are not rendered as messages of their own
+        assert_eq!(page.matches(r#"<div class="tapback">"#).count(), 1);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cannot_link_unsafe_urls() {
+        for url in [
+            "javascript:alert(1)",
+            "data:text/html,hi",
+            "vbscript:msgbox",
+        ] {
+            let card = AppCard {
+                url: Some(url),
+                name: Some("App"),
+                ..Default::default()
+            }
+            .render();
+            assert!(!card.contains("href"), "{card}");
+            assert!(card.starts_with(r#"<div class="app"><div class="app_header">"#));
+        }
+
+        let card = AppCard {
+            url: Some("https://a.com/\"onclick=\""),
+            name: Some("App"),
+            ..Default::default()
+        }
+        .render();
+        assert!(
+            card.starts_with(r#"<div class="app"><a href="https://a.com/&quot;onclick=&quot;">"#)
+        );
+    }
+}
@@ -0,0 +1,620 @@
+/*!
+Exports each conversation to a JSON document
+
+Every chat is written to its own file containing the chat, its deduplicated participants, and its messages.
+Messages are split into their body parts; each part carries its text attributes, attachment, edit history,
+tapbacks, and the threaded replies made to it.
+*/
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use tracing::warn;
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::{
+        app::OwnedAppMessage,
+        edited::EditStatus,
+        expressives::OwnedExpressive,
+        variants::{
+            OwnedAnnouncement, OwnedTapback, OwnedURLOverride, OwnedVariant, ParsedBalloon,
+            TapbackAction, Variant,
+        },
+    },
+    tables::{
+        attachment::Attachment,
+        chat::Chat,
+        messages::{
+            Message,
+            models::{BubbleComponent, OwnedBubbleComponent, OwnedGroupAction},
+        },
+        table::{AttributedBody, ORPHANED, Table},
+    },
+    util::dates::get_local_time,
+};
+
+use crate::app::{error::RuntimeError, runtime::Config};
+
+/// A conversation file that is being written to
+struct ChatFile {
+    /// Buffered writer for the file
+    writer: BufWriter<File>,
+    /// `true` once the first message has been written, so later messages are preceded by a comma
+    has_messages: bool,
+}
+
+impl ChatFile {
+    /// Create the file and write everything that precedes the array of messages
+    fn create(path: &Path, header: &ChatHeader) -> Result<Self, RuntimeError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let header = serde_json::to_string(header).map_err(io::Error::from)?;
+        // Reopen the header object so messages can be streamed into it
+        writer.write_all(&header.as_bytes()[..header.len() - 1])?;
+        writer.write_all(br#","messages":["#)?;
+        Ok(ChatFile {
+            writer,
+            has_messages: false,
+        })
+    }
+
+    /// Append a message to the array of messages
+    fn write_message(&mut self, message: &ExportedMessage) -> Result<(), RuntimeError> {
+        if self.has_messages {
+            self.writer.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut self.writer, message).map_err(io::Error::from)?;
+        self.has_messages = true;
+        Ok(())
+    }
+
+    /// Close the array of messages and the document
+    fn finish(mut self) -> Result<(), RuntimeError> {
+        self.writer.write_all(b"]}")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Data written before the messages in a conversation file
+#[derive(Serialize)]
+struct ChatHeader<'a> {
+    /// The chat, or `None` for orphaned messages
+    chat: Option<&'a Chat>,
+    /// The deduplicated participants of the chat
+    participants: Vec<Participant<'a>>,
+}
+
+/// A deduplicated chat participant
+#[derive(Serialize)]
+struct Participant<'a> {
+    /// The deduplicated handle ID
+    handle_id: i32,
+    /// The phone number or email address of the participant
+    id: &'a str,
+}
+
+/// A message and everything attached to it
+#[derive(Serialize)]
+struct ExportedMessage {
+    rowid: i32,
+    guid: String,
+    /// The name of the sender
+    sender: String,
+    handle_id: Option<i32>,
+    is_from_me: bool,
+    service: Option<String>,
+    /// The date the message was written to the database, as RFC 3339
+    date: Option<String>,
+    /// The date the message was delivered, as RFC 3339
+    date_delivered: Option<String>,
+    /// The date the message was read, as RFC 3339
+    date_read: Option<String>,
+    /// The date the message was last edited, as RFC 3339
+    date_edited: Option<String>,
+    text: Option<String>,
+    subject: Option<String>,
+    variant: OwnedVariant,
+    expressive: OwnedExpressive,
+    announcement: Option<OwnedAnnouncement>,
+    group_action: Option<OwnedGroupAction>,
+    /// `true` if the message is in the Recently Deleted folder
+    is_deleted: bool,
+    thread_originator_guid: Option<String>,
+    thread_originator_part: Option<String>,
+    /// The parsed app balloon payload, if any
+    balloon: Option<ExportedBalloon>,
+    /// The parts of the message body
+    parts: Vec<ExportedPart>,
+}
+
+/// A single part of a message body
+#[derive(Serialize)]
+struct ExportedPart {
+    /// The index of the part in the message body
+    index: usize,
+    component: OwnedBubbleComponent,
+    /// The text covered by the part's attributes, for text parts
+    text: Option<String>,
+    attachment: Option<ExportedAttachment>,
+    edit_status: Option<EditStatus>,
+    edit_history: Vec<ExportedEdit>,
+    tapbacks: Vec<ExportedTapback>,
+    replies: Vec<ExportedMessage>,
+}
+
+/// An attachment and the location of its exported copy
+#[derive(Serialize)]
+struct ExportedAttachment {
+    rowid: i32,
+    filename: Option<String>,
+    mime_type: Option<String>,
+    uti: Option<String>,
+    total_bytes: i64,
+    is_sticker: bool,
+    /// The path of the copied file relative to the export directory, or `None` if the file is missing
+    path: Option<PathBuf>,
+}
+
+/// A previous version of a message part
+#[derive(Serialize)]
+struct ExportedEdit {
+    /// The date of the edit, as RFC 3339
+    date: Option<String>,
+    text: Option<String>,
+}
+
+/// A tapback applied to a message part
+#[derive(Serialize)]
+struct ExportedTapback {
+    action: TapbackAction,
+    tapback: OwnedTapback,
+    /// The name of the sender
+    sender: String,
+    /// The date of the tapback, as RFC 3339
+    date: Option<String>,
+    /// The sticker used as a tapback, if any
+    attachment: Option<ExportedAttachment>,
+}
+
+/// The parsed payload of an app message
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ExportedBalloon {
+    /// URL previews and the other messages that use the URL balloon provider
+    Url(OwnedURLOverride),
+    /// Messages generated by other apps
+    App(OwnedAppMessage),
+}
+
+pub struct JSON<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Map of deduplicated chatroom ID to the file its messages are written to
+    files: HashMap<i32, ChatFile>,
+    /// File for messages that do not belong to a chat
+    orphaned: Option<ChatFile>,
+}
+
+impl<'a> JSON<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        JSON {
+            config,
+            files: HashMap::new(),
+            orphaned: None,
+        }
+    }
+
+    /// Write every message matching the query context to its conversation's file
+    pub fn iter_messages(mut self) -> Result<(), RuntimeError> {
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        let mut statement = Message::stream_rows(
+            self.config.db(),
+            self.config.capabilities(),
+            &self.config.options.query_context,
+        )?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Tapbacks and replies are written under the messages they refer to
+            if msg.is_tapback() || msg.is_reply() {
+                continue;
+            }
+
+            let exported = self.export_message(&mut msg)?;
+            self.get_or_create_file(&msg)?.write_message(&exported)?;
+        }
+
+        for (_, file) in self.files.drain() {
+            file.finish()?;
+        }
+        if let Some(file) = self.orphaned.take() {
+            file.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Get the file a message belongs in, creating it if it does not exist
+    fn get_or_create_file(&mut self, message: &Message) -> Result<&mut ChatFile, RuntimeError> {
+        let config = self.config;
+        match config.conversation(message) {
+            Some((chatroom, &real_id)) => {
+                if !self.files.contains_key(&real_id) {
+                    let header = ChatHeader {
+                        chat: Some(chatroom),
+                        participants: config
+                            .chatroom_participants(chatroom.rowid)
+                            .into_iter()
+                            .map(|(handle_id, id)| Participant { handle_id, id })
+                            .collect(),
+                    };
+                    let path = self.path(&config.filename(chatroom, real_id));
+                    self.files
+                        .insert(real_id, ChatFile::create(&path, &header)?);
+                }
+                Ok(self
+                    .files
+                    .get_mut(&real_id)
+                    .expect("file was just inserted"))
+            }
+            None => {
+                if self.orphaned.is_none() {
+                    let header = ChatHeader {
+                        chat: None,
+                        participants: vec![],
+                    };
+                    self.orphaned = Some(ChatFile::create(&self.path(ORPHANED), &header)?);
+                }
+                Ok(self.orphaned.as_mut().expect("file was just created"))
+            }
+        }
+    }
+
+    /// Build the path to an export file from its name
+    fn path(&self, name: &str) -> PathBuf {
+        let mut path = self.config.options.export_path.join(name);
+        path.set_extension("json");
+        path
+    }
+
+    /// Build the serializable representation of a message, including its tapbacks and replies
+    fn export_message(&self, msg: &mut Message) -> Result<ExportedMessage, RuntimeError> {
+        let db = self.config.db();
+        if let Err(why) = msg.generate_text(db) {
+            // Messages without text may still have attachments or app data
+            if msg.num_attachments == 0 && msg.balloon_bundle_id.is_none() {
+                warn!("Unable to generate text for message {}: {why}", msg.rowid);
+            }
+        }
+
+        let mut attachments =
+            Attachment::from_message(db, self.config.capabilities(), msg)?.into_iter();
+        let mut replies = msg.get_replies(db, self.config.capabilities())?;
+        let tapbacks = self.config.tapbacks.get(&msg.guid);
+
+        let mut parts = vec![];
+        for (index, component) in msg.body().iter().enumerate() {
+            let text = match component {
+                BubbleComponent::Text(attributes) => {
+                    let start = attributes.iter().map(|a| a.start).min().unwrap_or(0);
+                    let end = attributes.iter().map(|a| a.end).max().unwrap_or(0);
+                    msg.text
+                        .as_deref()
+                        .and_then(|text| text.get(start..end))
+                        .map(str::to_string)
+                }
+                _ => None,
+            };
+
+            let attachment = match component {
+                BubbleComponent::Attachment(_) => attachments
+                    .next()
+                    .map(|mut attachment| self.export_attachment(&mut attachment, msg)),
+                _ => None,
+            };
+
+            let edited = msg.edited_parts.as_ref().and_then(|e| e.part(index));
+
+            let tapbacks = tapbacks
+                .and_then(|tapbacks| tapbacks.get(&index))
+                .into_iter()
+                .flatten()
+                .filter_map(|tapback| self.export_tapback(tapback).transpose())
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let replies = replies
+                .remove(&index)
+                .unwrap_or_default()
+                .iter_mut()
+                .map(|reply| self.export_message(reply))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            parts.push(ExportedPart {
+                index,
+                component: component.into(),
+                text,
+                attachment,
+                edit_status: edited.map(|part| part.status.clone()),
+                edit_history: edited
+                    .map(|part| {
+                        part.edit_history
+                            .iter()
+                            .map(|event| ExportedEdit {
+                                date: self.date(event.date),
+                                text: event.text.clone(),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                tapbacks,
+                replies,
+            });
+        }
+
+        Ok(ExportedMessage {
+            rowid: msg.rowid,
+            guid: msg.guid.clone(),
+            sender: self.config.who(msg.handle_id, msg.is_from_me()).to_string(),
+            handle_id: msg.handle_id,
+            is_from_me: msg.is_from_me(),
+            service: msg.service.clone(),
+            date: self.date(msg.date),
+            date_delivered: self.date(msg.date_delivered),
+            date_read: self.date(msg.date_read),
+            date_edited: self.date(msg.date_edited),
+            text: msg.text.clone(),
+            subject: msg.subject.clone(),
+            variant: (&msg.variant()).into(),
+            expressive: (&msg.get_expressive()).into(),
+            announcement: msg.get_announcement().as_ref().map(Into::into),
+            group_action: msg.group_action().as_ref().map(Into::into),
+            is_deleted: msg.is_deleted(),
+            thread_originator_guid: msg.thread_originator_guid.clone(),
+            thread_originator_part: msg.thread_originator_part.clone(),
+            balloon: self.export_balloon(msg),
+            parts,
+        })
+    }
+
+    /// Copy an attachment and describe it
+    fn export_attachment(&self, attachment: &mut Attachment, msg: &Message) -> ExportedAttachment {
+        let path = self.config.copy_attachment(attachment, msg);
+        ExportedAttachment {
+            rowid: attachment.rowid,
+            filename: attachment.filename().map(str::to_string),
+            mime_type: attachment.mime_type.clone(),
+            uti: attachment.uti.clone(),
+            total_bytes: attachment.total_bytes,
+            is_sticker: attachment.is_sticker,
+            path,
+        }
+    }
+
+    /// Describe a tapback, resolving sticker tapbacks to their attachment
+    fn export_tapback(&self, tapback: &Message) -> Result<Option<ExportedTapback>, RuntimeError> {
+        let Variant::Tapback(_, action, kind) = tapback.variant() else {
+            return Ok(None);
+        };
+
+        let attachment = if tapback.has_attachments() {
+            Attachment::from_message(self.config.db(), self.config.capabilities(), tapback)?
+                .into_iter()
+                .next()
+                .map(|mut attachment| self.export_attachment(&mut attachment, tapback))
+        } else {
+            None
+        };
+
+        Ok(Some(ExportedTapback {
+            action,
+            tapback: (&kind).into(),
+            sender: self
+                .config
+                .who(tapback.handle_id, tapback.is_from_me())
+                .to_string(),
+            date: self.date(tapback.date),
+            attachment,
+        }))
+    }
+
+    /// Parse the payload of an app message, if it has one
+    fn export_balloon(&self, msg: &Message) -> Option<ExportedBalloon> {
+        let parsed = msg.balloon_payload(self.config.db())?.and_then(|payload| {
+            payload.parse().map(|balloon| match balloon {
+                ParsedBalloon::URL(balloon) => ExportedBalloon::Url((&balloon).into()),
+                ParsedBalloon::App(balloon) => ExportedBalloon::App((&balloon).into()),
+            })
+        });
+        parsed
+            .inspect_err(|why| warn!("Unable to parse app message {}: {why}", msg.rowid))
+            .ok()
+    }
+
+    /// Format a timestamp from the database as RFC 3339, or `None` if it is not set
+    fn date(&self, stamp: i64) -> Option<String> {
+        if stamp == 0 {
+            return None;
+        }
+        get_local_time(&stamp, &self.config.offset)
+            .ok()
+            .map(|date| date.to_rfc3339())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_dir, read_to_string, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use serde_json::Value;
+
+    use imessage_database::{
+        message_types::variants::{Tapback, TapbackAction},
+        util::fixture::{FixtureBuilder, NewMessage, Schema},
+    };
+
+    use crate::app::{export_type::ExportType, options::Options, runtime::Config};
+
+    const DATE: i64 = 694_000_000_000_000_000;
+    const MINUTE: i64 = 60_000_000_000;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-json-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn can_export_conversation() {
+        let dir = temp("conversation");
+        let db_path = dir.join("chat.db");
+        let mut fixture = FixtureBuilder::create(&db_path, Schema::Ios16Newer).unwrap();
+        let handle = fixture.handle("+15558675309", "iMessage").unwrap();
+        let chat = fixture
+            .chat("+15558675309", Some("Jenny"), &[handle])
+            .unwrap();
+        let original = fixture
+            .message(
+                chat,
+                NewMessage::text("Hello", DATE)
+                    .from_handle(handle)
+                    .with_attachment("photo.png", "image/png", b"png bytes"),
+            )
+            .unwrap();
+        fixture
+            .message(
+                chat,
+                NewMessage::text("A reply", DATE + MINUTE).reply_to(&original.guid, 0),
+            )
+            .unwrap();
+        fixture
+            .message(
+                chat,
+                NewMessage::tapback(
+                    &original.guid,
+                    0,
+                    &Tapback::Loved,
+                    &TapbackAction::Added,
+                    DATE + 2 * MINUTE,
+                ),
+            )
+            .unwrap();
+
+        let export_path = dir.join("export");
+        Config::new(Options::fake(ExportType::Json, &db_path, &export_path))
+            .unwrap()
+            .start()
+            .unwrap();
+        let file = read_dir(&export_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.to_string_lossy().contains("Jenny"))
+            .unwrap();
+        let document: Value = serde_json::from_str(&read_to_string(file).unwrap()).unwrap();
+
+        assert_eq!(document["chat"]["display_name"], "Jenny");
+        assert_eq!(document["chat"]["chat_identifier"], "+15558675309");
+        assert_eq!(document["participants"][0]["id"], "+15558675309");
+
+        // Replies and tapbacks are nested under the part they refer to, not listed as messages
+        let messages = document["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 1);
+        let message = &messages[0];
+        assert_eq!(message["guid"], original.guid.as_str());
+        assert_eq!(message["sender"], "+15558675309");
+        assert_eq!(message["is_from_me"], false);
+        assert_eq!(message["variant"], "normal");
+        assert!(message["date"].is_string());
+
+        let parts = message["parts"].as_array().unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0]["index"], 0);
+        assert_eq!(parts[0]["text"], "Hello");
+        assert_eq!(parts[0]["tapbacks"][0]["tapback"], "loved");
+        assert_eq!(parts[0]["tapbacks"][0]["action"], "added");
+        assert_eq!(parts[0]["tapbacks"][0]["sender"], "Me");
+        let reply = &parts[0]["replies"][0];
+        assert_eq!(reply["text"], "A reply");
+        assert_eq!(reply["is_from_me"], true);
+        assert_eq!(reply["thread_originator_guid"], original.guid.as_str());
+
+        // Attachments are copied into the export and linked by their relative path
+        let attachment = &parts[1]["attachment"];
+        assert_eq!(attachment["filename"], "photo.png");
+        assert_eq!(attachment["mime_type"], "image/png");
+        let path = attachment["path"].as_str().unwrap();
+        assert_eq!(read_to_string(export_path.join(path)).unwrap(), "png bytes");
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_export_attachments_with_the_same_name() {
+        let dir = temp("same-name");
+        let db_path = dir.join("chat.db");
+        let mut fixture = FixtureBuilder::create(&db_path, Schema::Ios16Newer).unwrap();
+        let handle = fixture.handle("+15558675309", "iMessage").unwrap();
+        let chat = fixture
+            .chat("+15558675309", Some("Jenny"), &[handle])
+            .unwrap();
+        fixture
+            .message(
+                chat,
+                NewMessage::new(DATE)
+                    .from_handle(handle)
+                    .with_attachment("photo.png", "image/png", b"first")
+                    .with_attachment("photo.png", "image/png", b"second"),
+            )
+            .unwrap();
+
+        let export_path = dir.join("export");
+        Config::new(Options::fake(ExportType::Json, &db_path, &export_path))
+            .unwrap()
+            .start()
+            .unwrap();
+        let file = read_dir(&export_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.to_string_lossy().contains("Jenny"))
+            .unwrap();
+        let document: Value = serde_json::from_str(&read_to_string(file).unwrap()).unwrap();
+
+        let parts = document["messages"][0]["parts"].as_array().unwrap();
+        let paths = parts
+            .iter()
+            .map(|part| part["attachment"]["path"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(paths.len(), 2);
+        assert_ne!(paths[0], paths[1]);
+        assert_eq!(read_to_string(export_path.join(paths[0])).unwrap(), "first");
+        assert_eq!(
+            read_to_string(export_path.join(paths[1])).unwrap(),
+            "second"
+        );
+
+        remove_dir_all(dir).unwrap();
+    }
+}
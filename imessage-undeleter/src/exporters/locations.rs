@@ -0,0 +1,531 @@
+/*!
+Exports every shared location to a single GeoJSON or KML file
+
+Each of these messages becomes a feature:
+- Places shared from the Maps app, located by the coordinates in their Apple Maps URL
+- Check In messages, which name a place but do not locate it
+- Find My messages and the older messages that start or stop sharing a location, which do not locate it either
+
+Features without coordinates are still exported, with a `null` geometry in GeoJSON and no `<Point>` in KML, so
+every shared location appears in the export even when it cannot be drawn on a map. Each feature records who sent
+it, when, the conversation it was sent in, the place's address components, and a link that opens the message.
+*/
+
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use serde_json::{Map, Value, json};
+use tracing::warn;
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::{
+        check_in::CheckInMessage,
+        find_my::FindMyMessage,
+        placemark::{Coordinates, OwnedPlacemark},
+        variants::{CustomBalloon, ParsedBalloon, URLOverride, Variant},
+    },
+    tables::{messages::Message, table::Table},
+    util::dates::get_local_time,
+};
+
+use crate::app::{error::RuntimeError, runtime::Config};
+
+/// Name of the exported file, without an extension
+pub const LOCATIONS_FILENAME: &str = "locations";
+/// Name of the document in a KML export
+const KML_DOCUMENT_NAME: &str = "Shared locations";
+
+/// The file formats shared locations can be written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocationFormat {
+    /// A GeoJSON `FeatureCollection`, from RFC 7946
+    GeoJson,
+    /// A KML document, from the OGC KML 2.2 standard
+    Kml,
+}
+
+impl LocationFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            LocationFormat::GeoJson => "geojson",
+            LocationFormat::Kml => "kml",
+        }
+    }
+}
+
+/// The kinds of messages that share a location
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LocationKind {
+    /// A place shared from the Maps app
+    Placemark,
+    /// A Check In message
+    CheckIn,
+    /// A Find My message
+    FindMy,
+    /// The sender started sharing their location
+    StartedSharing,
+    /// The sender stopped sharing their location
+    StoppedSharing,
+}
+
+impl LocationKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            LocationKind::Placemark => "placemark",
+            LocationKind::CheckIn => "check_in",
+            LocationKind::FindMy => "find_my",
+            LocationKind::StartedSharing => "started_sharing",
+            LocationKind::StoppedSharing => "stopped_sharing",
+        }
+    }
+}
+
+/// A location shared in a message
+struct SharedLocation {
+    kind: LocationKind,
+    /// The GUID of the message that shared the location
+    guid: String,
+    /// The name of the sender
+    sender: String,
+    /// The date the message was sent, as RFC 3339
+    date: Option<String>,
+    /// The name of the conversation the message was sent in
+    chat: Option<String>,
+    /// The name of the place, or a description of the message if the place is not named
+    name: Option<String>,
+    /// Where the place is, if the message locates it
+    coordinates: Option<Coordinates>,
+    /// The address components of the place
+    placemark: OwnedPlacemark,
+    /// The URL the message links to
+    url: Option<String>,
+}
+
+impl SharedLocation {
+    /// A link that opens the message in Messages.app
+    fn link(&self) -> String {
+        format!("sms://open?message-guid={}", self.guid)
+    }
+
+    /// The address components of the place that are set, with their GeoJSON property names
+    fn address_components(&self) -> Vec<(&'static str, &str)> {
+        let placemark = &self.placemark;
+        [
+            ("address", &placemark.address),
+            ("street", &placemark.street),
+            ("sub_locality", &placemark.sub_locality),
+            ("city", &placemark.city),
+            (
+                "sub_administrative_area",
+                &placemark.sub_administrative_area,
+            ),
+            ("state", &placemark.state),
+            ("postal_code", &placemark.postal_code),
+            ("country", &placemark.country),
+            ("iso_country_code", &placemark.iso_country_code),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value.as_deref()?)))
+        .collect()
+    }
+}
+
+pub struct Locations<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// The file format to write
+    format: LocationFormat,
+    /// The locations found so far, in the order they were sent
+    locations: Vec<SharedLocation>,
+}
+
+impl<'a> Locations<'a> {
+    pub fn new(config: &'a Config, format: LocationFormat) -> Self {
+        Locations {
+            config,
+            format,
+            locations: vec![],
+        }
+    }
+
+    /// Write every location shared in a message matching the query context
+    pub fn iter_messages(mut self) -> Result<(), RuntimeError> {
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        let mut statement = Message::stream_rows(
+            self.config.db(),
+            self.config.capabilities(),
+            &self.config.options.query_context,
+        )?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let msg = Message::extract(message)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            if let Some(location) = self.find_location(&msg) {
+                self.locations.push(location);
+            }
+        }
+
+        let contents = match self.format {
+            LocationFormat::GeoJson => self.to_geojson()?,
+            LocationFormat::Kml => self.to_kml(),
+        };
+        let mut path = self.config.options.export_path.join(LOCATIONS_FILENAME);
+        path.set_extension(self.format.extension());
+        let mut file = BufWriter::new(File::create(&path)?);
+        file.write_all(contents.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Get the location a message shares, if any
+    fn find_location(&self, msg: &Message) -> Option<SharedLocation> {
+        if msg.started_sharing_location() {
+            return Some(self.location(msg, LocationKind::StartedSharing));
+        }
+        if msg.stopped_sharing_location() {
+            return Some(self.location(msg, LocationKind::StoppedSharing));
+        }
+
+        let Variant::App(balloon) = msg.variant() else {
+            return None;
+        };
+        if !matches!(
+            balloon,
+            CustomBalloon::URL | CustomBalloon::CheckIn | CustomBalloon::FindMy
+        ) {
+            return None;
+        }
+        let payload = msg.balloon_payload(self.config.db())?;
+
+        let parsed = payload.and_then(|payload| {
+            Ok(match payload.parse()? {
+                ParsedBalloon::URL(URLOverride::SharedPlacemark(placemark)) => {
+                    let mut location = self.location(msg, LocationKind::Placemark);
+                    location.name = placemark
+                        .placemark
+                        .name
+                        .or(placemark.place_name)
+                        .map(str::to_string);
+                    location.coordinates = placemark.coordinates();
+                    location.placemark = (&placemark.placemark).into();
+                    location.url = placemark.get_url().map(str::to_string);
+                    Some(location)
+                }
+                ParsedBalloon::URL(_) => None,
+                ParsedBalloon::App(bubble) => match balloon {
+                    CustomBalloon::CheckIn => {
+                        let check_in = CheckInMessage::from_app(&bubble);
+                        let mut location = self.location(msg, LocationKind::CheckIn);
+                        location.name = check_in.location.or(check_in.caption).map(str::to_string);
+                        Some(location)
+                    }
+                    CustomBalloon::FindMy => {
+                        let find_my = FindMyMessage::from_app(&bubble);
+                        let mut location = self.location(msg, LocationKind::FindMy);
+                        location.name = find_my.description.map(str::to_string);
+                        Some(location)
+                    }
+                    _ => None,
+                },
+            })
+        });
+        parsed
+            .inspect_err(|why| warn!("Unable to parse location in message {}: {why}", msg.rowid))
+            .ok()
+            .flatten()
+    }
+
+    /// Describe who shared a location, when, and where, without any details of the place
+    fn location(&self, msg: &Message, kind: LocationKind) -> SharedLocation {
+        SharedLocation {
+            kind,
+            guid: msg.guid.clone(),
+            sender: self.config.who(msg.handle_id, msg.is_from_me()).to_string(),
+            date: get_local_time(&msg.date, &self.config.offset)
+                .ok()
+                .map(|date| date.to_rfc3339()),
+            chat: self
+                .config
+                .conversation(msg)
+                .map(|(chatroom, &real_id)| self.config.filename(chatroom, real_id)),
+            name: None,
+            coordinates: None,
+            placemark: OwnedPlacemark::default(),
+            url: None,
+        }
+    }
+
+    /// Build a GeoJSON `FeatureCollection` of every location
+    fn to_geojson(&self) -> Result<String, RuntimeError> {
+        let features: Vec<Value> = self
+            .locations
+            .iter()
+            .map(|location| {
+                let mut properties = Map::new();
+                properties.insert("kind".to_string(), json!(location.kind.as_str()));
+                properties.insert("name".to_string(), json!(location.name));
+                properties.insert("sender".to_string(), json!(location.sender));
+                properties.insert("date".to_string(), json!(location.date));
+                properties.insert("chat".to_string(), json!(location.chat));
+                properties.insert("guid".to_string(), json!(location.guid));
+                properties.insert("link".to_string(), json!(location.link()));
+                properties.insert("url".to_string(), json!(location.url));
+                for (key, value) in location.address_components() {
+                    properties.insert(key.to_string(), json!(value));
+                }
+
+                // GeoJSON positions are longitude first
+                let geometry = location.coordinates.map(|point| {
+                    json!({
+                        "type": "Point",
+                        "coordinates": [point.longitude, point.latitude],
+                    })
+                });
+                json!({
+                    "type": "Feature",
+                    "id": location.guid,
+                    "geometry": geometry,
+                    "properties": properties,
+                })
+            })
+            .collect();
+
+        let collection = json!({
+            "type": "FeatureCollection",
+            "features": features,
+        });
+        Ok(serde_json::to_string_pretty(&collection).map_err(io::Error::from)?)
+    }
+
+    /// Build a KML document with a `Placemark` for every location
+    fn to_kml(&self) -> String {
+        let mut out_s = String::new();
+        out_s.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        out_s.push('\n');
+        out_s.push_str(r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#);
+        out_s.push('\n');
+        let _ = writeln!(
+            out_s,
+            "<Document>\n<name>{}</name>",
+            escape(KML_DOCUMENT_NAME)
+        );
+
+        for location in &self.locations {
+            let _ = writeln!(out_s, "<Placemark id=\"{}\">", escape(&location.guid));
+            let name = location.name.as_deref().unwrap_or(location.kind.as_str());
+            let _ = writeln!(out_s, "<name>{}</name>", escape(name));
+            let mut description = format!("Shared by {}", location.sender);
+            if let Some(chat) = &location.chat {
+                let _ = write!(description, " in {chat}");
+            }
+            let _ = writeln!(out_s, "<description>{}</description>", escape(&description));
+            if let Some(date) = &location.date {
+                let _ = writeln!(
+                    out_s,
+                    "<TimeStamp><when>{}</when></TimeStamp>",
+                    escape(date)
+                );
+            }
+            if let Some(address) = &location.placemark.address {
+                let _ = writeln!(out_s, "<address>{}</address>", escape(address));
+            }
+
+            out_s.push_str("<ExtendedData>\n");
+            let link = location.link();
+            let mut data = vec![
+                ("kind", location.kind.as_str()),
+                ("sender", location.sender.as_str()),
+                ("guid", location.guid.as_str()),
+                ("link", link.as_str()),
+            ];
+            data.extend(location.url.as_deref().map(|url| ("url", url)));
+            data.extend(location.address_components());
+            for (key, value) in data {
+                let _ = writeln!(
+                    out_s,
+                    "<Data name=\"{key}\"><value>{}</value></Data>",
+                    escape(value)
+                );
+            }
+            out_s.push_str("</ExtendedData>\n");
+
+            // KML coordinates are longitude first
+            if let Some(point) = location.coordinates {
+                let _ = writeln!(
+                    out_s,
+                    "<Point><coordinates>{},{}</coordinates></Point>",
+                    point.longitude, point.latitude
+                );
+            }
+            out_s.push_str("</Placemark>\n");
+        }
+
+        out_s.push_str("</Document>\n</kml>\n");
+        out_s
+    }
+}
+
+/// Escape text for use in an XML element or attribute
+fn escape(text: &str) -> String {
+    let mut out_s = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out_s.push_str("&amp;"),
+            '<' => out_s.push_str("&lt;"),
+            '>' => out_s.push_str("&gt;"),
+            '"' => out_s.push_str("&quot;"),
+            '\'' => out_s.push_str("&apos;"),
+            // Other control characters cannot be represented in XML 1.0
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => out_s.push(c),
+        }
+    }
+    out_s
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read, read_to_string, remove_dir_all},
+        path::{Path, PathBuf},
+    };
+
+    use rusqlite::params;
+    use serde_json::Value;
+
+    use imessage_database::util::fixture::{FixtureBuilder, NewMessage, Schema};
+
+    use crate::app::{export_type::ExportType, options::Options, runtime::Config};
+
+    const DATE: i64 = 694_000_000_000_000_000;
+    const MINUTE: i64 = 60_000_000_000;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-locations-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Create a database with a place shared from the Maps app and a message that starts sharing a location
+    fn locations_db(path: &Path) {
+        let mut fixture = FixtureBuilder::create(path, Schema::Ios16Newer).unwrap();
+        let handle = fixture.handle("+15558675309", "iMessage").unwrap();
+        let chat = fixture
+            .chat("+15558675309", Some("Jenny"), &[handle])
+            .unwrap();
+        let placemark = fixture
+            .message(chat, NewMessage::new(DATE).from_handle(handle))
+            .unwrap();
+        let sharing = fixture
+            .message(chat, NewMessage::new(DATE + MINUTE))
+            .unwrap();
+
+        let payload = read(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../imessage-database/test_data/shared_placemark/SharedPlacemark.plist"),
+        )
+        .unwrap();
+        fixture
+            .connection()
+            .execute(
+                "UPDATE message SET balloon_bundle_id = ?1, payload_data = ?2 WHERE ROWID = ?3",
+                params![
+                    "com.apple.messages.URLBalloonProvider",
+                    payload,
+                    placemark.rowid
+                ],
+            )
+            .unwrap();
+        fixture
+            .connection()
+            .execute(
+                "UPDATE message SET item_type = 4 WHERE ROWID = ?1",
+                params![sharing.rowid],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn can_export_geojson() {
+        let dir = temp("geojson");
+        let db_path = dir.join("chat.db");
+        locations_db(&db_path);
+
+        let export_path = dir.join("export");
+        Config::new(Options::fake(ExportType::GeoJson, &db_path, &export_path))
+            .unwrap()
+            .start()
+            .unwrap();
+        let document: Value =
+            serde_json::from_str(&read_to_string(export_path.join("locations.geojson")).unwrap())
+                .unwrap();
+
+        assert_eq!(document["type"], "FeatureCollection");
+        let features = document["features"].as_array().unwrap();
+        assert_eq!(features.len(), 2);
+
+        // Positions are longitude first
+        let placemark = features
+            .iter()
+            .find(|feature| feature["properties"]["kind"] == "placemark")
+            .unwrap();
+        assert_eq!(placemark["geometry"]["type"], "Point");
+        assert_eq!(
+            placemark["geometry"]["coordinates"],
+            serde_json::json!([-118.508212, 33.450858])
+        );
+        assert_eq!(placemark["properties"]["sender"], "+15558675309");
+
+        // Locations without coordinates are still exported
+        let sharing = features
+            .iter()
+            .find(|feature| feature["properties"]["kind"] == "started_sharing")
+            .unwrap();
+        assert!(sharing["geometry"].is_null());
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_export_kml() {
+        let dir = temp("kml");
+        let db_path = dir.join("chat.db");
+        locations_db(&db_path);
+
+        let export_path = dir.join("export");
+        Config::new(Options::fake(ExportType::Kml, &db_path, &export_path))
+            .unwrap()
+            .start()
+            .unwrap();
+        let document = read_to_string(export_path.join("locations.kml")).unwrap();
+
+        assert!(document.starts_with(r#"<?xml version="1.0" encoding="UTF-8"?>"#));
+        assert_eq!(document.matches("<Placemark id=").count(), 2);
+        // Coordinates are longitude first, and only places with coordinates have a point
+        assert_eq!(document.matches("<Point>").count(), 1);
+        assert!(
+            document.contains("<Point><coordinates>-118.508212,33.450858</coordinates></Point>")
+        );
+
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,2 +1,12 @@
+#![allow(clippy::upper_case_acronyms)]
+
+pub mod csv;
+pub mod email;
 pub mod exporter;
+pub mod html;
+pub mod json;
+pub mod locations;
+pub mod parquet;
+pub mod sms_backup;
+pub mod tabular;
 pub mod txt;
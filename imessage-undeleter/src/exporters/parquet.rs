@@ -0,0 +1,414 @@
+/*!
+Exports every message and every detected deletion to Parquet tables
+
+See [`tabular`](crate::exporters::tabular) for the columns in each table. Columns use native types: IDs are integers,
+flags are booleans, and dates are microsecond timestamps in UTC.
+*/
+
+use std::{
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use arrow_array::{
+    ArrayRef, BooleanArray, Int32Array, Int64Array, RecordBatch, StringArray,
+    TimestampMicrosecondArray,
+    builder::{ListBuilder, StringBuilder},
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, Utc};
+use parquet::{
+    arrow::ArrowWriter, basic::Compression, errors::ParquetError,
+    file::properties::WriterProperties,
+};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{messages::Message, table::Table},
+};
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config},
+    exporters::tabular::{
+        DELETION_COLUMNS, DELETIONS_TABLE, DeletionRow, MESSAGE_COLUMNS, MESSAGES_TABLE, MessageRow,
+    },
+};
+
+/// Number of messages buffered before they are written as a row group
+const BATCH_SIZE: usize = 8192;
+/// Timezone stored with every timestamp column
+const TIMEZONE: &str = "UTC";
+
+pub struct Parquet<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Table that every message is written to
+    messages: ArrowWriter<File>,
+    /// Messages that have not been written yet
+    buffer: Vec<MessageRow>,
+}
+
+impl<'a> Parquet<'a> {
+    pub fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        Ok(Parquet {
+            messages: create_table(&path(config, MESSAGES_TABLE), message_schema())?,
+            buffer: Vec::with_capacity(BATCH_SIZE),
+            config,
+        })
+    }
+
+    /// Write every message matching the query context, then every deletion in the tracker's state database
+    pub fn iter_messages(mut self) -> Result<(), RuntimeError> {
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        let mut statement = Message::stream_rows(
+            self.config.db(),
+            self.config.capabilities(),
+            &self.config.options.query_context,
+        )?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            self.buffer
+                .push(MessageRow::from_message(self.config, &mut msg));
+            if self.buffer.len() >= BATCH_SIZE {
+                self.flush()?;
+            }
+        }
+        self.flush()?;
+        self.messages.close().map_err(disk_error)?;
+
+        let deletions = DeletionRow::load(&self.config.options.state_db_path)?;
+        let mut table = create_table(&path(self.config, DELETIONS_TABLE), deletion_schema())?;
+        table
+            .write(&deletion_batch(&deletions)?)
+            .map_err(disk_error)?;
+        table.close().map_err(disk_error)?;
+
+        Ok(())
+    }
+
+    /// Write the buffered messages to the table
+    fn flush(&mut self) -> Result<(), RuntimeError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let batch = message_batch(&self.buffer)?;
+        self.messages.write(&batch).map_err(disk_error)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+/// Build the path to a table from its name
+fn path(config: &Config, name: &str) -> PathBuf {
+    let mut path = config.options.export_path.join(name);
+    path.set_extension("parquet");
+    path
+}
+
+/// Create a Snappy compressed table
+fn create_table(path: &Path, schema: SchemaRef) -> Result<ArrowWriter<File>, RuntimeError> {
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    ArrowWriter::try_new(File::create(path)?, schema, Some(properties)).map_err(disk_error)
+}
+
+/// Build a schema from column names and their types, in the same order
+fn schema(columns: &[&str], types: Vec<(DataType, bool)>) -> SchemaRef {
+    Arc::new(Schema::new(
+        columns
+            .iter()
+            .zip(types)
+            .map(|(name, (data_type, nullable))| Field::new(*name, data_type, nullable))
+            .collect::<Vec<_>>(),
+    ))
+}
+
+fn timestamp() -> DataType {
+    DataType::Timestamp(TimeUnit::Microsecond, Some(TIMEZONE.into()))
+}
+
+fn message_schema() -> SchemaRef {
+    schema(
+        MESSAGE_COLUMNS,
+        vec![
+            (DataType::Int32, false),   // rowid
+            (DataType::Utf8, false),    // guid
+            (DataType::Int32, true),    // chat_id
+            (DataType::Utf8, true),     // chat_identifier
+            (DataType::Utf8, true),     // chat_name
+            (DataType::Int32, true),    // handle_id
+            (DataType::Utf8, false),    // sender
+            (DataType::Boolean, false), // is_from_me
+            (DataType::Utf8, true),     // service
+            (DataType::Utf8, false),    // variant
+            (DataType::Utf8, true),     // balloon_bundle_id
+            (timestamp(), true),        // date
+            (timestamp(), true),        // date_delivered
+            (timestamp(), true),        // date_read
+            (timestamp(), true),        // date_edited
+            (DataType::Boolean, false), // is_edited
+            (DataType::Boolean, false), // is_unsent
+            (DataType::Boolean, false), // is_deleted
+            (DataType::Boolean, false), // is_reply
+            (DataType::Utf8, true),     // thread_originator_guid
+            (DataType::Utf8, true),     // associated_message_guid
+            (DataType::Int32, false),   // num_attachments
+            (DataType::Int32, false),   // num_replies
+            (DataType::Utf8, true),     // subject
+            (DataType::Utf8, true),     // text
+        ],
+    )
+}
+
+fn deletion_schema() -> SchemaRef {
+    schema(
+        DELETION_COLUMNS,
+        vec![
+            (DataType::Int64, false), // id
+            (DataType::Int32, false), // message_id
+            (timestamp(), true),      // deleted_at
+            (DataType::Utf8, false),  // deletion_type
+            (timestamp(), true),      // fingerprinted_at
+            (DataType::Int32, true),  // conversation_id
+            (DataType::Utf8, true),   // sender_handle
+            (DataType::Utf8, false),  // content_hash
+            (DataType::Utf8, true),   // recovered_content
+            (
+                DataType::List(Arc::new(Field::new_list_field(DataType::Utf8, true))),
+                false,
+            ), // recovered_attachments
+        ],
+    )
+}
+
+fn message_batch(rows: &[MessageRow]) -> Result<RecordBatch, RuntimeError> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from_iter_values(rows.iter().map(|r| r.rowid))),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|r| &r.guid))),
+        Arc::new(Int32Array::from_iter(rows.iter().map(|r| r.chat_id))),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.chat_identifier.as_deref()),
+        )),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.chat_name.as_deref()),
+        )),
+        Arc::new(Int32Array::from_iter(rows.iter().map(|r| r.handle_id))),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| &r.sender),
+        )),
+        Arc::new(BooleanArray::from_iter(
+            rows.iter().map(|r| Some(r.is_from_me)),
+        )),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.service.as_deref()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| r.variant),
+        )),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.balloon_bundle_id.as_deref()),
+        )),
+        timestamps(rows.iter().map(|r| r.date)),
+        timestamps(rows.iter().map(|r| r.date_delivered)),
+        timestamps(rows.iter().map(|r| r.date_read)),
+        timestamps(rows.iter().map(|r| r.date_edited)),
+        Arc::new(BooleanArray::from_iter(
+            rows.iter().map(|r| Some(r.is_edited)),
+        )),
+        Arc::new(BooleanArray::from_iter(
+            rows.iter().map(|r| Some(r.is_unsent)),
+        )),
+        Arc::new(BooleanArray::from_iter(
+            rows.iter().map(|r| Some(r.is_deleted)),
+        )),
+        Arc::new(BooleanArray::from_iter(
+            rows.iter().map(|r| Some(r.is_reply)),
+        )),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.thread_originator_guid.as_deref()),
+        )),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.associated_message_guid.as_deref()),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            rows.iter().map(|r| r.num_attachments),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            rows.iter().map(|r| r.num_replies),
+        )),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.subject.as_deref()),
+        )),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.text.as_deref()),
+        )),
+    ];
+    RecordBatch::try_new(message_schema(), columns)
+        .map_err(|why| disk_error(ParquetError::from(why)))
+}
+
+fn deletion_batch(rows: &[DeletionRow]) -> Result<RecordBatch, RuntimeError> {
+    let mut attachments = ListBuilder::new(StringBuilder::new())
+        .with_field(Arc::new(Field::new_list_field(DataType::Utf8, true)));
+    for row in rows {
+        for attachment in &row.recovered_attachments {
+            attachments.values().append_value(attachment);
+        }
+        attachments.append(true);
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from_iter_values(rows.iter().map(|r| r.id))),
+        Arc::new(Int32Array::from_iter_values(
+            rows.iter().map(|r| r.message_id),
+        )),
+        timestamps(rows.iter().map(|r| r.deleted_at)),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| &r.deletion_type),
+        )),
+        timestamps(rows.iter().map(|r| r.fingerprinted_at)),
+        Arc::new(Int32Array::from_iter(
+            rows.iter().map(|r| r.conversation_id),
+        )),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.sender_handle.as_deref()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| &r.content_hash),
+        )),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.recovered_content.as_deref()),
+        )),
+        Arc::new(attachments.finish()),
+    ];
+    RecordBatch::try_new(deletion_schema(), columns)
+        .map_err(|why| disk_error(ParquetError::from(why)))
+}
+
+/// Build a timestamp column in the table's timezone
+fn timestamps(dates: impl Iterator<Item = Option<DateTime<Utc>>>) -> ArrayRef {
+    Arc::new(
+        TimestampMicrosecondArray::from_iter(dates.map(|date| date.map(|d| d.timestamp_micros())))
+            .with_timezone(TIMEZONE),
+    )
+}
+
+/// Parquet errors happen while encoding or writing a table
+fn disk_error(why: ParquetError) -> RuntimeError {
+    RuntimeError::DiskError(io::Error::other(why))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{File, create_dir_all, remove_dir_all},
+        path::{Path, PathBuf},
+    };
+
+    use arrow_array::{Array, RecordBatch, StringArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use imessage_database::util::fixture::{FixtureBuilder, NewMessage, Schema};
+
+    use crate::{
+        app::{export_type::ExportType, options::Options, runtime::Config},
+        core::state_manager::tests::state_db,
+        exporters::tabular::{DELETION_COLUMNS, MESSAGE_COLUMNS},
+    };
+
+    const DATE: i64 = 694_000_000_000_000_000;
+    const MINUTE: i64 = 60_000_000_000;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-parquet-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Read a table's column names and every row
+    fn read_table(path: &Path) -> (Vec<String>, Vec<RecordBatch>) {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap()).unwrap();
+        let columns = builder
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect();
+        let batches = builder.build().unwrap().map(Result::unwrap).collect();
+        (columns, batches)
+    }
+
+    #[test]
+    fn can_export_tables() {
+        let dir = temp("tables");
+        let db_path = dir.join("chat.db");
+        let mut fixture = FixtureBuilder::create(&db_path, Schema::Ios16Newer).unwrap();
+        let handle = fixture.handle("+15558675309", "iMessage").unwrap();
+        let chat = fixture
+            .chat("+15558675309", Some("Jenny"), &[handle])
+            .unwrap();
+        for (idx, text) in ["Hello", "Hi", "Bye"].into_iter().enumerate() {
+            fixture
+                .message(
+                    chat,
+                    NewMessage::text(text, DATE + idx as i64 * MINUTE).from_handle(handle),
+                )
+                .unwrap();
+        }
+
+        let export_path = dir.join("export");
+        create_dir_all(&export_path).unwrap();
+        state_db(&export_path.join("tracker_state.db"), &[100, 200]);
+        Config::new(Options::fake(ExportType::Parquet, &db_path, &export_path))
+            .unwrap()
+            .start()
+            .unwrap();
+
+        let (columns, batches) = read_table(&export_path.join("messages.parquet"));
+        assert_eq!(columns, MESSAGE_COLUMNS);
+        assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), 3);
+        let texts = batches[0]
+            .column_by_name("text")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let mut texts: Vec<&str> = texts.iter().map(Option::unwrap).collect();
+        texts.sort_unstable();
+        assert_eq!(texts, ["Bye", "Hello", "Hi"]);
+
+        let (columns, batches) = read_table(&export_path.join("deletions.parquet"));
+        assert_eq!(columns, DELETION_COLUMNS);
+        assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), 2);
+        let content = batches[0]
+            .column_by_name("recovered_content")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(content.value(0), "Hello");
+        assert_eq!(content.null_count(), 0);
+
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,405 @@
+/*!
+Exports every message to the XML format used by the Android app SMS Backup & Restore
+
+Messages to a single participant that only contain text become `<sms>` elements. Group messages and messages with
+attachments or subjects become `<mms>` elements, with one `<part>` for the text and one for each attachment, whose
+data is embedded as base64. Tapbacks have no equivalent in the format and are not exported.
+
+The same file can be read back with [`SMSBackupImporter`](crate::importers::sms_backup::SMSBackupImporter).
+*/
+
+use std::{
+    fmt::Write as _,
+    fs::{File, remove_file},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use tracing::warn;
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{attachment::Attachment, messages::Message, table::Table},
+    util::dates::{format, get_local_time},
+};
+
+use crate::app::{error::RuntimeError, runtime::Config};
+
+/// Name of the exported file, without an extension
+pub const BACKUP_FILENAME: &str = "sms_backup";
+/// Android writes `null` for attributes that are not set
+pub const NULL: &str = "null";
+/// Address Android uses for the device owner in MMS messages
+pub const OWNER_ADDRESS: &str = "insert-address-token";
+/// Separator between participants in an MMS `address` attribute
+pub const ADDRESS_SEPARATOR: char = '~';
+/// Contact name used when the sender is not a contact
+const UNKNOWN_CONTACT: &str = "(Unknown)";
+
+/// `type` of an `<sms>` that was received
+pub const SMS_RECEIVED: i32 = 1;
+/// `type` of an `<sms>` that was sent
+pub const SMS_SENT: i32 = 2;
+/// `msg_box` of an `<mms>` that was received
+pub const MMS_INBOX: i32 = 1;
+/// `msg_box` of an `<mms>` that was sent
+pub const MMS_SENT: i32 = 2;
+/// `m_type` of a sent MMS, `m-send-req`
+const MMS_SEND_REQ: i32 = 128;
+/// `m_type` of a received MMS, `m-retrieve-conf`
+const MMS_RETRIEVE_CONF: i32 = 132;
+/// `type` of the `<addr>` that sent an MMS
+pub const ADDR_FROM: i32 = 137;
+/// `type` of an `<addr>` that received an MMS
+pub const ADDR_TO: i32 = 151;
+/// `chset` of UTF-8 text, from the IANA character set registry
+const UTF8_CHARSET: i32 = 106;
+/// Content type of the text part of an MMS
+pub const TEXT_PLAIN: &str = "text/plain";
+/// Content type of the layout part of an MMS, which is not exported
+pub const APPLICATION_SMIL: &str = "application/smil";
+/// Content type of attachments without a known type
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+pub struct SMSBackup<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Messages are written here first, since the root element contains the number of messages
+    body: BufWriter<File>,
+    /// Path to the file messages are written to before they are copied into the backup
+    body_path: PathBuf,
+    /// The number of messages written
+    count: usize,
+}
+
+impl<'a> SMSBackup<'a> {
+    pub fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let body_path = config
+            .options
+            .export_path
+            .join(format!(".{BACKUP_FILENAME}.part"));
+        Ok(SMSBackup {
+            config,
+            body: BufWriter::new(File::create(&body_path)?),
+            body_path,
+            count: 0,
+        })
+    }
+
+    /// Write every message matching the query context to the backup file
+    pub fn iter_messages(mut self) -> Result<(), RuntimeError> {
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        let mut statement = Message::stream_rows(
+            self.config.db(),
+            self.config.capabilities(),
+            &self.config.options.query_context,
+        )?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            if msg.is_tapback() {
+                continue;
+            }
+
+            if let Some(element) = self.format_message(&mut msg)? {
+                self.body.write_all(element.as_bytes())?;
+                self.count += 1;
+            }
+        }
+
+        self.finish()
+    }
+
+    /// Write the root element around the messages
+    fn finish(mut self) -> Result<(), RuntimeError> {
+        self.body.flush()?;
+
+        let mut path = self.config.options.export_path.join(BACKUP_FILENAME);
+        path.set_extension("xml");
+        let mut file = BufWriter::new(File::create(&path)?);
+        write!(
+            file,
+            "<?xml version='1.0' encoding='UTF-8' standalone='yes' ?>\n<smses count=\"{}\" type=\"full\">\n",
+            self.count
+        )?;
+        io::copy(&mut File::open(&self.body_path)?, &mut file)?;
+        file.write_all(b"</smses>\n")?;
+        file.flush()?;
+
+        remove_file(&self.body_path)?;
+        Ok(())
+    }
+
+    /// Build the `<sms>` or `<mms>` element for a message, or `None` if it has no content
+    fn format_message(&self, msg: &mut Message) -> Result<Option<String>, RuntimeError> {
+        let db = self.config.db();
+        if let Err(why) = msg.generate_text(db) {
+            // Messages without text may still have attachments
+            if msg.num_attachments == 0 {
+                warn!("Unable to generate text for message {}: {why}", msg.rowid);
+            }
+        }
+
+        // Attachments are written as parts, so remove their placeholders
+        let text = msg
+            .text
+            .as_deref()
+            .map(|text| text.replace(['\u{FFFC}', '\u{FFFD}'], ""))
+            .filter(|text| !text.trim().is_empty());
+        let mut attachments = Attachment::from_message(db, self.config.capabilities(), msg)?;
+        if text.is_none() && attachments.is_empty() {
+            return Ok(None);
+        }
+
+        let addresses = self.addresses(msg);
+        let is_mms = addresses.len() > 1 || !attachments.is_empty() || msg.subject.is_some();
+
+        let date = get_local_time(&msg.date, &self.config.offset);
+        let date_ms = date
+            .as_ref()
+            .map_or(0, |date| date.timestamp_millis())
+            .to_string();
+        let readable_date = format(&date);
+        let contact_name = self
+            .config
+            .conversation(msg)
+            .and_then(|(chat, _)| chat.display_name())
+            .unwrap_or(UNKNOWN_CONTACT);
+        let read = if msg.is_read || msg.is_from_me() {
+            "1"
+        } else {
+            "0"
+        };
+
+        let mut out_s = String::new();
+        if !is_mms {
+            let kind = if msg.is_from_me() {
+                SMS_SENT
+            } else {
+                SMS_RECEIVED
+            }
+            .to_string();
+            write_tag(
+                &mut out_s,
+                "sms",
+                &[
+                    ("protocol", "0"),
+                    ("address", addresses[0]),
+                    ("date", &date_ms),
+                    ("type", &kind),
+                    ("subject", NULL),
+                    ("body", text.as_deref().unwrap_or_default()),
+                    ("toa", NULL),
+                    ("sc_toa", NULL),
+                    ("service_center", NULL),
+                    ("read", read),
+                    ("status", "-1"),
+                    ("locked", "0"),
+                    ("date_sent", &date_ms),
+                    ("sub_id", "-1"),
+                    ("readable_date", &readable_date),
+                    ("contact_name", contact_name),
+                ],
+                true,
+            );
+            out_s.push('\n');
+            return Ok(Some(out_s));
+        }
+
+        let (msg_box, m_type) = if msg.is_from_me() {
+            (MMS_SENT, MMS_SEND_REQ)
+        } else {
+            (MMS_INBOX, MMS_RETRIEVE_CONF)
+        };
+        write_tag(
+            &mut out_s,
+            "mms",
+            &[
+                ("date", &date_ms),
+                ("ct_t", "application/vnd.wap.multipart.related"),
+                ("msg_box", &msg_box.to_string()),
+                ("address", &addresses.join(&ADDRESS_SEPARATOR.to_string())),
+                ("m_type", &m_type.to_string()),
+                ("m_id", &msg.guid),
+                ("sub", msg.subject.as_deref().unwrap_or(NULL)),
+                ("sub_cs", if msg.subject.is_some() { "106" } else { NULL }),
+                ("text_only", if attachments.is_empty() { "1" } else { "0" }),
+                ("read", read),
+                ("seen", "1"),
+                ("locked", "0"),
+                ("date_sent", "0"),
+                ("sub_id", "-1"),
+                ("readable_date", &readable_date),
+                ("contact_name", contact_name),
+            ],
+            false,
+        );
+
+        out_s.push_str("\n  <parts>\n");
+        if let Some(text) = &text {
+            out_s.push_str("    ");
+            write_tag(
+                &mut out_s,
+                "part",
+                &[
+                    ("seq", "0"),
+                    ("ct", TEXT_PLAIN),
+                    ("name", NULL),
+                    ("chset", &UTF8_CHARSET.to_string()),
+                    ("cid", "<text>"),
+                    ("cl", "text.txt"),
+                    ("text", text),
+                ],
+                true,
+            );
+            out_s.push('\n');
+        }
+        for attachment in &mut attachments {
+            if let Some(part) = self.format_attachment(attachment) {
+                out_s.push_str("    ");
+                out_s.push_str(&part);
+                out_s.push('\n');
+            }
+        }
+        out_s.push_str("  </parts>\n  <addrs>\n");
+
+        let sender = if msg.is_from_me() {
+            OWNER_ADDRESS
+        } else {
+            self.config.who(msg.handle_id, false)
+        };
+        let from = ADDR_FROM.to_string();
+        let to = ADDR_TO.to_string();
+        let charset = UTF8_CHARSET.to_string();
+        let recipients = addresses
+            .iter()
+            .copied()
+            .filter(|address| *address != sender)
+            .chain((!msg.is_from_me()).then_some(OWNER_ADDRESS));
+        for (address, kind) in std::iter::once((sender, &from)).chain(recipients.map(|a| (a, &to)))
+        {
+            out_s.push_str("    ");
+            write_tag(
+                &mut out_s,
+                "addr",
+                &[("address", address), ("type", kind), ("charset", &charset)],
+                true,
+            );
+            out_s.push('\n');
+        }
+        out_s.push_str("  </addrs>\n</mms>\n");
+
+        Ok(Some(out_s))
+    }
+
+    /// Build the `<part>` element for an attachment, embedding its data
+    fn format_attachment(&self, attachment: &mut Attachment) -> Option<String> {
+        let options = &self.config.options;
+        let bytes = match attachment.as_bytes(
+            &options.platform,
+            &options.db_path,
+            options.attachment_root.as_deref(),
+        ) {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => {
+                warn!("Attachment {} has no file", attachment.rowid);
+                return None;
+            }
+            Err(why) => {
+                warn!("Unable to read attachment {}: {why}", attachment.rowid);
+                return None;
+            }
+        };
+
+        let name = attachment
+            .filename()
+            .and_then(|name| Path::new(name).file_name())
+            .map_or_else(
+                || attachment.rowid.to_string(),
+                |name| name.to_string_lossy().to_string(),
+            );
+
+        let mut out_s = String::new();
+        write_tag(
+            &mut out_s,
+            "part",
+            &[
+                ("seq", "0"),
+                (
+                    "ct",
+                    attachment.mime_type.as_deref().unwrap_or(OCTET_STREAM),
+                ),
+                ("name", &name),
+                ("chset", NULL),
+                ("cid", &format!("<{name}>")),
+                ("cl", &name),
+                ("text", NULL),
+                ("data", &STANDARD.encode(bytes)),
+            ],
+            true,
+        );
+        Some(out_s)
+    }
+
+    /// Get the addresses of everyone in the conversation other than the database owner
+    fn addresses(&self, msg: &Message) -> Vec<&str> {
+        let addresses: Vec<&str> = match self.config.conversation(msg) {
+            Some((chat, _)) => self
+                .config
+                .chatroom_participants(chat.rowid)
+                .into_iter()
+                .map(|(_, address)| address)
+                .collect(),
+            None => vec![],
+        };
+        if addresses.is_empty() {
+            return vec![self.config.who(msg.handle_id, false)];
+        }
+        addresses
+    }
+}
+
+/// Append an element with escaped attribute values, leaving it open if it has children
+fn write_tag(out_s: &mut String, name: &str, attributes: &[(&str, &str)], empty: bool) {
+    let _ = write!(out_s, "<{name}");
+    for (key, value) in attributes {
+        let _ = write!(out_s, " {key}=\"{}\"", escape(value));
+    }
+    out_s.push_str(if empty { " />" } else { ">" });
+}
+
+/// Escape an attribute value, encoding whitespace so parsers do not normalize it to spaces
+fn escape(text: &str) -> String {
+    let mut out_s = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out_s.push_str("&amp;"),
+            '<' => out_s.push_str("&lt;"),
+            '>' => out_s.push_str("&gt;"),
+            '"' => out_s.push_str("&quot;"),
+            '\'' => out_s.push_str("&apos;"),
+            '\n' => out_s.push_str("&#10;"),
+            '\r' => out_s.push_str("&#13;"),
+            '\t' => out_s.push_str("&#9;"),
+            // Other control characters cannot be represented in XML 1.0
+            c if c.is_control() => {}
+            c => out_s.push(c),
+        }
+    }
+    out_s
+}
@@ -0,0 +1,321 @@
+/*!
+Flat row types shared by the CSV and Parquet exporters
+
+Messages are flattened to one row each, including tapbacks and replies, so they can be loaded into tools like
+pandas or DuckDB without any further processing. Deletions detected by the tracker are read from its state
+database into a second table. All dates are converted from the iMessage epoch and stored in UTC.
+*/
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Serializer};
+use tracing::warn;
+
+use imessage_database::{
+    message_types::{edited::EditStatus, variants::Variant},
+    tables::messages::Message,
+    util::dates::get_local_time,
+};
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config},
+    core::state_manager::{DeletionRecord, StateManager},
+};
+
+/// Name of the table that contains every message, without an extension
+pub const MESSAGES_TABLE: &str = "messages";
+/// Name of the table that contains every deletion the tracker detected, without an extension
+pub const DELETIONS_TABLE: &str = "deletions";
+
+/// Column names of the messages table, in the order of the fields of [`MessageRow`]
+pub const MESSAGE_COLUMNS: &[&str] = &[
+    "rowid",
+    "guid",
+    "chat_id",
+    "chat_identifier",
+    "chat_name",
+    "handle_id",
+    "sender",
+    "is_from_me",
+    "service",
+    "variant",
+    "balloon_bundle_id",
+    "date",
+    "date_delivered",
+    "date_read",
+    "date_edited",
+    "is_edited",
+    "is_unsent",
+    "is_deleted",
+    "is_reply",
+    "thread_originator_guid",
+    "associated_message_guid",
+    "num_attachments",
+    "num_replies",
+    "subject",
+    "text",
+];
+
+/// Column names of the deletions table, in the order of the fields of [`DeletionRow`]
+pub const DELETION_COLUMNS: &[&str] = &[
+    "id",
+    "message_id",
+    "deleted_at",
+    "deletion_type",
+    "fingerprinted_at",
+    "conversation_id",
+    "sender_handle",
+    "content_hash",
+    "recovered_content",
+    "recovered_attachments",
+];
+
+/// A single message, flattened to scalar columns
+#[derive(Debug, Serialize)]
+pub struct MessageRow {
+    pub rowid: i32,
+    pub guid: String,
+    /// Deduplicated chat ID, shared by chats with the same participants
+    pub chat_id: Option<i32>,
+    pub chat_identifier: Option<String>,
+    pub chat_name: Option<String>,
+    pub handle_id: Option<i32>,
+    /// Contact info for the sender, or the configured name for messages from the database owner
+    pub sender: String,
+    pub is_from_me: bool,
+    pub service: Option<String>,
+    /// One of `normal`, `edited`, `tapback`, `app`, `shareplay`, or `unknown`
+    pub variant: &'static str,
+    /// Bundle ID of the app that generated the message, if any
+    pub balloon_bundle_id: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+    pub date_delivered: Option<DateTime<Utc>>,
+    pub date_read: Option<DateTime<Utc>>,
+    pub date_edited: Option<DateTime<Utc>>,
+    pub is_edited: bool,
+    /// `true` if any part of the message was unsent
+    pub is_unsent: bool,
+    pub is_deleted: bool,
+    pub is_reply: bool,
+    pub thread_originator_guid: Option<String>,
+    pub associated_message_guid: Option<String>,
+    pub num_attachments: i32,
+    pub num_replies: i32,
+    pub subject: Option<String>,
+    /// Text decoded from the message's `attributedBody`
+    pub text: Option<String>,
+}
+
+impl MessageRow {
+    /// Flatten a message, decoding its text if it has not been already
+    pub fn from_message(config: &Config, msg: &mut Message) -> Self {
+        if msg.text.is_none() {
+            if let Err(why) = msg.generate_text(config.db()) {
+                // Messages without text may still have attachments or app data
+                if msg.num_attachments == 0 && msg.balloon_bundle_id.is_none() {
+                    warn!("Unable to generate text for message {}: {why}", msg.rowid);
+                }
+            }
+        }
+
+        let conversation = config.conversation(msg);
+        let date = |stamp: i64| -> Option<DateTime<Utc>> {
+            if stamp == 0 {
+                return None;
+            }
+            get_local_time(&stamp, &config.offset)
+                .ok()
+                .map(|date| date.with_timezone(&Utc))
+        };
+
+        MessageRow {
+            rowid: msg.rowid,
+            guid: msg.guid.clone(),
+            chat_id: conversation.map(|(_, &real_id)| real_id),
+            chat_identifier: conversation.map(|(chat, _)| chat.chat_identifier.clone()),
+            chat_name: conversation.and_then(|(chat, _)| chat.display_name().map(str::to_string)),
+            handle_id: msg.handle_id,
+            sender: config.who(msg.handle_id, msg.is_from_me()).to_string(),
+            is_from_me: msg.is_from_me(),
+            service: msg.service.clone(),
+            variant: match msg.variant() {
+                Variant::Normal => "normal",
+                Variant::Edited => "edited",
+                Variant::Tapback(..) => "tapback",
+                Variant::App(_) => "app",
+                Variant::SharePlay => "shareplay",
+                Variant::Unknown(_) => "unknown",
+            },
+            balloon_bundle_id: msg.balloon_bundle_id.clone(),
+            date: date(msg.date),
+            date_delivered: date(msg.date_delivered),
+            date_read: date(msg.date_read),
+            date_edited: date(msg.date_edited),
+            is_edited: msg.is_edited(),
+            is_unsent: msg.edited_parts.as_ref().is_some_and(|edited| {
+                edited
+                    .parts
+                    .iter()
+                    .any(|part| matches!(part.status, EditStatus::Unsent))
+            }),
+            is_deleted: msg.is_deleted(),
+            is_reply: msg.is_reply(),
+            thread_originator_guid: msg.thread_originator_guid.clone(),
+            associated_message_guid: msg.associated_message_guid.clone(),
+            num_attachments: msg.num_attachments,
+            num_replies: msg.num_replies,
+            subject: msg.subject.clone(),
+            text: msg.text.clone(),
+        }
+    }
+}
+
+/// A deletion detected by the tracker, flattened to scalar columns
+#[derive(Debug, Serialize)]
+pub struct DeletionRow {
+    pub id: i64,
+    pub message_id: i32,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub deletion_type: String,
+    /// When the deleted message was fingerprinted
+    pub fingerprinted_at: Option<DateTime<Utc>>,
+    pub conversation_id: Option<i32>,
+    pub sender_handle: Option<String>,
+    pub content_hash: String,
+    pub recovered_content: Option<String>,
+    /// Paths to recovered attachment files, serialized as a JSON array in text formats
+    #[serde(serialize_with = "serialize_json")]
+    pub recovered_attachments: Vec<String>,
+}
+
+impl DeletionRow {
+    /// Read every deletion record from the tracker's state database, oldest first
+    ///
+    /// If the state database does not exist, the tracker has not run yet, so there are no deletions.
+    pub fn load(state_db_path: &Path) -> Result<Vec<Self>, RuntimeError> {
+        if !state_db_path.is_file() {
+            warn!(
+                "No tracker state database at {}, the deletions table will be empty",
+                state_db_path.display()
+            );
+            return Ok(vec![]);
+        }
+
+        Self::query(state_db_path, i64::MIN, i64::MAX).map_err(RuntimeError::StateDatabaseError)
+    }
+
+    /// Read the deletion records detected between `start` and `end`, inclusive, as Unix timestamps, oldest first
+    pub fn load_range(
+        state_db_path: &Path,
+        start: i64,
+        end: i64,
+    ) -> Result<Vec<Self>, RuntimeError> {
+        if !state_db_path.is_file() {
+            warn!(
+                "No tracker state database at {}, there are no deletions to report",
+                state_db_path.display()
+            );
+            return Ok(vec![]);
+        }
+
+        Self::query(state_db_path, start, end).map_err(RuntimeError::StateDatabaseError)
+    }
+
+    /// Read deletion records with the tracker's [`StateManager::get_deletions_in_range()`]
+    fn query(state_db_path: &Path, start: i64, end: i64) -> Result<Vec<Self>, rusqlite::Error> {
+        let manager = StateManager::open_read_only(state_db_path)?;
+        let mut rows: Vec<Self> = manager
+            .get_deletions_in_range(start, end)?
+            .into_iter()
+            .map(DeletionRow::from)
+            .collect();
+        // The state manager lists the newest deletions first
+        rows.reverse();
+        Ok(rows)
+    }
+}
+
+impl From<DeletionRecord> for DeletionRow {
+    fn from(record: DeletionRecord) -> Self {
+        let fingerprint = record.original_fingerprint;
+        DeletionRow {
+            id: record.id,
+            message_id: record.message_id,
+            deleted_at: DateTime::from_timestamp(record.deletion_timestamp, 0),
+            deletion_type: record.deletion_type,
+            fingerprinted_at: DateTime::from_timestamp(fingerprint.timestamp, 0)
+                .filter(|_| fingerprint.timestamp != 0),
+            conversation_id: fingerprint.conversation_id,
+            sender_handle: fingerprint.sender_handle,
+            content_hash: fingerprint.content_hash,
+            recovered_content: record.recovered_content,
+            recovered_attachments: record.recovered_attachments,
+        }
+    }
+}
+
+/// Serialize a value as a JSON string, for columns that hold lists
+fn serialize_json<S: Serializer, T: Serialize>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let json = serde_json::to_string(value).map_err(serde::ser::Error::custom)?;
+    serializer.serialize_str(&json)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use crate::{core::state_manager::tests::state_db, exporters::tabular::DeletionRow};
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-tabular-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn can_load_range() {
+        let dir = temp("range");
+        let path = dir.join("tracker_state.db");
+        state_db(&path, &[300, 100, 200, 400]);
+
+        let rows = DeletionRow::load_range(&path, 200, 300).unwrap();
+        let ids: Vec<i32> = rows.iter().map(|row| row.message_id).collect();
+        assert_eq!(ids, [3, 1]);
+
+        let row = &rows[0];
+        assert_eq!(row.deleted_at.unwrap().timestamp(), 200);
+        assert_eq!(row.fingerprinted_at.unwrap().timestamp(), 140);
+        assert_eq!(row.conversation_id, Some(1));
+        assert_eq!(row.sender_handle.as_deref(), Some("+15558675309"));
+        assert_eq!(row.content_hash, "hash3");
+        assert_eq!(row.recovered_content.as_deref(), Some("Hello"));
+        assert_eq!(row.recovered_attachments, ["a.png"]);
+
+        assert_eq!(DeletionRow::load(&path).unwrap().len(), 4);
+        assert!(DeletionRow::load_range(&path, 500, 600).unwrap().is_empty());
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_load_range_without_state_db() {
+        let dir = temp("missing");
+
+        assert!(
+            DeletionRow::load_range(&dir.join("tracker_state.db"), i64::MIN, i64::MAX)
+                .unwrap()
+                .is_empty()
+        );
+
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,31 +1,61 @@
+/*!
+Exports each conversation to a plain text file
+*/
+
 use std::{
-    collections::{
-        HashMap,
-    },
+    collections::{HashMap, hash_map::Entry},
+    fmt::Write as _,
     fs::File,
-    io::BufWriter,
+    io::{BufWriter, Write},
 };
 
-use crate::app::{
-        error::RuntimeError,
-        runtime::Config,
-    };
+use crate::{
+    app::{error::RuntimeError, runtime::Config},
+    exporters::exporter::{ATTACHMENT_NO_FILENAME, Writer},
+};
 
 use imessage_database::{
-    error::table::TableError,
+    error::{plist::PlistParseError, table::TableError},
+    message_types::{
+        app::AppMessage,
+        app_store::AppStoreMessage,
+        check_in::{CheckInMessage, CheckInStatus},
+        collaboration::CollaborationMessage,
+        digital_touch::DigitalTouchMessage,
+        edited::{EditStatus, EditedMessage},
+        expressives::{BubbleEffect, Expressive, ScreenEffect},
+        handwriting::HandwrittenMessage,
+        music::MusicMessage,
+        placemark::PlacemarkMessage,
+        sticker::StickerSource,
+        url::URLMessage,
+        variants::{
+            Announcement, CustomBalloon, ParsedBalloon, Tapback, TapbackAction, URLOverride,
+            Variant,
+        },
+    },
     tables::{
-        messages::Message,
-        table::{ORPHANED, Table},
+        attachment::Attachment,
+        messages::{
+            Message,
+            models::{AttachmentMeta, BubbleComponent, GroupAction, TextAttributes},
+        },
+        table::{AttributedBody, FITNESS_RECEIVER, ME, ORPHANED, Table, YOU},
     },
-    util::dates::format,
+    util::dates::{TIMESTAMP_FACTOR, format, get_local_time, readable_diff},
 };
 
+/// Number of spaces replies are indented under the message they respond to
+const REPLY_INDENT: usize = 4;
+
 pub struct TXT<'a> {
     /// Data that is setup from the application's runtime
     pub config: &'a Config,
     /// Handles to files we want to write messages to
     /// Map of resolved chatroom file location to a buffered writer
     pub files: HashMap<String, BufWriter<File>>,
+    /// Writer instance for orphaned messages
+    pub orphaned: BufWriter<File>,
 }
 
 impl<'a> TXT<'a> {
@@ -37,23 +67,27 @@ impl<'a> TXT<'a> {
         Ok(TXT {
             config,
             files: HashMap::new(),
+            orphaned: BufWriter::new(File::create(orphaned)?),
         })
     }
 
-    pub fn iter_messages(&mut self) -> Result<HashMap<i32, Message>, RuntimeError> {
+    /// Write every message matching the query context to its conversation's file
+    pub fn iter_messages(&mut self) -> Result<(), RuntimeError> {
         // Keep track of current message ROWID
         let mut current_message_row = -1;
 
-        let mut statement =
-            Message::stream_rows(self.config.db(), &self.config.options.query_context)?;
+        let mut statement = Message::stream_rows(
+            self.config.db(),
+            self.config.capabilities(),
+            &self.config.options.query_context,
+        )?;
 
         let messages = statement
             .query_map([], |row| Ok(Message::from_row(row)))
             .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
 
-        let mut msgs: HashMap<i32, Message> = HashMap::new();
         for message in messages {
-            let msg = Message::extract(message)?;
+            let mut msg = Message::extract(message)?;
 
             // Early escape if we try and render the same message GUID twice
             // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
@@ -61,13 +95,51 @@ impl<'a> TXT<'a> {
                 continue;
             }
             current_message_row = msg.rowid;
-            msgs.insert(msg.rowid, msg).map(|o|println!("{}", o.rowid));
+
+            // Generate the text of the message; messages without text may still have attachments or app data
+            let _ = msg.generate_text(self.config.db());
+
+            // Render the announcement in-line
+            if msg.is_announcement() {
+                let announcement = self.format_announcement(&msg);
+                TXT::write_to_file(self.get_or_create_file(&msg)?, &announcement)?;
+            }
+            // Tapbacks are rendered under the messages they refer to
+            else if !msg.is_tapback() {
+                let message = self.format_message(&msg, 0)?;
+                TXT::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+            }
+        }
+
+        for file in self.files.values_mut() {
+            file.flush()?;
+        }
+        self.orphaned.flush()?;
+        Ok(())
+    }
+
+    /// Get the file a message belongs in, creating it if it does not exist
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, &real_id)) => {
+                let filename = self.config.filename(chatroom, real_id);
+                match self.files.entry(filename) {
+                    Entry::Occupied(entry) => Ok(entry.into_mut()),
+                    Entry::Vacant(entry) => {
+                        let mut path = self.config.options.export_path.join(entry.key());
+                        path.set_extension("txt");
+                        Ok(entry.insert(BufWriter::new(File::create(path)?)))
+                    }
+                }
+            }
+            None => Ok(&mut self.orphaned),
         }
-        Ok(msgs)
     }
-}
 
-impl TXT<'_> {
+    /// Get the formatted date of a message, annotated with how long it took to be read
     pub fn get_time(&self, message: &Message) -> String {
         let mut date = format(&message.date(&self.config.offset));
         let read_after = message.time_until_read(&self.config.offset);
@@ -83,4 +155,812 @@ impl TXT<'_> {
         }
         date
     }
-}
\ No newline at end of file
+
+    /// Append an indented line of text to a message
+    fn add_line(&self, string: &mut String, part: &str, indent: &str) {
+        if !part.is_empty() {
+            string.push_str(indent);
+            string.push_str(part);
+            string.push('\n');
+        }
+    }
+
+    /// Join the lines that are present, indenting each one
+    fn join_lines(&self, lines: &[Option<&str>], indent: &str) -> String {
+        lines
+            .iter()
+            .flatten()
+            .map(|line| format!("{indent}{line}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Get the name used when the database owner performed an action
+    fn you(&self) -> &str {
+        self.config.options.custom_name.as_deref().unwrap_or(YOU)
+    }
+
+    /// Format a URL preview
+    fn format_url(&self, msg: &Message, balloon: &URLMessage, indent: &str) -> String {
+        self.join_lines(
+            &[
+                balloon.get_url().or(msg.text.as_deref()),
+                balloon.title,
+                balloon.summary,
+            ],
+            indent,
+        )
+    }
+
+    /// Format an Apple Music message
+    fn format_music(&self, balloon: &MusicMessage, indent: &str) -> String {
+        let mut out_s = String::new();
+        if let Some(lyrics) = &balloon.lyrics {
+            out_s.push_str(indent);
+            out_s.push_str("Lyrics:\n");
+            out_s.push_str(&self.join_lines(
+                &lyrics.iter().map(|line| Some(*line)).collect::<Vec<_>>(),
+                indent,
+            ));
+            out_s.push_str("\n\n");
+        }
+        out_s.push_str(&self.join_lines(
+            &[
+                balloon.track_name,
+                balloon.album,
+                balloon.artist,
+                balloon.url,
+            ],
+            indent,
+        ));
+        out_s
+    }
+
+    /// Format a collaboration invitation
+    fn format_collaboration(&self, balloon: &CollaborationMessage, indent: &str) -> String {
+        let app = balloon.app_name.or(balloon.bundle_id).unwrap_or("Unknown");
+        self.join_lines(
+            &[
+                Some(&format!("{app} message:")),
+                balloon.title,
+                balloon.get_url(),
+            ],
+            indent,
+        )
+    }
+
+    /// Format an App Store link
+    fn format_app_store(&self, balloon: &AppStoreMessage, indent: &str) -> String {
+        self.join_lines(
+            &[
+                balloon.app_name,
+                balloon.description,
+                balloon.platform,
+                balloon.genre,
+                balloon.url,
+            ],
+            indent,
+        )
+    }
+
+    /// Format a shared map location
+    fn format_placemark(&self, balloon: &PlacemarkMessage, indent: &str) -> String {
+        self.join_lines(
+            &[
+                balloon.place_name,
+                balloon.get_url(),
+                balloon.placemark.name,
+                balloon.placemark.address,
+                balloon.placemark.state,
+                balloon.placemark.city,
+                balloon.placemark.iso_country_code,
+                balloon.placemark.postal_code,
+                balloon.placemark.country,
+                balloon.placemark.street,
+                balloon.placemark.sub_administrative_area,
+                balloon.placemark.sub_locality,
+            ],
+            indent,
+        )
+    }
+
+    /// Format a message sent by a third party app
+    fn format_generic_app(&self, balloon: &AppMessage, bundle_id: &str, indent: &str) -> String {
+        let app = balloon.app_name.unwrap_or(bundle_id);
+        self.join_lines(
+            &[
+                Some(&format!("{app} message:")),
+                balloon.title,
+                balloon.subtitle,
+                balloon.caption,
+                balloon.subcaption,
+                balloon.trailing_caption,
+                balloon.trailing_subcaption,
+            ],
+            indent,
+        )
+    }
+
+    /// Format an Apple Pay transaction
+    fn format_apple_pay(&self, balloon: &AppMessage, indent: &str) -> String {
+        let mut out_s = String::from(indent);
+        if let Some(caption) = balloon.caption {
+            out_s.push_str(caption);
+            out_s.push_str(" transaction: ");
+        }
+        out_s.push_str(balloon.ldtext.unwrap_or("unknown amount"));
+        out_s
+    }
+
+    /// Format a Fitness.app message
+    fn format_fitness(&self, balloon: &AppMessage, indent: &str) -> String {
+        let mut out_s = String::from(indent);
+        if let Some(app_name) = balloon.app_name {
+            out_s.push_str(app_name);
+            out_s.push_str(" message: ");
+        }
+        out_s.push_str(balloon.ldtext.unwrap_or("unknown workout"));
+        out_s
+    }
+
+    /// Format a Photos.app slideshow
+    fn format_slideshow(&self, balloon: &AppMessage, indent: &str) -> String {
+        let mut out_s = String::from(indent);
+        out_s.push_str("Photo album: ");
+        out_s.push_str(balloon.ldtext.unwrap_or("unknown album"));
+        if let Some(url) = balloon.url {
+            out_s.push(' ');
+            out_s.push_str(url);
+        }
+        out_s
+    }
+
+    /// Format a Find My message
+    fn format_find_my(&self, balloon: &AppMessage, indent: &str) -> String {
+        let mut out_s = String::from(indent);
+        out_s.push_str(balloon.app_name.unwrap_or("Find My"));
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str(": ");
+            out_s.push_str(ldtext);
+        }
+        out_s
+    }
+
+    /// Format a Check In message
+    fn format_check_in(&self, balloon: &CheckInMessage, indent: &str) -> String {
+        let mut out_s = String::from(indent);
+        out_s.push_str(balloon.caption.unwrap_or("Check In"));
+
+        // The query string stores unix timestamps, so no offset is applied
+        let date = |stamp: f64| format(&get_local_time(&(stamp as i64 * TIMESTAMP_FACTOR), &0));
+
+        // Before manual check-in
+        if let Some(expected) = balloon.estimated_arrival {
+            let _ = write!(out_s, "\n{indent}Expected at {}", date(expected));
+        }
+        // Expired check-in
+        else if let Some(expected) = balloon.expected {
+            let _ = write!(out_s, "\n{indent}Was expected at {}", date(expected));
+        }
+        // Started timer
+        else if let (CheckInStatus::TimerStarted, Some(sent)) = (balloon.status, balloon.sent) {
+            let _ = write!(out_s, "\n{indent}Started at {}", date(sent));
+        }
+        // Accepted check-in
+        else if let Some(sent) = balloon.sent {
+            let _ = write!(out_s, "\n{indent}Checked in at {}", date(sent));
+        }
+        out_s
+    }
+
+    /// Format a handwritten message as ASCII art
+    fn format_handwriting(&self, balloon: &HandwrittenMessage, indent: &str) -> String {
+        balloon
+            .render_ascii(40)
+            .lines()
+            .map(|line| format!("{indent}{line}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl<'a> Writer<'a> for TXT<'a> {
+    fn format_message(&self, message: &Message, indent_size: usize) -> Result<String, TableError> {
+        let indent = " ".repeat(indent_size);
+        // Data we want to write to a file
+        let mut formatted_message = String::new();
+
+        // Add message date
+        self.add_line(&mut formatted_message, &self.get_time(message), &indent);
+
+        // Add message sender
+        self.add_line(
+            &mut formatted_message,
+            self.config.who(message.handle_id, message.is_from_me()),
+            &indent,
+        );
+
+        // If message was deleted, annotate it
+        if message.is_deleted() {
+            self.add_line(
+                &mut formatted_message,
+                "This message was deleted from the conversation!",
+                &indent,
+            );
+        }
+
+        // Useful message metadata
+        let message_parts = message.body();
+        let mut attachments =
+            Attachment::from_message(self.config.db(), self.config.capabilities(), message)?;
+        let mut replies = message.get_replies(self.config.db(), self.config.capabilities())?;
+
+        // Index of where we are in the attachment Vector
+        let mut attachment_index: usize = 0;
+
+        // Render subject
+        if let Some(subject) = &message.subject {
+            self.add_line(&mut formatted_message, subject, &indent);
+        }
+
+        // Handle SharePlay
+        if message.is_shareplay() {
+            self.add_line(&mut formatted_message, self.format_shareplay(), &indent);
+        }
+
+        // Handle Shared Location
+        if message.started_sharing_location() || message.stopped_sharing_location() {
+            self.add_line(
+                &mut formatted_message,
+                self.format_shared_location(message),
+                &indent,
+            );
+        }
+
+        // Generate the message body from its components
+        for (idx, message_part) in message_parts.iter().enumerate() {
+            match message_part {
+                BubbleComponent::Text(text_attrs) => {
+                    if let Some(text) = &message.text {
+                        // Render edited message content, if applicable
+                        if message.is_part_edited(idx) {
+                            if let Some(edited_parts) = &message.edited_parts {
+                                if let Some(edited) =
+                                    self.format_edited(message, edited_parts, idx, &indent)
+                                {
+                                    self.add_line(&mut formatted_message, &edited, &indent);
+                                }
+                            }
+                        } else {
+                            let mut formatted_text = self.format_attributes(text, text_attrs);
+
+                            // If we failed to parse any text above, use the original text
+                            if formatted_text.is_empty() {
+                                formatted_text.push_str(text);
+                            }
+
+                            // Fitness messages have a prefix that we need to replace with the sender
+                            if formatted_text.starts_with(FITNESS_RECEIVER) {
+                                formatted_text =
+                                    formatted_text.replace(FITNESS_RECEIVER, self.you());
+                            }
+
+                            self.add_line(&mut formatted_message, &formatted_text, &indent);
+                        }
+                    }
+                }
+                BubbleComponent::Attachment(metadata) => {
+                    match attachments.get_mut(attachment_index) {
+                        Some(attachment) => {
+                            attachment_index += 1;
+                            let result = if attachment.is_sticker {
+                                self.format_sticker(attachment, message)
+                            } else {
+                                self.format_attachment(attachment, message, metadata)
+                                    .unwrap_or_else(String::from)
+                            };
+                            self.add_line(&mut formatted_message, &result, &indent);
+                        }
+                        // Attachment does not exist in attachments table
+                        None => self.add_line(
+                            &mut formatted_message,
+                            "Attachment does not exist!",
+                            &indent,
+                        ),
+                    }
+                }
+                BubbleComponent::App => match self.format_app(message, &mut attachments, &indent) {
+                    Ok(app_bubble) => {
+                        self.add_line(&mut formatted_message, &app_bubble, "");
+                    }
+                    Err(why) => self.add_line(
+                        &mut formatted_message,
+                        &format!("Unable to format app message: {why}"),
+                        &indent,
+                    ),
+                },
+                BubbleComponent::Retracted => {
+                    if let Some(edited_parts) = &message.edited_parts {
+                        if let Some(edited) =
+                            self.format_edited(message, edited_parts, idx, &indent)
+                        {
+                            self.add_line(&mut formatted_message, &edited, &indent);
+                        }
+                    }
+                }
+            }
+
+            // Handle expressives
+            if message.expressive_send_style_id.is_some() {
+                self.add_line(
+                    &mut formatted_message,
+                    self.format_expressive(message),
+                    &indent,
+                );
+            }
+
+            // Handle tapbacks
+            if let Some(tapbacks) = self
+                .config
+                .tapbacks
+                .get(&message.guid)
+                .and_then(|tapbacks| tapbacks.get(&idx))
+            {
+                let mut formatted_tapbacks = String::new();
+                for tapback in tapbacks {
+                    let formatted = self.format_tapback(tapback)?;
+                    self.add_line(&mut formatted_tapbacks, &formatted, &indent);
+                }
+
+                if !formatted_tapbacks.is_empty() {
+                    self.add_line(&mut formatted_message, "Tapbacks:", &indent);
+                    formatted_message.push_str(&formatted_tapbacks);
+                }
+            }
+
+            // Handle replies
+            if let Some(replies) = replies.get_mut(&idx) {
+                for reply in replies {
+                    let _ = reply.generate_text(self.config.db());
+                    if !reply.is_tapback() {
+                        formatted_message
+                            .push_str(&self.format_message(reply, indent_size + REPLY_INDENT)?);
+                    }
+                }
+            }
+        }
+
+        // Add a note if the message is a reply
+        if message.is_reply() && indent.is_empty() {
+            self.add_line(
+                &mut formatted_message,
+                "This message responded to an earlier message.",
+                &indent,
+            );
+        }
+
+        if indent.is_empty() {
+            // Add a newline for top-level messages
+            formatted_message.push('\n');
+        }
+
+        Ok(formatted_message)
+    }
+
+    fn format_attachment(
+        &self,
+        attachment: &'a mut Attachment,
+        msg: &'a Message,
+        metadata: &AttachmentMeta,
+    ) -> Result<String, &'a str> {
+        let path = self.config.copy_attachment(attachment, msg);
+        let attachment: &'a Attachment = attachment;
+        let mut out_s = path
+            .ok_or_else(|| attachment.filename().unwrap_or(ATTACHMENT_NO_FILENAME))?
+            .display()
+            .to_string();
+
+        // Append the transcription if one is provided
+        if let Some(transcription) = metadata.transcription {
+            let _ = write!(out_s, "\nTranscription: {transcription}");
+        }
+        Ok(out_s)
+    }
+
+    fn format_sticker(&self, sticker: &'a mut Attachment, msg: &'a Message) -> String {
+        let who = self.config.who(msg.handle_id, msg.is_from_me());
+        let path = self.config.copy_attachment(sticker, msg);
+        let mut out_s = format!(
+            "Sticker from {who}: {}",
+            path.map_or_else(
+                || sticker
+                    .filename()
+                    .unwrap_or(ATTACHMENT_NO_FILENAME)
+                    .to_string(),
+                |path| path.display().to_string()
+            )
+        );
+
+        // Determine the source of the sticker
+        match sticker.get_sticker_source(self.config.db()) {
+            Some(StickerSource::Genmoji) => {
+                if let Some(prompt) = &sticker.emoji_description {
+                    let _ = write!(out_s, " (Genmoji prompt: {prompt})");
+                }
+            }
+            Some(StickerSource::Memoji) => out_s.push_str(" (App: Stickers)"),
+            Some(StickerSource::UserGenerated) => {
+                if let Ok(Some(effect)) = sticker.get_sticker_effect(
+                    &self.config.options.platform,
+                    &self.config.options.db_path,
+                    self.config.options.attachment_root.as_deref(),
+                ) {
+                    let _ = write!(out_s, " ({effect})");
+                }
+            }
+            Some(StickerSource::App(bundle_id)) => {
+                let app_name = sticker
+                    .get_sticker_source_application_name(self.config.db())
+                    .unwrap_or(bundle_id);
+                let _ = write!(out_s, " (App: {app_name})");
+            }
+            None => {}
+        }
+
+        out_s
+    }
+
+    fn format_app(
+        &self,
+        message: &'a Message,
+        _: &mut Vec<Attachment>,
+        indent: &str,
+    ) -> Result<String, PlistParseError> {
+        let Variant::App(balloon) = message.variant() else {
+            return Err(PlistParseError::WrongMessageType);
+        };
+
+        // Handwriting and Digital Touch messages use a different payload type, so handle them first
+        match balloon {
+            CustomBalloon::Handwriting => {
+                let payload = message
+                    .raw_payload_data(self.config.db())
+                    .ok_or(PlistParseError::NoPayload)?;
+                let handwriting = HandwrittenMessage::from_payload(&payload)
+                    .map_err(PlistParseError::HandwritingError)?;
+                return Ok(self.format_handwriting(&handwriting, indent));
+            }
+            CustomBalloon::DigitalTouch => {
+                let payload = message
+                    .raw_payload_data(self.config.db())
+                    .ok_or(PlistParseError::NoPayload)?;
+                let touch = DigitalTouchMessage::from_payload(&payload)
+                    .map_err(|_| PlistParseError::DigitalTouchError)?;
+                return Ok(format!("{indent}Digital Touch: {touch}"));
+            }
+            _ => {}
+        }
+
+        let Some(payload) = message.balloon_payload(self.config.db()) else {
+            // Sometimes, URL messages are missing their payloads
+            if message.is_url() {
+                if let Some(text) = &message.text {
+                    return Ok(format!("{indent}{text}"));
+                }
+            }
+            return Err(PlistParseError::NoPayload);
+        };
+        let payload = payload?;
+
+        let bubble = match payload.parse()? {
+            // URL messages are a special sub-case of regular app messages
+            ParsedBalloon::URL(balloon) => {
+                return Ok(match balloon {
+                    URLOverride::Normal(balloon) => self.format_url(message, &balloon, indent),
+                    URLOverride::AppleMusic(balloon) => self.format_music(&balloon, indent),
+                    URLOverride::AppStore(balloon) => self.format_app_store(&balloon, indent),
+                    URLOverride::Collaboration(balloon) => {
+                        self.format_collaboration(&balloon, indent)
+                    }
+                    URLOverride::SharedPlacemark(balloon) => {
+                        self.format_placemark(&balloon, indent)
+                    }
+                });
+            }
+            ParsedBalloon::App(bubble) => bubble,
+        };
+        Ok(match balloon {
+            CustomBalloon::Application(bundle_id) => {
+                self.format_generic_app(&bubble, bundle_id, indent)
+            }
+            CustomBalloon::ApplePay => self.format_apple_pay(&bubble, indent),
+            CustomBalloon::Fitness => self.format_fitness(&bubble, indent),
+            CustomBalloon::Slideshow => self.format_slideshow(&bubble, indent),
+            CustomBalloon::FindMy => self.format_find_my(&bubble, indent),
+            CustomBalloon::CheckIn => {
+                self.format_check_in(&CheckInMessage::from_app(&bubble), indent)
+            }
+            CustomBalloon::URL | CustomBalloon::Handwriting | CustomBalloon::DigitalTouch => {
+                unreachable!("handled above")
+            }
+        })
+    }
+
+    fn format_tapback(&self, msg: &Message) -> Result<String, TableError> {
+        let Variant::Tapback(_, action, tapback) = msg.variant() else {
+            return Ok(String::new());
+        };
+        // Removed tapbacks are no longer visible in the conversation
+        if action == TapbackAction::Removed {
+            return Ok(String::new());
+        }
+
+        let who = self.config.who(msg.handle_id, msg.is_from_me());
+        if let Tapback::Sticker = tapback {
+            let mut stickers =
+                Attachment::from_message(self.config.db(), self.config.capabilities(), msg)?;
+            // Sticker tapbacks have only one attachment, the sticker image
+            return Ok(match stickers.get_mut(0) {
+                Some(sticker) => self.format_sticker(sticker, msg),
+                None => format!("Sticker from {who} not found!"),
+            });
+        }
+        Ok(format!("{tapback} by {who}"))
+    }
+
+    fn format_expressive(&self, msg: &'a Message) -> &'a str {
+        match msg.get_expressive() {
+            Expressive::Screen(effect) => match effect {
+                ScreenEffect::Confetti => "Sent with Confetti",
+                ScreenEffect::Echo => "Sent with Echo",
+                ScreenEffect::Fireworks => "Sent with Fireworks",
+                ScreenEffect::Balloons => "Sent with Balloons",
+                ScreenEffect::Heart => "Sent with Heart",
+                ScreenEffect::Lasers => "Sent with Lasers",
+                ScreenEffect::ShootingStar => "Sent with Shooting Star",
+                ScreenEffect::Sparkles => "Sent with Sparkles",
+                ScreenEffect::Spotlight => "Sent with Spotlight",
+            },
+            Expressive::Bubble(effect) => match effect {
+                BubbleEffect::Slam => "Sent with Slam",
+                BubbleEffect::Loud => "Sent with Loud",
+                BubbleEffect::Gentle => "Sent with Gentle",
+                BubbleEffect::InvisibleInk => "Sent with Invisible Ink",
+            },
+            Expressive::Unknown(effect) => effect,
+            Expressive::None => "",
+        }
+    }
+
+    fn format_announcement(&self, msg: &'a Message) -> String {
+        let mut who = self.config.who(msg.handle_id, msg.is_from_me());
+        // Rename yourself so we render the proper grammar here
+        if who == ME {
+            who = self.you();
+        }
+        let timestamp = format(&msg.date(&self.config.offset));
+
+        match msg.get_announcement() {
+            Some(Announcement::GroupAction(action)) => match action {
+                GroupAction::ParticipantAdded(person) => format!(
+                    "{timestamp} {who} added {} to the conversation.\n\n",
+                    self.config.who(Some(person), false)
+                ),
+                GroupAction::ParticipantRemoved(person) => format!(
+                    "{timestamp} {who} removed {} from the conversation.\n\n",
+                    self.config.who(Some(person), false)
+                ),
+                GroupAction::NameChange(name) => {
+                    format!("{timestamp} {who} renamed the conversation to {name}\n\n")
+                }
+                GroupAction::ParticipantLeft => {
+                    format!("{timestamp} {who} left the conversation.\n\n")
+                }
+                GroupAction::GroupIconChanged => {
+                    format!("{timestamp} {who} changed the group photo.\n\n")
+                }
+                GroupAction::GroupIconRemoved => {
+                    format!("{timestamp} {who} removed the group photo.\n\n")
+                }
+            },
+            Some(Announcement::AudioMessageKept) => {
+                format!("{timestamp} {who} kept an audio message.\n\n")
+            }
+            Some(Announcement::FullyUnsent) => format!("{timestamp} {who} unsent a message!\n\n"),
+            Some(Announcement::Unknown(num)) => {
+                format!("{timestamp} {who} performed unknown action {num}.\n\n")
+            }
+            None => String::from("Unable to format announcement!\n\n"),
+        }
+    }
+
+    fn format_shareplay(&self) -> &str {
+        "SharePlay Message Ended"
+    }
+
+    fn format_shared_location(&self, msg: &'a Message) -> &str {
+        // Handle Shared Location
+        if msg.started_sharing_location() {
+            return "Started sharing location!";
+        } else if msg.stopped_sharing_location() {
+            return "Stopped sharing location!";
+        }
+        "Shared location!"
+    }
+
+    fn format_edited(
+        &self,
+        msg: &'a Message,
+        edited_message: &'a EditedMessage,
+        message_part_idx: usize,
+        indent: &str,
+    ) -> Option<String> {
+        let edited_message_part = edited_message.part(message_part_idx)?;
+        let mut out_s = String::new();
+
+        match edited_message_part.status {
+            EditStatus::Edited => {
+                let mut previous_timestamp: Option<&i64> = None;
+                for event in &edited_message_part.edit_history {
+                    match previous_timestamp {
+                        // The original message gets an absolute timestamp
+                        None => {
+                            out_s.push_str(&format(&get_local_time(
+                                &event.date,
+                                &self.config.offset,
+                            )));
+                            out_s.push(' ');
+                        }
+                        // Subsequent edits get a relative timestamp
+                        Some(previous_timestamp) => {
+                            let start = get_local_time(previous_timestamp, &self.config.offset);
+                            let end = get_local_time(&event.date, &self.config.offset);
+                            if let Some(diff) = readable_diff(start, end) {
+                                out_s.push_str(indent);
+                                out_s.push_str("Edited ");
+                                out_s.push_str(&diff);
+                                out_s.push_str(" later: ");
+                            }
+                        }
+                    }
+
+                    // Update the previous timestamp for the next loop
+                    previous_timestamp = Some(&event.date);
+
+                    // Render the message text
+                    if let Some(text) = &event.text {
+                        out_s.push_str(text);
+                        out_s.push('\n');
+                    }
+                }
+            }
+            EditStatus::Unsent => {
+                let who = if msg.is_from_me() { self.you() } else { "They" };
+                match readable_diff(
+                    msg.date(&self.config.offset),
+                    msg.date_edited(&self.config.offset),
+                ) {
+                    Some(diff) => {
+                        let _ = write!(
+                            out_s,
+                            "{who} unsent this message part {diff} after sending!"
+                        );
+                    }
+                    None => {
+                        let _ = write!(out_s, "{who} unsent this message part!");
+                    }
+                }
+            }
+            EditStatus::Original => return None,
+        }
+
+        Some(out_s.trim_end().to_string())
+    }
+
+    fn format_attributes(&'a self, text: &'a str, attributes: &'a [TextAttributes]) -> String {
+        // There is no way to represent formatted text in a plain text export, so only keep the text
+        attributes
+            .iter()
+            .filter_map(|attribute| text.get(attribute.start..attribute.end))
+            .collect()
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_dir, read_to_string, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use imessage_database::{
+        message_types::variants::{Tapback, TapbackAction},
+        util::{
+            dates::{format, get_local_time, get_offset},
+            fixture::{FixtureBuilder, NewMessage, Schema},
+        },
+    };
+
+    use crate::app::{export_type::ExportType, options::Options, runtime::Config};
+
+    const DATE: i64 = 694_000_000_000_000_000;
+    const MINUTE: i64 = 60_000_000_000;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-txt-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn can_export_conversation() {
+        let dir = temp("conversation");
+        let db_path = dir.join("chat.db");
+        let mut fixture = FixtureBuilder::create(&db_path, Schema::Ios16Newer).unwrap();
+        let handle = fixture.handle("+15558675309", "iMessage").unwrap();
+        let chat = fixture
+            .chat("+15558675309", Some("Jenny"), &[handle])
+            .unwrap();
+        let original = fixture
+            .message(chat, NewMessage::text("Hello", DATE).from_handle(handle))
+            .unwrap();
+        fixture
+            .message(
+                chat,
+                NewMessage::text("A reply", DATE + MINUTE).reply_to(&original.guid, 0),
+            )
+            .unwrap();
+        fixture
+            .message(
+                chat,
+                NewMessage::tapback(
+                    &original.guid,
+                    0,
+                    &Tapback::Loved,
+                    &TapbackAction::Added,
+                    DATE + 2 * MINUTE,
+                ),
+            )
+            .unwrap();
+        fixture
+            .message(chat, NewMessage::text("Later", DATE + 3 * MINUTE))
+            .unwrap();
+
+        let export_path = dir.join("export");
+        Config::new(Options::fake(ExportType::Txt, &db_path, &export_path))
+            .unwrap()
+            .start()
+            .unwrap();
+        let file = read_dir(&export_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.to_string_lossy().contains("Jenny"))
+            .unwrap();
+        let page = read_to_string(file).unwrap();
+
+        // Messages are separated by an empty line, with their tapbacks and indented replies below them
+        let date = |date| format(&get_local_time(&date, &get_offset()));
+        assert_eq!(
+            page,
+            format!(
+                "{}\nMe\nLater\n\n\
+                 {}\nMe\nA reply\nThis message responded to an earlier message.\n\n\
+                 {}\n+15558675309\nHello\nTapbacks:\nLoved by Me\n    \
+                 {}\n    Me\n    A reply\n\n",
+                date(DATE + 3 * MINUTE),
+                date(DATE + MINUTE),
+                date(DATE),
+                date(DATE + MINUTE),
+            )
+        );
+
+        remove_dir_all(dir).unwrap();
+    }
+}
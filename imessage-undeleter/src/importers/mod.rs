@@ -0,0 +1,5 @@
+/*!
+Importers that read messages from other tools into an archive the exporters can read
+*/
+
+pub mod sms_backup;
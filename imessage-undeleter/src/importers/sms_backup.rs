@@ -0,0 +1,784 @@
+/*!
+Imports SMS Backup & Restore XML files into an archive with the same schema as the iMessage database
+
+Every `<sms>` and `<mms>` element becomes a message in a chat with its participants, so the archive can be
+read by every exporter with `export --db-path`. Attachment data is decoded from base64 into an `attachments`
+directory next to the archive. Messages whose GUID is already in the archive are skipped, so importing the
+same file twice does not duplicate anything.
+
+Attachment directories and file names come from the backup, so they are reduced to a single path component that
+cannot leave the `attachments` directory.
+*/
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::{File, create_dir_all, remove_dir, remove_file},
+    io::BufReader,
+    path::{Component, Path, PathBuf},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use quick_xml::{
+    Reader,
+    events::{BytesStart, Event},
+};
+use rusqlite::{Connection, OptionalExtension, params};
+
+use imessage_database::{
+    tables::table::ATTACHMENTS_DIR,
+    util::dates::{TIMESTAMP_FACTOR, get_offset},
+};
+
+use crate::{
+    app::{error::RuntimeError, runtime::sanitize_filename},
+    exporters::sms_backup::{
+        ADDR_FROM, ADDRESS_SEPARATOR, APPLICATION_SMIL, MMS_SENT, NULL, OCTET_STREAM,
+        OWNER_ADDRESS, SMS_SENT, TEXT_PLAIN,
+    },
+};
+
+/// Tables and indexes of the archive
+const SCHEMA: &str = include_str!("resources/chat.sql");
+/// Service recorded for every imported message and handle
+const SERVICE: &str = "SMS";
+/// `chat.style` of a group conversation
+const GROUP_CHAT_STYLE: i32 = 43;
+/// `chat.style` of a conversation with one other person
+const DIRECT_CHAT_STYLE: i32 = 45;
+/// Placeholder for an attachment in a message's text
+const ATTACHMENT_PLACEHOLDER: char = '\u{FFFC}';
+
+/// The number of messages read from a backup
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    /// Messages added to the archive
+    pub imported: usize,
+    /// Messages that were already in the archive
+    pub duplicates: usize,
+}
+
+/// A part of a message, in the order it is displayed
+enum Part {
+    Text(String),
+    Attachment {
+        mime_type: String,
+        name: String,
+        data: Vec<u8>,
+    },
+}
+
+/// A message read from an `<sms>` or `<mms>` element
+struct BackupMessage {
+    guid: String,
+    /// Everyone in the conversation other than the device owner
+    addresses: Vec<String>,
+    /// The participant that sent the message, or `None` if it was sent by the device owner
+    sender: Option<String>,
+    /// Unix timestamp in milliseconds
+    date: i64,
+    is_read: bool,
+    subject: Option<String>,
+    parts: Vec<Part>,
+}
+
+/// The attributes of an element, keyed by name, with `null` values removed
+type Attributes = HashMap<String, String>;
+
+pub struct SMSBackupImporter {
+    /// Connection to the archive
+    conn: Connection,
+    /// Directory that decoded attachments are written to
+    attachments_dir: PathBuf,
+    /// Map of address to handle ROWID
+    handles: HashMap<String, i64>,
+    /// Map of chat GUID to chat ROWID
+    chats: HashMap<String, i64>,
+    /// Global date offset used by the iMessage database
+    offset: i64,
+    /// Directories and files created by the current import, in order, removed if it fails
+    created: Vec<PathBuf>,
+}
+
+impl SMSBackupImporter {
+    /// Open the archive, creating it if it does not exist
+    pub fn new(db_path: &Path) -> Result<Self, RuntimeError> {
+        let parent = db_path.parent().unwrap_or(Path::new("."));
+        create_dir_all(parent)?;
+
+        let conn = Connection::open(db_path).map_err(RuntimeError::ArchiveError)?;
+        conn.execute_batch(SCHEMA)
+            .map_err(RuntimeError::ArchiveError)?;
+
+        Ok(SMSBackupImporter {
+            conn,
+            attachments_dir: parent.join(ATTACHMENTS_DIR),
+            handles: HashMap::new(),
+            chats: HashMap::new(),
+            offset: get_offset(),
+            created: vec![],
+        })
+    }
+
+    /// Read every message in a backup file into the archive
+    ///
+    /// The import happens in a single transaction, so a backup that fails to parse does not add any messages,
+    /// and the attachment files written before the failure are removed.
+    pub fn import(&mut self, input: &Path) -> Result<ImportSummary, RuntimeError> {
+        self.conn
+            .execute_batch("BEGIN")
+            .map_err(RuntimeError::ArchiveError)?;
+        self.created.clear();
+        let result = self.read_backup(input);
+        if result.is_ok() {
+            self.conn
+                .execute_batch("COMMIT")
+                .map_err(RuntimeError::ArchiveError)?;
+        } else {
+            self.conn
+                .execute_batch("ROLLBACK")
+                .map_err(RuntimeError::ArchiveError)?;
+            self.remove_created();
+        }
+        result
+    }
+
+    /// Remove the attachment files and directories created by a failed import, newest first
+    fn remove_created(&mut self) {
+        for path in self.created.drain(..).rev() {
+            // Directories are only removed if they are empty
+            let _ = if path.is_dir() {
+                remove_dir(&path)
+            } else {
+                remove_file(&path)
+            };
+        }
+    }
+
+    /// Resolve the path an attachment is written to, which is always a file in its own directory inside `attachments_dir`
+    fn attachment_path(&self, guid: &str, name: &str) -> Result<PathBuf, RuntimeError> {
+        let path = self
+            .attachments_dir
+            .join(path_component(guid))
+            .join(path_component(name));
+        if path.parent().and_then(Path::parent) != Some(self.attachments_dir.as_path()) {
+            return Err(RuntimeError::ImportError(format!(
+                "Attachment {name} of message {guid} resolves outside of {}",
+                self.attachments_dir.display()
+            )));
+        }
+        Ok(path)
+    }
+
+    /// Create a directory and any missing parents, recording the ones that did not exist
+    fn create_dir(&mut self, directory: &Path) -> Result<(), RuntimeError> {
+        let missing: Vec<PathBuf> = directory
+            .ancestors()
+            .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !ancestor.exists())
+            .map(Path::to_path_buf)
+            .collect();
+        create_dir_all(directory)?;
+        self.created.extend(missing.into_iter().rev());
+        Ok(())
+    }
+
+    /// Parse a backup file, inserting each message as soon as it is read
+    fn read_backup(&mut self, input: &Path) -> Result<ImportSummary, RuntimeError> {
+        let mut reader = Reader::from_reader(BufReader::new(File::open(input)?));
+        let mut buf = vec![];
+        let mut summary = ImportSummary::default();
+
+        // The MMS being read, with its parts and addresses
+        let mut mms: Option<(Attributes, Vec<Attributes>, Vec<Attributes>)> = None;
+
+        loop {
+            let event = reader.read_event_into(&mut buf).map_err(|why| {
+                RuntimeError::ImportError(format!(
+                    "Invalid XML at byte {}: {why}",
+                    reader.buffer_position()
+                ))
+            })?;
+            let message = match event {
+                Event::Start(element) | Event::Empty(element) => {
+                    match (element.name().as_ref(), &mut mms) {
+                        (b"sms", _) => Some(BackupMessage::from_sms(attributes(&element)?)),
+                        (b"mms", None) => {
+                            mms = Some((attributes(&element)?, vec![], vec![]));
+                            None
+                        }
+                        (b"part", Some((_, parts, _))) => {
+                            parts.push(attributes(&element)?);
+                            None
+                        }
+                        (b"addr", Some((_, _, addrs))) => {
+                            addrs.push(attributes(&element)?);
+                            None
+                        }
+                        _ => None,
+                    }
+                }
+                Event::End(element) if element.name().as_ref() == b"mms" => mms
+                    .take()
+                    .map(|(mms, parts, addrs)| BackupMessage::from_mms(mms, parts, addrs))
+                    .transpose()?,
+                Event::Eof => break,
+                _ => None,
+            };
+
+            if let Some(message) = message {
+                if self.insert(&message)? {
+                    summary.imported += 1;
+                } else {
+                    summary.duplicates += 1;
+                }
+            }
+            buf.clear();
+        }
+
+        Ok(summary)
+    }
+
+    /// Insert a message with its chat, handles, and attachments, returning `false` if it was already imported
+    fn insert(&mut self, message: &BackupMessage) -> Result<bool, RuntimeError> {
+        let exists = self
+            .conn
+            .query_row(
+                "SELECT 1 FROM message WHERE guid = ?1",
+                [&message.guid],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(RuntimeError::ArchiveError)?;
+        if exists.is_some() {
+            return Ok(false);
+        }
+
+        let chat_id = self.chat(&message.addresses)?;
+        let handle_id = match (&message.sender, message.addresses.as_slice()) {
+            (Some(sender), _) => self.handle(sender)?,
+            // Messages the owner sent to one person belong to that person's handle
+            (None, [address]) => self.handle(address)?,
+            (None, _) => 0,
+        };
+
+        let date = (message.date - self.offset * 1000) * (TIMESTAMP_FACTOR / 1000);
+        let is_from_me = message.sender.is_none();
+        let text: String = message
+            .parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => Cow::Borrowed(text.as_str()),
+                Part::Attachment { .. } => Cow::Owned(ATTACHMENT_PLACEHOLDER.to_string()),
+            })
+            .collect();
+        let num_attachments = message.parts.len()
+            - message
+                .parts
+                .iter()
+                .filter(|part| matches!(part, Part::Text(_)))
+                .count();
+
+        self.conn
+            .execute(
+                "INSERT INTO message (guid, text, handle_id, subject, service, date, date_read, date_delivered, is_delivered, is_finished, is_from_me, is_read, is_sent, cache_has_attachments)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?6, 1, 1, ?8, ?9, ?8, ?10)",
+                params![
+                    message.guid,
+                    text,
+                    handle_id,
+                    message.subject,
+                    SERVICE,
+                    date,
+                    if message.is_read && !is_from_me { date } else { 0 },
+                    is_from_me,
+                    message.is_read,
+                    num_attachments > 0,
+                ],
+            )
+            .map_err(RuntimeError::ArchiveError)?;
+        let message_id = self.conn.last_insert_rowid();
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO chat_message_join (chat_id, message_id, message_date) VALUES (?1, ?2, ?3)",
+                params![chat_id, message_id, date],
+            )
+            .map_err(RuntimeError::ArchiveError)?;
+
+        let attachments = message.parts.iter().filter_map(|part| match part {
+            Part::Attachment {
+                mime_type,
+                name,
+                data,
+            } => Some((mime_type, name, data)),
+            Part::Text(_) => None,
+        });
+        for (index, (mime_type, name, data)) in attachments.enumerate() {
+            let path = self.attachment_path(&message.guid, name)?;
+            if let Some(directory) = path.parent() {
+                self.create_dir(directory)?;
+            }
+            if !path.exists() {
+                self.created.push(path.clone());
+            }
+            std::fs::write(&path, data)?;
+
+            let guid = format!("{}-{index}", message.guid);
+            self.conn
+                .execute(
+                    "INSERT INTO attachment (guid, original_guid, created_date, filename, mime_type, transfer_state, is_outgoing, transfer_name, total_bytes)
+                     VALUES (?1, ?1, ?2, ?3, ?4, 5, ?5, ?6, ?7)",
+                    params![
+                        guid,
+                        date / TIMESTAMP_FACTOR,
+                        path.canonicalize().unwrap_or(path).to_string_lossy(),
+                        mime_type,
+                        is_from_me,
+                        name,
+                        data.len() as i64,
+                    ],
+                )
+                .map_err(RuntimeError::ArchiveError)?;
+            self.conn
+                .execute(
+                    "INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (?1, ?2)",
+                    params![message_id, self.conn.last_insert_rowid()],
+                )
+                .map_err(RuntimeError::ArchiveError)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Get the ROWID of the chat with these participants, creating it if it does not exist
+    fn chat(&mut self, addresses: &[String]) -> Result<i64, RuntimeError> {
+        let mut sorted = addresses.to_vec();
+        sorted.sort();
+        sorted.dedup();
+
+        let (guid, identifier, style) = match sorted.as_slice() {
+            [address] => (
+                format!("{SERVICE};-;{address}"),
+                address.clone(),
+                DIRECT_CHAT_STYLE,
+            ),
+            _ => {
+                let hash = blake3::hash(sorted.join(",").as_bytes()).to_hex();
+                let identifier = format!("chat{}", &hash[..16]);
+                (
+                    format!("{SERVICE};+;{identifier}"),
+                    identifier,
+                    GROUP_CHAT_STYLE,
+                )
+            }
+        };
+        if let Some(&rowid) = self.chats.get(&guid) {
+            return Ok(rowid);
+        }
+
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO chat (guid, style, chat_identifier, service_name) VALUES (?1, ?2, ?3, ?4)",
+                params![guid, style, identifier, SERVICE],
+            )
+            .map_err(RuntimeError::ArchiveError)?;
+        let rowid: i64 = self
+            .conn
+            .query_row("SELECT ROWID FROM chat WHERE guid = ?1", [&guid], |row| {
+                row.get(0)
+            })
+            .map_err(RuntimeError::ArchiveError)?;
+
+        for address in &sorted {
+            let handle_id = self.handle(address)?;
+            self.conn
+                .execute(
+                    "INSERT OR IGNORE INTO chat_handle_join (chat_id, handle_id) VALUES (?1, ?2)",
+                    params![rowid, handle_id],
+                )
+                .map_err(RuntimeError::ArchiveError)?;
+        }
+
+        self.chats.insert(guid, rowid);
+        Ok(rowid)
+    }
+
+    /// Get the ROWID of the handle for an address, creating it if it does not exist
+    fn handle(&mut self, address: &str) -> Result<i64, RuntimeError> {
+        if let Some(&rowid) = self.handles.get(address) {
+            return Ok(rowid);
+        }
+
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO handle (id, service, uncanonicalized_id) VALUES (?1, ?2, ?1)",
+                params![address, SERVICE],
+            )
+            .map_err(RuntimeError::ArchiveError)?;
+        let rowid: i64 = self
+            .conn
+            .query_row(
+                "SELECT ROWID FROM handle WHERE id = ?1 AND service = ?2",
+                params![address, SERVICE],
+                |row| row.get(0),
+            )
+            .map_err(RuntimeError::ArchiveError)?;
+
+        self.handles.insert(address.to_string(), rowid);
+        Ok(rowid)
+    }
+}
+
+impl BackupMessage {
+    fn from_sms(mut sms: Attributes) -> Self {
+        let address = sms.remove("address").unwrap_or_default();
+        let date = parse(&sms, "date");
+        let body = sms.remove("body").unwrap_or_default();
+        let is_sent = parse(&sms, "type") == i64::from(SMS_SENT);
+
+        BackupMessage {
+            guid: derive_guid(&[&address, &date.to_string(), &is_sent.to_string(), &body]),
+            sender: (!is_sent).then(|| address.clone()),
+            addresses: vec![address],
+            date,
+            is_read: parse(&sms, "read") == 1,
+            subject: sms.remove("subject"),
+            parts: vec![Part::Text(body)],
+        }
+    }
+
+    fn from_mms(
+        mut mms: Attributes,
+        parts: Vec<Attributes>,
+        addrs: Vec<Attributes>,
+    ) -> Result<Self, RuntimeError> {
+        let addresses: Vec<String> = mms
+            .remove("address")
+            .unwrap_or_default()
+            .split(ADDRESS_SEPARATOR)
+            .filter(|address| !address.is_empty() && *address != OWNER_ADDRESS)
+            .map(str::to_string)
+            .collect();
+        let date = parse(&mms, "date");
+        let is_sent = parse(&mms, "msg_box") == i64::from(MMS_SENT);
+        let sender = if is_sent {
+            None
+        } else {
+            addrs
+                .iter()
+                .find(|addr| parse(addr, "type") == i64::from(ADDR_FROM))
+                .and_then(|addr| addr.get("address").cloned())
+                .or_else(|| addresses.first().cloned())
+        };
+
+        let parts = parts
+            .into_iter()
+            .filter_map(|mut part| {
+                let mime_type = part
+                    .remove("ct")
+                    .unwrap_or_else(|| OCTET_STREAM.to_string());
+                if mime_type == APPLICATION_SMIL {
+                    return None;
+                }
+                if mime_type == TEXT_PLAIN && !part.contains_key("data") {
+                    return part.remove("text").map(|text| Ok(Part::Text(text)));
+                }
+                let data = part.remove("data")?;
+                let name = part
+                    .remove("name")
+                    .or_else(|| part.remove("cl"))
+                    .unwrap_or_else(|| "attachment".to_string());
+                Some(
+                    STANDARD
+                        .decode(data.as_bytes())
+                        .map(|data| Part::Attachment {
+                            mime_type,
+                            name,
+                            data,
+                        })
+                        .map_err(|why| {
+                            RuntimeError::ImportError(format!("Invalid attachment data: {why}"))
+                        }),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let guid = match mms.remove("m_id") {
+            Some(guid) => guid,
+            None => {
+                let text: String = parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        Part::Text(text) => Some(text.as_str()),
+                        Part::Attachment { .. } => None,
+                    })
+                    .collect();
+                derive_guid(&[&addresses.join(","), &date.to_string(), &text])
+            }
+        };
+
+        Ok(BackupMessage {
+            guid,
+            addresses,
+            sender,
+            date,
+            is_read: parse(&mms, "read") == 1,
+            subject: mms.remove("sub"),
+            parts,
+        })
+    }
+}
+
+/// Read the attributes of an element
+fn attributes(element: &BytesStart) -> Result<Attributes, RuntimeError> {
+    let invalid = |why: &dyn std::fmt::Display| {
+        RuntimeError::ImportError(format!(
+            "Invalid attribute in <{}>: {why}",
+            String::from_utf8_lossy(element.name().as_ref())
+        ))
+    };
+
+    let mut attributes = Attributes::new();
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|why| invalid(&why))?;
+        let value = attribute.unescape_value().map_err(|why| invalid(&why))?;
+        if value != NULL {
+            attributes.insert(
+                String::from_utf8_lossy(attribute.key.as_ref()).to_string(),
+                value.into_owned(),
+            );
+        }
+    }
+    Ok(attributes)
+}
+
+/// Parse a numeric attribute, which is `0` if it is missing or invalid
+fn parse(attributes: &Attributes, key: &str) -> i64 {
+    attributes
+        .get(key)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Reduce a name from the backup to a single normal path component, deriving one from its hash if it cannot be
+fn path_component(name: &str) -> String {
+    let sanitized = sanitize_filename(name);
+    let mut components = Path::new(&sanitized).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => sanitized,
+        _ => derive_guid(&[name]),
+    }
+}
+
+/// Build a stable GUID for a message that does not have one, so reimporting it is detected
+fn derive_guid(fields: &[&str]) -> String {
+    let hash = blake3::hash(fields.join("\u{0}").as_bytes()).to_hex();
+    let hex = hash.to_ascii_uppercase();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read, read_dir, remove_dir_all, write},
+        path::{Path, PathBuf},
+    };
+
+    use rusqlite::Connection;
+
+    use imessage_database::util::fixture::{FixtureBuilder, NewMessage, Schema};
+
+    use crate::{
+        app::{export_type::ExportType, options::Options, runtime::Config},
+        importers::sms_backup::SMSBackupImporter,
+    };
+
+    const DATE: i64 = 694_000_000_000_000_000;
+    const MINUTE: i64 = 60_000_000_000;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-import-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Every file under a directory, recursively
+    fn files(dir: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = read_dir(dir) else {
+            return vec![];
+        };
+        entries
+            .flatten()
+            .flat_map(|entry| {
+                let path = entry.path();
+                if path.is_dir() {
+                    files(&path)
+                } else {
+                    vec![path]
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn can_round_trip_export() {
+        let dir = temp("round-trip");
+        let db_path = dir.join("chat.db");
+        let mut fixture = FixtureBuilder::create(&db_path, Schema::Ios16Newer).unwrap();
+        let handle = fixture.handle("+15558675309", "SMS").unwrap();
+        let chat = fixture.chat("+15558675309", None, &[handle]).unwrap();
+        fixture
+            .message(chat, NewMessage::text("Hello", DATE).from_handle(handle))
+            .unwrap();
+        fixture
+            .message(chat, NewMessage::text("Hi back", DATE + MINUTE))
+            .unwrap();
+        fixture
+            .message(
+                chat,
+                NewMessage::text("Look", DATE + 2 * MINUTE)
+                    .from_handle(handle)
+                    .with_attachment("photo.png", "image/png", b"png bytes"),
+            )
+            .unwrap();
+
+        let export_path = dir.join("export");
+        Config::new(Options::fake(ExportType::SmsBackup, &db_path, &export_path))
+            .unwrap()
+            .start()
+            .unwrap();
+
+        let archive = dir.join("archive").join("chat.db");
+        let backup = export_path.join("sms_backup.xml");
+        let summary = SMSBackupImporter::new(&archive)
+            .unwrap()
+            .import(&backup)
+            .unwrap();
+        assert_eq!(summary.imported, 3);
+        assert_eq!(summary.duplicates, 0);
+
+        let conn = Connection::open(&archive).unwrap();
+        let messages: Vec<(String, bool, i64)> = conn
+            .prepare("SELECT text, is_from_me, date FROM message ORDER BY date")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                ("Hello".to_string(), false, DATE),
+                ("Hi back".to_string(), true, DATE + MINUTE),
+                ("Look\u{FFFC}".to_string(), false, DATE + 2 * MINUTE),
+            ]
+        );
+
+        let (name, filename): (String, String) = conn
+            .query_row(
+                "SELECT transfer_name, filename FROM attachment",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(name, "photo.png");
+        assert_eq!(read(filename).unwrap(), b"png bytes");
+
+        // Importing the same backup again does not duplicate anything
+        let summary = SMSBackupImporter::new(&archive)
+            .unwrap()
+            .import(&backup)
+            .unwrap();
+        assert_eq!(summary.imported, 0);
+        assert_eq!(summary.duplicates, 3);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cannot_write_outside_attachments_dir() {
+        let dir = temp("hostile");
+        let backup = dir.join("backup.xml");
+        let mms = |m_id: &str, name: &str| {
+            format!(
+                r#"<mms date="1700000000000" msg_box="1" address="+15558675309" m_id="{m_id}" read="1"><parts><part seq="0" ct="image/png" name="{name}" data="cGF5bG9hZA==" /></parts><addrs><addr address="+15558675309" type="137" /></addrs></mms>"#
+            )
+        };
+        write(
+            &backup,
+            format!(
+                "<?xml version='1.0' encoding='UTF-8' standalone='yes' ?>\n<smses count=\"4\">{}{}{}{}</smses>",
+                mms("../../escaped", "../../../payload.png"),
+                mms("..", ".."),
+                mms("/tmp/absolute", "/etc/absolute.png"),
+                mms("", ""),
+            ),
+        )
+        .unwrap();
+
+        let archive_dir = dir.join("archive");
+        let summary = SMSBackupImporter::new(&archive_dir.join("chat.db"))
+            .unwrap()
+            .import(&backup)
+            .unwrap();
+        assert_eq!(summary.imported, 4);
+
+        let attachments_dir = archive_dir.join("attachments");
+        let written = files(&attachments_dir);
+        assert_eq!(written.len(), 4);
+        for path in &written {
+            assert_eq!(
+                path.parent().and_then(Path::parent),
+                Some(attachments_dir.as_path())
+            );
+            assert_eq!(read(path).unwrap(), b"payload");
+        }
+        // Nothing was written next to the archive or the backup, or above them
+        assert_eq!(files(&dir).len(), 4 + 2);
+        assert!(!dir.join("escaped").exists());
+        assert!(!temp_dir().join("payload.png").exists());
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_remove_attachments_after_failed_import() {
+        let dir = temp("failed");
+        let backup = dir.join("backup.xml");
+        write(
+            &backup,
+            r#"<?xml version='1.0' encoding='UTF-8' standalone='yes' ?>
+<smses count="2">
+<mms date="1700000000000" msg_box="1" address="+15558675309" m_id="first" read="1"><parts><part seq="0" ct="image/png" name="photo.png" data="cGF5bG9hZA==" /></parts></mms>
+<sms address="+15558675309" date="1700000060000" type="1" body="&invalid;" read="1" />
+</smses>"#,
+        )
+        .unwrap();
+
+        let archive_dir = dir.join("archive");
+        let archive = archive_dir.join("chat.db");
+        assert!(
+            SMSBackupImporter::new(&archive)
+                .unwrap()
+                .import(&backup)
+                .is_err()
+        );
+
+        let count: i64 = Connection::open(&archive)
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM message", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+        assert!(!archive_dir.join("attachments").exists());
+
+        remove_dir_all(dir).unwrap();
+    }
+}
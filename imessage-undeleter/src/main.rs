@@ -10,8 +10,28 @@ use tracing::{info, warn};
 use serde::{Serialize, Deserialize};
 use clap::{Arg, Command};
 use database::{IMessageDatabase, RealMessage};
+use app::{options::{AnalyzeOptions, DEFAULT_DELETIONS_PATH, DoctorOptions, ImportOptions, Options, ReportOptions}, runtime::Config};
+use importers::sms_backup::SMSBackupImporter;
+#[cfg(feature = "simulate")]
+use app::options::SimulateOptions;
+#[cfg(feature = "simulate")]
+use simulator::{Simulator, scenario::Scenario};
 
+mod analytics;
+mod app;
+// The event-driven tracker is not wired up yet, so only the state database it writes is built
+#[allow(dead_code)]
+mod core {
+    pub mod config;
+    pub mod state_manager;
+}
 mod database;
+mod doctor;
+mod exporters;
+mod importers;
+mod report;
+#[cfg(feature = "simulate")]
+mod simulator;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeletionEvent {
@@ -22,6 +42,35 @@ pub struct DeletionEvent {
     pub sender: String,
 }
 
+impl DeletionEvent {
+    /// Build the event reported when the tracker finds that `original_message` was deleted
+    pub fn new(original_message: &RealMessage, db: &IMessageDatabase) -> Self {
+        let sender = if let Some(handle_id) = original_message.handle_id {
+            if let Some(handle) = db.get_handle(handle_id) {
+                handle.identifier.clone()
+            } else {
+                format!("Unknown (ID: {})", handle_id)
+            }
+        } else if original_message.is_from_me {
+            "Me".to_string()
+        } else {
+            "Unknown".to_string()
+        };
+
+        DeletionEvent {
+            message_id: original_message.id,
+            timestamp: original_message.date / 1_000_000_000,
+            content: original_message.text.clone(),
+            attachments: if original_message.cache_has_attachments {
+                vec![format!("attachment_{}.dat", original_message.id)]
+            } else {
+                vec![]
+            },
+            sender,
+        }
+    }
+}
+
 pub struct MessageTracker {
     db_path: PathBuf,
     output_path: PathBuf,
@@ -55,7 +104,7 @@ impl MessageTracker {
                 self.imessage_db = Some(db);
             }
             Err(e) => {
-                return Err(format!("Failed to connect to iMessage database: {}", e).into());
+                return Err(format!("Failed to connect to iMessage database: {}\nRun `doctor` with the same options to find out why", e).into());
             }
         }
 
@@ -146,7 +195,7 @@ impl MessageTracker {
                     if was_deleted {
                         let deletion = {
                             let db = self.imessage_db.as_ref().unwrap();
-                            self.create_deletion_event(cached_msg, db).await?
+                            DeletionEvent::new(cached_msg, db)
                         };
                         self.handle_deletion(deletion).await?;
                         self.message_cache.insert(current_msg.id, current_msg);
@@ -158,32 +207,6 @@ impl MessageTracker {
         Ok(())
     }
 
-    async fn create_deletion_event(&self, original_message: &RealMessage, db: &IMessageDatabase) -> Result<DeletionEvent, Box<dyn std::error::Error>> {
-        let sender = if let Some(handle_id) = original_message.handle_id {
-            if let Some(handle) = db.get_handle(handle_id) {
-                handle.identifier.clone()
-            } else {
-                format!("Unknown (ID: {})", handle_id)
-            }
-        } else if original_message.is_from_me {
-            "Me".to_string()
-        } else {
-            "Unknown".to_string()
-        };
-
-        Ok(DeletionEvent {
-            message_id: original_message.id,
-            timestamp: original_message.date / 1_000_000_000,
-            content: original_message.text.clone(),
-            attachments: if original_message.cache_has_attachments {
-                vec![format!("attachment_{}.dat", original_message.id)]
-            } else {
-                vec![]
-            },
-            sender,
-        })
-    }
-
     async fn handle_deletion(&self, deletion: DeletionEvent) -> Result<(), Box<dyn std::error::Error>> {
         warn!("🚨 DELETED/EDITED MESSAGE: \"{}\" from {}", 
             deletion.content.as_deref().unwrap_or("No content"),
@@ -214,7 +237,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_max_level(tracing::Level::INFO)
         .init();
 
-    let matches = Command::new("iMessage Deletion Tracker")
+    let command = Command::new("iMessage Deletion Tracker")
         .version("2.0.0")
         .about("Monitors iMessage deletions in real-time")
         .arg(
@@ -230,7 +253,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .long("output")
                 .help("Output file path (JSON format)")
                 .value_name("PATH")
-                .default_value("./undeleted_messages/deletions.json")
+                .default_value(DEFAULT_DELETIONS_PATH)
         )
         .arg(
             Arg::new("filter")
@@ -239,7 +262,100 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .help("Filter conversations by contact")
                 .value_name("CONTACT")
         )
-        .get_matches();
+        .subcommand(app::options::export_command())
+        .subcommand(app::options::import_command())
+        .subcommand(app::options::analyze_command())
+        .subcommand(app::options::report_command())
+        .subcommand(app::options::doctor_command());
+    #[cfg(feature = "simulate")]
+    let command = command.subcommand(app::options::simulate_command());
+    let matches = command.get_matches();
+
+    if let Some(("export", export_matches)) = matches.subcommand() {
+        Config::new(Options::from_args(export_matches)?)?.start()?;
+        return Ok(());
+    }
+
+    if let Some(("import", import_matches)) = matches.subcommand() {
+        let options = ImportOptions::from_args(import_matches)?;
+        let summary = SMSBackupImporter::new(&options.db_path)?.import(&options.input)?;
+        info!(
+            "Imported {} messages into {} ({} already imported)",
+            summary.imported,
+            options.db_path.display(),
+            summary.duplicates
+        );
+        return Ok(());
+    }
+
+    #[cfg(feature = "simulate")]
+    if let Some(("simulate", simulate_matches)) = matches.subcommand() {
+        let options = SimulateOptions::from_args(simulate_matches)?;
+        let scenario = Scenario::from_file(&options.scenario)?;
+        for path in [&options.db_path, &options.expected_path] {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        let expected = Simulator::new(&options.db_path, &scenario, !options.instant)?
+            .run(&scenario.steps)?;
+        std::fs::write(&options.expected_path, serde_json::to_string_pretty(&expected)?)?;
+        info!(
+            "Simulated {} steps in {}; the tracker should report {} deletions, written to {}",
+            scenario.steps.len(),
+            options.db_path.display(),
+            expected.len(),
+            options.expected_path.display()
+        );
+        return Ok(());
+    }
+
+    if let Some(("analyze", analyze_matches)) = matches.subcommand() {
+        let options = AnalyzeOptions::from_args(analyze_matches)?;
+        let report = serde_json::to_string_pretty(&analytics::analyze(&options)?)?;
+        match &options.output {
+            Some(path) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path, report)?;
+                info!("Wrote statistics to {}", path.display());
+            }
+            None => println!("{report}"),
+        }
+        return Ok(());
+    }
+
+    if let Some(("report", report_matches)) = matches.subcommand() {
+        let options = ReportOptions::from_args(report_matches)?;
+        let (report, path) = report::generate(&options)?;
+        match path {
+            Some(path) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&path, report)?;
+                info!("Wrote deletion report to {}", path.display());
+            }
+            None => println!("{report}"),
+        }
+        return Ok(());
+    }
+
+    if let Some(("doctor", doctor_matches)) = matches.subcommand() {
+        let options = DoctorOptions::from_args(doctor_matches)?;
+        let checks = doctor::run(&options);
+        for check in &checks {
+            println!("{check}");
+        }
+        let failures = checks.iter().filter(|check| check.status == doctor::Status::Fail).count();
+        if failures > 0 {
+            eprintln!("{failures} {} failed; fix {} before watching for deletions", if failures == 1 { "check" } else { "checks" }, if failures == 1 { "it" } else { "them" });
+            std::process::exit(1);
+        }
+        println!("Ready to watch for deletions");
+        return Ok(());
+    }
 
     let db_path = matches.get_one::<String>("db-path")
         .map(PathBuf::from)
@@ -250,7 +366,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let output_path = matches.get_one::<String>("output")
         .map(PathBuf::from)
-        .unwrap_or_else(|| PathBuf::from("./undeleted_messages/deletions.json"));
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DELETIONS_PATH));
 
     let conversation_filter = matches.get_one::<String>("filter").cloned();
 
@@ -0,0 +1,827 @@
+/*!
+Summarizes the deletions the tracker detected, for the `report` command
+
+Deletion records are read from the tracker's state database and counted by the contact who sent the message,
+the chat it was in, the kind of deletion, and the local hour and day it was detected. The report also measures
+how long after sending each message was deleted, and ranks the contacts who unsend most often. Series are drawn
+as sparklines: block characters in Markdown, and inline SVG in HTML.
+
+The state database does not record when a message was sent or what its chat is named, so both are read from
+the iMessage database when it is available. Deletions whose message is no longer in the iMessage database are
+left out of the send-to-deletion times.
+
+A digest reports the day or week that just ended and is written to a file named for the current date, so a
+scheduled job like `cron` or `launchd` can run the same command every day or week and keep every digest.
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, Timelike};
+use rusqlite::Connection;
+use tracing::warn;
+
+use imessage_database::{
+    tables::{
+        chat::Chat,
+        table::{Cacheable, HANDLE, MESSAGE, UNKNOWN, get_connection},
+    },
+    util::{
+        analytics::Distribution,
+        dates::{TIMESTAMP_FACTOR, get_offset},
+    },
+};
+
+use crate::{
+    app::{
+        error::RuntimeError,
+        options::{DEFAULT_DIGEST_PATH, ReportOptions},
+    },
+    exporters::tabular::DeletionRow,
+};
+
+/// The deletion type the tracker records when every part of a message is unsent
+const UNSEND_TYPE: &str = "FullMessage";
+/// The number of contacts and chats listed in each ranking
+const TOP_COUNT: usize = 10;
+/// The width of a ranking's bar, in characters, for the largest count
+const BAR_WIDTH: usize = 20;
+/// Characters used to draw sparklines, from lowest to highest
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The file formats a report can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    /// Given the user's input, return the matching format
+    pub fn from_cli(format: &str) -> Option<Self> {
+        match format.to_lowercase().as_str() {
+            "markdown" | "md" => Some(Self::Markdown),
+            "html" => Some(Self::Html),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+}
+
+/// The length of time a digest covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestPeriod {
+    Daily,
+    Weekly,
+}
+
+impl DigestPeriod {
+    /// Given the user's input, return the matching period
+    pub fn from_cli(period: &str) -> Option<Self> {
+        match period.to_lowercase().as_str() {
+            "daily" => Some(Self::Daily),
+            "weekly" => Some(Self::Weekly),
+            _ => None,
+        }
+    }
+
+    fn duration(self) -> Duration {
+        match self {
+            DigestPeriod::Daily => Duration::days(1),
+            DigestPeriod::Weekly => Duration::weeks(1),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DigestPeriod::Daily => "Daily",
+            DigestPeriod::Weekly => "Weekly",
+        }
+    }
+}
+
+/// The number of deletions for a contact, chat, or kind of deletion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tally {
+    pub name: String,
+    pub count: u64,
+}
+
+/// A contact ranked by how many messages they unsent
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsender {
+    pub contact: String,
+    /// The number of messages the contact unsent
+    pub unsent: u64,
+    /// The number of messages the contact sent, if the iMessage database is available
+    pub messages: Option<u64>,
+}
+
+impl Unsender {
+    /// The share of the contact's messages they unsent, from `0` to `1`
+    pub fn rate(&self) -> Option<f64> {
+        self.messages
+            .filter(|&messages| messages > 0)
+            .map(|messages| self.unsent as f64 / messages as f64)
+    }
+}
+
+/// Data read from the iMessage database to describe deleted messages
+#[derive(Debug, Default)]
+pub struct MessageContext {
+    /// Chat ID to the chat's name
+    pub chat_names: HashMap<i32, String>,
+    /// Message ID to when the message was sent, as a Unix timestamp
+    pub sent: HashMap<i32, i64>,
+    /// Contact to the number of messages they sent
+    pub received: HashMap<String, u64>,
+}
+
+impl MessageContext {
+    /// Read the context for `deletions` from the iMessage database
+    pub fn load(conn: &Connection, deletions: &[DeletionRow]) -> Result<Self, rusqlite::Error> {
+        let mut context = MessageContext::default();
+
+        if let Ok(chats) = Chat::cache(conn) {
+            context.chat_names = chats
+                .into_iter()
+                .map(|(id, chat)| (id, chat.name().to_string()))
+                .collect();
+        }
+
+        let mut statement =
+            conn.prepare(&format!("SELECT date FROM {MESSAGE} WHERE ROWID = ?1"))?;
+        for deletion in deletions {
+            let date: Option<i64> = statement
+                .query_row([deletion.message_id], |row| row.get(0))
+                .ok();
+            if let Some(date) = date.filter(|date| *date != 0) {
+                context
+                    .sent
+                    .insert(deletion.message_id, date / TIMESTAMP_FACTOR + get_offset());
+            }
+        }
+
+        let mut statement = conn.prepare(&format!(
+            "SELECT h.id, COUNT(*) FROM {MESSAGE} m JOIN {HANDLE} h ON m.handle_id = h.ROWID WHERE m.is_from_me = 0 GROUP BY h.id"
+        ))?;
+        let counts = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        for count in counts {
+            let (contact, messages): (String, u64) = count?;
+            *context.received.entry(contact).or_default() += messages;
+        }
+
+        Ok(context)
+    }
+}
+
+/// Statistics about the deletions detected in a period
+#[derive(Debug)]
+pub struct DeletionReport {
+    pub title: String,
+    /// The start of the period, if it has one
+    pub start: Option<DateTime<Local>>,
+    /// The end of the period, if it has one
+    pub end: Option<DateTime<Local>>,
+    pub total: usize,
+    /// Deletions by kind, most common first
+    pub by_type: Vec<Tally>,
+    /// Deletions by the contact who sent the message, most common first
+    pub by_contact: Vec<Tally>,
+    /// Deletions by chat, most common first
+    pub by_chat: Vec<Tally>,
+    /// Deletions by the local hour they were detected
+    pub by_hour: [u64; 24],
+    /// Deletions by the local day they were detected, from the first day to the last
+    pub by_day: Vec<(NaiveDate, u64)>,
+    /// Seconds between a message being sent and being deleted
+    pub send_to_delete: Option<Distribution>,
+    /// Contacts who unsent messages, most unsent first
+    pub top_unsenders: Vec<Unsender>,
+}
+
+impl DeletionReport {
+    /// Count the deletions by contact, chat, kind, and time
+    pub fn build(
+        title: String,
+        start: Option<DateTime<Local>>,
+        end: Option<DateTime<Local>>,
+        deletions: &[DeletionRow],
+        context: &MessageContext,
+    ) -> Self {
+        let mut by_type: HashMap<&str, u64> = HashMap::new();
+        let mut by_contact: HashMap<&str, u64> = HashMap::new();
+        let mut by_chat: HashMap<String, u64> = HashMap::new();
+        let mut unsent: HashMap<&str, u64> = HashMap::new();
+        let mut by_hour = [0; 24];
+        let mut by_day: BTreeMap<NaiveDate, u64> = BTreeMap::new();
+        let mut send_to_delete = vec![];
+
+        for deletion in deletions {
+            let contact = deletion.sender_handle.as_deref().unwrap_or(UNKNOWN);
+            *by_type.entry(&deletion.deletion_type).or_default() += 1;
+            *by_contact.entry(contact).or_default() += 1;
+            if deletion.deletion_type == UNSEND_TYPE {
+                *unsent.entry(contact).or_default() += 1;
+            }
+
+            let chat = match deletion.conversation_id {
+                Some(id) => context
+                    .chat_names
+                    .get(&id)
+                    .cloned()
+                    .unwrap_or_else(|| format!("Chat {id}")),
+                None => UNKNOWN.to_string(),
+            };
+            *by_chat.entry(chat).or_default() += 1;
+
+            if let Some(deleted_at) = deletion.deleted_at {
+                let local = deleted_at.with_timezone(&Local);
+                by_hour[local.hour() as usize] += 1;
+                *by_day.entry(local.date_naive()).or_default() += 1;
+
+                if let Some(sent) = context.sent.get(&deletion.message_id) {
+                    let elapsed = deleted_at.timestamp() - sent;
+                    if elapsed >= 0 {
+                        send_to_delete.push(elapsed as f64);
+                    }
+                }
+            }
+        }
+
+        let mut top_unsenders: Vec<Unsender> = unsent
+            .into_iter()
+            .map(|(contact, unsent)| Unsender {
+                contact: contact.to_string(),
+                unsent,
+                messages: context.received.get(contact).copied(),
+            })
+            .collect();
+        top_unsenders.sort_by(|a, b| b.unsent.cmp(&a.unsent).then(a.contact.cmp(&b.contact)));
+        top_unsenders.truncate(TOP_COUNT);
+
+        DeletionReport {
+            title,
+            start,
+            end,
+            total: deletions.len(),
+            by_type: ranked(
+                by_type
+                    .into_iter()
+                    .map(|(name, count)| (name.to_string(), count)),
+            ),
+            by_contact: ranked(
+                by_contact
+                    .into_iter()
+                    .map(|(name, count)| (name.to_string(), count)),
+            ),
+            by_chat: ranked(by_chat.into_iter()),
+            by_hour,
+            by_day: fill_days(&by_day),
+            send_to_delete: Distribution::from_samples(send_to_delete),
+            top_unsenders,
+        }
+    }
+
+    /// Describe the period the report covers
+    fn period(&self) -> String {
+        let day = |date: &DateTime<Local>| date.format("%Y-%m-%d").to_string();
+        match (&self.start, &self.end) {
+            (Some(start), Some(end)) => format!("from {} to {}", day(start), day(end)),
+            (Some(start), None) => format!("since {}", day(start)),
+            (None, Some(end)) => format!("before {}", day(end)),
+            (None, None) => "since the tracker started".to_string(),
+        }
+    }
+
+    /// Render the report as Markdown
+    pub fn to_markdown(&self) -> String {
+        let mut out_s = String::new();
+        let _ = writeln!(out_s, "# {}\n", escape_markdown(&self.title));
+        let _ = writeln!(
+            out_s,
+            "**{}** deletions detected {}.\n",
+            self.total,
+            self.period()
+        );
+        if self.total == 0 {
+            return out_s;
+        }
+
+        out_s.push_str("## Activity\n\n");
+        let _ = writeln!(
+            out_s,
+            "By hour of day, 00 to 23: `{}`\n",
+            sparkline(&self.by_hour)
+        );
+        if let (Some((first, _)), Some((last, _))) = (self.by_day.first(), self.by_day.last()) {
+            let days: Vec<u64> = self.by_day.iter().map(|(_, count)| *count).collect();
+            let _ = writeln!(out_s, "By day, {first} to {last}: `{}`\n", sparkline(&days));
+        }
+
+        if let Some(latency) = &self.send_to_delete {
+            out_s.push_str("## Time from sending to deletion\n\n");
+            out_s.push_str("| Median | 90th percentile | Longest | Measured |\n");
+            out_s.push_str("|---:|---:|---:|---:|\n");
+            let _ = writeln!(
+                out_s,
+                "| {} | {} | {} | {} |\n",
+                readable_duration(latency.median),
+                readable_duration(latency.p90),
+                readable_duration(latency.max),
+                latency.count
+            );
+        }
+
+        for (heading, column, tallies) in [
+            ("By type", "Type", &self.by_type),
+            ("By contact", "Contact", &self.by_contact),
+            ("By chat", "Chat", &self.by_chat),
+        ] {
+            let _ = writeln!(out_s, "## {heading}\n");
+            let _ = writeln!(out_s, "| {column} | Deletions | |");
+            out_s.push_str("|---|---:|---|\n");
+            let most = tallies.first().map_or(0, |tally| tally.count);
+            for tally in tallies.iter().take(TOP_COUNT) {
+                let _ = writeln!(
+                    out_s,
+                    "| {} | {} | `{}` |",
+                    escape_markdown(&tally.name),
+                    tally.count,
+                    bar(tally.count, most)
+                );
+            }
+            out_s.push('\n');
+        }
+
+        if !self.top_unsenders.is_empty() {
+            out_s.push_str("## Contacts who unsend most often\n\n");
+            out_s.push_str("| Contact | Unsent | Messages | Rate |\n");
+            out_s.push_str("|---|---:|---:|---:|\n");
+            for unsender in &self.top_unsenders {
+                let _ = writeln!(
+                    out_s,
+                    "| {} | {} | {} | {} |",
+                    escape_markdown(&unsender.contact),
+                    unsender.unsent,
+                    unsender
+                        .messages
+                        .map_or_else(|| "-".to_string(), |count| count.to_string()),
+                    unsender
+                        .rate()
+                        .map_or_else(|| "-".to_string(), |rate| format!("{:.1}%", rate * 100.))
+                );
+            }
+            out_s.push('\n');
+        }
+
+        out_s
+    }
+
+    /// Render the report as a standalone HTML page
+    pub fn to_html(&self) -> String {
+        let mut out_s = String::new();
+        let title = escape_html(&self.title);
+        let _ = write!(
+            out_s,
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: -apple-system, sans-serif; max-width: 50em; margin: 2em auto; color: #222; }}
+table {{ border-collapse: collapse; margin-bottom: 1.5em; }}
+th, td {{ padding: 0.25em 0.75em; text-align: left; border-bottom: 1px solid #ddd; }}
+td.count {{ text-align: right; }}
+.bar {{ display: inline-block; height: 0.8em; background: #1982fc; }}
+svg.sparkline rect {{ fill: #1982fc; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p><strong>{}</strong> deletions detected {}.</p>
+"#,
+            self.total,
+            escape_html(&self.period())
+        );
+        if self.total == 0 {
+            out_s.push_str("</body>\n</html>\n");
+            return out_s;
+        }
+
+        out_s.push_str("<h2>Activity</h2>\n");
+        let _ = writeln!(
+            out_s,
+            "<p>By hour of day, 00 to 23<br>{}</p>",
+            sparkline_svg(&self.by_hour)
+        );
+        if let (Some((first, _)), Some((last, _))) = (self.by_day.first(), self.by_day.last()) {
+            let days: Vec<u64> = self.by_day.iter().map(|(_, count)| *count).collect();
+            let _ = writeln!(
+                out_s,
+                "<p>By day, {first} to {last}<br>{}</p>",
+                sparkline_svg(&days)
+            );
+        }
+
+        if let Some(latency) = &self.send_to_delete {
+            out_s.push_str("<h2>Time from sending to deletion</h2>\n");
+            let _ = writeln!(
+                out_s,
+                "<table><tr><th>Median</th><th>90th percentile</th><th>Longest</th><th>Measured</th></tr>\
+                 <tr><td>{}</td><td>{}</td><td>{}</td><td class=\"count\">{}</td></tr></table>",
+                readable_duration(latency.median),
+                readable_duration(latency.p90),
+                readable_duration(latency.max),
+                latency.count
+            );
+        }
+
+        for (heading, column, tallies) in [
+            ("By type", "Type", &self.by_type),
+            ("By contact", "Contact", &self.by_contact),
+            ("By chat", "Chat", &self.by_chat),
+        ] {
+            let _ = writeln!(out_s, "<h2>{heading}</h2>");
+            let _ = writeln!(
+                out_s,
+                "<table><tr><th>{column}</th><th>Deletions</th><th></th></tr>"
+            );
+            let most = tallies.first().map_or(0, |tally| tally.count);
+            for tally in tallies.iter().take(TOP_COUNT) {
+                let _ = writeln!(
+                    out_s,
+                    "<tr><td>{}</td><td class=\"count\">{}</td><td><span class=\"bar\" style=\"width: {}em\"></span></td></tr>",
+                    escape_html(&tally.name),
+                    tally.count,
+                    bar_width(tally.count, most) / 2
+                );
+            }
+            out_s.push_str("</table>\n");
+        }
+
+        if !self.top_unsenders.is_empty() {
+            out_s.push_str("<h2>Contacts who unsend most often</h2>\n");
+            out_s.push_str(
+                "<table><tr><th>Contact</th><th>Unsent</th><th>Messages</th><th>Rate</th></tr>\n",
+            );
+            for unsender in &self.top_unsenders {
+                let _ = writeln!(
+                    out_s,
+                    "<tr><td>{}</td><td class=\"count\">{}</td><td class=\"count\">{}</td><td class=\"count\">{}</td></tr>",
+                    escape_html(&unsender.contact),
+                    unsender.unsent,
+                    unsender
+                        .messages
+                        .map_or_else(|| "-".to_string(), |count| count.to_string()),
+                    unsender
+                        .rate()
+                        .map_or_else(|| "-".to_string(), |rate| format!("{:.1}%", rate * 100.))
+                );
+            }
+            out_s.push_str("</table>\n");
+        }
+
+        out_s.push_str("</body>\n</html>\n");
+        out_s
+    }
+
+    /// Render the report in `format`
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.to_markdown(),
+            ReportFormat::Html => self.to_html(),
+        }
+    }
+}
+
+/// Build the report selected by the options, and the path to write it to, or `None` to print it
+pub fn generate(options: &ReportOptions) -> Result<(String, Option<PathBuf>), RuntimeError> {
+    let now = Local::now();
+    let (title, start, end) = match options.digest {
+        Some(period) => (
+            format!("{} deletion digest", period.name()),
+            Some(now - period.duration()),
+            Some(now),
+        ),
+        None => (
+            "Deletion report".to_string(),
+            options
+                .start
+                .and_then(|stamp| DateTime::from_timestamp(stamp, 0))
+                .map(|date| date.with_timezone(&Local)),
+            options
+                .end
+                .and_then(|stamp| DateTime::from_timestamp(stamp, 0))
+                .map(|date| date.with_timezone(&Local)),
+        ),
+    };
+
+    // Dates are inclusive in the state database query, but the end date is exclusive in the options
+    let deletions = DeletionRow::load_range(
+        &options.state_db_path,
+        start.map_or(i64::MIN, |date| date.timestamp()),
+        end.map_or(i64::MAX, |date| {
+            date.timestamp() - i64::from(options.digest.is_none())
+        }),
+    )?;
+
+    let context = match get_connection(&options.get_db_path()) {
+        Ok(conn) => MessageContext::load(&conn, &deletions)
+            .inspect_err(|why| warn!("Unable to read messages from the iMessage database: {why}"))
+            .unwrap_or_default(),
+        Err(why) => {
+            warn!("Reporting without chat names or send times: {why}");
+            MessageContext::default()
+        }
+    };
+
+    let report = DeletionReport::build(title, start, end, &deletions, &context);
+    let path = options.output.clone().or_else(|| {
+        options.digest.map(|_| {
+            PathBuf::from(DEFAULT_DIGEST_PATH).join(format!(
+                "digest-{}.{}",
+                now.format("%Y-%m-%d"),
+                options.format.extension()
+            ))
+        })
+    });
+    Ok((report.render(options.format), path))
+}
+
+/// Sort tallies from most to least common, breaking ties by name
+fn ranked(tallies: impl Iterator<Item = (String, u64)>) -> Vec<Tally> {
+    let mut out_v: Vec<Tally> = tallies.map(|(name, count)| Tally { name, count }).collect();
+    out_v.sort_by(|a, b| b.count.cmp(&a.count).then(a.name.cmp(&b.name)));
+    out_v
+}
+
+/// List every day from the first to the last, including days without deletions
+fn fill_days(by_day: &BTreeMap<NaiveDate, u64>) -> Vec<(NaiveDate, u64)> {
+    let (Some(first), Some(last)) = (by_day.keys().next(), by_day.keys().next_back()) else {
+        return vec![];
+    };
+    first
+        .iter_days()
+        .take_while(|day| day <= last)
+        .map(|day| (day, by_day.get(&day).copied().unwrap_or(0)))
+        .collect()
+}
+
+/// Draw a series as block characters, scaled so the largest value is the tallest block
+fn sparkline(values: &[u64]) -> String {
+    let most = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|&value| {
+            if most == 0 {
+                SPARKS[0]
+            } else {
+                SPARKS[(value * (SPARKS.len() as u64 - 1)).div_ceil(most) as usize]
+            }
+        })
+        .collect()
+}
+
+/// Draw a series as an inline SVG bar chart, scaled so the largest value is the tallest bar
+fn sparkline_svg(values: &[u64]) -> String {
+    const BAR: usize = 6;
+    const HEIGHT: u64 = 24;
+    let most = values.iter().copied().max().unwrap_or(0).max(1);
+    let mut out_s = format!(
+        r#"<svg class="sparkline" width="{}" height="{HEIGHT}" viewBox="0 0 {} {HEIGHT}">"#,
+        values.len() * BAR,
+        values.len() * BAR
+    );
+    for (idx, &value) in values.iter().enumerate() {
+        // Keep a sliver for empty values, so the baseline is visible
+        let height = (value * HEIGHT).div_ceil(most).max(1);
+        let _ = write!(
+            out_s,
+            r#"<rect x="{}" y="{}" width="{}" height="{height}"><title>{value}</title></rect>"#,
+            idx * BAR,
+            HEIGHT - height,
+            BAR - 1
+        );
+    }
+    out_s.push_str("</svg>");
+    out_s
+}
+
+/// The length of a ranking's bar, in characters, relative to the largest count
+fn bar_width(count: u64, most: u64) -> u64 {
+    if most == 0 {
+        return 0;
+    }
+    (count * BAR_WIDTH as u64).div_ceil(most)
+}
+
+/// Draw a ranking's bar as block characters
+fn bar(count: u64, most: u64) -> String {
+    "█".repeat(bar_width(count, most) as usize)
+}
+
+/// Format a number of seconds like `3d 4h`, `2h 15m`, `5m 10s`, or `42s`
+fn readable_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    let (days, hours, minutes, seconds) = (
+        seconds / 86_400,
+        seconds % 86_400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+    );
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+/// Escape text for a Markdown table cell
+fn escape_markdown(text: &str) -> String {
+    let mut out_s = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '|' | '*' | '_' | '`' | '[' | ']' | '<' | '>' | '#' => {
+                out_s.push('\\');
+                out_s.push(c);
+            }
+            '\n' | '\r' => out_s.push(' '),
+            c if c.is_control() => {}
+            c => out_s.push(c),
+        }
+    }
+    out_s
+}
+
+/// Escape text for use in HTML
+fn escape_html(text: &str) -> String {
+    let mut out_s = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out_s.push_str("&amp;"),
+            '<' => out_s.push_str("&lt;"),
+            '>' => out_s.push_str("&gt;"),
+            '"' => out_s.push_str("&quot;"),
+            '\'' => out_s.push_str("&#39;"),
+            c => out_s.push(c),
+        }
+    }
+    out_s
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{DateTime, Local};
+
+    use crate::{
+        exporters::tabular::DeletionRow,
+        report::{
+            DeletionReport, DigestPeriod, MessageContext, ReportFormat, UNSEND_TYPE, Unsender,
+        },
+    };
+
+    /// Seconds since the Unix epoch of the first deletion
+    const DELETED_AT: i64 = 1_700_000_000;
+
+    fn deletion(id: i64, sender: &str, chat: i32, deletion_type: &str) -> DeletionRow {
+        DeletionRow {
+            id,
+            message_id: id as i32,
+            deleted_at: DateTime::from_timestamp(DELETED_AT + id * 60, 0),
+            deletion_type: deletion_type.to_string(),
+            fingerprinted_at: None,
+            conversation_id: Some(chat),
+            sender_handle: Some(sender.to_string()),
+            content_hash: String::new(),
+            recovered_content: None,
+            recovered_attachments: vec![],
+        }
+    }
+
+    fn report(deletions: &[DeletionRow]) -> DeletionReport {
+        let context = MessageContext {
+            chat_names: HashMap::from([(1, "Jenny".to_string()), (2, "<b>Team</b>".to_string())]),
+            // Every message was sent 90 seconds before it was deleted
+            sent: deletions
+                .iter()
+                .map(|deletion| (deletion.message_id, DELETED_AT + deletion.id * 60 - 90))
+                .collect(),
+            received: HashMap::from([("+15558675309".to_string(), 4)]),
+        };
+        DeletionReport::build(
+            "Deletion report".to_string(),
+            DateTime::from_timestamp(DELETED_AT, 0).map(|date| date.with_timezone(&Local)),
+            None,
+            deletions,
+            &context,
+        )
+    }
+
+    fn deletions() -> Vec<DeletionRow> {
+        vec![
+            deletion(1, "+15558675309", 1, UNSEND_TYPE),
+            deletion(2, "+15558675309", 1, UNSEND_TYPE),
+            deletion(3, "a|b@example.com", 2, "PartialEdit"),
+        ]
+    }
+
+    #[test]
+    fn can_parse_report_format() {
+        assert_eq!(
+            ReportFormat::from_cli("markdown"),
+            Some(ReportFormat::Markdown)
+        );
+        assert_eq!(ReportFormat::from_cli("MD"), Some(ReportFormat::Markdown));
+        assert_eq!(ReportFormat::from_cli("Html"), Some(ReportFormat::Html));
+        assert_eq!(ReportFormat::from_cli("pdf"), None);
+    }
+
+    #[test]
+    fn can_parse_digest_period() {
+        assert_eq!(DigestPeriod::from_cli("daily"), Some(DigestPeriod::Daily));
+        assert_eq!(DigestPeriod::from_cli("WEEKLY"), Some(DigestPeriod::Weekly));
+        assert_eq!(DigestPeriod::from_cli("monthly"), None);
+    }
+
+    #[test]
+    fn can_get_unsend_rate() {
+        let unsender = |messages| Unsender {
+            contact: "+15558675309".to_string(),
+            unsent: 2,
+            messages,
+        };
+
+        assert_eq!(unsender(Some(8)).rate(), Some(0.25));
+        assert_eq!(unsender(Some(0)).rate(), None);
+        assert_eq!(unsender(None).rate(), None);
+    }
+
+    #[test]
+    fn can_count_deletions() {
+        let report = report(&deletions());
+
+        assert_eq!(report.total, 3);
+        assert_eq!(report.by_type[0].name, UNSEND_TYPE);
+        assert_eq!(report.by_type[0].count, 2);
+        assert_eq!(report.by_chat[0].name, "Jenny");
+        assert_eq!(report.by_hour.iter().sum::<u64>(), 3);
+        assert_eq!(report.send_to_delete.as_ref().unwrap().median, 90.);
+        assert_eq!(report.top_unsenders.len(), 1);
+        assert_eq!(report.top_unsenders[0].rate(), Some(0.5));
+    }
+
+    #[test]
+    fn can_render_markdown() {
+        let markdown = report(&deletions()).to_markdown();
+
+        assert!(markdown.starts_with("# Deletion report\n\n**3** deletions detected since "));
+        assert!(markdown.contains("| 1m 30s | 1m 30s | 1m 30s | 3 |"));
+        assert!(markdown.contains(&format!("| {UNSEND_TYPE} | 2 | `{}` |", "█".repeat(20))));
+        assert!(markdown.contains("| a\\|b@example.com | 1 |"));
+        assert!(markdown.contains("| \\<b\\>Team\\</b\\> | 1 |"));
+        assert!(markdown.contains("| +15558675309 | 2 | 4 | 50.0% |"));
+    }
+
+    #[test]
+    fn can_render_html() {
+        let html = report(&deletions()).to_html();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<p><strong>3</strong> deletions detected since "));
+        assert!(html.contains(r#"<svg class="sparkline""#));
+        assert!(html.contains("<td>&lt;b&gt;Team&lt;/b&gt;</td>"));
+        assert!(!html.contains("<b>Team"));
+        assert!(html.contains(r#"<td class="count">50.0%</td>"#));
+        assert!(html.ends_with("</body>\n</html>\n"));
+    }
+
+    #[test]
+    fn can_render_empty_report() {
+        let report = report(&[]);
+
+        let markdown = report.render(ReportFormat::Markdown);
+        assert!(markdown.contains("**0** deletions detected"));
+        assert!(!markdown.contains("## Activity"));
+
+        let html = report.render(ReportFormat::Html);
+        assert!(!html.contains("<h2>"));
+        assert!(html.ends_with("</body>\n</html>\n"));
+    }
+}
@@ -0,0 +1,458 @@
+/*!
+Simulates `Messages.app` writing to a database, so the tracker can be tested end to end without a Mac
+
+A [`Simulator`] creates a fixture database in WAL mode and applies the steps of a [`Scenario`] to it, waiting
+between steps like a person would. Each step is written in its own transaction, so a tracker reading the database
+sees it change one step at a time. Message dates follow the scenario's timeline even when the simulation does not
+wait, so results do not depend on how fast it runs.
+
+The simulator also records the [`DeletionEvent`]s the tracker should report. The tracker reports a message once
+every part of it is unsent, with the content it had when the tracker first saw it; the other steps change the
+database without removing text from a message, so they are expected to go unreported.
+
+A scenario is a JSON file:
+
+```json
+{
+    "schema": "ios16",
+    "handles": [{ "id": "+15558675309" }],
+    "chats": [{ "name": "jenny", "participants": ["+15558675309"] }],
+    "steps": [
+        { "op": "send", "id": "hello", "chat": "jenny", "from": "+15558675309", "parts": [{ "text": "Hello!" }] },
+        { "op": "tapback", "message": "hello", "tapback": "loved" },
+        { "op": "edit", "message": "hello", "text": "Hello there!", "delay_ms": 5000 },
+        { "op": "unsend", "message": "hello" }
+    ]
+}
+```
+*/
+
+pub mod scenario;
+
+use std::{
+    collections::HashMap,
+    fs::remove_file,
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::Duration,
+};
+
+use chrono::Utc;
+use tracing::info;
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::variants::TapbackAction,
+    util::{
+        dates::{TIMESTAMP_FACTOR, get_offset},
+        fixture::{FixtureBuilder, InsertedMessage, NewMessage},
+        typedstream::models::AttributeRun,
+    },
+};
+
+use crate::{
+    DeletionEvent,
+    app::error::RuntimeError,
+    database::{IMessageDatabase, RealMessage},
+    simulator::scenario::{Operation, Scenario, ScenarioPart, Step},
+};
+
+/// Nanoseconds in a millisecond, used to advance message dates
+const NANOSECONDS_PER_MS: i64 = 1_000_000;
+
+/// A message sent during the simulation
+struct SentMessage {
+    inserted: InsertedMessage,
+    /// The chat the message was sent in
+    chat_id: i32,
+    /// The number of parts the message was sent with
+    parts: usize,
+    /// The parts that were unsent
+    unsent: Vec<usize>,
+    /// The message as the tracker first sees it, if the tracker watches it
+    tracked: Option<RealMessage>,
+}
+
+/// Applies the steps of a [`Scenario`] to a fixture database
+pub struct Simulator {
+    fixture: FixtureBuilder,
+    db_path: PathBuf,
+    /// The `ROWID` of each handle, keyed by its `id`
+    handles: HashMap<String, i32>,
+    /// The `ROWID` and participants of each chat, keyed by its name
+    chats: HashMap<String, (i32, Vec<i32>)>,
+    /// The messages that were sent, keyed by their `id`
+    messages: HashMap<String, SentMessage>,
+    /// The current time on the scenario's timeline, in the iMessage epoch
+    clock: i64,
+    /// Whether to wait between steps
+    realtime: bool,
+}
+
+impl Simulator {
+    /// Create the database at `db_path` with the handles and chats of a scenario
+    pub fn new(db_path: &Path, scenario: &Scenario, realtime: bool) -> Result<Self, RuntimeError> {
+        if db_path.exists() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "{} already exists; simulations create a new database",
+                db_path.display()
+            )));
+        }
+
+        let mut fixture = FixtureBuilder::create(db_path, scenario.schema.into())?;
+        fixture
+            .connection()
+            .query_row("PRAGMA journal_mode = WAL", [], |row| {
+                row.get::<_, String>(0)
+            })
+            .map_err(|why| {
+                RuntimeError::DatabaseError(TableError::CannotConnect(why.to_string()))
+            })?;
+
+        let mut handles = HashMap::new();
+        for handle in &scenario.handles {
+            handles.insert(
+                handle.id.clone(),
+                fixture.handle(&handle.id, &handle.service)?,
+            );
+        }
+
+        let mut chats = HashMap::new();
+        for chat in &scenario.chats {
+            let participants = chat
+                .participants
+                .iter()
+                .map(|id| lookup(&handles, id, "handle").copied())
+                .collect::<Result<Vec<_>, _>>()?;
+            let identifier = if participants.len() == 1 {
+                chat.participants[0].clone()
+            } else {
+                format!("chat{}", chats.len())
+            };
+            let chat_id = fixture.chat(&identifier, chat.display_name.as_deref(), &participants)?;
+            chats.insert(chat.name.clone(), (chat_id, participants));
+        }
+
+        let now = Utc::now();
+        Ok(Self {
+            fixture,
+            db_path: db_path.to_path_buf(),
+            handles,
+            chats,
+            messages: HashMap::new(),
+            clock: (now.timestamp() - get_offset()) * TIMESTAMP_FACTOR
+                + i64::from(now.timestamp_subsec_nanos()),
+            realtime,
+        })
+    }
+
+    /// Apply every step of a scenario, returning the events the tracker is expected to report
+    pub fn run(&mut self, steps: &[Step]) -> Result<Vec<DeletionEvent>, RuntimeError> {
+        // The tracker reads the database on its own connection, so the simulation does too
+        let reader = IMessageDatabase::new(&self.db_path).map_err(TableError::Messages)?;
+
+        let mut events = vec![];
+        for (idx, step) in steps.iter().enumerate() {
+            if self.realtime {
+                sleep(Duration::from_millis(step.delay_ms));
+            }
+            self.clock += step.delay_ms as i64 * NANOSECONDS_PER_MS;
+
+            info!("Step {}: {:?}", idx + 1, step.operation);
+            self.transaction(|simulator| simulator.apply(&step.operation))?;
+            if let Some(event) = self.observe(&step.operation, &reader)? {
+                events.push(event);
+            }
+        }
+        Ok(events)
+    }
+
+    /// Make a single change to the database
+    fn apply(&mut self, operation: &Operation) -> Result<(), RuntimeError> {
+        let date = self.clock;
+        match operation {
+            Operation::Send {
+                id,
+                chat,
+                from,
+                parts,
+            } => {
+                if self.messages.contains_key(id) {
+                    return Err(RuntimeError::SimulationError(format!(
+                        "Message {id} was already sent"
+                    )));
+                }
+                let chat_id = lookup(&self.chats, chat, "chat")?.0;
+                let mut message =
+                    self.with_sender(NewMessage::new(date), from.as_deref(), chat_id)?;
+                for part in parts {
+                    message = match part {
+                        ScenarioPart::Text(text) => {
+                            message.with_text(vec![AttributeRun::new(text)])
+                        }
+                        ScenarioPart::Attachment(attachment) => message.with_attachment(
+                            &attachment.name,
+                            &attachment.mime_type,
+                            &vec![0; attachment.size],
+                        ),
+                    };
+                }
+                let inserted = self.fixture.message(chat_id, message)?;
+                self.messages.insert(
+                    id.clone(),
+                    SentMessage {
+                        inserted,
+                        chat_id,
+                        parts: parts.len(),
+                        unsent: vec![],
+                        tracked: None,
+                    },
+                );
+            }
+            Operation::Edit {
+                message,
+                part,
+                text,
+            } => {
+                let rowid = self.message(message)?.inserted.rowid;
+                self.fixture.edit(rowid, *part, text, date)?;
+            }
+            Operation::Unsend { message, part } => {
+                let sent = self.message_mut(message)?;
+                let rowid = sent.inserted.rowid;
+                if *part < sent.parts && !sent.unsent.contains(part) {
+                    sent.unsent.push(*part);
+                }
+                self.fixture.unsend(rowid, *part, date)?;
+            }
+            Operation::Tapback {
+                message,
+                part,
+                from,
+                tapback,
+                emoji,
+            }
+            | Operation::RemoveTapback {
+                message,
+                part,
+                from,
+                tapback,
+                emoji,
+            } => {
+                let action = if matches!(operation, Operation::Tapback { .. }) {
+                    TapbackAction::Added
+                } else {
+                    TapbackAction::Removed
+                };
+                let target = self.message(message)?;
+                let (guid, chat_id) = (target.inserted.guid.clone(), target.chat_id);
+                let tapback = NewMessage::tapback(
+                    &guid,
+                    *part,
+                    &tapback.to_tapback(emoji.as_deref()),
+                    &action,
+                    date,
+                );
+                let tapback = self.with_sender(tapback, from.as_deref(), chat_id)?;
+                self.fixture.message(chat_id, tapback)?;
+            }
+            Operation::RecentlyDelete { message } => {
+                let rowid = self.message(message)?.inserted.rowid;
+                self.fixture.recently_delete(rowid, date)?;
+            }
+            Operation::Purge { message } => {
+                let rowid = self.message(message)?.inserted.rowid;
+                self.fixture.purge(rowid)?;
+            }
+            Operation::DeleteChat { chat } => {
+                let chat_id = lookup(&self.chats, chat, "chat")?.0;
+                self.fixture.delete_chat(chat_id, date)?;
+            }
+            Operation::RemoveAttachment {
+                message,
+                attachment,
+            } => {
+                let path = self
+                    .message(message)?
+                    .inserted
+                    .attachments
+                    .get(*attachment)
+                    .map(|inserted| inserted.path.clone())
+                    .ok_or_else(|| {
+                        RuntimeError::SimulationError(format!(
+                            "Message {message} does not have attachment {attachment}"
+                        ))
+                    })?;
+                remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the database like the tracker does after a step, returning the event the step should cause
+    fn observe(
+        &mut self,
+        operation: &Operation,
+        reader: &IMessageDatabase,
+    ) -> Result<Option<DeletionEvent>, RuntimeError> {
+        match operation {
+            // The tracker watches messages that have text when it first sees them
+            Operation::Send { id, .. } => {
+                let sent = self.message_mut(id)?;
+                sent.tracked = reader
+                    .get_messages_by_ids(&[sent.inserted.rowid])
+                    .map_err(TableError::Messages)?
+                    .into_iter()
+                    .find(|message| {
+                        message
+                            .text
+                            .as_deref()
+                            .is_some_and(|text| !text.trim().is_empty())
+                    });
+                Ok(None)
+            }
+            // Unsending the last part of a watched message removes all of its text
+            Operation::Unsend { message, .. } => {
+                let sent = self.message_mut(message)?;
+                if sent.unsent.len() < sent.parts {
+                    return Ok(None);
+                }
+                Ok(sent
+                    .tracked
+                    .take()
+                    .map(|original| DeletionEvent::new(&original, reader)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Mark a message as sent by a handle, or by the device owner if there is no handle
+    fn with_sender(
+        &self,
+        mut message: NewMessage,
+        from: Option<&str>,
+        chat_id: i32,
+    ) -> Result<NewMessage, RuntimeError> {
+        if let Some(id) = from {
+            return Ok(message.from_handle(*lookup(&self.handles, id, "handle")?));
+        }
+        // Messages.app records the other person's handle on messages sent in a conversation with them
+        if let Some((_, [participant])) = self
+            .chats
+            .values()
+            .find(|(id, _)| *id == chat_id)
+            .map(|(id, participants)| (id, participants.as_slice()))
+        {
+            message.handle_id = *participant;
+        }
+        Ok(message)
+    }
+
+    fn message(&self, id: &str) -> Result<&SentMessage, RuntimeError> {
+        lookup(&self.messages, id, "message")
+    }
+
+    fn message_mut(&mut self, id: &str) -> Result<&mut SentMessage, RuntimeError> {
+        self.messages
+            .get_mut(id)
+            .ok_or_else(|| RuntimeError::SimulationError(format!("Unknown message {id}")))
+    }
+
+    /// Apply a change in a single transaction, like `Messages.app` does
+    fn transaction(
+        &mut self,
+        change: impl FnOnce(&mut Self) -> Result<(), RuntimeError>,
+    ) -> Result<(), RuntimeError> {
+        let execute = |simulator: &Self, sql: &str| {
+            simulator
+                .fixture
+                .connection()
+                .execute_batch(sql)
+                .map_err(|why| RuntimeError::DatabaseError(TableError::Messages(why)))
+        };
+
+        execute(self, "BEGIN")?;
+        match change(self) {
+            Ok(()) => execute(self, "COMMIT"),
+            Err(why) => {
+                execute(self, "ROLLBACK")?;
+                Err(why)
+            }
+        }
+    }
+}
+
+/// Find an item a scenario refers to by name
+fn lookup<'a, T>(
+    items: &'a HashMap<String, T>,
+    name: &str,
+    kind: &str,
+) -> Result<&'a T, RuntimeError> {
+    items
+        .get(name)
+        .ok_or_else(|| RuntimeError::SimulationError(format!("Unknown {kind} {name}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use crate::simulator::{Simulator, scenario::Scenario};
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("imessage-simulator-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn can_report_unsent_messages() {
+        let dir = temp("unsent");
+        let scenario: Scenario = serde_json::from_str(
+            r#"{
+                "handles": [{ "id": "+15558675309" }],
+                "chats": [{ "name": "jenny", "participants": ["+15558675309"] }],
+                "steps": [
+                    { "op": "send", "id": "hello", "chat": "jenny", "from": "+15558675309", "parts": [{ "text": "Hello!" }] },
+                    { "op": "send", "id": "split", "chat": "jenny", "parts": [{ "text": "One" }, { "text": "Two" }] },
+                    { "op": "send", "id": "bye", "chat": "jenny", "parts": [{ "text": "Bye!" }] },
+                    { "op": "tapback", "message": "hello", "tapback": "loved" },
+                    { "op": "edit", "message": "hello", "text": "Hello there!" },
+                    { "op": "unsend", "message": "split", "part": 1 },
+                    { "op": "recently_delete", "message": "bye" },
+                    { "op": "unsend", "message": "hello", "delay_ms": 5000 }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let mut simulator = Simulator::new(&dir.join("chat.db"), &scenario, false).unwrap();
+        let events = simulator.run(&scenario.steps).unwrap();
+        let hello = simulator.messages["hello"].inserted.rowid;
+
+        // Only the message with every part unsent is reported, with the text the tracker first saw
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].message_id, hello);
+        assert_eq!(events[0].content.as_deref(), Some("Hello!"));
+        assert_eq!(events[0].sender, "+15558675309");
+        assert!(events[0].attachments.is_empty());
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cannot_simulate_over_existing_database() {
+        let dir = temp("existing");
+        let scenario: Scenario = serde_json::from_str(r#"{ "steps": [] }"#).unwrap();
+
+        assert!(Simulator::new(&dir.join("chat.db"), &scenario, false).is_ok());
+        assert!(Simulator::new(&dir.join("chat.db"), &scenario, false).is_err());
+
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+/*!
+Scenario files that script the changes a simulation makes to a database
+*/
+
+use std::{fs::read_to_string, path::Path};
+
+use serde::Deserialize;
+
+use imessage_database::{message_types::variants::Tapback, util::fixture::Schema};
+
+use crate::app::error::RuntimeError;
+
+/// Time between steps, in milliseconds, when a step does not set `delay_ms`
+///
+/// This is longer than the tracker's polling interval, so it sees the result of each step.
+pub const DEFAULT_DELAY_MS: u64 = 1000;
+/// Service used for handles that do not set one
+const DEFAULT_SERVICE: &str = "iMessage";
+
+/// A scripted sequence of changes to a database
+#[derive(Debug, Deserialize)]
+pub struct Scenario {
+    /// The schema generation of the database to create
+    #[serde(default)]
+    pub schema: ScenarioSchema,
+    /// The phone numbers and email addresses that take part in the scenario
+    #[serde(default)]
+    pub handles: Vec<ScenarioHandle>,
+    /// The conversations that take part in the scenario
+    #[serde(default)]
+    pub chats: Vec<ScenarioChat>,
+    /// The changes to make, in order
+    pub steps: Vec<Step>,
+}
+
+impl Scenario {
+    /// Read a scenario from a JSON file
+    pub fn from_file(path: &Path) -> Result<Self, RuntimeError> {
+        let contents = read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|why| {
+            RuntimeError::SimulationError(format!(
+                "{} is not a valid scenario: {why}",
+                path.display()
+            ))
+        })
+    }
+}
+
+/// The schema generations a scenario can target
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScenarioSchema {
+    /// macOS Catalina, iOS 13 and older
+    Ios13,
+    /// macOS Big Sur to Monterey, iOS 14 to iOS 15
+    Ios14,
+    /// macOS Ventura+ and iOS 16+
+    #[default]
+    Ios16,
+}
+
+impl From<ScenarioSchema> for Schema {
+    fn from(schema: ScenarioSchema) -> Self {
+        match schema {
+            ScenarioSchema::Ios13 => Schema::Ios13Older,
+            ScenarioSchema::Ios14 => Schema::Ios14To15,
+            ScenarioSchema::Ios16 => Schema::Ios16Newer,
+        }
+    }
+}
+
+/// A phone number or email address
+#[derive(Debug, Deserialize)]
+pub struct ScenarioHandle {
+    /// The phone number or email address
+    pub id: String,
+    /// The service the handle uses, i.e. `iMessage` or `SMS`
+    #[serde(default = "default_service")]
+    pub service: String,
+}
+
+/// A conversation
+#[derive(Debug, Deserialize)]
+pub struct ScenarioChat {
+    /// The name steps use to refer to the chat
+    pub name: String,
+    /// The `id` of each handle in the chat, other than the device owner
+    pub participants: Vec<String>,
+    /// The name of a group chat
+    pub display_name: Option<String>,
+}
+
+/// A change to make to the database
+#[derive(Debug, Deserialize)]
+pub struct Step {
+    /// How long to wait before making the change, in milliseconds
+    #[serde(default = "default_delay")]
+    pub delay_ms: u64,
+    /// The change to make
+    #[serde(flatten)]
+    pub operation: Operation,
+}
+
+/// The changes `Messages.app` makes to the database
+///
+/// Messages are referred to by the `id` given when they are sent, and chats by their `name`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    /// Send or receive a message
+    Send {
+        /// The name later steps use to refer to the message
+        id: String,
+        /// The chat the message is sent in
+        chat: String,
+        /// The `id` of the handle that sent the message, or none if the device owner sent it
+        from: Option<String>,
+        /// The parts of the message, in order
+        parts: Vec<ScenarioPart>,
+    },
+    /// Edit the text of a part of a message
+    Edit {
+        /// The message to edit
+        message: String,
+        /// The index of the part to edit
+        #[serde(default)]
+        part: usize,
+        /// The new text of the part
+        text: String,
+    },
+    /// Unsend a part of a message
+    Unsend {
+        /// The message to unsend a part of
+        message: String,
+        /// The index of the part to unsend
+        #[serde(default)]
+        part: usize,
+    },
+    /// React to a part of a message
+    Tapback {
+        /// The message to react to
+        message: String,
+        /// The index of the part to react to
+        #[serde(default)]
+        part: usize,
+        /// The `id` of the handle that reacted, or none if the device owner reacted
+        from: Option<String>,
+        /// The reaction
+        tapback: TapbackKind,
+        /// The emoji of a custom emoji reaction
+        emoji: Option<String>,
+    },
+    /// Remove a reaction from a part of a message
+    RemoveTapback {
+        /// The message to remove the reaction from
+        message: String,
+        /// The index of the part to remove the reaction from
+        #[serde(default)]
+        part: usize,
+        /// The `id` of the handle that removed the reaction, or none if the device owner did
+        from: Option<String>,
+        /// The reaction
+        tapback: TapbackKind,
+        /// The emoji of a custom emoji reaction
+        emoji: Option<String>,
+    },
+    /// Move a message to Recently Deleted
+    RecentlyDelete {
+        /// The message to delete
+        message: String,
+    },
+    /// Permanently delete a message
+    Purge {
+        /// The message to delete
+        message: String,
+    },
+    /// Delete a conversation, moving its messages to Recently Deleted
+    DeleteChat {
+        /// The chat to delete
+        chat: String,
+    },
+    /// Delete an attachment's file from the disk, like when `Messages.app` offloads it to iCloud
+    RemoveAttachment {
+        /// The message the attachment belongs to
+        message: String,
+        /// The index of the attachment in the message
+        #[serde(default)]
+        attachment: usize,
+    },
+}
+
+/// A part of a message that is sent
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScenarioPart {
+    /// Plain text
+    Text(String),
+    /// A file
+    Attachment(ScenarioAttachment),
+}
+
+/// A file attached to a message
+#[derive(Debug, Deserialize)]
+pub struct ScenarioAttachment {
+    /// The file's name
+    pub name: String,
+    /// The file's MIME type
+    pub mime_type: String,
+    /// The file's size in bytes
+    #[serde(default)]
+    pub size: usize,
+}
+
+/// The reactions a tapback can be
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TapbackKind {
+    Loved,
+    Liked,
+    Disliked,
+    Laughed,
+    Emphasized,
+    Questioned,
+    Emoji,
+    Sticker,
+}
+
+impl TapbackKind {
+    /// Convert to the tapback type used by the database, with the emoji of a custom emoji reaction
+    pub fn to_tapback(self, emoji: Option<&str>) -> Tapback<'_> {
+        match self {
+            TapbackKind::Loved => Tapback::Loved,
+            TapbackKind::Liked => Tapback::Liked,
+            TapbackKind::Disliked => Tapback::Disliked,
+            TapbackKind::Laughed => Tapback::Laughed,
+            TapbackKind::Emphasized => Tapback::Emphasized,
+            TapbackKind::Questioned => Tapback::Questioned,
+            TapbackKind::Emoji => Tapback::Emoji(emoji),
+            TapbackKind::Sticker => Tapback::Sticker,
+        }
+    }
+}
+
+fn default_delay() -> u64 {
+    DEFAULT_DELAY_MS
+}
+
+fn default_service() -> String {
+    DEFAULT_SERVICE.to_string()
+}